@@ -1,16 +1,30 @@
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use chrono::Utc;
+use regex::Regex;
 use oc_bots_sdk::oc_api::client::Client;
-use oc_bots_sdk::types::{BotCommandContext, BotCommandScope};
+use oc_bots_sdk::types::BotCommandContext;
 use oc_bots_sdk_offchain::AgentRuntime;
 use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
 use std::fmt;
+use std::future::Future;
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
-use tokio::time::sleep;
+use tokio::time::{sleep, timeout_at, Instant};
 use tracing::{debug, error, info, warn};
 use uuid::Uuid;
 
-use crate::llm::{ChatMessage, MistralClient};
+use crate::chat_id::canonical_chat_id;
+use crate::llm::{estimate_tokens, ChatMessage, LlmProvider, RetryBudget, ToolReply};
+use crate::memory::{AskTurn, MemoryBackend};
+use crate::tools::ToolRegistry;
+
+// Delay used between steps while the API has recently rate-limited us
+const RATE_LIMITED_STEP_DELAY: Duration = Duration::from_secs(2);
+// How long after a 429 we keep slowing down
+const RATE_LIMIT_COOLDOWN: Duration = Duration::from_secs(60);
+// Upper bound on the size of prior conversation fed back to the model
+const MAX_HISTORY_CHARS: usize = 8000;
 
 // ReAct planning stages
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -56,44 +70,6 @@ impl AgentAction {
             timestamp: Utc::now(),
         }
     }
-
-    fn parse_from_llm_response(response: &str) -> Option<Self> {
-        // For simple responses that could be direct answers, convert them to answer actions
-        if !response.contains("ACTION:") {
-            // If the response is short and simple, treat it as a direct answer
-            if response.len() < 500 && !response.contains("PARAMETERS:") {
-                // Create an answer action with the response as the final answer
-                return Some(Self::new(
-                    "answer".to_string(),
-                    serde_json::json!({ "final_answer": response.trim() })
-                ));
-            }
-            return None;
-        }
-
-        let action_parts: Vec<&str> = response.split("ACTION:").collect();
-        if action_parts.len() < 2 {
-            return None;
-        }
-
-        let action_text = action_parts[1].trim();
-        let action_name_end = action_text.find('\n').unwrap_or(action_text.len());
-        let action_name = action_text[..action_name_end].trim().to_string();
-        
-        // Extract parameters
-        let mut parameters = serde_json::json!({});
-        if let Some(params_start) = response.find("PARAMETERS:") {
-            let params_text = &response[params_start + "PARAMETERS:".len()..];
-            let params_end = params_text.find("\n\n").unwrap_or(params_text.len());
-            let params_json = params_text[..params_end].trim();
-            
-            if let Ok(parsed) = serde_json::from_str(params_json) {
-                parameters = parsed;
-            }
-        }
-
-        Some(Self::new(action_name, parameters))
-    }
 }
 
 impl fmt::Display for AgentAction {
@@ -107,52 +83,190 @@ impl fmt::Display for AgentAction {
     }
 }
 
+/// What produced an observation, so the answer can treat each kind appropriately
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ObservationKind {
+    SearchResults,
+    Calculation,
+    // The action failed; the content describes why
+    Error,
+    // Any other tool, including configured ones
+    #[default]
+    ToolOutput,
+}
+
+impl ObservationKind {
+    // The kind of a successful result from the named tool
+    fn for_tool(name: &str) -> Self {
+        match name {
+            "search" => ObservationKind::SearchResults,
+            "calculate" => ObservationKind::Calculation,
+            _ => ObservationKind::ToolOutput,
+        }
+    }
+
+    // How the observation is introduced to the model when writing the final answer
+    fn describe(&self) -> &'static str {
+        match self {
+            ObservationKind::SearchResults => "search results; cite what they say rather than adding to it",
+            ObservationKind::Calculation => "calculation result; use this exact value",
+            ObservationKind::Error => "the action failed; don't rely on it",
+            ObservationKind::ToolOutput => "tool output",
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Observation {
     pub id: String,
+    #[serde(default)]
+    pub kind: ObservationKind,
     pub content: String,
     pub action_id: String,
     pub timestamp: chrono::DateTime<Utc>,
 }
 
 impl Observation {
-    fn new(content: String, action_id: String) -> Self {
+    fn new(kind: ObservationKind, content: String, action_id: String) -> Self {
         Self {
             id: Uuid::new_v4().to_string(),
+            kind,
             content,
             action_id,
             timestamp: Utc::now(),
         }
     }
+
+    // The observation of an action's result, tagged with the kind of tool that ran or as an error
+    fn of_action(action: &AgentAction, result: Result<String>) -> Self {
+        match result {
+            Ok(content) => Self::new(ObservationKind::for_tool(&action.action_type), content, action.id.clone()),
+            Err(e) => {
+                error!("Error executing action: {}", e);
+                Self::new(ObservationKind::Error, format!("Error: {}", e), action.id.clone())
+            }
+        }
+    }
 }
 
+/// Outcome of an agent run, for callers that want more than the answer text
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AgentResult {
+    pub answer: String,
+    // Observations gathered while planning
+    pub sources: Vec<String>,
+    pub steps_taken: usize,
+    // Coarse 0.0-1.0 estimate based on how the run finished
+    pub confidence: f32,
+    // Questions the user might ask next, when enabled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub follow_ups: Vec<String>,
+}
+
+// Confidence by how the run finished
+const CONFIDENCE_DIRECT: f32 = 1.0;
+const CONFIDENCE_ANSWERED: f32 = 0.9;
+const CONFIDENCE_SUMMARIZED: f32 = 0.6;
+const CONFIDENCE_FALLBACK: f32 = 0.4;
+const CONFIDENCE_PARTIAL: f32 = 0.2;
+// The model said it couldn't answer
+const CONFIDENCE_UNKNOWN: f32 = 0.0;
+
+// Reply prefix the model uses instead of guessing, followed by what it is missing
+const CANNOT_ANSWER_MARKER: &str = "CANNOT_ANSWER";
+// Words suggesting a query needs tools or several steps, so it isn't answered directly
+const COMPLEX_QUERY_MARKERS: &[&str] = &[
+    "calculate", "compute", "search", "look up", "latest", "current", "today", "news",
+    "price", "weather", "compare", "step by step", " and then ", "http",
+];
+const NOT_ENOUGH_INFORMATION: &str = "I don't have enough information to answer that.";
+
+const MAX_FOLLOW_UPS: usize = 3;
+// Role and formatting tokens each message costs on top of its content
+const MESSAGE_OVERHEAD_TOKENS: usize = 4;
+// How many earlier steps a new one is compared against to spot the model going in circles
+const REPETITION_WINDOW: usize = 3;
+// Word overlap at which two steps count as the same
+const REPETITION_SIMILARITY: f32 = 0.9;
+
+// Planning scaffolding that can leak into answers: all-caps ReAct labels, a "Thought:"
+// line, and the numbered "Thought 2:" / "Action 2:" / "Observation 2 (...):" lines we
+// feed the model ourselves
+static SCAFFOLDING_LINE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"^\s*(?:(?:THOUGHT|ACTION|PARAMETERS|OBSERVATION)(?:\s+\d+)?\s*:|Thought\s*:|(?:Thought|Action|Observation)\s+\d+\s*(?:\([^)]*\))?\s*:)",
+    )
+    .unwrap()
+});
+// An action as `AgentAction` displays it: "Action: name" followed by "Parameters: {...}"
+static ACTION_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:ACTION|Action)(?:\s+\d+)?\s*:").unwrap());
+static PARAMETERS_LINE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\s*(?:Parameters|PARAMETERS)\s*:").unwrap());
+static FINAL_ANSWER_PREFIX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\s*)(?:FINAL[ _]ANSWER|Final [Aa]nswer)\s*:\s*").unwrap());
+
 // Configuration for the agent
 #[derive(Debug, Clone)]
 pub struct AgentConfig {
     pub max_steps: usize,
     pub temperature: f32,
+    // Baseline pause between LLM calls in the planning loop
+    pub step_delay: Duration,
+    // How many previous /ask exchanges in the chat to include as context
+    pub history_turns: usize,
+    // Overall time budget for answering a query
+    pub timeout: Duration,
+    // Who the bot is and how it talks; opens the system prompt
+    pub persona: String,
+    // Suggest follow-up questions after answering, at the cost of one more LLM call
+    pub suggest_follow_ups: bool,
+    // Estimated tokens the system prompt, tools and messages may use per LLM call
+    pub context_token_budget: usize,
+    // Rate-limit retries all of a run's LLM calls may make between them
+    pub max_run_retries: usize,
+    // Questions up to this many characters that look simple are answered in a single
+    // call, skipping the planning loop; 0 always plans
+    pub fast_path_max_chars: usize,
+    // Update the command's placeholder message as the planning loop advances
+    pub show_progress: bool,
 }
 
+pub const DEFAULT_PERSONA: &str = "You are KarmaSpark, an intelligent assistant capable of step-by-step problem solving.";
+pub const MAX_PERSONA_CHARS: usize = 2000;
+
 impl Default for AgentConfig {
     fn default() -> Self {
         Self {
             max_steps: 3,
             temperature: 0.7,
+            step_delay: Duration::ZERO,
+            history_turns: 5,
+            timeout: Duration::from_secs(25),
+            persona: DEFAULT_PERSONA.to_string(),
+            suggest_follow_ups: false,
+            context_token_budget: 24_000,
+            max_run_retries: 4,
+            fast_path_max_chars: 0,
+            show_progress: false,
         }
     }
 }
 
 #[derive(Clone)]
 pub struct Agent {
-    llm: MistralClient,
+    llm: Arc<dyn LlmProvider>,
     config: AgentConfig,
+    memory_store: Option<Arc<dyn MemoryBackend>>,
+    tools: Arc<ToolRegistry>,
 }
 
 impl Agent {
-    pub fn new(llm: MistralClient) -> Self {
+    pub fn new(llm: Arc<dyn LlmProvider>) -> Self {
         Self {
+            tools: Arc::new(ToolRegistry::with_builtin_tools(llm.clone())),
             llm,
             config: AgentConfig::default(),
+            memory_store: None,
         }
     }
 
@@ -161,29 +275,67 @@ impl Agent {
         self
     }
     
+    /// Remember /ask exchanges so follow-up questions have context
+    pub fn with_memory_store(mut self, memory_store: Arc<dyn MemoryBackend>) -> Self {
+        self.memory_store = Some(memory_store);
+        self
+    }
+    
+    /// Replace the tools offered to the model
+    pub fn with_tools(mut self, tools: ToolRegistry) -> Self {
+        self.tools = Arc::new(tools);
+        self
+    }
+    
+    /// Answer the query, planning and calling tools as needed. With `show_progress` (and
+    /// `AgentConfig::show_progress`), the command's placeholder shows each step as it runs;
+    /// callers replying ephemerally pass false, as the placeholder is visible to the chat.
     pub async fn plan_and_execute(
         &self,
         client: &Client<AgentRuntime, BotCommandContext>,
         query: &str,
-    ) -> Result<(String, Vec<String>)> {
-        info!("Starting planning for query: {}", query);
-        
-        // Extract scope and context
-        let scope = &client.context().scope;
-        
-        // Extract chat and user information based on scope type
-        let (chat_id, user_id) = match scope {
-            BotCommandScope::Chat(chat_details) => {
-                // For chat scope, extract chat info and user info
-                let chat_string = format!("{:?}", chat_details.chat);
-                (chat_string, client.context().command.initiator.to_string())
-            },
-            BotCommandScope::Community(community_details) => {
-                // For community scope, use community id
-                let community_string = format!("{:?}", community_details.community_id);
-                (community_string, client.context().command.initiator.to_string())
-            },
+        show_progress: bool,
+    ) -> Result<AgentResult> {
+        let chat_id = canonical_chat_id(&client.context().scope);
+        let user_id = client.context().command.initiator.to_string();
+        // The message is left unfinalised, so the reply replaces it in turn
+        let show_in_placeholder = |text: String| {
+            client
+                .send_text_message(text)
+                .with_finalised(false)
+                .execute_then_return_message(|_, _| ());
         };
+        self.plan(&chat_id, &user_id, query, show_progress, &show_in_placeholder).await
+    }
+    
+    // `plan_and_execute` for a chat and user, handing progress updates to `progress`
+    async fn plan(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        query: &str,
+        show_progress: bool,
+        progress: &(dyn Fn(String) + Send + Sync),
+    ) -> Result<AgentResult> {
+        // Under rate limiting, each step retrying on its own would stack up minutes of
+        // backoff; the run gives up instead once its shared retries or time are spent
+        let budget = Arc::new(RetryBudget::new(self.config.max_run_retries, self.config.timeout));
+        let show_progress = show_progress && self.config.show_progress;
+        let mut result = budget.scope(self.run_planning(chat_id, user_id, query, show_progress, progress)).await?;
+        // Partial answers skip the cleanup in `run_planning`
+        result.answer = strip_scaffolding(&result.answer);
+        Ok(result)
+    }
+    
+    async fn run_planning(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        query: &str,
+        show_progress: bool,
+        progress: &(dyn Fn(String) + Send + Sync),
+    ) -> Result<AgentResult> {
+        info!("Starting planning for query: {}", query);
         
         // For very simple queries, provide direct answers
         if query.len() < 10 && (
@@ -191,12 +343,18 @@ impl Agent {
             query.to_lowercase().contains("hi") || 
             query.to_lowercase().contains("hey")
         ) {
-            return Ok((
-                format!("Hello! How can I assist you today?"),
-                vec![]
-            ));
+            return Ok(AgentResult {
+                answer: "Hello! How can I assist you today?".to_string(),
+                sources: vec![],
+                steps_taken: 0,
+                confidence: CONFIDENCE_DIRECT,
+                follow_ups: Vec::new(),
+            });
         }
         
+        // Load earlier exchanges in this chat so follow-ups make sense
+        let history = self.load_history(chat_id).await;
+        
         // Initialize planning state and tracking structures
         let mut state = PlanningState::Start;
         let mut thoughts: Vec<Thought> = Vec::new();
@@ -204,13 +362,32 @@ impl Agent {
         let mut observations: Vec<Observation> = Vec::new();
         let mut current_step = 0;
         let mut final_answer = String::new();
+        let mut confidence = CONFIDENCE_ANSWERED;
         let mut consecutive_thinking_count = 0;
+        // What the model asked for at each step, to notice it repeating itself
+        let mut recent_steps: Vec<String> = Vec::new();
+        // Everything below, including the pauses between steps, must finish by the deadline
+        let deadline = Instant::now() + self.config.timeout;
+        let mut timed_out = false;
         
-        // Set up system prompt for ReAct planning
-        let system_prompt = self.create_system_prompt(query);
+        // Set up system prompt for ReAct planning, answering in the user's language
+        let language = response_language(query);
+        debug!("Answering in {}", language);
+        let persona = self.load_persona(chat_id).await;
+        let system_prompt = self.create_system_prompt(&persona, query, language);
+        let tool_definitions = self.tools.definitions();
+        // Whatever is left of the context budget after the fixed parts goes to messages
+        let message_budget = self.config.context_token_budget.saturating_sub(
+            estimate_tokens(&system_prompt) + estimate_tokens(&serde_json::to_string(&tool_definitions).unwrap_or_default()),
+        );
         
-        // Delay between LLM calls to avoid rate limits
-        let delay_duration = Duration::from_secs(2);
+        // Short, self-contained questions don't need tools, so try answering them in one call
+        if is_simple_query(query, self.config.fast_path_max_chars) {
+            if let Some(answer) = self.answer_directly(&persona, &history, query, language, deadline, message_budget).await {
+                final_answer = answer;
+                state = PlanningState::Finished;
+            }
+        }
         
         // Main planning loop
         while current_step < self.config.max_steps && state != PlanningState::Finished {
@@ -227,14 +404,18 @@ impl Agent {
                 
                 PlanningState::Thinking => {
                     info!("Step {}: Thinking...", current_step + 1);
+                    if show_progress {
+                        self.report_progress(progress, current_step + 1, "thinking");
+                    }
                     consecutive_thinking_count += 1;
                     
                     // If we've been in thinking state too many times, provide a fallback response
                     if consecutive_thinking_count > 5 {
                         info!("Too many consecutive thinking steps, providing fallback answer");
+                        confidence = CONFIDENCE_FALLBACK;
                         
                         if !observations.is_empty() {
-                            final_answer = self.generate_partial_answer_from_observations(&observations, query).await?.0;
+                            final_answer = self.generate_partial_answer_from_observations(&observations, query, current_step).await?.answer;
                         } else {
                             // Fallback to a direct answer attempt
                             final_answer = format!(
@@ -243,13 +424,17 @@ impl Agent {
                             );
                             
                             let simple_prompt = format!(
-                                "You are a helpful assistant. Provide a direct, concise answer to this question: \"{}\"",
-                                query
+                                "{}\nProvide a direct, concise answer to this question: \"{}\"",
+                                persona, query
                             );
                             
-                            let direct_response = match self.llm.chat(&simple_prompt, &[]).await {
-                                Ok(response) => response,
-                                Err(_) => "I'm not able to provide a complete answer at this time. Please try asking your question differently.".to_string(),
+                            let direct_response = match within(deadline, self.llm.chat(&simple_prompt, &[])).await {
+                                Some(Ok(response)) => response,
+                                Some(Err(_)) => "I'm not able to provide a complete answer at this time. Please try asking your question differently.".to_string(),
+                                None => {
+                                    timed_out = true;
+                                    break;
+                                }
                             };
                             
                             final_answer.push_str(&direct_response);
@@ -260,79 +445,107 @@ impl Agent {
                     }
                     
                     // Add delay before making LLM call to avoid rate limits
-                    sleep(delay_duration).await;
+                    if within(deadline, self.pause_between_steps()).await.is_none() {
+                        timed_out = true;
+                        break;
+                    }
                     
                     // Generate current context for LLM
-                    let messages = self.build_message_history(&thoughts, &actions, &observations);
+                    let messages = self.build_message_history(&history, &thoughts, &actions, &observations);
+                    let messages = trim_to_budget(messages, message_budget);
                     
                     // Get next step from LLM
-                    let response = match self.llm.chat(&system_prompt, &messages).await {
-                        Ok(response) => response,
-                        Err(e) => {
+                    let reply = match within(deadline, self.llm.chat_with_tools(&system_prompt, &messages, &tool_definitions)).await {
+                        None => {
+                            timed_out = true;
+                            break;
+                        }
+                        Some(Ok(response)) => response,
+                        Some(Err(e)) => {
                             error!("Error getting LLM response: {}", e);
                             // If we hit an error but have observations, try to provide a partial answer
                             if !observations.is_empty() {
-                                return self.generate_partial_answer_from_observations(&observations, query).await;
+                                return self.generate_partial_answer_from_observations(&observations, query, current_step).await;
                             }
-                            return Err(anyhow!("Failed to get LLM response: {}", e));
+                            return Err(e.context("Failed to get LLM response"));
                         }
                     };
                     
-                    // Parse response to determine next state
-                    if let Some(action) = AgentAction::parse_from_llm_response(&response) {
-                        // Reset consecutive thinking counter when we get an action
-                        consecutive_thinking_count = 0;
-                        
-                        // Handle 'answer' action separately as it's the exit condition
-                        if action.action_type == "answer" {
-                            if let Some(answer) = action.parameters.get("final_answer") {
-                                final_answer = answer.as_str().unwrap_or("").to_string();
-                                state = PlanningState::Finished;
-                                
-                                // Record this as the final thought
-                                thoughts.push(Thought::new(
-                                    format!("I now have the answer: {}", final_answer)
-                                ));
-                            } else {
-                                warn!("Answer action without final_answer parameter");
-                                thoughts.push(Thought::new("I need to provide a clear answer".to_string()));
+                    // Asking for the same thing again won't get anywhere new, so answer with what we have
+                    let step = match &reply {
+                        ToolReply::ToolCalls(calls) => calls
+                            .first()
+                            .map(|call| format!("{} {}", call.name, call.arguments))
+                            .unwrap_or_default(),
+                        ToolReply::Text(response) => response.trim().to_string(),
+                    };
+                    if is_repetition(&recent_steps, &step) {
+                        info!("Model is repeating itself, answering from what it has gathered");
+                        break;
+                    }
+                    recent_steps.push(step);
+                    
+                    match reply {
+                        ToolReply::ToolCalls(calls) => {
+                            // Reset consecutive thinking counter when we get an action
+                            consecutive_thinking_count = 0;
+                            
+                            // Steps run one action at a time; later calls can be requested again
+                            if calls.len() > 1 {
+                                debug!("Model requested {} tool calls, running the first", calls.len());
+                            }
+                            if let Some(call) = calls.into_iter().next() {
+                                thoughts.push(Thought::new(format!("I need to use {}", call.name)));
+                                actions.push(AgentAction::new(call.name, call.arguments));
+                                state = PlanningState::Acting;
                             }
-                        } else {
-                            // Record thought and action for other action types
-                            let thought_content = format!("I need to {}", action.action_type);
-                            thoughts.push(Thought::new(thought_content));
-                            actions.push(action);
-                            state = PlanningState::Acting;
                         }
-                    } else {
-                        // Treat response as a thought if it's not an action
-                        thoughts.push(Thought::new(response));
-                        // Stay in thinking state
+                        ToolReply::Text(response) if !response.trim().is_empty() => {
+                            // A plain reply is the final answer, unless the model admits it has none
+                            match cannot_answer(&response) {
+                                Some(answer) => {
+                                    info!("Model could not answer the query");
+                                    final_answer = answer;
+                                    confidence = CONFIDENCE_UNKNOWN;
+                                }
+                                None => final_answer = response.trim().to_string(),
+                            }
+                            state = PlanningState::Finished;
+                            
+                            // Record this as the final thought
+                            thoughts.push(Thought::new(
+                                format!("I now have the answer: {}", final_answer)
+                            ));
+                        }
+                        ToolReply::Text(_) => {
+                            warn!("Empty reply from the model, thinking again");
+                            thoughts.push(Thought::new("I need to provide a clear answer".to_string()));
+                        }
                     }
                 }
                 
                 PlanningState::Acting => {
                     if let Some(action) = actions.last() {
                         info!("Step {}: Acting - {}", current_step + 1, action.action_type);
+                        if show_progress {
+                            self.report_progress(progress, current_step + 1, &tool_activity(&action.action_type));
+                        }
                         
-                        // Add delay before making any potential LLM calls in execute_action
-                        sleep(delay_duration).await;
+                        // Add delay before making any potential LLM calls in the tool
+                        if within(deadline, self.pause_between_steps()).await.is_none() {
+                            timed_out = true;
+                            break;
+                        }
                         
                         // Perform the action
-                        match self.execute_action(action, chat_id.clone(), user_id.clone()).await {
-                            Ok(result) => {
+                        match within(deadline, self.tools.call(&action.action_type, action.parameters.clone())).await {
+                            None => {
+                                timed_out = true;
+                                break;
+                            }
+                            Some(result) => {
                                 // Record observation
-                                let observation = Observation::new(result, action.id.clone());
-                                observations.push(observation);
-                                state = PlanningState::Observing;
-                            },
-                            Err(e) => {
-                                error!("Error executing action: {}", e);
-                                let error_observation = Observation::new(
-                                    format!("Error: {}", e), 
-                                    action.id.clone()
-                                );
-                                observations.push(error_observation);
+                                observations.push(Observation::of_action(action, result));
                                 state = PlanningState::Observing;
                             }
                         }
@@ -357,18 +570,215 @@ impl Agent {
             }
         }
         
+        // If we ran out of time, return what we have rather than nothing
+        if timed_out {
+            warn!("Planning exceeded the {:?} deadline, returning a partial answer", self.config.timeout);
+            return self.generate_partial_answer_from_observations(&observations, query, current_step).await;
+        }
+        
         // If we reached max steps without finishing, provide a reasonable answer
         if state != PlanningState::Finished {
             info!("Reached maximum steps without final answer, generating summary");
-            final_answer = self.generate_final_answer(&thoughts, &actions, &observations, query).await?;
+            final_answer = match within(deadline, self.generate_final_answer(&persona, &thoughts, &actions, &observations, query, language)).await {
+                Some(answer) => answer?,
+                None => {
+                    warn!("Planning exceeded the {:?} deadline, returning a partial answer", self.config.timeout);
+                    return self.generate_partial_answer_from_observations(&observations, query, current_step).await;
+                }
+            };
+            confidence = CONFIDENCE_SUMMARIZED;
+            if let Some(answer) = cannot_answer(&final_answer) {
+                final_answer = answer;
+                confidence = CONFIDENCE_UNKNOWN;
+            }
         }
         
+        // Keep leaked planning lines out of the stored history too, or the model sees them again
+        final_answer = strip_scaffolding(&final_answer);
+        self.remember_turn(chat_id, user_id, query, &final_answer).await;
+        
+        // Suggestions are optional, so skip them rather than overrun the deadline
+        let follow_ups = if self.config.suggest_follow_ups {
+            within(deadline, self.suggest_follow_ups(query, &final_answer, language))
+                .await
+                .unwrap_or_default()
+        } else {
+            Vec::new()
+        };
+        
         // Collect observations for return
         let observation_texts = observations.iter()
             .map(|o| o.content.clone())
             .collect();
         
-        Ok((final_answer, observation_texts))
+        Ok(AgentResult {
+            answer: final_answer,
+            sources: observation_texts,
+            steps_taken: current_step,
+            confidence,
+            follow_ups,
+        })
+    }
+    
+    // Show how far the run has got, in place of the command's placeholder when run for a command
+    fn report_progress(&self, progress: &(dyn Fn(String) + Send + Sync), step: usize, activity: &str) {
+        let text = progress_text(step, self.config.max_steps, activity);
+        debug!("Progress: {}", text);
+        progress(text);
+    }
+    
+    /// Answer a question about an image in one call to the vision model. Tools aren't
+    /// offered, as the answer comes from what the image shows.
+    pub async fn answer_about_image(
+        &self,
+        client: &Client<AgentRuntime, BotCommandContext>,
+        query: &str,
+        image_url: &str,
+    ) -> Result<AgentResult> {
+        info!("Answering query about an image: {}", query);
+        
+        let chat_id = canonical_chat_id(&client.context().scope);
+        let user_id = client.context().command.initiator.to_string();
+        let language = response_language(query);
+        let persona = self.load_persona(&chat_id).await;
+        
+        let system_prompt = format!(
+            "{}\n\
+            The user has shared an image with their question. Look at it carefully and answer from what it shows.\n\
+            If the image doesn't show enough to answer, say so instead of guessing.\n\
+            Write your answer in {}, the language the user asked in.",
+            persona, language
+        );
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: query.to_string(),
+        }];
+        
+        let deadline = Instant::now() + self.config.timeout;
+        let answer = within(deadline, self.llm.chat_with_image(&system_prompt, &messages, image_url))
+            .await
+            .ok_or_else(|| anyhow::anyhow!("Timed out reading the image"))??;
+        
+        self.remember_turn(&chat_id, &user_id, query, &answer).await;
+        
+        Ok(AgentResult {
+            answer,
+            sources: Vec::new(),
+            steps_taken: 1,
+            confidence: CONFIDENCE_ANSWERED,
+            follow_ups: Vec::new(),
+        })
+    }
+    
+    // A single-call answer for a simple question, or None if planning should handle it:
+    // the call failed or ran out of time, or the model says it needs more to go on
+    async fn answer_directly(
+        &self,
+        persona: &str,
+        history: &[AskTurn],
+        query: &str,
+        language: &str,
+        deadline: Instant,
+        message_budget: usize,
+    ) -> Option<String> {
+        info!("Answering simple query without planning");
+        
+        let system_prompt = format!(
+            "{}\n\
+            Answer the user's question directly and concisely.\n\
+            If answering needs a search, a calculation or information you don't have, or you can't answer reliably, \
+            reply with {} only.\n\
+            Write your answer in {}, the language the user asked in.",
+            persona, CANNOT_ANSWER_MARKER, language
+        );
+        let mut messages = self.build_message_history(history, &[], &[], &[]);
+        messages.push(ChatMessage {
+            role: "user".to_string(),
+            content: query.to_string(),
+        });
+        let messages = trim_to_budget(messages, message_budget);
+        
+        let response = match within(deadline, self.llm.chat(&system_prompt, &messages)).await {
+            Some(Ok(response)) => response,
+            Some(Err(e)) => {
+                warn!("Direct answer failed, planning instead: {}", e);
+                return None;
+            }
+            None => {
+                warn!("Direct answer timed out, planning instead");
+                return None;
+            }
+        };
+        
+        if response.trim().is_empty() || cannot_answer(&response).is_some() {
+            info!("Query needs more than a direct answer, planning instead");
+            return None;
+        }
+        Some(response.trim().to_string())
+    }
+    
+    // The chat's persona override if one is stored, otherwise the configured persona
+    async fn load_persona(&self, chat_id: &str) -> String {
+        let Some(store) = &self.memory_store else {
+            return self.config.persona.clone();
+        };
+        
+        match store.get_chat_persona(chat_id).await {
+            Ok(Some(persona)) if !persona.trim().is_empty() => persona,
+            Ok(_) => self.config.persona.clone(),
+            Err(e) => {
+                warn!("Failed to load chat persona: {}", e);
+                self.config.persona.clone()
+            }
+        }
+    }
+    
+    // Previous /ask exchanges for the chat, dropping the oldest beyond MAX_HISTORY_CHARS
+    async fn load_history(&self, chat_id: &str) -> Vec<AskTurn> {
+        let store = match &self.memory_store {
+            Some(store) if self.config.history_turns > 0 => store,
+            _ => return Vec::new(),
+        };
+        
+        let mut history = match store.get_recent_ask_turns(chat_id, self.config.history_turns).await {
+            Ok(history) => history,
+            Err(e) => {
+                warn!("Failed to load conversation history: {}", e);
+                return Vec::new();
+            }
+        };
+        
+        let mut total_chars: usize = history.iter().map(|t| t.question.len() + t.answer.len()).sum();
+        while total_chars > MAX_HISTORY_CHARS && !history.is_empty() {
+            let oldest = history.remove(0);
+            total_chars -= oldest.question.len() + oldest.answer.len();
+        }
+        
+        history
+    }
+    
+    async fn remember_turn(&self, chat_id: &str, user_id: &str, question: &str, answer: &str) {
+        if let Some(store) = &self.memory_store {
+            if let Err(e) = store.store_ask_turn(chat_id, user_id, question, answer).await {
+                warn!("Failed to store conversation turn: {}", e);
+            }
+        }
+    }
+
+    // Delay between LLM calls: the configured baseline, raised while we are being rate limited
+    fn step_delay(&self) -> Duration {
+        if self.llm.rate_limited_within(RATE_LIMIT_COOLDOWN) {
+            self.config.step_delay.max(RATE_LIMITED_STEP_DELAY)
+        } else {
+            self.config.step_delay
+        }
+    }
+    
+    async fn pause_between_steps(&self) {
+        let delay = self.step_delay();
+        if !delay.is_zero() {
+            sleep(delay).await;
+        }
     }
 
     // New helper method to generate a partial answer from observations if we hit an error
@@ -376,7 +786,8 @@ impl Agent {
         &self,
         observations: &[Observation],
         query: &str,
-    ) -> Result<(String, Vec<String>)> {
+        steps_taken: usize,
+    ) -> Result<AgentResult> {
         let mut answer = format!(
             "I encountered an issue while processing your question about '{}', but here's what I found so far:\n\n",
             query
@@ -394,47 +805,78 @@ impl Agent {
             .map(|o| o.content.clone())
             .collect();
             
-        Ok((answer, observation_texts))
+        Ok(AgentResult {
+            answer,
+            sources: observation_texts,
+            steps_taken,
+            confidence: CONFIDENCE_PARTIAL,
+            follow_ups: Vec::new(),
+        })
+    }
+
+    // A few short questions the user might ask next; empty if the model doesn't oblige
+    async fn suggest_follow_ups(&self, query: &str, answer: &str, language: &str) -> Vec<String> {
+        let system_prompt = format!(
+            "Suggest up to {} short follow-up questions the user might ask next, given their question and the answer they received. \
+            Write one question per line in {}, with no numbering and no other text.",
+            MAX_FOLLOW_UPS, language
+        );
+        
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: format!("Question: {}\n\nAnswer: {}", query, answer),
+        }];
+        
+        match self.llm.chat(&system_prompt, &messages).await {
+            Ok(response) => parse_follow_ups(&response),
+            Err(e) => {
+                warn!("Failed to suggest follow-up questions: {}", e);
+                Vec::new()
+            }
+        }
     }
 
     // Helper function to create the system prompt
-    fn create_system_prompt(&self, query: &str) -> String {
+    fn create_system_prompt(&self, persona: &str, query: &str, language: &str) -> String {
         format!(
-            "You are KarmaSpark, an intelligent assistant capable of step-by-step problem solving. You will think carefully before taking actions.\n\
+            "{}\n\
+            You will think carefully before taking actions.\n\
             The user has asked: \"{}\"\n\n\
             To solve this, you should follow a structured approach:\n\
             1. Think about what you know and what information you need\n\
-            2. Decide what action to take\n\
+            2. Call one of the provided tools if you need information or a calculation\n\
             3. Observe the result\n\
             4. Plan your next step or provide a final answer\n\n\
-            When you need to take an action, respond using EXACTLY this format:\n\
-            ACTION: <action_name>\n\
-            PARAMETERS: {{\"parameter_name\": \"parameter_value\"}}\n\n\
-            For example, to search for information:\n\
-            ACTION: search_information\n\
-            PARAMETERS: {{\"query\": \"history of chess\"}}\n\n\
-            To provide a final answer:\n\
-            ACTION: answer\n\
-            PARAMETERS: {{\"final_answer\": \"Your complete answer here\"}}\n\n\
-            Valid actions are:\n\
-            - search_information: {{\"query\": \"search terms\"}}\n\
-            - perform_calculation: {{\"expression\": \"math expression\"}}\n\
-            - answer: {{\"final_answer\": \"your final answer to the user\"}}\n\n\
-            IMPORTANT: For simple questions, you can immediately use the answer action without other steps.\n\
-            Do not include any narrative text outside of the specified format.",
-            query
+            When you are ready to answer, reply with the final answer directly instead of calling a tool.\n\
+            IMPORTANT: For simple questions, you can answer immediately without using any tools.\n\
+            If you can't answer reliably, don't guess: reply with {} followed by a short explanation of what information is missing.\n\
+            Write your final answer in {}, the language the user asked in.",
+            persona, query, CANNOT_ANSWER_MARKER, language
         )
     }
 
     // Helper function to build the conversation history for the LLM
     fn build_message_history(
         &self, 
+        history: &[AskTurn],
         thoughts: &[Thought], 
         actions: &[AgentAction], 
         observations: &[Observation]
     ) -> Vec<ChatMessage> {
         let mut messages = Vec::new();
         
+        // Seed earlier exchanges in this chat as prior conversation
+        for turn in history {
+            messages.push(ChatMessage {
+                role: "user".to_string(),
+                content: format!("Earlier question: {}", turn.question),
+            });
+            messages.push(ChatMessage {
+                role: "assistant".to_string(),
+                content: format!("Earlier answer: {}", turn.answer),
+            });
+        }
+        
         // Add thoughts, actions, and observations as conversational history
         for (i, thought) in thoughts.iter().enumerate() {
             messages.push(ChatMessage {
@@ -459,7 +901,7 @@ impl Agent {
         }
         
         // Ask the LLM for the next step
-        let next_step_prompt = if messages.is_empty() {
+        let next_step_prompt = if thoughts.is_empty() {
             "What is your first step to solve this problem?".to_string()
         } else {
             "What is your next step? You can either think more about the problem, take an action, or provide your final answer.".to_string()
@@ -476,16 +918,21 @@ impl Agent {
     // Generate a final answer if we reached max steps
     async fn generate_final_answer(
         &self,
+        persona: &str,
         thoughts: &[Thought],
         actions: &[AgentAction],
         observations: &[Observation],
         query: &str,
+        language: &str,
     ) -> Result<String> {
         let system_prompt = format!(
-            "You are KarmaSpark, an intelligent assistant. Based on the following thought process and observations, \
+            "{}\n\
+            Based on the following thought process and observations, \
             provide a concise and helpful answer to the user's question: \"{}\". \
-            Focus on giving the most useful information you've gathered so far.",
-            query
+            Focus on giving the most useful information you've gathered so far. \
+            If it isn't enough to answer reliably, don't guess: reply with {} followed by a short explanation of what is missing. \
+            Answer in {}.",
+            persona, query, CANNOT_ANSWER_MARKER, language
         );
 
         let mut messages = Vec::new();
@@ -506,7 +953,12 @@ impl Agent {
                 if i < observations.len() {
                     messages.push(ChatMessage {
                         role: "user".to_string(),
-                        content: format!("Observation {}: {}", i + 1, observations[i].content),
+                        content: format!(
+                            "Observation {} ({}): {}",
+                            i + 1,
+                            observations[i].kind.describe(),
+                            observations[i].content
+                        ),
                     });
                 }
             }
@@ -516,6 +968,7 @@ impl Agent {
             role: "user".to_string(),
             content: "Based on all the information you've gathered, what's your final answer to my question?".to_string(),
         });
+        let messages = trim_to_budget(messages, self.config.context_token_budget.saturating_sub(estimate_tokens(&system_prompt)));
         
         match self.llm.chat(&system_prompt, &messages).await {
             Ok(answer) => Ok(answer),
@@ -525,73 +978,865 @@ impl Agent {
             }
         }
     }
+}
+
+// Language the model should answer in. Short or mixed queries are often
+// misdetected, so anything whatlang isn't confident about falls back to English.
+fn response_language(query: &str) -> &'static str {
+    match whatlang::detect(query) {
+        Some(info) if info.is_reliable() => info.lang().eng_name(),
+        _ => "English",
+    }
+}
+
+// Questions from a one-per-line model reply, ignoring list markers and anything that isn't a question
+fn parse_follow_ups(response: &str) -> Vec<String> {
+    response
+        .lines()
+        .map(|line| {
+            line.trim()
+                .trim_start_matches(|c: char| c.is_ascii_digit() || matches!(c, '-' | '*' | '•' | '.' | ')'))
+                .trim()
+        })
+        .filter(|line| line.ends_with(['?', '？', '؟']))
+        .take(MAX_FOLLOW_UPS)
+        .map(str::to_string)
+        .collect()
+}
+
+// The honest reply to give when the model says it can't answer, None for a real answer
+fn cannot_answer(response: &str) -> Option<String> {
+    let explanation = response.trim().strip_prefix(CANNOT_ANSWER_MARKER)?;
+    let explanation = explanation.trim_start_matches([':', '-']).trim();
     
-    async fn execute_action(
-        &self,
-        action: &AgentAction,
-        _chat_id: String,
-        _user_id: String, 
-    ) -> Result<String> {
-        match action.action_type.as_str() {
-            "search_information" => {
-                let query = action.parameters.get("query")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| anyhow!("No search query provided"))?;
-                
-                if query.is_empty() {
-                    return Ok("No search query provided.".to_string());
-                }
-                
-                // Simulate search (in a real system, this would call a search API)
-                let search_prompt = format!(
-                    "You are a search engine. Provide a brief, factual answer to this query: \"{}\"",
-                    query
-                );
-                
-                let messages = vec![
-                    ChatMessage {
-                        role: "user".to_string(),
-                        content: query.to_string(),
-                    }
-                ];
-                
-                // Use the LLM as a simulated search engine
-                match self.llm.chat(&search_prompt, &messages).await {
-                    Ok(result) => Ok(result),
-                    Err(e) => Err(anyhow!("Search error: {}", e)),
-                }
-            },
-            
-            "perform_calculation" => {
-                let expression = action.parameters.get("expression")
-                    .and_then(|v| v.as_str())
-                    .ok_or_else(|| anyhow!("No calculation expression provided"))?;
-                
-                if expression.is_empty() {
-                    return Ok("No calculation expression provided.".to_string());
-                }
-                
-                // Use LLM to evaluate the expression (in production, you'd want a proper math engine)
-                let calc_prompt = format!(
-                    "You are a calculator. Compute the result of this expression: \"{}\". \
-                    Return only the numeric result without explanation.",
-                    expression
-                );
-                
-                let messages = vec![
-                    ChatMessage {
-                        role: "user".to_string(),
-                        content: expression.to_string(),
-                    }
-                ];
-                
-                match self.llm.chat(&calc_prompt, &messages).await {
-                    Ok(result) => Ok(result),
-                    Err(e) => Err(anyhow!("Calculation error: {}", e)),
-                }
-            },
-            
-            _ => Err(anyhow!("Unsupported action: {}", action.action_type)),
+    Some(if explanation.is_empty() {
+        NOT_ENOUGH_INFORMATION.to_string()
+    } else {
+        format!("{} {}", NOT_ENOUGH_INFORMATION, explanation)
+    })
+}
+
+// The answer without planning scaffolding the model echoed into it: ReAct label lines
+// (with the JSON parameters that follow an action) are dropped and a "Final Answer:"
+// label is removed from its text. Code blocks are left alone, and an answer that was
+// nothing but scaffolding is returned unchanged rather than emptied.
+fn strip_scaffolding(answer: &str) -> String {
+    let lines: Vec<&str> = answer.lines().collect();
+    let mut kept: Vec<String> = Vec::with_capacity(lines.len());
+    let mut in_code_block = false;
+    // Open braces of a parameters object still being skipped
+    let mut skipping_depth = 0i32;
+    let mut after_action = false;
+
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") {
+            in_code_block = !in_code_block;
+            kept.push(line.to_string());
+            continue;
+        }
+        if in_code_block {
+            kept.push(line.to_string());
+            continue;
+        }
+
+        if skipping_depth > 0 {
+            skipping_depth += brace_balance(line);
+            continue;
+        }
+
+        // A mixed-case "Action:" or "Parameters:" line only counts as scaffolding as a pair
+        let is_action = ACTION_LINE.is_match(line);
+        let is_parameters = PARAMETERS_LINE.is_match(line);
+        let next_is_parameters = lines
+            .get(i + 1)
+            .is_some_and(|next| PARAMETERS_LINE.is_match(next));
+
+        if SCAFFOLDING_LINE.is_match(line) || (is_action && next_is_parameters) || (is_parameters && after_action) {
+            if is_parameters {
+                skipping_depth = brace_balance(line).max(0);
+            }
+            after_action = is_action;
+            continue;
+        }
+        after_action = false;
+
+        // Close the gaps left by dropped lines
+        if line.trim().is_empty() && kept.last().is_none_or(|last| last.trim().is_empty()) {
+            continue;
+        }
+        kept.push(FINAL_ANSWER_PREFIX.replace(line, "$1").into_owned());
+    }
+
+    let cleaned = kept.join("\n").trim().to_string();
+
+    if cleaned.is_empty() {
+        answer.trim().to_string()
+    } else {
+        cleaned
+    }
+}
+
+// Opening minus closing braces on a line
+fn brace_balance(line: &str) -> i32 {
+    line.chars().fold(0, |depth, c| match c {
+        '{' => depth + 1,
+        '}' => depth - 1,
+        _ => depth,
+    })
+}
+
+// The placeholder's text while the run is on `step`
+fn progress_text(step: usize, max_steps: usize, activity: &str) -> String {
+    format!("Thinking... (step {}/{}: {})", step, max_steps, activity)
+}
+
+// What a step running the named tool is doing, for progress updates
+fn tool_activity(name: &str) -> String {
+    match name {
+        "search" => "searching".to_string(),
+        "calculate" => "calculating".to_string(),
+        other => format!("using {}", other),
+    }
+}
+
+// Whether `step` is the same as, or nearly the same as, one of the last few steps
+fn is_repetition(recent_steps: &[String], step: &str) -> bool {
+    let words = |text: &str| -> HashSet<String> {
+        text.split_whitespace().map(str::to_lowercase).collect()
+    };
+    let step_words = words(step);
+    
+    recent_steps.iter().rev().take(REPETITION_WINDOW).any(|earlier| {
+        let earlier_words = words(earlier);
+        let union = step_words.union(&earlier_words).count();
+        if union == 0 {
+            // Two empty steps
+            return true;
+        }
+        step_words.intersection(&earlier_words).count() as f32 / union as f32 >= REPETITION_SIMILARITY
+    })
+}
+
+// Drop the oldest messages until the rest fit in `budget` estimated tokens. The last
+// message, which asks for the next step, is always kept.
+fn trim_to_budget(mut messages: Vec<ChatMessage>, budget: usize) -> Vec<ChatMessage> {
+    let cost = |message: &ChatMessage| estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS;
+    let mut total: usize = messages.iter().map(cost).sum();
+
+    let mut dropped = 0;
+    while total > budget && dropped + 1 < messages.len() {
+        total -= cost(&messages[dropped]);
+        dropped += 1;
+    }
+
+    if dropped > 0 {
+        warn!("Dropped {} oldest messages to stay within the {} token context budget", dropped, budget);
+        messages.drain(..dropped);
+    }
+    messages
+}
+
+// Short single questions that don't ask for anything the tools are for, such as a
+// lookup, a calculation or several steps. Anything longer than `max_chars` isn't simple.
+fn is_simple_query(query: &str, max_chars: usize) -> bool {
+    let query = query.trim();
+    if query.is_empty() || query.chars().count() > max_chars || query.contains('\n') {
+        return false;
+    }
+    
+    let lower = query.to_lowercase();
+    let needs_tools = COMPLEX_QUERY_MARKERS.iter().any(|marker| lower.contains(marker));
+    let has_arithmetic = query.chars().any(|c| c.is_ascii_digit())
+        && query.chars().any(|c| matches!(c, '+' | '*' | '/' | '^' | '%' | '='));
+    
+    !needs_tools && !has_arithmetic && query.matches('?').count() <= 1
+}
+
+// Run `fut` to completion unless the deadline passes first
+async fn within<F: Future>(deadline: Instant, fut: F) -> Option<F::Output> {
+    timeout_at(deadline, fut).await.ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatResult, MockLlm, ToolCall, ToolDefinition};
+    use async_trait::async_trait;
+    use serde_json::json;
+    use std::collections::VecDeque;
+    use std::sync::Mutex;
+
+    // Replies "Done." and keeps the system prompt and messages of the last call
+    #[derive(Default)]
+    struct RecordingLlm {
+        system_prompt: std::sync::Mutex<String>,
+        messages: std::sync::Mutex<Vec<ChatMessage>>,
+    }
+
+    #[async_trait]
+    impl LlmProvider for RecordingLlm {
+        async fn chat_with_usage(&self, system_prompt: &str, messages: &[ChatMessage]) -> Result<ChatResult> {
+            *self.system_prompt.lock().unwrap() = system_prompt.to_string();
+            *self.messages.lock().unwrap() = messages.to_vec();
+            Ok(ChatResult {
+                content: "Done.".to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    // Replies the same text to everything
+    struct FixedLlm(&'static str);
+
+    #[async_trait]
+    impl LlmProvider for FixedLlm {
+        async fn chat_with_usage(&self, _system_prompt: &str, _messages: &[ChatMessage]) -> Result<ChatResult> {
+            Ok(ChatResult {
+                content: self.0.to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    fn agent() -> Agent {
+        Agent::new(Arc::new(MockLlm))
+    }
+
+    #[test]
+    fn agent_result_round_trips_through_json() {
+        let result = AgentResult {
+            answer: "Paris".to_string(),
+            sources: vec!["Paris is the capital of France.".to_string()],
+            steps_taken: 2,
+            confidence: CONFIDENCE_ANSWERED,
+            follow_ups: vec!["What is the population of Paris?".to_string()],
+        };
+
+        let json = serde_json::to_string(&result).unwrap();
+        let parsed: AgentResult = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(parsed.answer, result.answer);
+        assert_eq!(parsed.sources, result.sources);
+        assert_eq!(parsed.steps_taken, 2);
+        assert_eq!(parsed.confidence, CONFIDENCE_ANSWERED);
+        assert_eq!(parsed.follow_ups, result.follow_ups);
+    }
+
+    #[test]
+    fn agent_result_omits_empty_follow_ups() {
+        let result = AgentResult {
+            answer: "42".to_string(),
+            sources: Vec::new(),
+            steps_taken: 0,
+            confidence: CONFIDENCE_DIRECT,
+            follow_ups: Vec::new(),
+        };
+
+        let json = serde_json::to_value(&result).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "answer": "42", "sources": [], "steps_taken": 0, "confidence": 1.0 })
+        );
+        assert!(serde_json::from_value::<AgentResult>(json).unwrap().follow_ups.is_empty());
+    }
+
+    #[tokio::test]
+    async fn within_gives_up_at_the_deadline() {
+        let started = Instant::now();
+        let slow = within(started + Duration::from_millis(50), sleep(Duration::from_secs(5))).await;
+
+        assert!(slow.is_none());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        assert_eq!(within(Instant::now() + Duration::from_secs(1), async { 7 }).await, Some(7));
+    }
+
+    #[tokio::test]
+    async fn partial_answer_lists_what_was_found() {
+        let observations = vec![
+            Observation::new(ObservationKind::SearchResults, "Paris is in France.".to_string(), "a1".to_string()),
+            Observation::new(ObservationKind::Calculation, "4".to_string(), "a2".to_string()),
+        ];
+
+        let result = agent()
+            .generate_partial_answer_from_observations(&observations, "capital?", 2)
+            .await
+            .unwrap();
+
+        assert!(result.answer.contains("Finding 1: Paris is in France."));
+        assert!(result.answer.contains("Finding 2: 4"));
+        assert_eq!(result.sources, vec!["Paris is in France.", "4"]);
+        assert_eq!(result.steps_taken, 2);
+        assert_eq!(result.confidence, CONFIDENCE_PARTIAL);
+    }
+
+    #[test]
+    fn observations_are_tagged_by_the_action_that_made_them() {
+        let action = |tool: &str| AgentAction::new(tool.to_string(), serde_json::json!({}));
+
+        let search = action("search");
+        let observation = Observation::of_action(&search, Ok("Paris is in France.".to_string()));
+        assert_eq!(observation.kind, ObservationKind::SearchResults);
+        assert_eq!(observation.action_id, search.id);
+
+        assert_eq!(Observation::of_action(&action("calculate"), Ok("4".to_string())).kind, ObservationKind::Calculation);
+        assert_eq!(Observation::of_action(&action("weather"), Ok("Sunny".to_string())).kind, ObservationKind::ToolOutput);
+
+        let failed = Observation::of_action(&action("search"), Err(anyhow::anyhow!("Unknown tool: search")));
+        assert_eq!(failed.kind, ObservationKind::Error);
+        assert_eq!(failed.content, "Error: Unknown tool: search");
+    }
+
+    #[test]
+    fn observations_without_a_kind_read_as_tool_output() {
+        let json = serde_json::json!({
+            "id": "o1",
+            "content": "Sunny",
+            "action_id": "a1",
+            "timestamp": "2026-01-01T00:00:00Z",
+        });
+        assert_eq!(serde_json::from_value::<Observation>(json).unwrap().kind, ObservationKind::ToolOutput);
+    }
+
+    #[tokio::test]
+    async fn the_final_answer_is_told_what_each_observation_is() {
+        let llm = Arc::new(RecordingLlm::default());
+        let agent = Agent::new(llm.clone());
+        let thoughts = vec![Thought::new("Look it up".to_string()), Thought::new("Work it out".to_string())];
+        let actions = vec![
+            AgentAction::new("search".to_string(), serde_json::json!({ "query": "capital of France" })),
+            AgentAction::new("calculate".to_string(), serde_json::json!({ "expression": "2+2" })),
+        ];
+        let observations = vec![
+            Observation::of_action(&actions[0], Ok("Paris is in France.".to_string())),
+            Observation::of_action(&actions[1], Err(anyhow::anyhow!("division by zero"))),
+        ];
+
+        agent.generate_final_answer(DEFAULT_PERSONA, &thoughts, &actions, &observations, "capital?", "English").await.unwrap();
+
+        let messages = llm.messages.lock().unwrap().clone();
+        let observed: Vec<&str> = messages
+            .iter()
+            .filter(|m| m.content.starts_with("Observation"))
+            .map(|m| m.content.as_str())
+            .collect();
+        assert_eq!(
+            observed,
+            vec![
+                "Observation 1 (search results; cite what they say rather than adding to it): Paris is in France.",
+                "Observation 2 (the action failed; don't rely on it): Error: division by zero",
+            ]
+        );
+    }
+
+    #[test]
+    fn strips_leaked_scaffolding_from_answers() {
+        let leaked = "Thought: I should search.\nACTION: search\nPARAMETERS: {\n  \"query\": \"rust release\"\n}\n\nFinal Answer: Rust 1.80 came out in July.";
+        assert_eq!(strip_scaffolding(leaked), "Rust 1.80 came out in July.");
+
+        let echoed = "Observation 1 (search results; cite what they say rather than adding to it): Paris is in France.\nThe capital of France is Paris.";
+        assert_eq!(strip_scaffolding(echoed), "The capital of France is Paris.");
+
+        let numbered = "THOUGHT 2: almost done\nIt is 4.\n\n\nAction 3: calculate\nParameters: {\"expression\": \"2+2\"}\nThat's all.";
+        assert_eq!(strip_scaffolding(numbered), "It is 4.\n\nThat's all.");
+    }
+
+    #[test]
+    fn leaves_legitimate_content_alone() {
+        let agenda = "Action: items for the meeting\n- Book a room\n\n```\nACTION: search\nPARAMETERS: {}\n```\nMy thought: keep it short.";
+        assert_eq!(strip_scaffolding(agenda), agenda);
+
+        // An answer that is nothing but scaffolding is better than no answer
+        assert_eq!(strip_scaffolding("  ACTION: search  "), "ACTION: search");
+    }
+
+    #[test]
+    fn detects_the_query_language() {
+        assert_eq!(response_language("What is the tallest mountain in the whole world?"), "English");
+        assert_eq!(response_language("¿Cuál es la montaña más alta del mundo entero?"), "Spanish");
+        assert_eq!(response_language("Quelle est la plus haute montagne du monde entier ?"), "French");
+        assert_eq!(response_language("Welcher ist der höchste Berg der ganzen Welt?"), "German");
+    }
+
+    #[test]
+    fn unsure_detection_falls_back_to_english() {
+        assert_eq!(response_language(""), "English");
+        assert_eq!(response_language("ok"), "English");
+    }
+
+    #[test]
+    fn system_prompt_names_the_answer_language() {
+        let prompt = agent().create_system_prompt(DEFAULT_PERSONA, "¿Qué hora es?", "Spanish");
+
+        assert!(prompt.contains("Write your final answer in Spanish"), "{}", prompt);
+        assert!(prompt.contains("The user has asked: \"¿Qué hora es?\""));
+    }
+
+    #[test]
+    fn system_prompt_opens_with_the_persona() {
+        let prompt = agent().create_system_prompt("You are Sparky, a cheerful pirate.", "hi", "English");
+
+        assert!(prompt.starts_with("You are Sparky, a cheerful pirate.\n"), "{}", prompt);
+    }
+
+    #[tokio::test]
+    async fn uses_the_configured_persona_without_a_store() {
+        let agent = agent().with_config(AgentConfig {
+            persona: "You are Sparky.".to_string(),
+            ..AgentConfig::default()
+        });
+
+        assert_eq!(agent.load_persona("group:1").await, "You are Sparky.");
+    }
+
+    #[tokio::test]
+    async fn picks_up_the_chat_persona_override() {
+        let store = Arc::new(crate::memory::MemoryStore::new(":memory:").unwrap());
+        store.set_chat_persona("group:1", "admin", "You are Sparky.").await.unwrap();
+        let agent = agent().with_memory_store(store.clone());
+
+        assert_eq!(agent.load_persona("group:1").await, "You are Sparky.");
+        assert_eq!(agent.load_persona("group:2").await, DEFAULT_PERSONA);
+
+        store.reset_chat_persona("group:1").await.unwrap();
+        assert_eq!(agent.load_persona("group:1").await, DEFAULT_PERSONA);
+    }
+
+    #[tokio::test]
+    async fn the_final_answer_speaks_as_the_chat_persona() {
+        let store = Arc::new(crate::memory::MemoryStore::new(":memory:").unwrap());
+        store.set_chat_persona("group:1", "admin", "You are Sparky.").await.unwrap();
+        let llm = Arc::new(RecordingLlm::default());
+        let agent = Agent::new(llm.clone()).with_memory_store(store);
+
+        let persona = agent.load_persona("group:1").await;
+        agent.generate_final_answer(&persona, &[], &[], &[], "capital?", "English").await.unwrap();
+
+        let system_prompt = llm.system_prompt.lock().unwrap().clone();
+        assert!(system_prompt.starts_with("You are Sparky.\n"), "{}", system_prompt);
+        assert!(!system_prompt.contains("KarmaSpark"), "{}", system_prompt);
+    }
+
+    #[test]
+    fn parses_follow_up_questions() {
+        let response = "Here are some ideas:\n1. What is the population of Paris?\n- How old is the Eiffel Tower?\n\n* Is Paris expensive?\n4) What about Lyon?";
+
+        assert_eq!(
+            parse_follow_ups(response),
+            vec![
+                "What is the population of Paris?".to_string(),
+                "How old is the Eiffel Tower?".to_string(),
+                "Is Paris expensive?".to_string(),
+            ]
+        );
+        assert!(parse_follow_ups("No questions here.").is_empty());
+    }
+
+    #[test]
+    fn trims_the_oldest_messages_to_the_budget() {
+        let messages: Vec<ChatMessage> = (0..20)
+            .map(|i| ChatMessage {
+                role: "user".to_string(),
+                content: format!("{:02} {}", i, "x".repeat(397)),
+            })
+            .collect();
+        // 100 tokens of content plus the overhead each
+        let budget = 500;
+
+        let trimmed = trim_to_budget(messages, budget);
+
+        let total: usize = trimmed
+            .iter()
+            .map(|message| estimate_tokens(&message.content) + MESSAGE_OVERHEAD_TOKENS)
+            .sum();
+        assert!(total <= budget, "{} tokens", total);
+        assert_eq!(trimmed.len(), 4);
+        assert!(trimmed[0].content.starts_with("16 "));
+        assert!(trimmed[3].content.starts_with("19 "));
+    }
+
+    #[test]
+    fn keeps_the_last_message_even_over_budget() {
+        let messages = vec![
+            ChatMessage { role: "user".to_string(), content: "hello".to_string() },
+            ChatMessage { role: "user".to_string(), content: "x".repeat(4000) },
+        ];
+
+        let trimmed = trim_to_budget(messages.clone(), 10);
+        assert_eq!(trimmed.len(), 1);
+        assert_eq!(trimmed[0].content, messages[1].content);
+
+        assert_eq!(trim_to_budget(messages, 10_000).len(), 2);
+    }
+
+    #[test]
+    fn stops_at_the_first_repeated_step() {
+        // What a looping model asks for, step after step
+        let replies = [
+            r#"search_memory {"query":"project deadline"}"#,
+            r#"calculate {"expression":"14 * 3"}"#,
+            r#"search_memory {"query":"Project  deadline"}"#,
+            r#"search_memory {"query":"project deadline"}"#,
+        ];
+
+        let mut recent_steps = Vec::new();
+        let stopped_at = replies.iter().position(|step| {
+            let repeated = is_repetition(&recent_steps, step);
+            recent_steps.push(step.to_string());
+            repeated
+        });
+
+        assert_eq!(stopped_at, Some(2));
+    }
+
+    #[test]
+    fn different_steps_are_not_repetition() {
+        let recent_steps = vec![
+            "search_memory {\"query\":\"project deadline\"}".to_string(),
+            "The deadline is Friday the 14th.".to_string(),
+        ];
+
+        assert!(!is_repetition(&recent_steps, "calculate {\"expression\":\"14 * 3\"}"));
+        assert!(!is_repetition(&recent_steps, "The deadline moved to Monday the 17th after the review."));
+        assert!(!is_repetition(&[], "anything"));
+    }
+
+    #[test]
+    fn only_recent_steps_count() {
+        let mut recent_steps = vec!["weather {\"city\":\"Paris\"}".to_string()];
+        recent_steps.extend((0..REPETITION_WINDOW).map(|i| format!("calculate {{\"expression\":\"{} + 1\"}}", i)));
+
+        assert!(!is_repetition(&recent_steps, "weather {\"city\":\"Paris\"}"));
+    }
+
+    #[test]
+    fn recognizes_the_cannot_answer_marker() {
+        assert_eq!(cannot_answer("CANNOT_ANSWER"), Some(NOT_ENOUGH_INFORMATION.to_string()));
+        assert_eq!(
+            cannot_answer("  CANNOT_ANSWER: I have no sales figures for 2026."),
+            Some(format!("{} I have no sales figures for 2026.", NOT_ENOUGH_INFORMATION))
+        );
+        assert_eq!(cannot_answer("Paris is the capital of France."), None);
+    }
+
+    #[tokio::test]
+    async fn an_unsure_final_answer_becomes_the_honest_reply() {
+        let agent = Agent::new(Arc::new(FixedLlm("CANNOT_ANSWER - nothing I found mentions the budget.")));
+
+        let answer = agent.generate_final_answer(DEFAULT_PERSONA, &[], &[], &[], "What is the budget?", "English").await.unwrap();
+
+        assert_eq!(
+            cannot_answer(&answer),
+            Some("I don't have enough information to answer that. nothing I found mentions the budget.".to_string())
+        );
+    }
+
+    #[test]
+    fn short_plain_questions_skip_planning() {
+        for query in ["What's the capital of France?", "  Who wrote Hamlet?  ", "Explain borrowing in Rust"] {
+            assert!(is_simple_query(query, 80), "{}", query);
+        }
+    }
+
+    #[test]
+    fn tool_shaped_or_long_questions_are_planned() {
+        for query in [
+            "What's the weather in Paris?",
+            "Search for the latest Rust release",
+            "What is 17 * 23?",
+            "Who is the CEO? Where is the company based?",
+            "First question\nSecond question",
+            "",
+        ] {
+            assert!(!is_simple_query(query, 80), "{:?}", query);
+        }
+
+        let long = format!("Why {}?", "really ".repeat(20));
+        assert!(!is_simple_query(&long, 80));
+        assert!(is_simple_query(&long, 500));
+        // The fast path is off at 0
+        assert!(!is_simple_query("Who wrote Hamlet?", 0));
+    }
+
+    #[tokio::test]
+    async fn an_unsure_direct_answer_falls_back_to_planning() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let unsure = Agent::new(Arc::new(FixedLlm("CANNOT_ANSWER")));
+        assert_eq!(unsure.answer_directly("", &[], "What is 2 + 2?", "English", deadline, 1000).await, None);
+
+        let sure = Agent::new(Arc::new(FixedLlm(" Four. ")));
+        assert_eq!(
+            sure.answer_directly("", &[], "What is 2 + 2?", "English", deadline, 1000).await,
+            Some("Four.".to_string())
+        );
+    }
+
+    #[test]
+    fn progress_names_the_step_and_what_it_is_doing() {
+        let config = AgentConfig::default();
+        let steps = [
+            progress_text(1, config.max_steps, "thinking"),
+            progress_text(2, config.max_steps, &tool_activity("search")),
+            progress_text(3, config.max_steps, &tool_activity("weather")),
+        ];
+
+        assert_eq!(
+            steps,
+            [
+                "Thinking... (step 1/3: thinking)",
+                "Thinking... (step 2/3: searching)",
+                "Thinking... (step 3/3: using weather)",
+            ]
+        );
+        assert_eq!(tool_activity("calculate"), "calculating");
+    }
+
+    #[test]
+    fn progress_is_off_by_default() {
+        assert!(!AgentConfig::default().show_progress);
+    }
+
+    // Answers like MockLlm, but reports a recent rate limit
+    struct RateLimitedLlm;
+
+    #[async_trait]
+    impl LlmProvider for RateLimitedLlm {
+        async fn chat_with_usage(&self, system_prompt: &str, messages: &[ChatMessage]) -> Result<ChatResult> {
+            MockLlm.chat_with_usage(system_prompt, messages).await
+        }
+
+        fn rate_limited_within(&self, _window: Duration) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn steps_run_back_to_back_by_default() {
+        assert_eq!(agent().step_delay(), Duration::ZERO);
+
+        let configured = agent().with_config(AgentConfig {
+            step_delay: Duration::from_millis(500),
+            ..AgentConfig::default()
+        });
+        assert_eq!(configured.step_delay(), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn steps_slow_down_after_a_rate_limit() {
+        let agent = Agent::new(Arc::new(RateLimitedLlm));
+        assert_eq!(agent.step_delay(), RATE_LIMITED_STEP_DELAY);
+
+        let slower = Agent::new(Arc::new(RateLimitedLlm)).with_config(AgentConfig {
+            step_delay: Duration::from_secs(5),
+            ..AgentConfig::default()
+        });
+        assert_eq!(slower.step_delay(), Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn follow_ups_see_the_previous_answer() {
+        let store = Arc::new(crate::memory::MemoryStore::new(":memory:").unwrap());
+        let agent = agent().with_memory_store(store);
+        agent
+            .remember_turn("group:1", "alice", "What is the capital of France?", "Paris.")
+            .await;
+
+        let history = agent.load_history("group:1").await;
+        let messages = agent.build_message_history(&history, &[], &[], &[]);
+
+        let seeded: Vec<(&str, &str)> = messages.iter().map(|m| (m.role.as_str(), m.content.as_str())).collect();
+        assert_eq!(
+            seeded,
+            vec![
+                ("user", "Earlier question: What is the capital of France?"),
+                ("assistant", "Earlier answer: Paris."),
+            ]
+        );
+        assert!(agent.load_history("group:2").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn history_keeps_the_latest_turns() {
+        let store = Arc::new(crate::memory::MemoryStore::new(":memory:").unwrap());
+        let agent = agent()
+            .with_memory_store(store)
+            .with_config(AgentConfig {
+                history_turns: 2,
+                ..AgentConfig::default()
+            });
+        for i in 1..=3 {
+            agent
+                .remember_turn("group:1", "alice", &format!("Question {}", i), &format!("Answer {}", i))
+                .await;
+        }
+
+        let questions: Vec<String> = agent
+            .load_history("group:1")
+            .await
+            .into_iter()
+            .map(|turn| turn.question)
+            .collect();
+
+        assert_eq!(questions, vec!["Question 2", "Question 3"]);
+
+        let without_history = agent.with_config(AgentConfig {
+            history_turns: 0,
+            ..AgentConfig::default()
+        });
+        assert!(without_history.load_history("group:1").await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn the_mock_llm_answers_simple_questions_directly() {
+        let deadline = Instant::now() + Duration::from_secs(5);
+
+        let answer = agent().answer_directly(DEFAULT_PERSONA, &[], "What is 2 + 2?", "English", deadline, 1000).await;
+
+        assert_eq!(answer, Some("[mock] What is 2 + 2?".to_string()));
+    }
+
+    // Answers tool-offering calls from a script, then with plain text once it runs out.
+    // Plain calls, from tools and the final answer, reply "4" and are recorded.
+    struct ScriptedLlm {
+        replies: Mutex<VecDeque<ToolReply>>,
+        system_prompts: Mutex<Vec<String>>,
+    }
+
+    impl ScriptedLlm {
+        fn new(replies: Vec<ToolReply>) -> Self {
+            Self {
+                replies: Mutex::new(replies.into()),
+                system_prompts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for ScriptedLlm {
+        async fn chat_with_usage(&self, system_prompt: &str, _messages: &[ChatMessage]) -> Result<ChatResult> {
+            self.system_prompts.lock().unwrap().push(system_prompt.to_string());
+            Ok(ChatResult {
+                content: "4".to_string(),
+                usage: None,
+            })
+        }
+
+        async fn chat_with_tools(
+            &self,
+            _system_prompt: &str,
+            _messages: &[ChatMessage],
+            _tools: &[ToolDefinition],
+        ) -> Result<ToolReply> {
+            let next = self.replies.lock().unwrap().pop_front();
+            Ok(next.unwrap_or_else(|| ToolReply::Text("The answer is 4.".to_string())))
+        }
+    }
+
+    fn calculate(expression: &str) -> ToolReply {
+        ToolReply::ToolCalls(vec![ToolCall {
+            name: "calculate".to_string(),
+            arguments: json!({ "expression": expression }),
+        }])
+    }
+
+    fn scripted_agent(llm: Arc<ScriptedLlm>, config: AgentConfig) -> Agent {
+        Agent::new(llm).with_config(config)
+    }
+
+    #[tokio::test]
+    async fn progress_follows_the_planning_steps() {
+        let llm = Arc::new(ScriptedLlm::new(vec![calculate("2 + 2")]));
+        let agent = scripted_agent(llm, AgentConfig {
+            show_progress: true,
+            ..AgentConfig::default()
+        });
+        let updates = Mutex::new(Vec::new());
+        let record = |text: String| updates.lock().unwrap().push(text);
+
+        let result = agent.plan("group:1", "alice", "What is 2 + 2?", true, &record).await.unwrap();
+
+        assert_eq!(result.steps_taken, 1);
+        assert_eq!(result.answer, "The answer is 4.");
+        // Each step shows while the model thinks and again while its tool runs
+        assert_eq!(
+            *updates.lock().unwrap(),
+            vec![
+                "Thinking... (step 1/3: thinking)",
+                "Thinking... (step 1/3: calculating)",
+                "Thinking... (step 2/3: thinking)",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn progress_stays_quiet_unless_asked_for() {
+        for (requested, configured) in [(false, true), (true, false)] {
+            let llm = Arc::new(ScriptedLlm::new(vec![calculate("2 + 2")]));
+            let agent = scripted_agent(llm, AgentConfig {
+                show_progress: configured,
+                ..AgentConfig::default()
+            });
+            let updates = Mutex::new(Vec::new());
+            let record = |text: String| updates.lock().unwrap().push(text);
+
+            let result = agent.plan("group:1", "alice", "What is 2 + 2?", requested, &record).await.unwrap();
+
+            assert_eq!(result.answer, "The answer is 4.");
+            assert!(updates.lock().unwrap().is_empty());
         }
     }
-}
\ No newline at end of file
+
+    fn ignore_progress(_text: String) {}
+
+    #[tokio::test]
+    async fn a_repeated_tool_call_ends_planning_early() {
+        let llm = Arc::new(ScriptedLlm::new(vec![calculate("2 + 2"), calculate("2 + 2"), calculate("2 + 2")]));
+        let config = AgentConfig {
+            max_steps: 5,
+            ..AgentConfig::default()
+        };
+        let max_steps = config.max_steps;
+        let agent = scripted_agent(llm.clone(), config);
+
+        let result = agent.plan("group:1", "alice", "What is 2 + 2?", false, &ignore_progress).await.unwrap();
+
+        assert!(result.steps_taken < max_steps);
+        assert_eq!(result.steps_taken, 1);
+        // The calculation ran once, then the final answer was written from it
+        assert_eq!(result.sources, vec!["4"]);
+        assert_eq!(result.confidence, CONFIDENCE_SUMMARIZED);
+        let system_prompts = llm.system_prompts.lock().unwrap();
+        assert_eq!(system_prompts.len(), 2);
+        assert!(system_prompts[1].starts_with(DEFAULT_PERSONA));
+        assert!(system_prompts[1].contains("Based on the following thought process"));
+    }
+
+    // Takes far longer than any test timeout to answer
+    struct SlowLlm;
+
+    #[async_trait]
+    impl LlmProvider for SlowLlm {
+        async fn chat_with_usage(&self, system_prompt: &str, messages: &[ChatMessage]) -> Result<ChatResult> {
+            sleep(Duration::from_secs(30)).await;
+            MockLlm.chat_with_usage(system_prompt, messages).await
+        }
+    }
+
+    #[tokio::test]
+    async fn a_slow_model_gets_a_partial_answer_at_the_deadline() {
+        let agent = Agent::new(Arc::new(SlowLlm)).with_config(AgentConfig {
+            timeout: Duration::from_millis(200),
+            ..AgentConfig::default()
+        });
+        let started = Instant::now();
+
+        let result = agent.plan("group:1", "alice", "What is 2 + 2?", false, &ignore_progress).await.unwrap();
+
+        let elapsed = started.elapsed();
+        assert!(elapsed >= Duration::from_millis(200), "returned after {:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "returned after {:?}", elapsed);
+        assert_eq!(result.confidence, CONFIDENCE_PARTIAL);
+        assert_eq!(result.steps_taken, 0);
+        assert!(result.answer.contains("What is 2 + 2?"));
+    }
+}
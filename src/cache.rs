@@ -0,0 +1,126 @@
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+struct Entry<V> {
+    value: V,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+struct Inner<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    tick: u64,
+}
+
+/// A small thread-safe LRU cache whose entries also expire after a fixed TTL
+pub struct TtlCache<K, V> {
+    capacity: usize,
+    ttl: Duration,
+    inner: Mutex<Inner<K, V>>,
+}
+
+impl<K: Hash + Eq + Clone, V: Clone> TtlCache<K, V> {
+    pub fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity,
+            ttl,
+            inner: Mutex::new(Inner {
+                entries: HashMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        let expired = match inner.entries.get_mut(key) {
+            Some(entry) if entry.inserted_at.elapsed() <= self.ttl => {
+                entry.last_used = tick;
+                return Some(entry.value.clone());
+            }
+            Some(_) => true,
+            None => false,
+        };
+
+        if expired {
+            inner.entries.remove(key);
+        }
+        None
+    }
+
+    /// The cached value for `key`, inserting `make()` first if there is none.
+    /// Get and insert happen under one lock, so concurrent callers share one value.
+    pub fn get_or_insert_with<F: FnOnce() -> V>(&self, key: K, make: F) -> V {
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        if let Some(entry) = inner.entries.get_mut(&key) {
+            if entry.inserted_at.elapsed() <= self.ttl {
+                entry.last_used = tick;
+                return entry.value.clone();
+            }
+        }
+
+        let value = make();
+        if self.capacity == 0 {
+            return value;
+        }
+
+        self.evict_if_full(&mut inner, &key);
+
+        inner.entries.insert(
+            key,
+            Entry {
+                value: value.clone(),
+                inserted_at: Instant::now(),
+                last_used: tick,
+            },
+        );
+        value
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut inner = self.inner.lock().unwrap();
+        inner.tick += 1;
+        let tick = inner.tick;
+
+        self.evict_if_full(&mut inner, &key);
+
+        inner.entries.insert(
+            key,
+            Entry {
+                value,
+                inserted_at: Instant::now(),
+                last_used: tick,
+            },
+        );
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.inner.lock().unwrap().entries.remove(key);
+    }
+
+    // Evict the least recently used entry when full
+    fn evict_if_full(&self, inner: &mut Inner<K, V>, key: &K) {
+        if inner.entries.len() >= self.capacity && !inner.entries.contains_key(key) {
+            let oldest = inner
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                inner.entries.remove(&oldest);
+            }
+        }
+    }
+}
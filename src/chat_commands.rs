@@ -0,0 +1,130 @@
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Commands admins have turned off in particular chats. Commands are on unless listed here.
+#[derive(Debug, Clone)]
+pub struct ChatCommandStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl ChatCommandStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS disabled_commands (
+                chat_id TEXT NOT NULL,
+                command TEXT NOT NULL,
+                disabled_by TEXT NOT NULL,
+                disabled_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, command)
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn disable(&self, chat_id: &str, command: &str, user_id: &str) -> Result<()> {
+        let chat_id = chat_id.to_string();
+        let command = command.to_string();
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO disabled_commands (chat_id, command, disabled_by, disabled_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(chat_id, command) DO NOTHING",
+                params![chat_id, command, user_id, Utc::now().to_rfc3339()],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+
+    /// Turn the command back on; returns whether it had been off
+    pub async fn enable(&self, chat_id: &str, command: &str) -> Result<bool> {
+        let chat_id = chat_id.to_string();
+        let command = command.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+
+            let deleted = conn.execute(
+                "DELETE FROM disabled_commands WHERE chat_id = ?1 AND command = ?2",
+                params![chat_id, command],
+            )?;
+
+            Ok(deleted > 0)
+        }).await?
+    }
+
+    pub async fn is_disabled(&self, chat_id: &str, command: &str) -> Result<bool> {
+        let chat_id = chat_id.to_string();
+        let command = command.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM disabled_commands WHERE chat_id = ?1 AND command = ?2",
+                params![chat_id, command],
+                |row| row.get(0),
+            )?;
+
+            Ok(count > 0)
+        }).await?
+    }
+
+    /// Commands turned off in the chat, alphabetically
+    pub async fn disabled(&self, chat_id: &str) -> Result<Vec<String>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT command FROM disabled_commands WHERE chat_id = ?1 ORDER BY command",
+            )?;
+            let commands = stmt
+                .query_map(params![chat_id], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+
+            Ok(commands)
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn commands_are_turned_off_per_chat() {
+        let store = ChatCommandStore::new(":memory:").unwrap();
+        store.disable("group:1", "weather", "admin").await.unwrap();
+        store.disable("group:1", "ask", "admin").await.unwrap();
+        // Turning it off twice is harmless
+        store.disable("group:1", "weather", "admin").await.unwrap();
+
+        assert!(store.is_disabled("group:1", "weather").await.unwrap());
+        assert!(!store.is_disabled("group:2", "weather").await.unwrap());
+        assert!(!store.is_disabled("group:1", "echo").await.unwrap());
+        assert_eq!(store.disabled("group:1").await.unwrap(), vec!["ask".to_string(), "weather".to_string()]);
+
+        assert!(store.enable("group:1", "weather").await.unwrap());
+        assert!(!store.enable("group:1", "weather").await.unwrap());
+        assert!(!store.is_disabled("group:1", "weather").await.unwrap());
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::Result;
+use oc_bots_sdk::types::{BotCommandScope, Chat};
+use rusqlite::{params, Connection};
+
+/// Stable id of the chat or community a command runs in, used as the key for
+/// everything we store per chat. The format is part of the on-disk data:
+///
+/// - `direct:<canister id>`
+/// - `group:<canister id>`
+/// - `channel:<community canister id>:<channel id>`
+/// - `community:<canister id>`
+pub fn canonical_chat_id(scope: &BotCommandScope) -> String {
+    match scope {
+        BotCommandScope::Chat(chat_details) => match &chat_details.chat {
+            Chat::Direct(canister_id) => format!("direct:{}", canister_id),
+            Chat::Group(canister_id) => format!("group:{}", canister_id),
+            Chat::Channel(community_id, channel_id) => format!("channel:{}:{}", community_id, channel_id),
+        },
+        BotCommandScope::Community(community_details) => {
+            format!("community:{}", community_details.community_id)
+        }
+    }
+}
+
+/// Whether commands may run in `chat_id`. An empty allowlist allows every chat; a
+/// `community:<id>` entry also covers that community's channels.
+pub fn chat_allowed(chat_id: &str, allowlist: &[String]) -> bool {
+    if allowlist.is_empty() {
+        return true;
+    }
+    allowlist.iter().any(|allowed| {
+        if allowed == chat_id {
+            return true;
+        }
+        match (allowed.strip_prefix("community:"), chat_id.strip_prefix("channel:")) {
+            (Some(community_id), Some(channel)) => channel
+                .split_once(':')
+                .is_some_and(|(channel_community, _)| channel_community == community_id),
+            _ => false,
+        }
+    })
+}
+
+/// Whether `id` looks like an id `canonical_chat_id` produces
+pub fn is_canonical_chat_id(id: &str) -> bool {
+    ["direct:", "group:", "channel:", "community:"]
+        .iter()
+        .any(|prefix| id.strip_prefix(prefix).is_some_and(|rest| !rest.is_empty()))
+}
+
+// Map an id written by older versions, which used the SDK's Debug output
+// (e.g. `Group(abcde-cai)`, `Channel(abcde-cai, 42)` or a bare community id),
+// to its canonical form. Returns None for ids that are already canonical.
+fn canonical_from_legacy(legacy: &str) -> Option<String> {
+    let wrapped = |prefix: &str| {
+        legacy
+            .strip_prefix(prefix)
+            .and_then(|rest| rest.strip_suffix(')'))
+            .map(str::trim)
+    };
+
+    if let Some(inner) = wrapped("Direct(") {
+        return Some(format!("direct:{}", inner));
+    }
+    if let Some(inner) = wrapped("Group(") {
+        return Some(format!("group:{}", inner));
+    }
+    if let Some(inner) = wrapped("Channel(") {
+        let (community_id, channel_id) = inner.split_once(',')?;
+        return Some(format!("channel:{}:{}", community_id.trim(), channel_id.trim()));
+    }
+
+    // Community scopes were stored as the bare canister id
+    let is_principal = legacy.contains('-')
+        && legacy.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if is_principal {
+        return Some(format!("community:{}", legacy));
+    }
+
+    None
+}
+
+/// Rewrite legacy chat ids in `table` to their canonical form, returning how many rows changed
+pub(crate) fn migrate_legacy_chat_ids(conn: &Connection, table: &str) -> Result<usize> {
+    let chat_ids: Vec<String> = {
+        let mut stmt = conn.prepare(&format!("SELECT DISTINCT chat_id FROM {}", table))?;
+        let rows = stmt.query_map([], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<_>>()?
+    };
+
+    let mut migrated = 0;
+    for legacy in chat_ids {
+        if let Some(canonical) = canonical_from_legacy(&legacy) {
+            migrated += conn.execute(
+                &format!("UPDATE {} SET chat_id = ?1 WHERE chat_id = ?2", table),
+                params![canonical, legacy],
+            )?;
+        }
+    }
+
+    Ok(migrated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn maps_legacy_ids_to_the_canonical_format() {
+        assert_eq!(canonical_from_legacy("Direct(abcde-cai)").as_deref(), Some("direct:abcde-cai"));
+        assert_eq!(canonical_from_legacy("Group(abcde-cai)").as_deref(), Some("group:abcde-cai"));
+        assert_eq!(
+            canonical_from_legacy("Channel(abcde-cai, 42)").as_deref(),
+            Some("channel:abcde-cai:42")
+        );
+        assert_eq!(canonical_from_legacy("abcde-cai").as_deref(), Some("community:abcde-cai"));
+    }
+
+    #[test]
+    fn leaves_canonical_ids_alone() {
+        for id in ["direct:abcde-cai", "group:abcde-cai", "channel:abcde-cai:42", "community:abcde-cai"] {
+            assert_eq!(canonical_from_legacy(id), None, "{}", id);
+            assert!(is_canonical_chat_id(id), "{}", id);
+        }
+        assert!(!is_canonical_chat_id("group:"));
+        assert!(!is_canonical_chat_id("Group(abcde-cai)"));
+    }
+
+    #[test]
+    fn migrates_legacy_rows() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute("CREATE TABLE notes (chat_id TEXT NOT NULL)", []).unwrap();
+        for chat_id in ["Group(abcde-cai)", "Group(abcde-cai)", "channel:abcde-cai:1"] {
+            conn.execute("INSERT INTO notes (chat_id) VALUES (?1)", params![chat_id]).unwrap();
+        }
+
+        assert_eq!(migrate_legacy_chat_ids(&conn, "notes").unwrap(), 2);
+        let groups: i64 = conn
+            .query_row("SELECT COUNT(*) FROM notes WHERE chat_id = 'group:abcde-cai'", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(groups, 2);
+        assert_eq!(migrate_legacy_chat_ids(&conn, "notes").unwrap(), 0);
+    }
+
+    #[test]
+    fn community_entries_allow_their_channels() {
+        let allowlist = vec!["community:abcde-cai".to_string(), "group:fghij-cai".to_string()];
+
+        assert!(chat_allowed("group:fghij-cai", &allowlist));
+        assert!(chat_allowed("channel:abcde-cai:7", &allowlist));
+        assert!(!chat_allowed("channel:klmno-cai:7", &allowlist));
+        assert!(!chat_allowed("group:abcde-cai", &allowlist));
+        assert!(chat_allowed("direct:anyone", &[]));
+    }
+
+    #[test]
+    fn only_listed_chats_are_allowed() {
+        let allowlist = vec!["group:fghij-cai".to_string(), "direct:alice".to_string()];
+
+        assert!(chat_allowed("group:fghij-cai", &allowlist));
+        assert!(chat_allowed("direct:alice", &allowlist));
+        assert!(!chat_allowed("direct:bob", &allowlist));
+        assert!(!chat_allowed("group:other-cai", &allowlist));
+        assert!(!chat_allowed("channel:fghij-cai:1", &allowlist));
+    }
+}
@@ -0,0 +1,172 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    // Calls go through; counts failures in a row
+    Closed { consecutive_failures: usize },
+    // Calls fail fast until the cooldown ends
+    Open { until: Instant },
+    // One probe call is in flight to see whether the service has recovered
+    HalfOpen { probe_started: Instant },
+}
+
+/// Stops calling a failing service for a while, so callers get a fast error instead of
+/// waiting on requests that are bound to fail. After `failure_threshold` failures in a
+/// row it opens for `cooldown`, then lets a single probe through: success closes it
+/// again, failure reopens it.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+impl CircuitBreaker {
+    pub fn new(failure_threshold: usize, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(State::Closed { consecutive_failures: 0 }),
+        }
+    }
+
+    /// Whether a call may go ahead now. Every allowed call must be followed by
+    /// `record_success` or `record_failure`.
+    pub fn allow(&self) -> bool {
+        self.allow_at(Instant::now())
+    }
+
+    fn allow_at(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        match *state {
+            State::Closed { .. } => true,
+            State::Open { until } if now >= until => {
+                info!("Circuit half-open, probing whether the service has recovered");
+                *state = State::HalfOpen { probe_started: now };
+                true
+            }
+            // A probe that was cancelled never reports back, so allow another after a while
+            State::HalfOpen { probe_started } if now >= probe_started + self.cooldown => {
+                *state = State::HalfOpen { probe_started: now };
+                true
+            }
+            State::Open { .. } | State::HalfOpen { .. } => false,
+        }
+    }
+
+    pub fn record_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        if matches!(*state, State::HalfOpen { .. }) {
+            info!("Circuit closed, the service has recovered");
+        }
+        *state = State::Closed { consecutive_failures: 0 };
+    }
+
+    pub fn record_failure(&self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        let consecutive_failures = match *state {
+            State::Closed { consecutive_failures } => consecutive_failures + 1,
+            // A failed probe reopens straight away
+            State::HalfOpen { .. } => self.failure_threshold,
+            State::Open { .. } => return,
+        };
+
+        if consecutive_failures >= self.failure_threshold {
+            warn!("Circuit open after {} failures in a row, failing fast for {}s",
+                  consecutive_failures, self.cooldown.as_secs());
+            metrics::counter!("karmaspark_llm_circuit_opened_total").increment(1);
+            *state = State::Open { until: now + self.cooldown };
+        } else {
+            *state = State::Closed { consecutive_failures };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_secs(30);
+
+    // A breaker that opened at `now` after three failures
+    fn opened_at(now: Instant) -> CircuitBreaker {
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+        for _ in 0..3 {
+            assert!(breaker.allow_at(now));
+            breaker.record_failure_at(now);
+        }
+        breaker
+    }
+
+    #[test]
+    fn opens_after_the_threshold() {
+        let now = Instant::now();
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        assert!(breaker.allow_at(now));
+
+        breaker.record_failure_at(now);
+        assert!(!breaker.allow_at(now));
+        assert!(!breaker.allow_at(now + COOLDOWN - Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn a_success_resets_the_count() {
+        let now = Instant::now();
+        let breaker = CircuitBreaker::new(3, COOLDOWN);
+
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+        breaker.record_success();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+
+        assert!(breaker.allow_at(now));
+    }
+
+    #[test]
+    fn lets_one_probe_through_after_the_cooldown() {
+        let now = Instant::now();
+        let breaker = opened_at(now);
+
+        let later = now + COOLDOWN;
+        assert!(breaker.allow_at(later));
+        // Only the probe, until it reports back
+        assert!(!breaker.allow_at(later));
+
+        breaker.record_success();
+        assert!(breaker.allow_at(later));
+        assert!(breaker.allow_at(later));
+    }
+
+    #[test]
+    fn a_failed_probe_reopens() {
+        let now = Instant::now();
+        let breaker = opened_at(now);
+
+        let later = now + COOLDOWN;
+        assert!(breaker.allow_at(later));
+        breaker.record_failure_at(later);
+
+        assert!(!breaker.allow_at(later + COOLDOWN - Duration::from_secs(1)));
+        assert!(breaker.allow_at(later + COOLDOWN));
+    }
+
+    #[test]
+    fn a_lost_probe_is_replaced_after_the_cooldown() {
+        let now = Instant::now();
+        let breaker = opened_at(now);
+
+        assert!(breaker.allow_at(now + COOLDOWN));
+        assert!(!breaker.allow_at(now + COOLDOWN + Duration::from_secs(1)));
+        assert!(breaker.allow_at(now + COOLDOWN * 2));
+    }
+}
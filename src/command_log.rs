@@ -0,0 +1,158 @@
+use anyhow::Result;
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// Outcome of one dispatched command
+#[derive(Debug, Clone)]
+pub struct CommandLogEntry {
+    pub user_id: String,
+    pub chat_id: String,
+    pub command: String,
+    pub success: bool,
+    pub latency_ms: u64,
+    pub error: Option<String>,
+}
+
+/// Append-only audit log of who ran which command, and how it went
+#[derive(Debug, Clone)]
+pub struct CommandLogStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl CommandLogStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS command_log (
+                id INTEGER PRIMARY KEY,
+                timestamp TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                chat_id TEXT NOT NULL,
+                command TEXT NOT NULL,
+                success INTEGER NOT NULL,
+                latency_ms INTEGER NOT NULL,
+                error TEXT
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS command_log_chat_idx ON command_log (chat_id, timestamp)",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn record(&self, entry: CommandLogEntry) -> Result<()> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO command_log
+                (timestamp, user_id, chat_id, command, success, latency_ms, error)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    Utc::now().to_rfc3339(),
+                    entry.user_id,
+                    entry.chat_id,
+                    entry.command,
+                    entry.success,
+                    entry.latency_ms,
+                    entry.error,
+                ],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+
+    /// Delete every command the user ran, in any chat; returns how many entries there were
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM command_log WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted)
+        }).await?
+    }
+
+    /// Record `entry` in the background; failures are logged and otherwise ignored
+    pub fn record_in_background(self: &Arc<Self>, entry: CommandLogEntry) {
+        let store = self.clone();
+        tokio::spawn(async move {
+            if let Err(e) = store.record(entry).await {
+                error!("Failed to write command log: {}", e);
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(user_id: &str, success: bool) -> CommandLogEntry {
+        CommandLogEntry {
+            user_id: user_id.to_string(),
+            chat_id: "group:1".to_string(),
+            command: "ask".to_string(),
+            success,
+            latency_ms: 120,
+            error: (!success).then(|| "Internal error".to_string()),
+        }
+    }
+
+    // (user_id, command, success, latency_ms, error) of every row, oldest first
+    fn rows(store: &CommandLogStore) -> Vec<(String, String, bool, u64, Option<String>)> {
+        let conn = store.db.lock().unwrap();
+        let mut stmt = conn
+            .prepare("SELECT user_id, command, success, latency_ms, error FROM command_log ORDER BY id")
+            .unwrap();
+        stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn writes_a_row_per_command() {
+        let store = CommandLogStore::new(":memory:").unwrap();
+
+        store.record(entry("alice", true)).await.unwrap();
+        store.record(entry("bob", false)).await.unwrap();
+
+        assert_eq!(
+            rows(&store),
+            vec![
+                ("alice".to_string(), "ask".to_string(), true, 120, None),
+                ("bob".to_string(), "ask".to_string(), false, 120, Some("Internal error".to_string())),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn writes_in_the_background() {
+        let store = Arc::new(CommandLogStore::new(":memory:").unwrap());
+
+        store.record_in_background(entry("alice", true));
+
+        for _ in 0..100 {
+            if !rows(&store).is_empty() {
+                return;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        panic!("the command log row was never written");
+    }
+}
@@ -4,73 +4,186 @@ use oc_bots_sdk::api::definition::*;
 use oc_bots_sdk::types::BotCommandContext;
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
-use std::sync::LazyLock;
+use reqwest::Url;
 use std::sync::Arc;
 use tracing::{error, info};
 
+use super::{params, Visibility};
 use crate::agent::Agent;
-
-static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Ask::definition);
+use crate::llm::is_rate_limited;
 
 pub struct Ask {
-    pub agent: Arc<Agent>,
+    agent: Arc<Agent>,
+    visibility: Visibility,
+    definition: BotCommandDefinition,
 }
 
 #[async_trait]
 impl CommandHandler<AgentRuntime> for Ask {
     fn definition(&self) -> &BotCommandDefinition {
-        &DEFINITION
+        &self.definition
     }
 
     async fn execute(
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let query = client.context().command.arg::<String>("query").to_string();
+        let query = match params::required_string(client.context(), "query") {
+            Ok(query) => query,
+            Err(e) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+        };
+        let as_json = params::optional_string(client.context(), "format").as_deref() == Some("json");
+        let image = params::optional_string(client.context(), "image");
         
         info!("Processing ask command with query: {}", query);
         
-        // Call agent to plan and execute based on query
-        let (response, _observations) = match self.agent.plan_and_execute(&client, &query).await {
-            Ok((answer, obs)) => (answer, obs),
+        // Questions about an image go straight to the vision model; the rest are planned
+        let result = match image {
+            Some(image) => match parse_image_url(&image) {
+                Ok(image_url) => self.agent.answer_about_image(&client, &query, image_url.as_str()).await,
+                Err(e) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+            },
+            // Progress shows in the placeholder, which everyone sees, so not for private replies
+            None => {
+                let show_progress = !self.visibility.is_ephemeral(false);
+                self.agent.plan_and_execute(&client, &query, show_progress).await
+            }
+        };
+        
+        let (response, is_error) = match result {
+            Ok(result) if as_json => match serde_json::to_string_pretty(&result) {
+                Ok(json) => (format!("```json\n{}\n```", json), false),
+                Err(e) => {
+                    error!("Failed to serialize agent result: {}", e);
+                    (result.answer, false)
+                }
+            },
+            Ok(result) => (with_follow_ups(result.answer, &result.follow_ups), false),
+            Err(e) if is_rate_limited(&e) => {
+                error!("Agent gave up, rate limited: {}", e);
+                return Err(super::rate_limited_error());
+            }
             Err(e) => {
                 error!("Agent error: {}", e);
-                (format!("I'm sorry, I encountered an error: {}", e), Vec::new())
+                (format!("I'm sorry, I encountered an error: {}", e), true)
             }
         };
         
         info!("Ask command response: {}", response);
         
-        let message = client
-            .send_text_message(response)
-            .with_block_level_markdown(true)
-            .execute_then_return_message(|_, _| ());
-
-        Ok(SuccessResult { message })
+        Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)))
     }
 }
 
 impl Ask {
-    fn definition() -> BotCommandDefinition {
+    /// `max_length` caps how long a question OpenChat accepts
+    pub fn new(agent: Arc<Agent>, visibility: Visibility, max_length: u16) -> Self {
+        Self {
+            agent,
+            visibility,
+            definition: Self::definition(max_length),
+        }
+    }
+    
+    /// Offer an optional image URL to ask about; needs an LLM client with a vision model
+    pub fn with_image_input(mut self) -> Self {
+        self.definition.params.push(BotCommandParam {
+            name: "image".to_string(),
+            description: Some("Link to an image or screenshot to ask about".to_string()),
+            placeholder: Some("https://example.com/screenshot.png".to_string()),
+            required: false,
+            param_type: BotCommandParamType::StringParam(StringParam {
+                min_length: 1,
+                max_length: 2000,
+                choices: Vec::new(),
+                multi_line: false,
+            }),
+        });
+        self
+    }
+    
+    fn definition(max_length: u16) -> BotCommandDefinition {
         BotCommandDefinition {
             name: "ask".to_string(),
             description: Some("Ask KarmaSpark a question and get an intelligent response".to_string()),
             placeholder: Some("Thinking...".to_string()),
-            params: vec![BotCommandParam {
-                name: "query".to_string(),
-                description: Some("Your question or request".to_string()),
-                placeholder: Some("What would you like to know?".to_string()),
-                required: true,
-                param_type: BotCommandParamType::StringParam(StringParam {
-                    min_length: 1,
-                    max_length: 10000,
-                    choices: Vec::new(),
-                    multi_line: true,
-                }),
-            }],
+            params: vec![
+                BotCommandParam {
+                    name: "query".to_string(),
+                    description: Some("Your question or request".to_string()),
+                    placeholder: Some("What would you like to know?".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+                BotCommandParam {
+                    name: "format".to_string(),
+                    description: Some("Reply as readable text (default) or structured JSON".to_string()),
+                    placeholder: Some("Choose a format".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "text".to_string(),
+                                value: "text".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "json".to_string(),
+                                value: "json".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+            ],
             permissions: BotPermissions::from_message_permission(MessagePermission::Text),
             default_role: None,
             direct_messages: Some(true),
         }
     }
-} 
\ No newline at end of file
+}
+
+// Images are fetched by the LLM provider, which only takes web links
+fn parse_image_url(image: &str) -> Result<Url, String> {
+    match Url::parse(image) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => Ok(url),
+        _ => Err(format!("\"{}\" isn't an http or https link to an image.", image)),
+    }
+}
+
+// The answer followed by any suggested questions as a numbered list
+fn with_follow_ups(answer: String, follow_ups: &[String]) -> String {
+    if follow_ups.is_empty() {
+        return answer;
+    }
+    
+    let suggestions: Vec<String> = follow_ups
+        .iter()
+        .enumerate()
+        .map(|(i, question)| format!("{}. {}", i + 1, question))
+        .collect();
+    
+    format!("{}\n\n**You might also ask:**\n{}", answer, suggestions.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn appends_follow_ups_as_a_numbered_list() {
+        let follow_ups = vec!["Why?".to_string(), "How?".to_string()];
+
+        assert_eq!(
+            with_follow_ups("Because.".to_string(), &follow_ups),
+            "Because.\n\n**You might also ask:**\n1. Why?\n2. How?"
+        );
+        assert_eq!(with_follow_ups("Because.".to_string(), &[]), "Because.");
+    }
+}
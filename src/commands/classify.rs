@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::{params, Visibility};
+use crate::llm::{is_rate_limited, ChatMessage, LlmProvider};
+
+const MIN_LABELS: usize = 2;
+const MAX_LABELS: usize = 20;
+
+pub struct Classify {
+    llm: Arc<dyn LlmProvider>,
+    visibility: Visibility,
+    definition: BotCommandDefinition,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Classify {
+    fn definition(&self) -> &BotCommandDefinition {
+        &self.definition
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let (text, labels) = match (
+            params::required_string(client.context(), "text"),
+            params::required_string(client.context(), "labels"),
+        ) {
+            (Ok(text), Ok(labels)) => (text, labels),
+            (Err(e), _) | (_, Err(e)) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+        };
+
+        let labels = match parse_labels(&labels) {
+            Ok(labels) => labels,
+            Err(e) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+        };
+
+        info!("Classifying text of length {} into {} labels", text.len(), labels.len());
+
+        let (response, is_error) = match self.classify(&text, &labels).await {
+            Ok((label, reason)) => (format!("**Label:** {}\n\n{}", label, reason), false),
+            Err(e) if is_rate_limited(&e) => {
+                error!("Classification rate limited: {}", e);
+                return Err(super::rate_limited_error());
+            }
+            Err(e) => {
+                error!("Error classifying text: {}", e);
+                (format!("I encountered an error while classifying: {}", e), true)
+            }
+        };
+
+        Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)))
+    }
+}
+
+impl Classify {
+    /// `max_length` caps how much text OpenChat accepts
+    pub fn new(llm: Arc<dyn LlmProvider>, visibility: Visibility, max_length: u16) -> Self {
+        Self {
+            llm,
+            visibility,
+            definition: Self::definition(max_length),
+        }
+    }
+
+    fn definition(max_length: u16) -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "classify".to_string(),
+            description: Some("Pick the label that best fits a piece of text".to_string()),
+            placeholder: Some("Classifying...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "text".to_string(),
+                    description: Some("The text to classify".to_string()),
+                    placeholder: Some("Paste the text to classify".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+                BotCommandParam {
+                    name: "labels".to_string(),
+                    description: Some(format!("{} to {} comma-separated labels to choose from", MIN_LABELS, MAX_LABELS)),
+                    placeholder: Some("e.g. bug, feature request, question".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 3,
+                        max_length: 1000,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    // The best-fitting label and the model's reason for it
+    async fn classify(&self, text: &str, labels: &[String]) -> anyhow::Result<(String, String)> {
+        let system_prompt = format!(
+            "You are a text classifier. Choose the single label from this list that best fits the user's text: {}. \
+            Reply with exactly two lines: \"LABEL: <label>\", using the label exactly as written in the list, \
+            then \"REASON: <one short sentence explaining why>\".",
+            labels.join(", ")
+        );
+
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: text.to_string(),
+        }];
+
+        let response = self.llm.chat(&system_prompt, &messages).await?;
+        parse_classification(&response, labels).map_err(|e| anyhow::anyhow!(e))
+    }
+}
+
+// Distinct labels from a comma-separated list, in the order given
+fn parse_labels(raw: &str) -> Result<Vec<String>, String> {
+    let mut seen = HashSet::new();
+    let labels: Vec<String> = raw
+        .split(',')
+        .map(str::trim)
+        .filter(|label| !label.is_empty())
+        .filter(|label| seen.insert(label.to_lowercase()))
+        .map(str::to_string)
+        .collect();
+
+    if !(MIN_LABELS..=MAX_LABELS).contains(&labels.len()) {
+        return Err(format!(
+            "Please give between {} and {} different comma-separated labels.",
+            MIN_LABELS, MAX_LABELS
+        ));
+    }
+    Ok(labels)
+}
+
+// The label the model chose, spelled as the user gave it, and its reason. Anything
+// outside the given labels is an error rather than a new category.
+fn parse_classification(response: &str, labels: &[String]) -> Result<(String, String), String> {
+    let mut chosen = None;
+    let mut reason = String::new();
+
+    // Models sometimes bold the field names
+    for line in response.lines().map(|line| line.trim().trim_start_matches('*')) {
+        if let Some(value) = strip_prefix_ignore_case(line, "LABEL:") {
+            chosen = Some(value.trim().trim_matches(|c: char| matches!(c, '"' | '\'' | '*' | '`' | '.')).trim());
+        } else if let Some(value) = strip_prefix_ignore_case(line, "REASON:") {
+            reason = value.trim().trim_start_matches('*').trim().to_string();
+        }
+    }
+
+    let chosen = chosen.ok_or_else(|| "The model didn't choose a label.".to_string())?;
+    let label = labels
+        .iter()
+        .find(|label| label.to_lowercase() == chosen.to_lowercase())
+        .ok_or_else(|| format!("The model chose \"{}\", which isn't one of the labels.", chosen))?;
+
+    Ok((label.clone(), reason))
+}
+
+fn strip_prefix_ignore_case<'a>(line: &'a str, prefix: &str) -> Option<&'a str> {
+    line.get(..prefix.len())
+        .filter(|start| start.eq_ignore_ascii_case(prefix))
+        .map(|_| &line[prefix.len()..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels() -> Vec<String> {
+        vec!["Bug".to_string(), "Feature request".to_string(), "Question".to_string()]
+    }
+
+    #[test]
+    fn parses_distinct_labels_in_order() {
+        assert_eq!(parse_labels(" Bug, Feature request,,question , bug"), Ok(vec![
+            "Bug".to_string(),
+            "Feature request".to_string(),
+            "question".to_string(),
+        ]));
+    }
+
+    #[test]
+    fn needs_two_to_twenty_labels() {
+        let error = "Please give between 2 and 20 different comma-separated labels.".to_string();
+        assert_eq!(parse_labels("Bug"), Err(error.clone()));
+        assert_eq!(parse_labels("Bug, bug"), Err(error.clone()));
+
+        let many: Vec<String> = (1..=21).map(|i| format!("label {}", i)).collect();
+        assert_eq!(parse_labels(&many.join(",")), Err(error));
+        assert_eq!(parse_labels(&many[..20].join(",")).map(|labels| labels.len()), Ok(20));
+    }
+
+    #[test]
+    fn parses_the_chosen_label_and_reason() {
+        assert_eq!(
+            parse_classification("LABEL: \"feature request\".\nREASON: Asks for dark mode.", &labels()),
+            Ok(("Feature request".to_string(), "Asks for dark mode.".to_string()))
+        );
+        assert_eq!(
+            parse_classification("**Label:** Bug\n**Reason:** The app crashes on start.", &labels()),
+            Ok(("Bug".to_string(), "The app crashes on start.".to_string()))
+        );
+    }
+
+    #[test]
+    fn rejects_labels_outside_the_set() {
+        assert_eq!(
+            parse_classification("LABEL: Complaint\nREASON: The user is unhappy.", &labels()),
+            Err("The model chose \"Complaint\", which isn't one of the labels.".to_string())
+        );
+        assert_eq!(
+            parse_classification("It's probably a bug.", &labels()),
+            Err("The model didn't choose a label.".to_string())
+        );
+    }
+}
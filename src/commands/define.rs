@@ -0,0 +1,248 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use chrono::Utc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::{Memory, MemoryBackend};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Define::definition);
+
+pub struct Define {
+    pub memory_store: Arc<dyn MemoryBackend>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Define {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let (action, term) = match (
+            params::required_string(client.context(), "action"),
+            params::required_string(client.context(), "term"),
+        ) {
+            (Ok(action), Ok(term)) => (action, term),
+            (Err(e), _) | (_, Err(e)) => return Ok(super::reply(&client, e, false)),
+        };
+        let definition = params::optional_string(client.context(), "definition");
+
+        info!("Processing define command with action: {} and term: {}", action, term);
+
+        // Extract chat and user information
+        let scope = &client.context().scope;
+        let chat_id = canonical_chat_id(scope);
+        let user_id = client.context().command.initiator.to_string();
+
+        let result = match action.as_str() {
+            "set" => match definition {
+                Some(definition) => self.set_term(chat_id, user_id, &term, definition).await,
+                None => Err("Please provide a definition to store.".to_string()),
+            },
+            "get" => self.get_term(&chat_id, &term).await,
+            _ => Err(format!("Unknown define action: {}", action)),
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing define command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Define {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "define".to_string(),
+            description: Some("Set or look up a glossary term for this chat".to_string()),
+            placeholder: Some("Looking up glossary...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "action".to_string(),
+                    description: Some("Whether to set or get a term".to_string()),
+                    placeholder: Some("Choose an action".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "set".to_string(),
+                                value: "set".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "get".to_string(),
+                                value: "get".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "term".to_string(),
+                    description: Some("The term to define or look up".to_string()),
+                    placeholder: Some("Enter a term".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 100,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "definition".to_string(),
+                    description: Some("The definition to store (only for set)".to_string()),
+                    placeholder: Some("Enter the definition".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 2000,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    async fn set_term(&self, chat_id: String, user_id: String, term: &str, definition: String) -> Result<String, String> {
+        let metadata = glossary_metadata(term);
+
+        // Overwrite any previous definition of the term
+        self.memory_store
+            .delete_memories_by_metadata(&chat_id, &metadata)
+            .await
+            .map_err(|e| format!("Failed to update term: {}", e))?;
+
+        let memory = Memory {
+            id: None,
+            chat_id,
+            // Glossary terms apply to the whole chat
+            thread_id: None,
+            user_id,
+            timestamp: Utc::now(),
+            content: definition,
+            embedding: None,
+            embedding_model: None,
+            metadata: Some(metadata),
+        };
+
+        match self.memory_store.store_memory(memory).await {
+            Ok(_) => Ok(format!("Saved the definition of **{}**.", term)),
+            Err(e) => Err(format!("Failed to store term: {}", e)),
+        }
+    }
+
+    async fn get_term(&self, chat_id: &str, term: &str) -> Result<String, String> {
+        match self.memory_store.get_memory_by_metadata(chat_id, &glossary_metadata(term)).await {
+            Ok(Some(memory)) => Ok(format!("**{}**: {}", term, memory.content)),
+            Ok(None) => Ok(format!("No definition found for **{}**.", term)),
+            Err(e) => Err(format!("Failed to look up term: {}", e)),
+        }
+    }
+}
+
+// Metadata tag marking a memory as a glossary entry. Terms match exactly, ignoring case.
+fn glossary_metadata(term: &str) -> String {
+    serde_json::json!({ "kind": "glossary", "term": term.to_lowercase() }).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStore;
+
+    fn define() -> Define {
+        Define {
+            memory_store: Arc::new(MemoryStore::new(":memory:").unwrap()),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_set_term_can_be_looked_up() {
+        let define = define();
+
+        let saved = define
+            .set_term("group:1".to_string(), "alice".to_string(), "OKR", "Objectives and key results".to_string())
+            .await;
+
+        assert_eq!(saved, Ok("Saved the definition of **OKR**.".to_string()));
+        assert_eq!(
+            define.get_term("group:1", "OKR").await,
+            Ok("**OKR**: Objectives and key results".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn setting_a_term_again_replaces_it() {
+        let define = define();
+        define
+            .set_term("group:1".to_string(), "alice".to_string(), "OKR", "Objectives and key results".to_string())
+            .await
+            .unwrap();
+        define
+            .set_term("group:1".to_string(), "bob".to_string(), "okr", "Quarterly goals".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(define.get_term("group:1", "OKR").await, Ok("**OKR**: Quarterly goals".to_string()));
+        let glossary = define
+            .memory_store
+            .export_chat("group:1")
+            .await
+            .unwrap();
+        assert_eq!(glossary.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn lookup_ignores_case() {
+        let define = define();
+        define
+            .set_term("group:1".to_string(), "alice".to_string(), "KPI", "Key performance indicator".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            define.get_term("group:1", "kpi").await,
+            Ok("**kpi**: Key performance indicator".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn a_missing_term_says_so() {
+        let define = define();
+        define
+            .set_term("group:1".to_string(), "alice".to_string(), "KPI", "Key performance indicator".to_string())
+            .await
+            .unwrap();
+
+        assert_eq!(define.get_term("group:1", "ROI").await, Ok("No definition found for **ROI**.".to_string()));
+        // Terms belong to the chat they were defined in
+        assert_eq!(define.get_term("group:2", "KPI").await, Ok("No definition found for **KPI**.".to_string()));
+    }
+}
@@ -4,27 +4,31 @@ use oc_bots_sdk::api::definition::*;
 use oc_bots_sdk::types::BotCommandContext;
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
-use std::sync::LazyLock;
 
-static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Echo::definition);
+use super::params;
 
-pub struct Echo;
+pub struct Echo {
+    definition: BotCommandDefinition,
+}
 
 #[async_trait]
 impl CommandHandler<AgentRuntime> for Echo {
     fn definition(&self) -> &BotCommandDefinition {
-        &DEFINITION
+        &self.definition
     }
 
     async fn execute(
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let text = client.context().command.arg::<String>("message").to_string();
+        let text = match params::required_string(client.context(), "message") {
+            Ok(text) => text,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
 
         let message = client
             .send_text_message(text)
-            .with_block_level_markdown(true)
+            .with_block_level_markdown(super::markdown_enabled())
             .execute_then_return_message(|_, _| ());
 
         Ok(SuccessResult { message })
@@ -32,7 +36,13 @@ impl CommandHandler<AgentRuntime> for Echo {
 }
 
 impl Echo {
-    fn definition() -> BotCommandDefinition {
+    pub fn new(max_length: u16) -> Self {
+        Self {
+            definition: Self::definition(max_length),
+        }
+    }
+    
+    fn definition(max_length: u16) -> BotCommandDefinition {
         BotCommandDefinition {
             name: "echo".to_string(),
             description: Some("A simple echo bot that repeats your messages".to_string()),
@@ -44,7 +54,7 @@ impl Echo {
                 required: true,
                 param_type: BotCommandParamType::StringParam(StringParam {
                     min_length: 1,
-                    max_length: 10000,
+                    max_length,
                     choices: Vec::new(),
                     multi_line: true,
                 }),
@@ -54,4 +64,19 @@ impl Echo {
             direct_messages: Some(true),
         }
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_configured_limit_flows_into_the_definition() {
+        let echo = Echo::new(500);
+
+        match &echo.definition().params[0].param_type {
+            BotCommandParamType::StringParam(param) => assert_eq!(param.max_length, 500),
+            _ => panic!("expected a string param"),
+        }
+    }
+}
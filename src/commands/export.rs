@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::MemoryBackend;
+use crate::memory_export::MemoryExport;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Export::definition);
+
+// OpenChat messages are kept well under its size limit, so a large export is split
+// across several messages that have to be joined back together
+const MAX_PART_CHARS: usize = 8000;
+// Beyond this the export is refused rather than flooding the chat
+const MAX_PARTS: usize = 25;
+
+pub struct Export {
+    pub memory_store: Arc<dyn MemoryBackend>,
+    pub admins: Vec<String>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Export {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let include_embeddings = params::optional_string(client.context(), "embeddings").as_deref() == Some("include");
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing export command for {} (embeddings: {})", chat_id, include_embeddings);
+
+        let parts = if !self.admins.contains(&user_id) {
+            vec!["Only admins can export the chat's memories.".to_string()]
+        } else {
+            match self.export(&chat_id, include_embeddings).await {
+                Ok(parts) => parts,
+                Err(e) => {
+                    error!("Error exporting memories: {}", e);
+                    vec![format!("I encountered an error: {}", e)]
+                }
+            }
+        };
+
+        // The first part is the command's reply and the others follow it as new messages
+        Ok(SuccessResult { message: super::send_parts(&client, parts) })
+    }
+}
+
+impl Export {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "export".to_string(),
+            description: Some("Export the chat's memories as JSON (admins only)".to_string()),
+            placeholder: Some("Exporting memories...".to_string()),
+            params: vec![BotCommandParam {
+                name: "embeddings".to_string(),
+                description: Some("Include embeddings, which makes the export much larger (omitted by default)".to_string()),
+                placeholder: Some("Include or omit embeddings".to_string()),
+                required: false,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 10,
+                    choices: vec![
+                        BotCommandOptionChoice {
+                            name: "include".to_string(),
+                            value: "include".to_string()
+                        },
+                        BotCommandOptionChoice {
+                            name: "omit".to_string(),
+                            value: "omit".to_string()
+                        }
+                    ],
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(false),
+        }
+    }
+
+    // The messages to post: a header followed by the JSON, split into numbered parts
+    async fn export(&self, chat_id: &str, include_embeddings: bool) -> Result<Vec<String>, String> {
+        let memories = self
+            .memory_store
+            .export_chat(chat_id)
+            .await
+            .map_err(|e| format!("Failed to load memories: {}", e))?;
+
+        if memories.is_empty() {
+            return Ok(vec!["This chat has no memories to export.".to_string()]);
+        }
+
+        let json = MemoryExport::new(chat_id, &memories, include_embeddings)
+            .to_json()
+            .map_err(|e| format!("Failed to serialize memories: {}", e))?;
+
+        let chunks = split_chars(&json, MAX_PART_CHARS);
+        if chunks.len() > MAX_PARTS {
+            let hint = if include_embeddings { " Try again without embeddings." } else { "" };
+            return Err(format!(
+                "The export would take {} messages, more than the {} allowed.{}",
+                chunks.len(),
+                MAX_PARTS,
+                hint
+            ));
+        }
+
+        let count = chunks.len();
+        let mut parts = vec![if count == 1 {
+            format!("**Exported {} memories.**", memories.len())
+        } else {
+            format!(
+                "**Exported {} memories in {} parts.** Join the parts in order to get the full JSON.",
+                memories.len(),
+                count
+            )
+        }];
+        parts.extend(
+            chunks
+                .iter()
+                .enumerate()
+                .map(|(i, chunk)| format!("Part {}/{}\n{}", i + 1, count, code_block(chunk))),
+        );
+        Ok(parts)
+    }
+}
+
+// Pieces of at most `max_chars` characters, never splitting one
+fn split_chars(text: &str, max_chars: usize) -> Vec<&str> {
+    let mut chunks = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let end = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i);
+        chunks.push(&rest[..end]);
+        rest = &rest[end..];
+    }
+    chunks
+}
+
+// Fence the text with more backticks than it contains in a row, so memory content
+// can't close the block early
+fn code_block(text: &str) -> String {
+    let longest_run = text
+        .split(|c| c != '`')
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let fence = "`".repeat((longest_run + 1).max(3));
+    format!("{}json\n{}\n{}", fence, text, fence)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStore;
+    use crate::test_support::memory;
+
+    fn exporter(store: MemoryStore) -> Export {
+        Export {
+            memory_store: Arc::new(store),
+            admins: vec!["admin".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn parts_round_trip_into_another_store() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        // Long enough to need several parts, with a multi-byte character and backticks thrown in
+        for i in 0..20 {
+            store.store_memory(memory(&format!("note {} é ``` {}", i, "x".repeat(1000))).embedding(vec![0.5, -0.5]).age_secs(100 - i).build()).await.unwrap();
+        }
+        let exporter = exporter(store);
+
+        let parts = exporter.export("group:1", true).await.unwrap();
+        assert!(parts.len() > 2);
+        assert_eq!(
+            parts[0],
+            format!("**Exported 20 memories in {} parts.** Join the parts in order to get the full JSON.", parts.len() - 1)
+        );
+
+        let export = MemoryExport::from_pasted(&parts[1..].join("\n")).unwrap();
+        let fresh = MemoryStore::new(":memory:").unwrap();
+        for exported in export.memories {
+            fresh.store_memory(exported.into_memory("group:2").unwrap()).await.unwrap();
+        }
+
+        let original = exporter.memory_store.export_chat("group:1").await.unwrap();
+        let restored = fresh.export_chat("group:2").await.unwrap();
+        assert_eq!(restored.len(), original.len());
+        for (restored, original) in restored.iter().zip(&original) {
+            assert_eq!(restored.content, original.content);
+            assert_eq!(restored.timestamp, original.timestamp);
+            assert_eq!(restored.embedding, original.embedding);
+            assert_eq!(restored.embedding_model, original.embedding_model);
+        }
+    }
+
+    #[tokio::test]
+    async fn small_exports_fit_one_part() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        store.store_memory(memory("only note").embedding(vec![0.5, -0.5]).age_secs(10).build()).await.unwrap();
+        let exporter = exporter(store);
+
+        let parts = exporter.export("group:1", false).await.unwrap();
+        assert_eq!(parts.len(), 2);
+        assert_eq!(parts[0], "**Exported 1 memories.**");
+        assert!(parts[1].starts_with("Part 1/1\n```json\n"));
+        assert!(!parts[1].contains("embedding"));
+
+        assert_eq!(
+            exporter.export("group:2", false).await.unwrap(),
+            vec!["This chat has no memories to export."]
+        );
+    }
+
+    #[test]
+    fn splits_on_character_boundaries() {
+        assert_eq!(split_chars("ééé", 2), vec!["éé", "é"]);
+        assert_eq!(split_chars("abcd", 2), vec!["ab", "cd"]);
+        assert!(split_chars("", 2).is_empty());
+    }
+
+    #[test]
+    fn fences_outlast_backticks_in_the_text() {
+        assert_eq!(code_block("{}"), "```json\n{}\n```");
+        assert_eq!(code_block("a ```` b"), "`````json\na ```` b\n`````");
+    }
+}
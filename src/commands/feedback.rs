@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::feedback::{parse_rating, FeedbackStore, MAX_RATING, MIN_RATING};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Feedback::definition);
+
+pub struct Feedback {
+    pub store: Arc<FeedbackStore>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Feedback {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let text = params::optional_string(client.context(), "text");
+        let rating = params::optional_decimal(client.context(), "rating").and_then(parse_rating);
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing feedback from {} in {}", user_id, chat_id);
+
+        let response = match (rating, text) {
+            (None, _) => format!("Please give a whole-number rating from {} to {}.", MIN_RATING, MAX_RATING),
+            (Some(_), None) => "Please tell me what you think as well as a rating.".to_string(),
+            (Some(rating), Some(text)) => match self.store.add(&chat_id, &user_id, rating, &text).await {
+                Ok(()) => "Thanks for the feedback!".to_string(),
+                Err(e) => {
+                    error!("Failed to store feedback: {}", e);
+                    format!("I encountered an error while saving your feedback: {}", e)
+                }
+            },
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Feedback {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "feedback".to_string(),
+            description: Some("Tell the bot's admins what you think, with a rating from 1 to 5".to_string()),
+            placeholder: Some("Saving feedback...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "text".to_string(),
+                    description: Some("What you liked or what could be better".to_string()),
+                    placeholder: Some("Enter your feedback".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 2000,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+                BotCommandParam {
+                    name: "rating".to_string(),
+                    description: Some("How happy you are with the bot, from 1 to 5".to_string()),
+                    placeholder: Some("Enter a rating".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::DecimalParam(DecimalParam {
+                        min_value: MIN_RATING as f64,
+                        max_value: MAX_RATING as f64,
+                        choices: Vec::new(),
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+}
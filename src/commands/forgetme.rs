@@ -0,0 +1,267 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::future::Future;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::command_log::CommandLogStore;
+use crate::feedback::FeedbackStore;
+use crate::karma::KarmaStore;
+use crate::memory::MemoryBackend;
+use crate::quotes::QuoteStore;
+use crate::reminders::ReminderStore;
+use crate::timezones::TimezoneStore;
+use crate::usage::UsageStore;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(ForgetMe::definition);
+
+/// Deletes everything the bot has stored about the caller, across every chat.
+/// Stores that are disabled are skipped.
+pub struct ForgetMe {
+    pub memory_store: Option<Arc<dyn MemoryBackend>>,
+    pub usage_store: Option<Arc<UsageStore>>,
+    pub karma_store: Option<Arc<KarmaStore>>,
+    pub feedback_store: Option<Arc<FeedbackStore>>,
+    pub reminder_store: Option<Arc<ReminderStore>>,
+    pub timezone_store: Option<Arc<TimezoneStore>>,
+    pub quote_store: Option<Arc<QuoteStore>>,
+    pub command_log: Option<Arc<CommandLogStore>>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for ForgetMe {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let user_id = client.context().command.initiator.to_string();
+
+        info!("Processing forgetme command for user {}", user_id);
+
+        let mut lines = Vec::new();
+        let mut failures = Vec::new();
+        let mut total = 0;
+
+        if let Some(store) = &self.memory_store {
+            delete("memories and questions", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.usage_store {
+            delete("usage records", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.karma_store {
+            delete("karma points and gives", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.feedback_store {
+            delete("feedback entries", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.reminder_store {
+            delete("reminders", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.quote_store {
+            delete("quotes", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        // This command's own entry is written once it has replied, so it outlives the deletion
+        if let Some(store) = &self.command_log {
+            delete("command log entries", store.delete_user(&user_id), &mut lines, &mut failures, &mut total).await;
+        }
+        if let Some(store) = &self.timezone_store {
+            let count = async { store.delete_user(&user_id).await.map(usize::from) };
+            delete("timezone setting", count, &mut lines, &mut failures, &mut total).await;
+        }
+
+        let mut response = if total == 0 && failures.is_empty() {
+            "I had nothing stored about you.".to_string()
+        } else {
+            format!("**Deleted {} items stored about you:**\n\n{}", total, lines.join("\n"))
+        };
+        if !failures.is_empty() {
+            response.push_str(&format!(
+                "\n\nI couldn't delete your {}. Please try again later.",
+                failures.join(", ")
+            ));
+        }
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl ForgetMe {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "forgetme".to_string(),
+            description: Some("Delete everything the bot has stored about you, in every chat".to_string()),
+            placeholder: Some("Deleting your data...".to_string()),
+            params: vec![BotCommandParam {
+                name: "confirm".to_string(),
+                description: Some("This can't be undone".to_string()),
+                placeholder: Some("Confirm the deletion".to_string()),
+                required: true,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 10,
+                    choices: vec![BotCommandOptionChoice {
+                        name: "yes, delete my data".to_string(),
+                        value: "yes".to_string(),
+                    }],
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+}
+
+// Run one store's deletion, noting how many rows went or that it failed
+async fn delete(
+    label: &str,
+    deletion: impl Future<Output = anyhow::Result<usize>>,
+    lines: &mut Vec<String>,
+    failures: &mut Vec<String>,
+    total: &mut usize,
+) {
+    match deletion.await {
+        Ok(count) => {
+            lines.push(format!("- {}: {}", label, count));
+            *total += count;
+        }
+        Err(e) => {
+            error!("Failed to delete {}: {}", label, e);
+            failures.push(label.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::command_log::CommandLogEntry;
+    use crate::llm::TokenUsage;
+    use crate::memory::MemoryStore;
+    use crate::reminders::Repeat;
+    use crate::test_support::memory;
+    use crate::usage::UsageContext;
+    use chrono::{Duration, Utc};
+    use chrono_tz::Tz;
+    use std::collections::HashSet;
+
+    fn usage(user_id: &str) -> (UsageContext, TokenUsage) {
+        let context = UsageContext {
+            chat_id: "group:1".to_string(),
+            user_id: user_id.to_string(),
+            command: "ask".to_string(),
+        };
+        (context, TokenUsage { prompt_tokens: 10, completion_tokens: 5, total_tokens: 15 })
+    }
+
+    #[tokio::test]
+    async fn deletes_everything_about_the_user_and_nothing_else() {
+        let memories = MemoryStore::new(":memory:").unwrap();
+        let usage_store = UsageStore::new(":memory:").unwrap();
+        let karma = KarmaStore::new(":memory:").unwrap();
+        let feedback = FeedbackStore::new(":memory:").unwrap();
+        let reminders = ReminderStore::new(":memory:").unwrap();
+        let timezones = TimezoneStore::new(":memory:").unwrap();
+        let command_log = CommandLogStore::new(":memory:").unwrap();
+
+        memories.store_memory(memory("alice likes tea").user("alice").age_secs(30).build()).await.unwrap();
+        memories.store_memory(memory("alice lives in Lyon").user("alice").age_secs(20).build()).await.unwrap();
+        memories.store_memory(memory("bob likes coffee").user("bob").age_secs(10).build()).await.unwrap();
+        memories.store_ask_turn("group:1", "alice", "What's 2+2?", "4").await.unwrap();
+        for user_id in ["alice", "alice", "bob"] {
+            let (context, tokens) = usage(user_id);
+            usage_store.record_usage(context, tokens).await.unwrap();
+        }
+        karma.give("group:1", "alice", "bob", Duration::zero()).await.unwrap();
+        karma.give("group:1", "carol", "alice", Duration::zero()).await.unwrap();
+        feedback.add("group:1", "alice", 5, "Great bot").await.unwrap();
+        feedback.add("group:1", "bob", 4, "Pretty good").await.unwrap();
+        reminders
+            .add("group:1", "alice", "stretch", Utc::now() + Duration::hours(1), Some(Repeat::Daily), Tz::UTC)
+            .await
+            .unwrap();
+        timezones.set("alice", Tz::Europe__Paris).await.unwrap();
+        command_log
+            .record(CommandLogEntry {
+                user_id: "alice".to_string(),
+                chat_id: "group:1".to_string(),
+                command: "ask".to_string(),
+                success: true,
+                latency_ms: 100,
+                error: None,
+            })
+            .await
+            .unwrap();
+
+        let mut lines = Vec::new();
+        let mut failures = Vec::new();
+        let mut total = 0;
+        delete("memories and questions", memories.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        delete("usage records", usage_store.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        delete("karma points and gives", karma.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        delete("feedback entries", feedback.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        delete("reminders", reminders.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        delete("command log entries", command_log.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        let count = async { timezones.delete_user("alice").await.map(usize::from) };
+        delete("timezone setting", count, &mut lines, &mut failures, &mut total).await;
+
+        assert!(failures.is_empty());
+        assert_eq!(
+            lines,
+            vec![
+                "- memories and questions: 3",
+                "- usage records: 2",
+                "- karma points and gives: 3",
+                "- feedback entries: 1",
+                "- reminders: 1",
+                "- command log entries: 1",
+                "- timezone setting: 1",
+            ]
+        );
+        assert_eq!(total, 12);
+
+        let remaining = memories.get_recent_memories("group:1", None, 10).await.unwrap();
+        assert_eq!(remaining.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["bob likes coffee"]);
+        assert!(memories.get_recent_ask_turns("group:1", 10).await.unwrap().is_empty());
+        assert_eq!(usage_store.user_ids("group:1").await.unwrap(), HashSet::from(["bob".to_string()]));
+        assert_eq!(karma.points("group:1", "alice").await.unwrap(), 0);
+        assert_eq!(karma.points("group:1", "bob").await.unwrap(), 1);
+        assert_eq!(feedback.summary("group:1").await.unwrap().count, 1);
+        assert_eq!(reminders.count_pending_for_user("alice").await.unwrap(), 0);
+        assert_eq!(timezones.get("alice").await.unwrap(), None);
+
+        // A second run finds nothing left
+        let mut total = 0;
+        delete("memories and questions", memories.delete_user("alice"), &mut lines, &mut failures, &mut total).await;
+        let count = async { timezones.delete_user("alice").await.map(usize::from) };
+        delete("timezone setting", count, &mut lines, &mut failures, &mut total).await;
+        assert_eq!(total, 0);
+    }
+
+    #[tokio::test]
+    async fn failed_deletions_are_reported_without_a_count() {
+        let mut lines = Vec::new();
+        let mut failures = Vec::new();
+        let mut total = 0;
+        delete("reminders", async { Err::<usize, _>(anyhow::anyhow!("database is locked")) }, &mut lines, &mut failures, &mut total).await;
+
+        assert!(lines.is_empty());
+        assert_eq!(failures, vec!["reminders"]);
+        assert_eq!(total, 0);
+    }
+}
@@ -0,0 +1,169 @@
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, BotCommandScope, Chat};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::{Memory, MemoryBackend};
+use crate::timezones::{self, format_timestamp, TimezoneStore};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(History::definition);
+
+const DEFAULT_LIMIT: f64 = 10.0;
+// Longest memory content shown per line
+const MAX_PREVIEW_CHARS: usize = 200;
+
+pub struct History {
+    pub memory_store: Arc<dyn MemoryBackend>,
+    pub timezones: Option<Arc<TimezoneStore>>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for History {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let limit = params::optional_decimal(client.context(), "limit").unwrap_or(DEFAULT_LIMIT) as usize;
+        let user_id = client.context().command.initiator.to_string();
+
+        info!("Processing history command with limit: {}", limit);
+
+        // In a direct chat only show the user's own memories
+        let scope = &client.context().scope;
+        let chat_id = canonical_chat_id(scope);
+        let direct = matches!(scope, BotCommandScope::Chat(chat_details) if matches!(chat_details.chat, Chat::Direct(_)));
+
+        let thread_id = super::thread_id(scope);
+
+        let memories = if direct {
+            self.memory_store.get_recent_memories_by_user(&chat_id, thread_id.as_deref(), &user_id, limit).await
+        } else {
+            self.memory_store.get_recent_memories(&chat_id, thread_id.as_deref(), limit).await
+        };
+
+        let response = match memories {
+            Ok(memories) => {
+                let timezone = timezones::user_timezone(self.timezones.as_deref(), &user_id).await;
+                render_history(&memories, &timezone)
+            }
+            Err(e) => {
+                error!("Failed to load memory history: {}", e);
+                format!("I encountered an error while loading memories: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl History {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "history".to_string(),
+            description: Some("List the most recent memories stored in this chat".to_string()),
+            placeholder: Some("Loading memories...".to_string()),
+            params: vec![BotCommandParam {
+                name: "limit".to_string(),
+                description: Some("How many memories to list (default 10)".to_string()),
+                placeholder: Some("Enter a number".to_string()),
+                required: false,
+                param_type: BotCommandParamType::DecimalParam(DecimalParam {
+                    min_value: 1.0,
+                    max_value: 50.0,
+                    choices: Vec::new(),
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+}
+
+// Numbered, newest-first list including each memory's id
+fn render_history(memories: &[Memory], timezone: &Tz) -> String {
+    if memories.is_empty() {
+        return "I haven't stored any memories in this chat yet.".to_string();
+    }
+
+    let lines: Vec<String> = memories
+        .iter()
+        .enumerate()
+        .map(|(i, memory)| {
+            let mut preview: String = memory.content.chars().take(MAX_PREVIEW_CHARS).collect();
+            if preview.len() < memory.content.len() {
+                preview.push_str("...");
+            }
+            format!(
+                "{}. [{}] (id {}): {}",
+                i + 1,
+                format_timestamp(memory.timestamp, timezone),
+                memory.id.map(|id| id.to_string()).unwrap_or_else(|| "?".to_string()),
+                preview
+            )
+        })
+        .collect();
+
+    format!("**Recent memories:**\n\n{}", lines.join("\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support;
+    use chrono::{TimeZone, Utc};
+
+    // Stored at 09:30 UTC on 1 March 2025
+    fn memory(id: i64, content: &str) -> Memory {
+        test_support::memory(content)
+            .id(id)
+            .at(Utc.with_ymd_and_hms(2025, 3, 1, 9, 30, 0).unwrap())
+            .build()
+    }
+
+    #[test]
+    fn renders_a_numbered_list_with_ids() {
+        let memories = [memory(7, "Deploy is on Friday"), memory(3, "Alice likes tea")];
+
+        assert_eq!(
+            render_history(&memories, &Tz::UTC),
+            "**Recent memories:**\n\n\
+             1. [2025-03-01 09:30 UTC] (id 7): Deploy is on Friday\n\
+             2. [2025-03-01 09:30 UTC] (id 3): Alice likes tea"
+        );
+    }
+
+    #[test]
+    fn renders_timestamps_in_the_users_timezone() {
+        let rendered = render_history(&[memory(1, "hi")], &chrono_tz::Europe::Berlin);
+        assert!(rendered.contains("[2025-03-01 10:30 CET]"), "{}", rendered);
+    }
+
+    #[test]
+    fn truncates_long_memories() {
+        let rendered = render_history(&[memory(1, &"a".repeat(MAX_PREVIEW_CHARS + 1))], &Tz::UTC);
+        assert!(rendered.ends_with(&format!("{}...", "a".repeat(MAX_PREVIEW_CHARS))));
+    }
+
+    #[test]
+    fn renders_an_empty_history() {
+        assert_eq!(render_history(&[], &Tz::UTC), "I haven't stored any memories in this chat yet.");
+    }
+}
@@ -0,0 +1,283 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::Arc;
+use tracing::{error, info, warn};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::{EmbeddingModel, Memory, MemoryBackend};
+use crate::memory_export::MemoryExport;
+
+pub struct Import {
+    memory_store: Arc<dyn MemoryBackend>,
+    // Fills in embeddings the export left out; without it those memories aren't searchable
+    embedding_model: Option<Arc<dyn EmbeddingModel + Send + Sync>>,
+    admins: Vec<String>,
+    definition: BotCommandDefinition,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Import {
+    fn definition(&self) -> &BotCommandDefinition {
+        &self.definition
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let data = match params::required_string(client.context(), "data") {
+            Ok(data) => data,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing import command for {} ({} chars)", chat_id, data.len());
+
+        let result = if !self.admins.contains(&user_id) {
+            Ok("Only admins can import memories.".to_string())
+        } else {
+            self.import(&chat_id, &data).await
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error importing memories: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Import {
+    /// `max_length` caps how much pasted JSON OpenChat accepts
+    pub fn new(
+        memory_store: Arc<dyn MemoryBackend>,
+        embedding_model: Option<Arc<dyn EmbeddingModel + Send + Sync>>,
+        admins: Vec<String>,
+        max_length: u16,
+    ) -> Self {
+        Self {
+            memory_store,
+            embedding_model,
+            admins,
+            definition: Self::definition(max_length),
+        }
+    }
+
+    fn definition(max_length: u16) -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "import".to_string(),
+            description: Some("Restore memories into this chat from an /export (admins only)".to_string()),
+            placeholder: Some("Importing memories...".to_string()),
+            params: vec![BotCommandParam {
+                name: "data".to_string(),
+                description: Some("The JSON from /export; paste its parts in order".to_string()),
+                placeholder: Some("Paste the exported JSON".to_string()),
+                required: true,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 2,
+                    max_length,
+                    choices: Vec::new(),
+                    multi_line: true,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(false),
+        }
+    }
+
+    async fn import(&self, chat_id: &str, data: &str) -> Result<String, String> {
+        let export = MemoryExport::from_pasted(data).map_err(|e| e.to_string())?;
+        let total = export.memories.len();
+
+        let mut memories = Vec::with_capacity(total);
+        for exported in export.memories {
+            memories.push(exported.into_memory(chat_id).map_err(|e| e.to_string())?);
+        }
+
+        let unembedded = self.fill_embeddings(&mut memories).await;
+
+        let imported = self
+            .memory_store
+            .import_chat(chat_id, memories)
+            .await
+            .map_err(|e| format!("Failed to store memories: {}", e))?;
+
+        let mut response = format!("Imported {} of {} memories.", imported, total);
+        if imported < total {
+            response.push_str(&format!(
+                " The other {} were already in this chat, or too old to fit within its memory limit.",
+                total - imported
+            ));
+        }
+        if unembedded > 0 {
+            response.push_str(&format!(
+                " {} couldn't be embedded, so they won't turn up in memory searches.",
+                unembedded
+            ));
+        }
+        Ok(response)
+    }
+
+    // Embed memories that arrived without a usable embedding; returns how many are still without one
+    async fn fill_embeddings(&self, memories: &mut [Memory]) -> usize {
+        let mut unembedded = 0;
+
+        for memory in memories.iter_mut() {
+            // Memories with metadata, such as glossary terms, are looked up by name and never embedded
+            if memory.metadata.is_some() {
+                continue;
+            }
+            let Some(model) = &self.embedding_model else {
+                if memory.embedding.is_none() {
+                    unembedded += 1;
+                }
+                continue;
+            };
+
+            // Vectors from a different model can't be compared with this one's
+            let dimension = model.dimension();
+            let usable = memory
+                .embedding
+                .as_ref()
+                .is_some_and(|embedding| dimension == 0 || embedding.len() == dimension)
+                && memory.embedding_model.as_deref().is_none_or(|name| name == model.name());
+            if usable {
+                continue;
+            }
+
+            match model.embed_with_model(&memory.content).await {
+                Ok((embedding, name)) => {
+                    memory.embedding = Some(embedding);
+                    memory.embedding_model = Some(name);
+                }
+                Err(e) => {
+                    warn!("Failed to embed imported memory: {}", e);
+                    memory.embedding = None;
+                    memory.embedding_model = None;
+                    unembedded += 1;
+                }
+            }
+        }
+
+        unembedded
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockEmbedding;
+    use crate::memory::MemoryStore;
+    use crate::test_support::memory;
+
+    fn importer(store: Arc<dyn MemoryBackend>, embedding_model: Option<Arc<dyn EmbeddingModel + Send + Sync>>) -> Import {
+        Import::new(store, embedding_model, vec!["admin".to_string()], 10_000)
+    }
+
+    // The pasted text of an /export of group:1, without embeddings
+    async fn exported(store: &MemoryStore) -> String {
+        let memories = store.export_chat("group:1").await.unwrap();
+        MemoryExport::new("group:1", &memories, false).to_json().unwrap()
+    }
+
+    #[tokio::test]
+    async fn export_then_import_into_a_fresh_store_matches() {
+        let source = MemoryStore::new(":memory:").unwrap();
+        source.store_memory(memory("Deploys happen on Fridays").age_secs(30).build()).await.unwrap();
+        source.store_memory(memory("The wiki moved to Notion").age_secs(20).build()).await.unwrap();
+        let term = memory("Service level objective").age_secs(10).metadata(r#"{"glossary_term":"SLO"}"#).build();
+        source.store_memory(term).await.unwrap();
+        let data = exported(&source).await;
+
+        let target = Arc::new(MemoryStore::new(":memory:").unwrap());
+        let importer = importer(target.clone(), Some(Arc::new(MockEmbedding)));
+        assert_eq!(importer.import("group:2", &data).await, Ok("Imported 3 of 3 memories.".to_string()));
+
+        let original = source.export_chat("group:1").await.unwrap();
+        let restored = target.export_chat("group:2").await.unwrap();
+        assert_eq!(restored.len(), original.len());
+        for (restored, original) in restored.iter().zip(&original) {
+            assert_eq!(restored.chat_id, "group:2");
+            assert_eq!(restored.user_id, original.user_id);
+            assert_eq!(restored.timestamp, original.timestamp);
+            assert_eq!(restored.content, original.content);
+            assert_eq!(restored.metadata, original.metadata);
+        }
+
+        // Missing embeddings were regenerated, except for the glossary term
+        assert_eq!(restored[0].embedding, Some(MockEmbedding.embed_text("Deploys happen on Fridays").await.unwrap()));
+        assert_eq!(restored[0].embedding_model.as_deref(), Some("mock"));
+        assert_eq!(restored[2].embedding, None);
+    }
+
+    #[tokio::test]
+    async fn importing_twice_skips_duplicates() {
+        let source = MemoryStore::new(":memory:").unwrap();
+        source.store_memory(memory("Deploys happen on Fridays").age_secs(30).build()).await.unwrap();
+        source.store_memory(memory("The wiki moved to Notion").age_secs(20).build()).await.unwrap();
+        let data = exported(&source).await;
+
+        let target = Arc::new(MemoryStore::new(":memory:").unwrap());
+        let importer = importer(target.clone(), Some(Arc::new(MockEmbedding)));
+        importer.import("group:2", &data).await.unwrap();
+
+        assert_eq!(
+            importer.import("group:2", &data).await,
+            Ok("Imported 0 of 2 memories. The other 2 were already in this chat, or too old to fit within its memory limit.".to_string())
+        );
+        assert_eq!(target.export_chat("group:2").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn counts_memories_left_without_embeddings() {
+        let source = MemoryStore::new(":memory:").unwrap();
+        source.store_memory(memory("Deploys happen on Fridays").age_secs(30).build()).await.unwrap();
+        let data = exported(&source).await;
+
+        let importer = importer(Arc::new(MemoryStore::new(":memory:").unwrap()), None);
+        assert_eq!(
+            importer.import("group:2", &data).await,
+            Ok("Imported 1 of 1 memories. 1 couldn't be embedded, so they won't turn up in memory searches.".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn counts_only_memories_that_survive_the_limit() {
+        let source = MemoryStore::new(":memory:").unwrap();
+        for (i, content) in ["oldest", "older", "newest"].into_iter().enumerate() {
+            source.store_memory(memory(content).age_secs(30 - i as i64).build()).await.unwrap();
+        }
+        let data = exported(&source).await;
+
+        let target = Arc::new(MemoryStore::new(":memory:").unwrap().with_max_items(2));
+        let importer = importer(target.clone(), Some(Arc::new(MockEmbedding)));
+        assert_eq!(
+            importer.import("group:2", &data).await,
+            Ok("Imported 2 of 3 memories. The other 1 were already in this chat, or too old to fit within its memory limit.".to_string())
+        );
+        let kept: Vec<String> = target.export_chat("group:2").await.unwrap().into_iter().map(|m| m.content).collect();
+        assert_eq!(kept, vec!["older", "newest"]);
+    }
+
+    #[tokio::test]
+    async fn rejects_data_that_is_not_an_export() {
+        let importer = importer(Arc::new(MemoryStore::new(":memory:").unwrap()), None);
+        assert!(importer.import("group:2", "{\"memories\": 3}").await.unwrap_err().starts_with("Not a valid memory export"));
+    }
+}
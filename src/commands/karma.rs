@@ -0,0 +1,268 @@
+use async_trait::async_trait;
+use candid::Principal;
+use chrono::Duration;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::karma::{GiveOutcome, KarmaStore};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Karma::definition);
+
+const LEADERBOARD_SIZE: usize = 10;
+
+pub struct Karma {
+    pub store: Arc<KarmaStore>,
+    // How long before the same user can give the same person another point
+    pub cooldown: Duration,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Karma {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let action = match params::required_string(client.context(), "action") {
+            Ok(action) => action,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+        // Accept ids pasted as mentions, e.g. "@abc-123"
+        let user = params::optional_string(client.context(), "user")
+            .map(|user| user.trim_start_matches('@').to_string())
+            .filter(|user| !user.is_empty());
+        let initiator = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing karma command with action: {} in {}", action, chat_id);
+
+        let result = match action.as_str() {
+            "give" => match user.as_deref().map(parse_user_id) {
+                Some(Ok(receiver)) => self.give(&chat_id, &initiator, &receiver).await,
+                Some(Err(e)) => Err(e),
+                None => Err("Please say who to give a point to.".to_string()),
+            },
+            "show" => self.show(&chat_id, user.as_deref().unwrap_or(&initiator), user.is_none()).await,
+            "leaderboard" => self.leaderboard(&chat_id).await,
+            _ => Err(format!("Unknown karma action: {}", action)),
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing karma command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+// Points can only go to real users, so the receiver must be a user id (a principal).
+// It is returned in the same text form as the initiator's, for comparing and storing.
+fn parse_user_id(user: &str) -> Result<String, String> {
+    Principal::from_text(user)
+        .map(|principal| principal.to_text())
+        .map_err(|_| format!("'{}' isn't a user id.", user))
+}
+
+impl Karma {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "karma".to_string(),
+            description: Some("Give karma points, check them, or see the chat's leaderboard".to_string()),
+            placeholder: Some("Counting karma...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "action".to_string(),
+                    description: Some("Give a point, show someone's points, or list the top users".to_string()),
+                    placeholder: Some("Choose an action".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 20,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "give".to_string(),
+                                value: "give".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "show".to_string(),
+                                value: "show".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "leaderboard".to_string(),
+                                value: "leaderboard".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "user".to_string(),
+                    description: Some("User id to give a point to or look up (show defaults to you)".to_string()),
+                    placeholder: Some("Enter a user id".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 100,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(false),
+        }
+    }
+
+    async fn give(&self, chat_id: &str, giver: &str, receiver: &str) -> Result<String, String> {
+        if giver == receiver {
+            return Ok("Nice try, but you can't give karma to yourself.".to_string());
+        }
+
+        let outcome = self
+            .store
+            .give(chat_id, giver, receiver, self.cooldown)
+            .await
+            .map_err(|e| format!("Failed to give karma: {}", e))?;
+
+        Ok(match outcome {
+            GiveOutcome::Given { points } => format!("+1 karma for {}, who now has {} points.", receiver, points),
+            GiveOutcome::CoolingDown { remaining } => format!(
+                "You already gave {} a point recently. Try again in {} minutes.",
+                receiver,
+                remaining.num_minutes() + 1
+            ),
+        })
+    }
+
+    async fn show(&self, chat_id: &str, user: &str, own: bool) -> Result<String, String> {
+        let points = self
+            .store
+            .points(chat_id, user)
+            .await
+            .map_err(|e| format!("Failed to load karma: {}", e))?;
+
+        Ok(if own {
+            format!("You have {} karma points in this chat.", points)
+        } else {
+            format!("{} has {} karma points in this chat.", user, points)
+        })
+    }
+
+    async fn leaderboard(&self, chat_id: &str) -> Result<String, String> {
+        let top = self
+            .store
+            .leaderboard(chat_id, LEADERBOARD_SIZE)
+            .await
+            .map_err(|e| format!("Failed to load the leaderboard: {}", e))?;
+
+        if top.is_empty() {
+            return Ok("Nobody has any karma in this chat yet.".to_string());
+        }
+
+        let lines: Vec<String> = top
+            .iter()
+            .enumerate()
+            .map(|(i, (user, points))| format!("{}. {}: {} points", i + 1, user, points))
+            .collect();
+
+        Ok(format!("**Karma leaderboard:**\n\n{}", lines.join("\n")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_user_ids() {
+        assert_eq!(
+            parse_user_id("rrkah-fqaaa-aaaaa-aaaaq-cai"),
+            Ok("rrkah-fqaaa-aaaaa-aaaaq-cai".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_anything_else() {
+        for user in ["bob", "not a user", "rrkah-fqaaa-aaaaa-aaaaq-cax", "12345"] {
+            assert!(parse_user_id(user).is_err(), "{:?} should be rejected", user);
+        }
+    }
+
+    fn karma() -> Karma {
+        Karma {
+            store: Arc::new(KarmaStore::new(":memory:").unwrap()),
+            cooldown: Duration::hours(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn gives_and_shows_points() {
+        let karma = karma();
+
+        assert_eq!(
+            karma.give("group:1", "alice", "bob").await,
+            Ok("+1 karma for bob, who now has 1 points.".to_string())
+        );
+        assert_eq!(karma.show("group:1", "bob", false).await, Ok("bob has 1 karma points in this chat.".to_string()));
+        assert_eq!(karma.show("group:1", "alice", true).await, Ok("You have 0 karma points in this chat.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn rejects_giving_to_yourself() {
+        let karma = karma();
+
+        assert_eq!(
+            karma.give("group:1", "alice", "alice").await,
+            Ok("Nice try, but you can't give karma to yourself.".to_string())
+        );
+        assert_eq!(karma.store.points("group:1", "alice").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn a_second_give_waits_for_the_cooldown() {
+        let karma = karma();
+
+        karma.give("group:1", "alice", "bob").await.unwrap();
+        assert_eq!(
+            karma.give("group:1", "alice", "bob").await,
+            Ok("You already gave bob a point recently. Try again in 60 minutes.".to_string())
+        );
+        assert_eq!(karma.store.points("group:1", "bob").await.unwrap(), 1);
+    }
+
+    #[tokio::test]
+    async fn renders_the_leaderboard() {
+        let karma = karma();
+        assert_eq!(karma.leaderboard("group:1").await, Ok("Nobody has any karma in this chat yet.".to_string()));
+
+        karma.give("group:1", "alice", "bob").await.unwrap();
+        karma.give("group:1", "carol", "bob").await.unwrap();
+        karma.give("group:1", "bob", "carol").await.unwrap();
+
+        assert_eq!(
+            karma.leaderboard("group:1").await,
+            Ok("**Karma leaderboard:**\n\n1. bob: 2 points\n2. carol: 1 points".to_string())
+        );
+    }
+}
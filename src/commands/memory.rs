@@ -1,7 +1,7 @@
 use async_trait::async_trait;
 use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
 use oc_bots_sdk::api::definition::*;
-use oc_bots_sdk::types::{BotCommandContext, BotCommandScope};
+use oc_bots_sdk::types::BotCommandContext;
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
 use std::sync::LazyLock;
@@ -9,13 +9,17 @@ use std::sync::Arc;
 use chrono::Utc;
 use tracing::{error, info};
 
-use crate::memory::{Memory, MemoryStore, EmbeddingModel};
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::{Memory, MemoryBackend, EmbeddingModel};
+use crate::timezones::{self, format_timestamp, TimezoneStore};
 
 static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(MemoryCmd::definition);
 
 pub struct MemoryCmd {
-    pub memory_store: Arc<MemoryStore>,
+    pub memory_store: Arc<dyn MemoryBackend>,
     pub embedding_model: Arc<dyn EmbeddingModel + Send + Sync>,
+    pub timezones: Option<Arc<TimezoneStore>>,
 }
 
 #[async_trait]
@@ -28,29 +32,26 @@ impl CommandHandler<AgentRuntime> for MemoryCmd {
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let action = client.context().command.arg::<String>("action").to_string();
-        let content = client.context().command.arg::<String>("content").to_string();
+        let (action, content) = match (
+            params::required_string(client.context(), "action"),
+            params::required_string(client.context(), "content"),
+        ) {
+            (Ok(action), Ok(content)) => (action, content),
+            (Err(e), _) | (_, Err(e)) => return Ok(super::reply(&client, e, false)),
+        };
         
         info!("Processing memory command with action: {} and content: {}", action, content);
         
-        // Extract chat and user information based on scope type
+        // Extract chat and user information
         let scope = &client.context().scope;
-        let (chat_id, user_id) = match scope {
-            BotCommandScope::Chat(chat_details) => {
-                // For chat scope, extract chat info and user info
-                let chat_string = format!("{:?}", chat_details.chat);
-                (chat_string, client.context().command.initiator.to_string())
-            },
-            BotCommandScope::Community(community_details) => {
-                // For community scope, use community id
-                let community_string = format!("{:?}", community_details.community_id);
-                (community_string, client.context().command.initiator.to_string())
-            },
-        };
+        let chat_id = canonical_chat_id(scope);
+        let user_id = client.context().command.initiator.to_string();
+        
+        let thread_id = super::thread_id(scope);
         
         let result = match action.as_str() {
-            "store" => self.store_memory(chat_id, user_id, content).await,
-            "recall" => self.recall_memory(chat_id, content).await,
+            "store" => self.store_memory(chat_id, thread_id, user_id, content).await,
+            "recall" => self.recall_memory(chat_id, thread_id, &user_id, content).await,
             _ => Err(format!("Unknown memory action: {}", action)),
         };
         
@@ -64,7 +65,7 @@ impl CommandHandler<AgentRuntime> for MemoryCmd {
         
         let message = client
             .send_text_message(response)
-            .with_block_level_markdown(true)
+            .with_block_level_markdown(super::markdown_enabled())
             .execute_then_return_message(|_, _| ());
 
         Ok(SuccessResult { message })
@@ -118,13 +119,13 @@ impl MemoryCmd {
         }
     }
     
-    async fn store_memory(&self, chat_id: String, user_id: String, content: String) -> Result<String, String> {
+    async fn store_memory(&self, chat_id: String, thread_id: Option<String>, user_id: String, content: String) -> Result<String, String> {
         // Create embedding for the memory
-        let embedding = match self.embedding_model.embed_text(&content).await {
-            Ok(embed) => Some(embed),
+        let (embedding, embedding_model) = match self.embedding_model.embed_with_model(&content).await {
+            Ok((embed, model)) => (Some(embed), Some(model)),
             Err(e) => {
                 error!("Failed to create embedding: {}", e);
-                None
+                (None, None)
             }
         };
         
@@ -132,10 +133,12 @@ impl MemoryCmd {
         let memory = Memory {
             id: None,
             chat_id,
+            thread_id,
             user_id,
             timestamp: Utc::now(),
             content: content.clone(),
             embedding,
+            embedding_model,
             metadata: None,
         };
         
@@ -152,20 +155,23 @@ impl MemoryCmd {
         }
     }
     
-    async fn recall_memory(&self, chat_id: String, query: String) -> Result<String, String> {
+    async fn recall_memory(&self, chat_id: String, thread_id: Option<String>, user_id: &str, query: String) -> Result<String, String> {
+        // Timestamps are shown in the caller's timezone
+        let timezone = timezones::user_timezone(self.timezones.as_deref(), user_id).await;
+        
         // First, try to create an embedding for semantic search
-        let embedding_result = self.embedding_model.embed_text(&query).await;
+        let embedding_result = self.embedding_model.embed_with_model(&query).await;
         
         let memories: Vec<String> = match embedding_result {
-            Ok(query_embedding) => {
+            Ok((query_embedding, model)) => {
                 // Try semantic search first
-                match self.memory_store.search_similar_memories(&chat_id, &query_embedding, 5).await {
+                match self.memory_store.search_similar_memories(&chat_id, thread_id.as_deref(), &query_embedding, &model, 5).await {
                     Ok(results) if !results.is_empty() => {
                         // Found memories with semantic search
                         results.into_iter().map(|(m, score)| {
                             format!(
-                                "- [{}] (similarity: {:.2}): {}",
-                                m.timestamp.format("%Y-%m-%d %H:%M"),
+                                "- [{}] (relevance: {:.2}): {}",
+                                format_timestamp(m.timestamp, &timezone),
                                 score,
                                 m.content
                             )
@@ -173,12 +179,12 @@ impl MemoryCmd {
                     }
                     _ => {
                         // Fall back to recent memories
-                        match self.memory_store.get_recent_memories(&chat_id, 5).await {
+                        match self.memory_store.get_recent_memories(&chat_id, thread_id.as_deref(), 5).await {
                             Ok(recent) => {
                                 recent.into_iter().map(|m| {
                                     format!(
                                         "- [{}]: {}",
-                                        m.timestamp.format("%Y-%m-%d %H:%M"),
+                                        format_timestamp(m.timestamp, &timezone),
                                         m.content
                                     )
                                 }).collect()
@@ -192,12 +198,12 @@ impl MemoryCmd {
             }
             Err(_) => {
                 // Fall back to recent memories if embedding fails
-                match self.memory_store.get_recent_memories(&chat_id, 5).await {
+                match self.memory_store.get_recent_memories(&chat_id, thread_id.as_deref(), 5).await {
                     Ok(recent) => {
                         recent.into_iter().map(|m| {
                             format!(
                                 "- [{}]: {}",
-                                m.timestamp.format("%Y-%m-%d %H:%M"),
+                                format_timestamp(m.timestamp, &timezone),
                                 m.content
                             )
                         }).collect()
@@ -1,6 +1,244 @@
 pub mod echo;
 pub mod ask;
 pub mod summarize;
+pub mod summarizeurl;
 pub mod remindme;
 pub mod memory;
-pub mod moderate;
\ No newline at end of file
+pub mod moderate;
+pub mod classify;
+pub mod poll;
+pub mod define;
+pub mod usage;
+pub mod weather;
+pub mod history;
+pub mod karma;
+pub mod feedback;
+pub mod forgetme;
+pub mod quote;
+pub mod ping;
+pub mod roll;
+pub mod stats;
+pub mod export;
+pub mod import;
+pub mod reembed;
+pub mod persona;
+pub mod toggle;
+pub(crate) mod params;
+pub(crate) mod recent_messages;
+pub(crate) mod split_message;
+pub mod timezone;
+pub mod registry;
+
+use oc_bots_sdk::api::command::{EphemeralMessageBuilder, SuccessResult};
+use oc_bots_sdk::oc_api::client::Client;
+use oc_bots_sdk::types::{BotCommandContext, BotCommandScope, Message, MessageContentInitial, MessageId, TextContent};
+use oc_bots_sdk_offchain::AgentRuntime;
+use std::cell::Cell;
+use std::collections::VecDeque;
+use std::future::Future;
+
+/// Longest reply sent as one message unless `messages.max_length` says otherwise
+pub(crate) const DEFAULT_MAX_MESSAGE_LENGTH: usize = 4000;
+
+tokio::task_local! {
+    static LLM_RATE_LIMITED: Cell<bool>;
+    static REPLY_FORMAT: ReplyFormat;
+}
+
+/// How replies are sent in the chat a command runs in
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ReplyFormat {
+    pub markdown: bool,
+    // Longer replies are split across several messages
+    pub max_length: usize,
+}
+
+/// Run a command with the reply format of its chat
+pub(crate) async fn with_reply_format<F: Future>(format: ReplyFormat, fut: F) -> F::Output {
+    REPLY_FORMAT.scope(format, fut).await
+}
+
+/// Whether messages are sent as markdown in the chat the current command runs in.
+/// Outside a command, and by default, they are.
+pub(crate) fn markdown_enabled() -> bool {
+    REPLY_FORMAT.try_with(|format| format.markdown).unwrap_or(true)
+}
+
+fn max_message_length() -> usize {
+    REPLY_FORMAT
+        .try_with(|format| format.max_length)
+        .unwrap_or(DEFAULT_MAX_MESSAGE_LENGTH)
+}
+
+/// Run a command, also reporting whether it gave up because the LLM API rate-limited us
+pub(crate) async fn track_rate_limit<F: Future>(fut: F) -> (F::Output, bool) {
+    LLM_RATE_LIMITED
+        .scope(Cell::new(false), async {
+            let output = fut.await;
+            (output, LLM_RATE_LIMITED.with(|flag| flag.get()))
+        })
+        .await
+}
+
+/// Error for a command that could not run because the LLM API is rate limiting us.
+/// Instead of a chat reply, OpenChat receives 429 Too Many Requests.
+pub(crate) fn rate_limited_error() -> String {
+    let _ = LLM_RATE_LIMITED.try_with(|flag| flag.set(true));
+    "The LLM API is rate limiting requests".to_string()
+}
+
+/// The thread a command was invoked in, if any. Memories stored in a thread
+/// are only recalled within it; chat-level memories are visible everywhere.
+pub(crate) fn thread_id(scope: &BotCommandScope) -> Option<String> {
+    match scope {
+        BotCommandScope::Chat(chat_details) => chat_details.thread.as_ref().map(|thread| format!("{:?}", thread)),
+        BotCommandScope::Community(_) => None,
+    }
+}
+
+/// Whether a command's replies are shown to everyone in the chat or only to its initiator
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Visibility {
+    pub ephemeral: bool,
+    pub ephemeral_errors: bool,
+}
+
+impl Visibility {
+    pub fn is_ephemeral(&self, is_error: bool) -> bool {
+        self.ephemeral || (is_error && self.ephemeral_errors)
+    }
+}
+
+/// Reply to a command with markdown text. Ephemeral replies are returned to OpenChat
+/// for the initiator only instead of being posted to the chat.
+///
+/// Text longer than the chat's message limit is split: the first part replaces the
+/// command's placeholder and the rest follow as new messages. An ephemeral reply can
+/// only be one message, so it is cut short instead.
+pub(crate) fn reply(
+    client: &Client<AgentRuntime, BotCommandContext>,
+    text: String,
+    ephemeral: bool,
+) -> SuccessResult {
+    let parts = split_message::split_message(&text, max_message_length());
+
+    if ephemeral {
+        let truncated = parts.len() > 1;
+        let mut text = parts.into_iter().next().unwrap_or_default();
+        if truncated {
+            text.push_str("\n\n…");
+        }
+        let message = EphemeralMessageBuilder::new(MessageContentInitial::Text(TextContent { text }), client.context().scope.message_id())
+            .with_block_level_markdown(markdown_enabled())
+            .build();
+        return SuccessResult { message: Some(message) };
+    }
+
+    SuccessResult { message: send_parts(client, parts) }
+}
+
+/// Post a reply made of several parts. The first is sent as the command's message, which
+/// is returned; the others are new messages, each sent once the one before it has been
+/// so they appear in order.
+pub(crate) fn send_parts(client: &Client<AgentRuntime, BotCommandContext>, parts: Vec<String>) -> Option<Message> {
+    let markdown = markdown_enabled();
+    let mut parts: VecDeque<String> = parts.into();
+    let first = parts.pop_front().unwrap_or_default();
+    let rest = client.clone();
+
+    client
+        .send_text_message(first)
+        .with_block_level_markdown(markdown)
+        .execute_then_return_message(move |_, _| send_follow_ups(rest, parts, markdown))
+}
+
+// The command's own message id can only hold one message, so each part gets a new one
+fn send_follow_ups(client: Client<AgentRuntime, BotCommandContext>, mut parts: VecDeque<String>, markdown: bool) {
+    let Some(part) = parts.pop_front() else {
+        return;
+    };
+    let next = client.clone();
+
+    client
+        .send_text_message(part)
+        .with_message_id(MessageId::from(rand::random::<u64>()))
+        .with_block_level_markdown(markdown)
+        .execute_then_return_message(move |_, _| send_follow_ups(next, parts, markdown));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{is_rate_limited, LlmError};
+
+    // What an LLM-backed command does with an error from the LLM
+    fn handle(error: anyhow::Error) -> Result<String, String> {
+        if is_rate_limited(&error) {
+            return Err(rate_limited_error());
+        }
+        Ok(format!("I'm sorry, I encountered an error: {}", error))
+    }
+
+    #[tokio::test]
+    async fn rate_limited_llm_calls_are_reported() {
+        let error = anyhow::Error::from(LlmError::RateLimited).context("Search error");
+
+        let (result, rate_limited) = track_rate_limit(async { handle(error) }).await;
+
+        assert!(result.is_err());
+        assert!(rate_limited);
+    }
+
+    #[tokio::test]
+    async fn other_llm_failures_are_not() {
+        let error = anyhow::Error::from(LlmError::Unavailable);
+
+        let (result, rate_limited) = track_rate_limit(async { handle(error) }).await;
+
+        assert!(result.is_ok());
+        assert!(!rate_limited);
+    }
+
+    #[test]
+    fn rate_limited_error_outside_a_command_is_harmless() {
+        assert_eq!(rate_limited_error(), "The LLM API is rate limiting requests");
+    }
+
+    #[tokio::test]
+    async fn markdown_follows_the_chats_reply_format() {
+        assert!(markdown_enabled());
+
+        let plain = ReplyFormat {
+            markdown: false,
+            max_length: DEFAULT_MAX_MESSAGE_LENGTH,
+        };
+        assert!(!with_reply_format(plain, async { markdown_enabled() }).await);
+
+        let rich = ReplyFormat { markdown: true, ..plain };
+        assert!(with_reply_format(rich, async { markdown_enabled() }).await);
+    }
+
+    #[test]
+    fn moderate_replies_are_ephemeral_by_default() {
+        let messages = crate::config::MessagesConfig::default();
+
+        let moderate = messages.visibility("moderate");
+        assert!(moderate.is_ephemeral(false));
+        assert!(moderate.is_ephemeral(true));
+
+        let ask = messages.visibility("ask");
+        assert!(!ask.is_ephemeral(false));
+        assert!(ask.is_ephemeral(true));
+    }
+
+    #[test]
+    fn errors_can_be_posted_publicly() {
+        let visibility = Visibility {
+            ephemeral: false,
+            ephemeral_errors: false,
+        };
+
+        assert!(!visibility.is_ephemeral(false));
+        assert!(!visibility.is_ephemeral(true));
+    }
+}
@@ -1,80 +1,297 @@
 use async_trait::async_trait;
 use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
 use oc_bots_sdk::api::definition::*;
-use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk::types::{BotCommandContext, ChatPermission};
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
-use std::sync::LazyLock;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tracing::{error, info};
 
-use crate::llm::MistralClient;
+use super::recent_messages::{fetch_recent_messages, RecentMessage};
+use super::{params, Visibility};
+use crate::chat_id::canonical_chat_id;
+use crate::llm::{is_rate_limited, LlmProvider};
+use crate::moderation::{moderate_content, ModerationResult};
+use crate::webhook::{WebhookNotifier, WebhookPayload};
 
-static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Moderate::definition);
+// Each scanned message costs an LLM call
+const MAX_SCANNED_MESSAGES: usize = 20;
+// Longest excerpt of a flagged message quoted in the report
+const MAX_EXCERPT_CHARS: usize = 80;
 
 pub struct Moderate {
-    pub llm: Arc<MistralClient>,
+    llm: Arc<dyn LlmProvider>,
+    visibility: Visibility,
+    definition: BotCommandDefinition,
+    // Told about flagged content
+    webhook: Option<Arc<WebhookNotifier>>,
 }
 
 #[async_trait]
 impl CommandHandler<AgentRuntime> for Moderate {
     fn definition(&self) -> &BotCommandDefinition {
-        &DEFINITION
+        &self.definition
     }
 
     async fn execute(
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let content = client.context().command.arg::<String>("content").to_string();
+        let content = params::optional_string(client.context(), "content");
+        let count = params::optional_decimal(client.context(), "messages").map(|count| count as usize);
+        let chat_id = canonical_chat_id(&client.context().scope);
+        let user_id = client.context().command.initiator.to_string();
+        
+        // Scan the chat's recent messages when asked to, otherwise the pasted content
+        let content = match (content, count) {
+            (_, Some(count)) => {
+                let count = count.clamp(1, MAX_SCANNED_MESSAGES);
+                info!("Scanning the last {} messages for moderation", count);
+                let (response, is_error) = match fetch_recent_messages(&client, count).await {
+                    Ok(messages) if messages.is_empty() => {
+                        ("There are no recent text messages in this chat to check.".to_string(), true)
+                    }
+                    Ok(messages) => match self.scan(&chat_id, &messages).await {
+                        Ok(report) => (report, false),
+                        Err(e) if is_rate_limited(&e) => {
+                            error!("Moderation rate limited: {}", e);
+                            return Err(super::rate_limited_error());
+                        }
+                        Err(e) => {
+                            error!("Error moderating recent messages: {}", e);
+                            (format!("I encountered an error while moderating: {}", e), true)
+                        }
+                    },
+                    Err(e) => {
+                        error!("Failed to fetch recent messages: {}", e);
+                        (e, true)
+                    }
+                };
+                return Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)));
+            }
+            (Some(content), None) => content,
+            (None, None) => {
+                let response = "Please paste the content to check, or choose how many recent messages to scan.".to_string();
+                return Ok(super::reply(&client, response, self.visibility.is_ephemeral(true)));
+            }
+        };
         
         info!("Processing moderation request for content: {}", content);
         
         // Use the LLM to moderate the content
-        let moderation_result = match self.llm.moderate(&content).await {
-            Ok((is_flagged, reason)) => {
-                if is_flagged {
-                    format!("⚠️ **Content flagged**\n\nReason: {}", reason)
-                } else {
-                    "✅ **Content safe**\n\nNo harmful content detected.".to_string()
+        let (moderation_result, is_error) = match moderate_content(self.llm.as_ref(), &content).await {
+            Ok(result) => {
+                if let ModerationResult::Flagged { reason } = &result {
+                    self.notify_flagged(&chat_id, &user_id, &content, reason);
                 }
+                (render_result(&result), false)
+            }
+            Err(e) if is_rate_limited(&e) => {
+                error!("Moderation rate limited: {}", e);
+                return Err(super::rate_limited_error());
             }
             Err(e) => {
                 error!("Error moderating content: {}", e);
-                format!("I encountered an error while moderating: {}", e)
+                (format!("I encountered an error while moderating: {}", e), true)
             }
         };
         
-        let message = client
-            .send_text_message(moderation_result)
-            .with_block_level_markdown(true)
-            .execute_then_return_message(|_, _| ());
-
-        Ok(SuccessResult { message })
+        Ok(super::reply(&client, moderation_result, self.visibility.is_ephemeral(is_error)))
     }
 }
 
 impl Moderate {
-    fn definition() -> BotCommandDefinition {
+    /// `max_length` caps how much pasted content OpenChat accepts
+    pub fn new(llm: Arc<dyn LlmProvider>, visibility: Visibility, max_length: u16) -> Self {
+        Self {
+            llm,
+            visibility,
+            definition: Self::definition(max_length),
+            webhook: None,
+        }
+    }
+    
+    /// Report flagged content to an external system as well
+    pub fn with_webhook(mut self, webhook: Arc<WebhookNotifier>) -> Self {
+        self.webhook = Some(webhook);
+        self
+    }
+    
+    fn notify_flagged(&self, chat_id: &str, user_id: &str, content: &str, reason: &str) {
+        if let Some(webhook) = &self.webhook {
+            webhook.notify(WebhookPayload::moderation_flagged(chat_id, user_id, content, reason));
+        }
+    }
+    
+    fn definition(max_length: u16) -> BotCommandDefinition {
         BotCommandDefinition {
             name: "moderate".to_string(),
             description: Some("Check if content contains harmful or inappropriate material".to_string()),
             placeholder: Some("Analyzing content...".to_string()),
-            params: vec![BotCommandParam {
-                name: "content".to_string(),
-                description: Some("The content to check for harmful material".to_string()),
-                placeholder: Some("Enter the content to moderate".to_string()),
-                required: true,
-                param_type: BotCommandParamType::StringParam(StringParam {
-                    min_length: 1,
-                    max_length: 10000,
-                    choices: Vec::new(),
-                    multi_line: true,
-                }),
-            }],
-            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            params: vec![
+                BotCommandParam {
+                    name: "content".to_string(),
+                    description: Some("The content to check for harmful material".to_string()),
+                    placeholder: Some("Enter the content to moderate".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+                BotCommandParam {
+                    name: "messages".to_string(),
+                    description: Some("Check this many recent messages of the chat instead of pasted content".to_string()),
+                    placeholder: Some("Enter a number".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::DecimalParam(DecimalParam {
+                        min_value: 1.0,
+                        max_value: MAX_SCANNED_MESSAGES as f64,
+                        choices: Vec::new(),
+                    }),
+                },
+            ],
+            // Reading messages is needed to scan the chat's recent history
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text)
+                .with_chat(&HashSet::from([ChatPermission::ReadMessages])),
             default_role: None,
             direct_messages: Some(true),
         }
     }
-} 
\ No newline at end of file
+    
+    // Moderate each message in turn and report the flagged ones
+    async fn scan(&self, chat_id: &str, messages: &[RecentMessage]) -> anyhow::Result<String> {
+        let mut flagged = Vec::new();
+        for message in messages {
+            if let ModerationResult::Flagged { reason } = moderate_content(self.llm.as_ref(), &message.text).await? {
+                self.notify_flagged(chat_id, &message.sender, &message.text, &reason);
+                flagged.push((message, reason));
+            }
+        }
+        
+        if flagged.is_empty() {
+            return Ok(format!("✅ **All {} recent messages look safe**", messages.len()));
+        }
+        
+        let lines: Vec<String> = flagged
+            .iter()
+            .map(|(message, reason)| {
+                let mut excerpt: String = message.text.chars().take(MAX_EXCERPT_CHARS).collect();
+                if excerpt.len() < message.text.len() {
+                    excerpt.push_str("...");
+                }
+                format!("- {}: \"{}\" — {}", message.sender, excerpt, reason)
+            })
+            .collect();
+        
+        Ok(format!(
+            "⚠️ **{} of {} recent messages flagged**\n\n{}",
+            flagged.len(),
+            messages.len(),
+            lines.join("\n")
+        ))
+    }
+} 
+
+fn render_result(result: &ModerationResult) -> String {
+    match result {
+        ModerationResult::Flagged { reason } => format!("⚠️ **Content flagged**\n\nReason: {}", reason),
+        ModerationResult::Safe => "✅ **Content safe**\n\nNo harmful content detected.".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatMessage, ChatResult};
+    use crate::test_support::{MockResponse, MockServer};
+    use axum::http::StatusCode;
+
+    // Flags any message calling someone an idiot
+    struct InsultFilter;
+
+    #[async_trait]
+    impl LlmProvider for InsultFilter {
+        async fn chat_with_usage(&self, _system_prompt: &str, messages: &[ChatMessage]) -> anyhow::Result<ChatResult> {
+            let text = messages.last().map(|m| m.content.as_str()).unwrap_or_default();
+            Ok(ChatResult {
+                content: if text.contains("idiot") { "FLAGGED: insult" } else { "SAFE" }.to_string(),
+                usage: None,
+            })
+        }
+    }
+
+    fn history(messages: &[(&str, &str)]) -> Vec<RecentMessage> {
+        messages
+            .iter()
+            .enumerate()
+            .map(|(i, (sender, text))| RecentMessage {
+                event_index: 10 + i as u32,
+                sender: sender.to_string(),
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_the_verdict_for_the_user() {
+        assert_eq!(
+            render_result(&ModerationResult::Flagged { reason: "insult".to_string() }),
+            "⚠️ **Content flagged**\n\nReason: insult"
+        );
+        assert_eq!(render_result(&ModerationResult::Safe), "✅ **Content safe**\n\nNo harmful content detected.");
+    }
+
+    #[tokio::test]
+    async fn reports_the_flagged_message_in_a_scan() {
+        let moderate = Moderate::new(Arc::new(InsultFilter), Visibility::default(), 10000);
+        let messages = history(&[
+            ("alice", "Good morning everyone"),
+            ("bob", "You are an idiot"),
+            ("carol", "Lunch at noon?"),
+        ]);
+
+        let report = moderate.scan("group:1", &messages).await.unwrap();
+
+        assert_eq!(report, "⚠️ **1 of 3 recent messages flagged**\n\n- bob: \"You are an idiot\" — insult");
+    }
+
+    #[tokio::test]
+    async fn a_clean_history_is_safe() {
+        let moderate = Moderate::new(Arc::new(InsultFilter), Visibility::default(), 10000);
+        let messages = history(&[("alice", "Good morning everyone"), ("carol", "Lunch at noon?")]);
+
+        assert_eq!(
+            moderate.scan("group:1", &messages).await.unwrap(),
+            "✅ **All 2 recent messages look safe**"
+        );
+    }
+
+    #[tokio::test]
+    async fn posts_a_webhook_for_a_flagged_message() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::OK)]).await;
+        let webhook = WebhookNotifier::new(&server.url, &["moderation_flagged".to_string()], std::time::Duration::from_secs(5));
+        let moderate = Moderate::new(Arc::new(InsultFilter), Visibility::default(), 10000).with_webhook(Arc::new(webhook));
+
+        moderate.scan("group:1", &history(&[("alice", "Hi"), ("bob", "You are an idiot")])).await.unwrap();
+
+        // Delivery happens in the background
+        for _ in 0..100 {
+            if !server.requests().is_empty() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let payload = requests[0].json();
+        assert_eq!(payload["event"], "moderation_flagged");
+        assert_eq!(payload["chat_id"], "group:1");
+        assert_eq!(payload["user_id"], "bob");
+        assert_eq!(payload["content"], "You are an idiot");
+        assert_eq!(payload["reason"], "insult");
+    }
+}
@@ -0,0 +1,72 @@
+use oc_bots_sdk::types::BotCommandContext;
+
+// Reading a command's arguments. OpenChat checks their types and lengths, but text can
+// still be all whitespace, so strings are trimmed and a blank one counts as missing.
+// Errors are ready to show to the user.
+
+/// A text argument the command can't do without
+pub(crate) fn required_string(context: &BotCommandContext, name: &str) -> Result<String, String> {
+    optional_string(context, name).ok_or_else(|| missing_string(name))
+}
+
+/// A text argument, or None if it was left out or blank
+pub(crate) fn optional_string(context: &BotCommandContext, name: &str) -> Option<String> {
+    non_blank(context.command.maybe_arg::<String>(name))
+}
+
+/// A number argument the command can't do without
+pub(crate) fn required_decimal(context: &BotCommandContext, name: &str) -> Result<f64, String> {
+    optional_decimal(context, name).ok_or_else(|| missing_decimal(name))
+}
+
+/// A number argument, or None if it was left out or isn't a finite number
+pub(crate) fn optional_decimal(context: &BotCommandContext, name: &str) -> Option<f64> {
+    finite(context.command.maybe_arg::<f64>(name))
+}
+
+fn non_blank(value: Option<String>) -> Option<String> {
+    value
+        .map(|value| value.trim().to_string())
+        .filter(|value| !value.is_empty())
+}
+
+fn finite(value: Option<f64>) -> Option<f64> {
+    value.filter(|value| value.is_finite())
+}
+
+fn missing_string(name: &str) -> String {
+    format!("Please provide the {}.", name)
+}
+
+fn missing_decimal(name: &str) -> String {
+    format!("Please provide a number for the {}.", name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_are_trimmed_and_blank_ones_are_missing() {
+        assert_eq!(non_blank(Some("  Paris \n".to_string())), Some("Paris".to_string()));
+        assert_eq!(non_blank(Some(" \t\n".to_string())), None);
+        assert_eq!(non_blank(Some(String::new())), None);
+        assert_eq!(non_blank(None), None);
+    }
+
+    #[test]
+    fn decimals_must_be_finite() {
+        assert_eq!(finite(Some(2.5)), Some(2.5));
+        assert_eq!(finite(Some(-0.0)), Some(-0.0));
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            assert_eq!(finite(Some(value)), None);
+        }
+        assert_eq!(finite(None), None);
+    }
+
+    #[test]
+    fn missing_arguments_name_the_parameter() {
+        assert_eq!(missing_string("question"), "Please provide the question.");
+        assert_eq!(missing_decimal("rating"), "Please provide a number for the rating.");
+    }
+}
@@ -0,0 +1,191 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::agent::MAX_PERSONA_CHARS;
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::memory::MemoryBackend;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Persona::definition);
+
+pub struct Persona {
+    pub memory_store: Arc<dyn MemoryBackend>,
+    pub admins: Vec<String>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Persona {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let action = match params::required_string(client.context(), "action") {
+            Ok(action) => action,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+        let text = params::optional_string(client.context(), "text");
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing persona command with action: {} for {}", action, chat_id);
+
+        let result = if !self.admins.contains(&user_id) {
+            Ok("Only admins can change the bot's persona.".to_string())
+        } else {
+            match action.as_str() {
+                "set" => match text {
+                    Some(text) => self.set_persona(&chat_id, &user_id, &text).await,
+                    None => Err("Please provide the persona text to use.".to_string()),
+                },
+                "reset" => self.reset_persona(&chat_id).await,
+                _ => Err(format!("Unknown persona action: {}", action)),
+            }
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing persona command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Persona {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "persona".to_string(),
+            description: Some("Set or reset the bot's persona in this chat (admins only)".to_string()),
+            placeholder: Some("Updating persona...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "action".to_string(),
+                    description: Some("Whether to set a persona or go back to the default".to_string()),
+                    placeholder: Some("Choose an action".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "set".to_string(),
+                                value: "set".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "reset".to_string(),
+                                value: "reset".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "text".to_string(),
+                    description: Some("Who the bot is and how it should talk (only for set)".to_string()),
+                    placeholder: Some("e.g. You are Sparky, a cheerful guide for our gaming community.".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 2000,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(true),
+        }
+    }
+
+    async fn set_persona(&self, chat_id: &str, user_id: &str, text: &str) -> Result<String, String> {
+        if text.chars().count() > MAX_PERSONA_CHARS {
+            return Err(format!("The persona must be at most {} characters.", MAX_PERSONA_CHARS));
+        }
+
+        self.memory_store
+            .set_chat_persona(chat_id, user_id, text)
+            .await
+            .map_err(|e| format!("Failed to store persona: {}", e))?;
+
+        Ok(format!("Persona updated. From now on in this chat:\n\n> {}", text))
+    }
+
+    async fn reset_persona(&self, chat_id: &str) -> Result<String, String> {
+        let had_override = self
+            .memory_store
+            .reset_chat_persona(chat_id)
+            .await
+            .map_err(|e| format!("Failed to reset persona: {}", e))?;
+
+        if had_override {
+            Ok("Persona reset to the default.".to_string())
+        } else {
+            Ok("This chat already uses the default persona.".to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryStore;
+
+    fn persona() -> Persona {
+        Persona {
+            memory_store: Arc::new(MemoryStore::new(":memory:").unwrap()),
+            admins: vec!["admin".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn sets_and_resets_the_chat_persona() {
+        let persona = persona();
+
+        let reply = persona.set_persona("group:1", "admin", "You are Sparky.").await.unwrap();
+        assert_eq!(reply, "Persona updated. From now on in this chat:\n\n> You are Sparky.");
+        assert_eq!(
+            persona.memory_store.get_chat_persona("group:1").await.unwrap().as_deref(),
+            Some("You are Sparky.")
+        );
+
+        assert_eq!(persona.reset_persona("group:1").await.unwrap(), "Persona reset to the default.");
+        assert_eq!(persona.memory_store.get_chat_persona("group:1").await.unwrap(), None);
+        assert_eq!(
+            persona.reset_persona("group:1").await.unwrap(),
+            "This chat already uses the default persona."
+        );
+    }
+
+    #[tokio::test]
+    async fn rejects_overlong_personas() {
+        let persona = persona();
+
+        let error = persona
+            .set_persona("group:1", "admin", &"x".repeat(MAX_PERSONA_CHARS + 1))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error, format!("The persona must be at most {} characters.", MAX_PERSONA_CHARS));
+        assert_eq!(persona.memory_store.get_chat_persona("group:1").await.unwrap(), None);
+    }
+}
@@ -0,0 +1,127 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Ping::definition);
+
+/// Confirms the bot is alive without calling the LLM
+pub struct Ping {
+    pub started_at: Instant,
+    // What each model is used for and its name, e.g. ("chat", "mistral-medium")
+    pub models: Vec<(&'static str, String)>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Ping {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let received = Instant::now();
+
+        info!("Processing ping command");
+
+        let response = self.response(received.elapsed());
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Ping {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "ping".to_string(),
+            description: Some("Check the bot is alive, with its version, models and uptime".to_string()),
+            placeholder: Some("Pinging...".to_string()),
+            params: Vec::new(),
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    fn response(&self, handled_in: Duration) -> String {
+        let models = if self.models.is_empty() {
+            "none (LLM commands are disabled)".to_string()
+        } else {
+            self.models
+                .iter()
+                .map(|(role, model)| format!("{} `{}`", role, model))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+
+        format!(
+            "**Pong!**\n\n- Version: {}\n- Models: {}\n- Uptime: {}\n- Handled in: {} ms",
+            env!("CARGO_PKG_VERSION"),
+            models,
+            format_uptime(self.started_at.elapsed()),
+            handled_in.as_millis()
+        )
+    }
+}
+
+// e.g. "3d 4h 12m 5s", leaving out leading units that are zero
+fn format_uptime(uptime: Duration) -> String {
+    let secs = uptime.as_secs();
+    let (days, hours, minutes, seconds) = (secs / 86_400, secs / 3600 % 24, secs / 60 % 60, secs % 60);
+
+    if days > 0 {
+        format!("{}d {}h {}m {}s", days, hours, minutes, seconds)
+    } else if hours > 0 {
+        format!("{}h {}m {}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_version_models_and_uptime() {
+        let ping = Ping {
+            started_at: Instant::now() - Duration::from_secs(125),
+            models: vec![("chat", "mistral-medium".to_string()), ("embeddings", "mistral-embed".to_string())],
+        };
+
+        let response = ping.response(Duration::from_millis(3));
+
+        assert!(response.starts_with("**Pong!**\n\n"));
+        assert!(response.contains(&format!("- Version: {}\n", env!("CARGO_PKG_VERSION"))));
+        assert!(response.contains("- Models: chat `mistral-medium`, embeddings `mistral-embed`\n"));
+        assert!(response.contains("- Uptime: 2m 5s\n"));
+        assert!(response.ends_with("- Handled in: 3 ms"));
+    }
+
+    #[test]
+    fn says_when_no_models_are_configured() {
+        let ping = Ping { started_at: Instant::now(), models: Vec::new() };
+        assert!(ping.response(Duration::ZERO).contains("- Models: none (LLM commands are disabled)\n"));
+    }
+
+    #[test]
+    fn uptime_leaves_out_leading_zero_units() {
+        assert_eq!(format_uptime(Duration::from_secs(5)), "5s");
+        assert_eq!(format_uptime(Duration::from_secs(3600)), "1h 0m 0s");
+        assert_eq!(format_uptime(Duration::from_secs(3 * 86_400 + 4 * 3600 + 12 * 60 + 5)), "3d 4h 12m 5s");
+    }
+}
@@ -0,0 +1,188 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use tracing::info;
+
+use super::params;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Poll::definition);
+
+const MIN_OPTIONS: usize = 2;
+const MAX_OPTIONS: usize = 10;
+
+pub struct Poll;
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Poll {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let (question, raw_options) = match (
+            params::required_string(client.context(), "question"),
+            params::required_string(client.context(), "options"),
+        ) {
+            (Ok(question), Ok(raw_options)) => (question, raw_options),
+            (Err(e), _) | (_, Err(e)) => return Ok(super::reply(&client, e, false)),
+        };
+
+        info!("Processing poll command with question: {}", question);
+
+        let text = match parse_options(&raw_options) {
+            Ok(options) => render_poll(&question, &options),
+            Err(e) => format!("I couldn't create that poll: {}", e),
+        };
+
+        let message = client
+            .send_text_message(text)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Poll {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "poll".to_string(),
+            description: Some("Create a quick numbered poll".to_string()),
+            placeholder: Some("Creating poll...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "question".to_string(),
+                    description: Some("The question to ask".to_string()),
+                    placeholder: Some("What should we vote on?".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 500,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "options".to_string(),
+                    description: Some("Comma-separated list of 2-10 options".to_string()),
+                    placeholder: Some("Option A, Option B, Option C".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 3,
+                        max_length: 2000,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(false),
+        }
+    }
+}
+
+// Split a comma-separated options string, rejecting bad counts and duplicates
+fn parse_options(raw: &str) -> Result<Vec<String>, String> {
+    let options: Vec<String> = raw
+        .split(',')
+        .map(|option| option.trim().to_string())
+        .filter(|option| !option.is_empty())
+        .collect();
+
+    if options.len() < MIN_OPTIONS || options.len() > MAX_OPTIONS {
+        return Err(format!(
+            "a poll needs between {} and {} options, but {} were given",
+            MIN_OPTIONS,
+            MAX_OPTIONS,
+            options.len()
+        ));
+    }
+
+    let mut seen = HashSet::new();
+    for option in &options {
+        if !seen.insert(option.to_lowercase()) {
+            return Err(format!("the option '{}' appears more than once", option));
+        }
+    }
+
+    Ok(options)
+}
+
+fn render_poll(question: &str, options: &[String]) -> String {
+    let lines: Vec<String> = options
+        .iter()
+        .enumerate()
+        .map(|(i, option)| format!("{}. {}", i + 1, option))
+        .collect();
+
+    format!(
+        "📊 **Poll:** {}\n\n{}\n\nReply with the number of your choice!",
+        question,
+        lines.join("\n")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn needs_at_least_two_options() {
+        assert_eq!(
+            parse_options("Pizza"),
+            Err("a poll needs between 2 and 10 options, but 1 were given".to_string())
+        );
+        assert_eq!(
+            parse_options(" , ,"),
+            Err("a poll needs between 2 and 10 options, but 0 were given".to_string())
+        );
+    }
+
+    #[test]
+    fn allows_at_most_ten_options() {
+        let ten: Vec<String> = (1..=10).map(|i| format!("Option {}", i)).collect();
+        assert_eq!(parse_options(&ten.join(",")), Ok(ten.clone()));
+
+        let eleven = format!("{},Option 11", ten.join(","));
+        assert_eq!(
+            parse_options(&eleven),
+            Err("a poll needs between 2 and 10 options, but 11 were given".to_string())
+        );
+    }
+
+    #[test]
+    fn drops_empty_entries_and_trims() {
+        assert_eq!(parse_options("a,,b"), Ok(vec!["a".to_string(), "b".to_string()]));
+        assert_eq!(
+            parse_options("  Tacos , Sushi ,"),
+            Ok(vec!["Tacos".to_string(), "Sushi".to_string()])
+        );
+    }
+
+    #[test]
+    fn rejects_duplicates_ignoring_case() {
+        assert_eq!(
+            parse_options("Pizza, Tacos, pizza"),
+            Err("the option 'pizza' appears more than once".to_string())
+        );
+    }
+
+    #[test]
+    fn numbers_the_options_in_order() {
+        let options = vec!["Pizza".to_string(), "Tacos".to_string(), "Sushi".to_string()];
+
+        assert_eq!(
+            render_poll("What's for lunch?", &options),
+            "📊 **Poll:** What's for lunch?\n\n1. Pizza\n2. Tacos\n3. Sushi\n\nReply with the number of your choice!"
+        );
+    }
+}
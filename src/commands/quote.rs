@@ -0,0 +1,209 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::quotes::{Quote, QuoteStore};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(QuoteCmd::definition);
+
+const MAX_QUOTE_CHARS: u16 = 1000;
+// How many matches a search lists
+const SEARCH_RESULTS: usize = 5;
+
+pub struct QuoteCmd {
+    pub store: Arc<QuoteStore>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for QuoteCmd {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let action = match params::required_string(client.context(), "action") {
+            Ok(action) => action,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+        let text = params::optional_string(client.context(), "text");
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing quote command with action: {} in {}", action, chat_id);
+
+        let result = match (action.as_str(), text) {
+            ("add", Some(text)) => self.add(&chat_id, &user_id, &text).await,
+            ("add", None) => Err("Please give the quote to save.".to_string()),
+            ("random", _) => self.random(&chat_id).await,
+            ("search", Some(keyword)) => self.search(&chat_id, &keyword).await,
+            ("search", None) => Err("Please give a word to search for.".to_string()),
+            _ => Err(format!("Unknown quote action: {}", action)),
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing quote command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl QuoteCmd {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "quote".to_string(),
+            description: Some("Save memorable quotes and bring them back later".to_string()),
+            placeholder: Some("Finding quotes...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "action".to_string(),
+                    description: Some("Save a quote, get a random one, or search them".to_string()),
+                    placeholder: Some("Choose an action".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "add".to_string(),
+                                value: "add".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "random".to_string(),
+                                value: "random".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "search".to_string(),
+                                value: "search".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "text".to_string(),
+                    description: Some("The quote to save, or a word to search for".to_string()),
+                    placeholder: Some("Enter the quote or a keyword".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: MAX_QUOTE_CHARS,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(false),
+        }
+    }
+
+    async fn add(&self, chat_id: &str, user_id: &str, text: &str) -> Result<String, String> {
+        let id = self
+            .store
+            .add(chat_id, user_id, text)
+            .await
+            .map_err(|e| format!("Failed to save the quote: {}", e))?;
+
+        Ok(format!("Saved quote #{}.", id))
+    }
+
+    async fn random(&self, chat_id: &str) -> Result<String, String> {
+        let quote = self
+            .store
+            .random(chat_id)
+            .await
+            .map_err(|e| format!("Failed to load quotes: {}", e))?;
+
+        Ok(match quote {
+            Some(quote) => format_quote(&quote),
+            None => "No quotes saved in this chat yet. Add one with /quote add.".to_string(),
+        })
+    }
+
+    async fn search(&self, chat_id: &str, keyword: &str) -> Result<String, String> {
+        let quotes = self
+            .store
+            .search(chat_id, keyword, SEARCH_RESULTS)
+            .await
+            .map_err(|e| format!("Failed to search quotes: {}", e))?;
+
+        if quotes.is_empty() {
+            return Ok(format!("No quotes mention \"{}\".", keyword));
+        }
+
+        let quotes: Vec<String> = quotes.iter().map(format_quote).collect();
+        Ok(format!("**Quotes mentioning \"{}\":**\n\n{}", keyword, quotes.join("\n\n")))
+    }
+}
+
+fn format_quote(quote: &Quote) -> String {
+    let text: Vec<String> = quote.text.lines().map(|line| format!("> {}", line)).collect();
+    format!(
+        "{}\n— #{}, added by {} on {}",
+        text.join("\n"),
+        quote.id,
+        quote.user_id,
+        quote.created_at.format("%Y-%m-%d")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn quote_cmd() -> QuoteCmd {
+        QuoteCmd {
+            store: Arc::new(QuoteStore::new(":memory:").unwrap()),
+        }
+    }
+
+    #[test]
+    fn formats_quotes_as_attributed_block_quotes() {
+        let quote = Quote {
+            id: 3,
+            user_id: "alice".to_string(),
+            text: "Ship it\non Friday".to_string(),
+            created_at: Utc.with_ymd_and_hms(2026, 3, 14, 9, 30, 0).unwrap(),
+        };
+        assert_eq!(format_quote(&quote), "> Ship it\n> on Friday\n— #3, added by alice on 2026-03-14");
+    }
+
+    #[tokio::test]
+    async fn adds_recalls_and_searches() {
+        let quotes = quote_cmd();
+        assert_eq!(
+            quotes.random("group:1").await,
+            Ok("No quotes saved in this chat yet. Add one with /quote add.".to_string())
+        );
+
+        assert_eq!(quotes.add("group:1", "alice", "Ship it on Friday").await, Ok("Saved quote #1.".to_string()));
+        assert!(quotes.random("group:1").await.unwrap().starts_with("> Ship it on Friday\n— #1, added by alice on "));
+
+        let found = quotes.search("group:1", "friday").await.unwrap();
+        assert!(found.starts_with("**Quotes mentioning \"friday\":**\n\n> Ship it on Friday"));
+        assert_eq!(quotes.search("group:1", "monday").await, Ok("No quotes mention \"monday\".".to_string()));
+    }
+}
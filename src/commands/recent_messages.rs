@@ -0,0 +1,100 @@
+use oc_bots_sdk::oc_api::actions::chat_details::Response as ChatDetailsResponse;
+use oc_bots_sdk::oc_api::actions::chat_events::Response as ChatEventsResponse;
+use oc_bots_sdk::oc_api::client::Client;
+use oc_bots_sdk::types::{BotCommandContext, ChatEvent, EventsPageArgs, EventsSelectionCriteria, MessageContent};
+use oc_bots_sdk_offchain::AgentRuntime;
+
+/// A text message read back from the chat
+#[derive(Debug, Clone)]
+pub(crate) struct RecentMessage {
+    // Position of the message in the chat's event list
+    pub event_index: u32,
+    pub sender: String,
+    pub text: String,
+}
+
+/// The last `count` text messages of the chat, oldest first. Commands using this need
+/// the `ReadMessages` chat permission.
+pub(crate) async fn fetch_recent_messages(
+    client: &Client<AgentRuntime, BotCommandContext>,
+    count: usize,
+) -> Result<Vec<RecentMessage>, String> {
+    let count = count.max(1);
+    let latest_event_index = latest_event_index(client).await?;
+
+    let criteria = EventsSelectionCriteria::Page(EventsPageArgs {
+        start_index: latest_event_index,
+        ascending: false,
+        max_messages: count as u32,
+        max_events: (count * 2) as u32,
+    });
+
+    // Events arrive newest first
+    let mut messages = fetch_messages(client, criteria).await?;
+    messages.reverse();
+    Ok(messages)
+}
+
+/// Up to `count` text messages posted after the event at `after_index`, oldest first
+pub(crate) async fn fetch_messages_since(
+    client: &Client<AgentRuntime, BotCommandContext>,
+    after_index: u32,
+    count: usize,
+) -> Result<Vec<RecentMessage>, String> {
+    let count = count.max(1);
+    if latest_event_index(client).await? <= after_index {
+        return Ok(Vec::new());
+    }
+
+    let criteria = EventsSelectionCriteria::Page(EventsPageArgs {
+        start_index: after_index + 1,
+        ascending: true,
+        max_messages: count as u32,
+        max_events: (count * 2) as u32,
+    });
+
+    fetch_messages(client, criteria).await
+}
+
+async fn latest_event_index(client: &Client<AgentRuntime, BotCommandContext>) -> Result<u32, String> {
+    match client.chat_details().execute_async().await {
+        Ok(ChatDetailsResponse::Success(details)) => Ok(details.latest_event_index),
+        Ok(ChatDetailsResponse::NotAuthorized) => {
+            Err("I don't have permission to read messages in this chat.".to_string())
+        }
+        Ok(response) => Err(format!("I couldn't read this chat: {:?}", response)),
+        Err((code, message)) => Err(format!("Failed to load chat details: {} {}", code, message)),
+    }
+}
+
+// The text messages among the selected events, in the order they arrive
+async fn fetch_messages(
+    client: &Client<AgentRuntime, BotCommandContext>,
+    criteria: EventsSelectionCriteria,
+) -> Result<Vec<RecentMessage>, String> {
+    let events = match client.chat_events(criteria).execute_async().await {
+        Ok(ChatEventsResponse::Success(response)) => response.events,
+        Ok(ChatEventsResponse::NotAuthorized) => {
+            return Err("I don't have permission to read messages in this chat.".to_string())
+        }
+        Ok(response) => return Err(format!("I couldn't read this chat's messages: {:?}", response)),
+        Err((code, message)) => return Err(format!("Failed to load messages: {} {}", code, message)),
+    };
+
+    let messages = events
+        .into_iter()
+        .filter_map(|event| match event.event {
+            ChatEvent::Message(message) => match message.content {
+                MessageContent::Text(content) => Some(RecentMessage {
+                    event_index: event.index,
+                    sender: message.sender.to_string(),
+                    text: content.text,
+                }),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+
+    Ok(messages)
+}
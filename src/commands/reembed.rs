@@ -0,0 +1,195 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::Arc;
+use std::sync::LazyLock;
+use tracing::{error, info};
+
+use crate::chat_id::canonical_chat_id;
+use crate::memory::{EmbeddingModel, MemoryBackend};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Reembed::definition);
+
+// Memories sent to the embeddings API per call
+const BATCH_SIZE: usize = 32;
+// Progress is shown in the placeholder after this many batches
+const PROGRESS_EVERY: usize = 10;
+
+/// Recomputes a chat's memory embeddings with the current model, e.g. after
+/// `embeddings.model` changes and the stored vectors can no longer be compared
+pub struct Reembed {
+    pub memory_store: Arc<dyn MemoryBackend>,
+    pub embedding_model: Arc<dyn EmbeddingModel + Send + Sync>,
+    pub admins: Vec<String>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Reembed {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing reembed command for {}", chat_id);
+
+        let response = if !self.admins.contains(&user_id) {
+            "Only admins can re-embed the chat's memories.".to_string()
+        } else {
+            // Shown in place of the placeholder and left unfinalised, so the reply replaces it
+            let progress = |updated: usize, total: usize| {
+                client
+                    .send_text_message(format!("Re-embedded {} of {} memories...", updated, total))
+                    .with_finalised(false)
+                    .with_block_level_markdown(super::markdown_enabled())
+                    .execute_then_return_message(|_, _| ());
+            };
+            match self.reembed(&chat_id, progress).await {
+                Ok(response) => response,
+                Err(e) => {
+                    error!("Error re-embedding memories: {}", e);
+                    format!("I encountered an error: {}", e)
+                }
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Reembed {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "reembed".to_string(),
+            description: Some("Recompute the chat's memory embeddings with the current model (admins only)".to_string()),
+            placeholder: Some("Re-embedding memories...".to_string()),
+            params: Vec::new(),
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(false),
+        }
+    }
+
+    // Batches already written stay updated if a later one fails; running the command
+    // again redoes them all. `progress` is told how far it got every few batches.
+    async fn reembed(&self, chat_id: &str, progress: impl Fn(usize, usize)) -> Result<String, String> {
+        let memories = self
+            .memory_store
+            .export_chat(chat_id)
+            .await
+            .map_err(|e| format!("Failed to load memories: {}", e))?;
+
+        // Memories with metadata, such as glossary terms, are looked up by name and never embedded
+        let memories: Vec<(i64, String)> = memories
+            .into_iter()
+            .filter(|memory| memory.metadata.is_none())
+            .filter_map(|memory| memory.id.map(|id| (id, memory.content)))
+            .collect();
+
+        if memories.is_empty() {
+            return Ok("This chat has no memories to re-embed.".to_string());
+        }
+
+        let total = memories.len();
+        let model = self.embedding_model.name().to_string();
+        let mut updated = 0;
+
+        for (batch_number, batch) in memories.chunks(BATCH_SIZE).enumerate() {
+            let texts: Vec<String> = batch.iter().map(|(_, content)| content.clone()).collect();
+            let embeddings = self.embedding_model.embed_batch(&texts).await.map_err(|e| {
+                format!("Embedding failed after {} of {} memories: {}", updated, total, e)
+            })?;
+
+            let ids = batch.iter().map(|(id, _)| *id);
+            updated += self
+                .memory_store
+                .update_embeddings(ids.zip(embeddings).collect(), &model)
+                .await
+                .map_err(|e| format!("Failed to save embeddings after {} of {} memories: {}", updated, total, e))?;
+
+            let batches_done = batch_number + 1;
+            if batches_done % PROGRESS_EVERY == 0 && updated < total {
+                info!("Re-embedded {} of {} memories in {}", updated, total, chat_id);
+                progress(updated, total);
+            }
+        }
+
+        let dimension = self.embedding_model.dimension();
+        Ok(format!(
+            "Re-embedded {} memories with `{}` ({} dimensions).",
+            updated, model, dimension
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockEmbedding;
+    use crate::memory::MemoryStore;
+    use crate::test_support::memory;
+    use std::sync::Mutex;
+
+    fn reembedder(store: Arc<MemoryStore>) -> Reembed {
+        Reembed {
+            memory_store: store,
+            embedding_model: Arc::new(MockEmbedding),
+            admins: vec!["admin".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn updates_dimensions_and_model_tags() {
+        let store = Arc::new(MemoryStore::new(":memory:").unwrap());
+        let count = BATCH_SIZE * PROGRESS_EVERY + 5;
+        for i in 0..count {
+            // Embedded by the previous model
+            let note = memory(&format!("note number {}", i))
+                .embedding(vec![0.1, 0.2, 0.3])
+                .embedding_model("old-model")
+                .age_secs((count - i) as i64)
+                .build();
+            store.store_memory(note).await.unwrap();
+        }
+        let term = memory("Service level objective").metadata(r#"{"glossary_term":"SLO"}"#).build();
+        store.store_memory(term).await.unwrap();
+
+        let reported = Mutex::new(Vec::new());
+        let response = reembedder(store.clone())
+            .reembed("group:1", |updated, total| reported.lock().unwrap().push((updated, total)))
+            .await
+            .unwrap();
+
+        assert_eq!(response, format!("Re-embedded {} memories with `mock` (64 dimensions).", count));
+        assert_eq!(reported.into_inner().unwrap(), vec![(BATCH_SIZE * PROGRESS_EVERY, count)]);
+        for memory in store.export_chat("group:1").await.unwrap() {
+            if memory.metadata.is_some() {
+                // Glossary terms are left unembedded
+                assert_eq!(memory.embedding, None);
+                continue;
+            }
+            assert_eq!(memory.embedding_model.as_deref(), Some("mock"));
+            assert_eq!(memory.embedding, Some(MockEmbedding.embed_text(&memory.content).await.unwrap()));
+        }
+    }
+
+    #[tokio::test]
+    async fn says_when_there_is_nothing_to_reembed() {
+        let store = Arc::new(MemoryStore::new(":memory:").unwrap());
+        let response = reembedder(store).reembed("group:1", |_, _| panic!("no progress expected")).await;
+        assert_eq!(response, Ok("This chat has no memories to re-embed.".to_string()));
+    }
+}
@@ -0,0 +1,256 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, CommandHandlerRegistry, SuccessResult};
+use oc_bots_sdk::api::definition::BotCommandDefinition;
+use oc_bots_sdk::oc_api::client::Client;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+type Registry = CommandHandlerRegistry<AgentRuntime>;
+
+/// Names `[commands]` in config may toggle
+pub const COMMAND_NAMES: &[&str] = &[
+    "echo", "ask", "summarize", "remindme", "poll", "moderate", "memory", "usage",
+    "define", "history", "persona", "stats", "weather", "timezone", "karma",
+    "feedback", "summarizeurl", "classify", "toggle", "forgetme", "export", "import", "quote",
+    "ping", "roll", "reembed",
+];
+
+// One command to register, unless it is disabled
+struct Registration {
+    name: &'static str,
+    enabled: bool,
+    // Registers the handler under its own name and each of the given aliases
+    register: Option<Box<dyn FnOnce(Registry, Vec<String>) -> Registry>>,
+}
+
+impl Registration {
+    // Whether the command is switched on, by config or else by default
+    fn enabled_with(&self, overrides: &HashMap<String, bool>) -> bool {
+        overrides.get(self.name).copied().unwrap_or(self.enabled)
+    }
+}
+
+/// The bot's commands with whether each is on by default. `[commands]` in config
+/// can switch any of them on or off by name.
+#[derive(Default)]
+pub struct CommandRegistrations {
+    entries: Vec<Registration>,
+}
+
+impl CommandRegistrations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a command. `handler` is None when something it depends on is unavailable,
+    /// in which case it is skipped even if enabled.
+    pub fn add<C>(mut self, name: &'static str, enabled: bool, handler: Option<C>) -> Self
+    where
+        C: CommandHandler<AgentRuntime> + 'static,
+    {
+        self.entries.push(Registration {
+            name,
+            enabled,
+            register: handler.map(|handler| {
+                Box::new(move |mut registry: Registry, aliases: Vec<String>| {
+                    let handler = Arc::new(handler);
+                    for alias in aliases {
+                        registry = registry.register(Named::alias(alias, handler.clone()));
+                    }
+                    registry.register(Named::new(handler))
+                }) as Box<dyn FnOnce(Registry, Vec<String>) -> Registry>
+            }),
+        });
+        self
+    }
+
+    /// Register every enabled command, with `overrides` taking precedence over the defaults.
+    /// `aliases` maps extra names to the command each one runs.
+    pub fn register_all(
+        self,
+        mut registry: Registry,
+        overrides: &HashMap<String, bool>,
+        aliases: &HashMap<String, String>,
+    ) -> Registry {
+        for entry in self.entries {
+            let enabled = entry.enabled_with(overrides);
+            match entry.register {
+                Some(register) if enabled => {
+                    let names = aliases_of(entry.name, aliases);
+                    if names.is_empty() {
+                        info!("Registering command {}", entry.name);
+                    } else {
+                        info!("Registering command {} with aliases {}", entry.name, names.join(", "));
+                    }
+                    registry = register(registry, names);
+                }
+                None if enabled => info!("Command {} is enabled but unavailable, skipping", entry.name),
+                _ => info!("Command {} is disabled", entry.name),
+            }
+        }
+        registry
+    }
+}
+
+// The aliases that run `command`, sorted so they register and log in a stable order
+fn aliases_of(command: &str, aliases: &HashMap<String, String>) -> Vec<String> {
+    let mut names: Vec<String> = aliases
+        .iter()
+        .filter(|(_, target)| target.as_str() == command)
+        .map(|(alias, _)| alias.clone())
+        .collect();
+    names.sort();
+    names
+}
+
+// A handler shared between a command and its aliases, registered under one of their names
+struct Named<C> {
+    definition: BotCommandDefinition,
+    handler: Arc<C>,
+}
+
+impl<C: CommandHandler<AgentRuntime>> Named<C> {
+    fn new(handler: Arc<C>) -> Self {
+        Self {
+            definition: handler.definition().clone(),
+            handler,
+        }
+    }
+
+    // The command's definition under another name, saying what it is short for
+    fn alias(alias: String, handler: Arc<C>) -> Self {
+        let mut definition = handler.definition().clone();
+        definition.description = Some(match &definition.description {
+            Some(description) => format!("Short for /{}: {}", definition.name, description),
+            None => format!("Short for /{}", definition.name),
+        });
+        definition.name = alias;
+        Self { definition, handler }
+    }
+}
+
+#[async_trait]
+impl<C: CommandHandler<AgentRuntime> + 'static> CommandHandler<AgentRuntime> for Named<C> {
+    fn definition(&self) -> &BotCommandDefinition {
+        &self.definition
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        self.handler.execute(client).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::echo::Echo;
+    use crate::commands::poll::Poll;
+
+    // Names of the commands `register_all` would register, in order
+    fn registered(registrations: &CommandRegistrations, overrides: &HashMap<String, bool>) -> Vec<&'static str> {
+        registrations
+            .entries
+            .iter()
+            .filter(|entry| entry.register.is_some() && entry.enabled_with(overrides))
+            .map(|entry| entry.name)
+            .collect()
+    }
+
+    #[test]
+    fn echo_is_left_out_when_disabled() {
+        let registrations = CommandRegistrations::new()
+            .add("echo", false, Some(Echo::new(100)))
+            .add("poll", true, Some(Poll));
+
+        assert_eq!(registered(&registrations, &HashMap::new()), vec!["poll"]);
+    }
+
+    #[test]
+    fn echo_is_registered_when_enabled() {
+        let registrations = CommandRegistrations::new()
+            .add("echo", true, Some(Echo::new(100)))
+            .add("poll", true, Some(Poll));
+
+        assert_eq!(registered(&registrations, &HashMap::new()), vec!["echo", "poll"]);
+    }
+
+    #[test]
+    fn config_switches_commands_on_and_off_by_name() {
+        let registrations = || {
+            CommandRegistrations::new()
+                .add("echo", false, Some(Echo::new(100)))
+                .add("poll", true, Some(Poll))
+        };
+        let overrides = HashMap::from([("echo".to_string(), true), ("poll".to_string(), false)]);
+
+        assert_eq!(registered(&registrations(), &overrides), vec!["echo"]);
+        assert_eq!(registered(&registrations(), &HashMap::new()), vec!["poll"]);
+    }
+
+    #[test]
+    fn aliases_are_found_by_the_command_they_run() {
+        let aliases = HashMap::from([
+            ("s".to_string(), "summarize".to_string()),
+            ("q".to_string(), "ask".to_string()),
+            ("sum".to_string(), "summarize".to_string()),
+        ]);
+
+        assert_eq!(aliases_of("summarize", &aliases), vec!["s", "sum"]);
+        assert_eq!(aliases_of("ask", &aliases), vec!["q"]);
+        assert!(aliases_of("echo", &aliases).is_empty());
+    }
+
+    #[test]
+    fn an_alias_runs_the_same_handler_under_its_own_name() {
+        let handler = Arc::new(Echo::new(100));
+        let command = Named::new(handler.clone());
+        let alias = Named::alias("e".to_string(), handler.clone());
+
+        assert!(Arc::ptr_eq(&alias.handler, &handler));
+        assert!(Arc::ptr_eq(&command.handler, &handler));
+        assert_eq!(command.definition().name, "echo");
+        assert_eq!(alias.definition().name, "e");
+
+        let original = handler.definition();
+        assert_eq!(
+            alias.definition().description,
+            Some(format!("Short for /echo: {}", original.description.clone().unwrap()))
+        );
+        assert_eq!(alias.definition().params.len(), original.params.len());
+    }
+
+    #[test]
+    fn without_an_llm_only_the_other_commands_register() {
+        use crate::agent::Agent;
+        use crate::commands::ask::Ask;
+        use crate::commands::remindme::RemindMe;
+        use crate::commands::summarize::Summarize;
+        use crate::commands::Visibility;
+        use crate::llm::LlmProvider;
+        use crate::reminders::ReminderStore;
+        use std::sync::Arc;
+
+        // As in main.rs when no Mistral key is configured
+        let llm_client: Option<Arc<dyn LlmProvider>> = None;
+        let reminder_store = Some(Arc::new(ReminderStore::new(":memory:").unwrap()));
+
+        let registrations = CommandRegistrations::new()
+            .add("echo", true, Some(Echo::new(100)))
+            .add("ask", true, llm_client.clone().map(|llm| Ask::new(Arc::new(Agent::new(llm)), Visibility::default(), 100)))
+            .add("summarize", true, llm_client.clone().map(|llm| Summarize::new(llm, Visibility::default(), 100)))
+            .add("remindme", true, reminder_store.map(|store| RemindMe {
+                store,
+                max_active_per_user: 10,
+                timezones: None,
+                webhook: None,
+            }));
+
+        assert_eq!(registered(&registrations, &HashMap::new()), vec!["echo", "remindme"]);
+    }
+}
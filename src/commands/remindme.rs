@@ -5,15 +5,32 @@ use oc_bots_sdk::types::BotCommandContext;
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
 use std::sync::LazyLock;
-use std::thread;
-use tracing::info;
+use std::sync::Arc;
+use std::sync::atomic::AtomicUsize;
+use chrono::{DateTime, Duration, Utc};
+use chrono_tz::Tz;
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::reminders::{self, ReminderStore, Repeat};
+use crate::time_parse::parse_natural_time;
+use crate::timezones::{self, TimezoneStore};
+use crate::webhook::WebhookNotifier;
 
 static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(RemindMe::definition);
 
-pub struct RemindMe;
+pub struct RemindMe {
+    pub store: Arc<ReminderStore>,
+    // Most reminders a user may have waiting at once
+    pub max_active_per_user: usize,
+    pub timezones: Option<Arc<TimezoneStore>>,
+    // Told when a reminder fires
+    pub webhook: Option<Arc<WebhookNotifier>>,
+}
 
-// Global reminder counter to help with logging
-static mut REMINDER_COUNTER: usize = 0;
+/// Reminders scheduled in this process that have not fired yet
+pub static PENDING_REMINDERS: AtomicUsize = AtomicUsize::new(0);
 
 #[async_trait]
 impl CommandHandler<AgentRuntime> for RemindMe {
@@ -25,48 +42,45 @@ impl CommandHandler<AgentRuntime> for RemindMe {
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let reminder = client.context().command.arg::<String>("reminder").to_string();
-        let minutes = client.context().command.arg::<f64>("minutes");
-        
-        // Get a unique ID for this reminder for logging purposes
-        let reminder_id = unsafe {
-            REMINDER_COUNTER += 1;
-            REMINDER_COUNTER
+        let reminder = match params::required_string(client.context(), "reminder") {
+            Ok(reminder) => reminder,
+            Err(e) => return Ok(super::reply(&client, e, false)),
         };
+        let when = params::optional_string(client.context(), "when");
+        let minutes = params::optional_decimal(client.context(), "minutes");
+        // "none" or absent means a one-shot reminder
+        let repeat = params::optional_string(client.context(), "repeat")
+            .and_then(|repeat| repeat.parse::<Repeat>().ok());
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
         
-        info!("Setting reminder #{} for {} minutes: {}", reminder_id, minutes, reminder);
+        info!("Setting reminder (when: {:?}, minutes: {:?}): {}", when, minutes, reminder);
         
-        // Create a confirmation message
-        let confirmation = format!(
-            "I'll remind you in {} minutes about: {}",
-            minutes,
-            reminder
-        );
+        // A phrase like "tomorrow at 9am" takes precedence over a plain number of minutes
+        let timezone = timezones::user_timezone(self.timezones.as_deref(), &user_id).await;
+        let now = Utc::now();
+        let fire_at = match (when.as_deref(), minutes) {
+            (Some(when), _) => parse_natural_time(when, now, &timezone),
+            (_, Some(minutes)) => Ok(now + Duration::seconds((minutes * 60.0) as i64)),
+            _ => Err("Please say when, e.g. \"in 2 hours\" or \"tomorrow at 9am\", or give a number of minutes.".to_string()),
+        };
+        
+        let result = match fire_at {
+            Ok(fire_at) => self.add_reminder(&chat_id, &user_id, &reminder, fire_at, repeat, timezone).await,
+            Err(e) => Err(e),
+        };
+        let response = match result {
+            Ok(response) => response,
+            Err(e) => {
+                error!("Failed to set reminder: {}", e);
+                format!("I couldn't set that reminder: {}", e)
+            }
+        };
         
-        // Send confirmation message first and get the result
         let message = client
-            .send_text_message(confirmation.clone())
-            .with_block_level_markdown(true)
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
             .execute_then_return_message(|_, _| ());
-            
-        // Extract information needed for reminder
-        let user_id = client.context().command.initiator.to_string();
-        let seconds = (minutes * 60.0) as u64;
-        let reminder_clone = reminder.clone();
-        
-        // Use std::thread for the reminder to completely detach it from Tokio runtime
-        thread::spawn(move || {
-            // Sleep using std::thread::sleep to avoid tokio runtime issues
-            info!("Reminder #{} scheduled to trigger in {} seconds", reminder_id, seconds);
-            thread::sleep(std::time::Duration::from_secs(seconds));
-            
-            // Log that the reminder was triggered
-            info!("REMINDER #{} TRIGGERED for user {}: {}", 
-                  reminder_id, user_id, reminder_clone);
-                  
-            // Note: In a production system, you would want to implement a more robust
-            // reminder system using a persistent storage and a separate process/service
-        });
 
         Ok(SuccessResult { message })
     }
@@ -91,21 +105,130 @@ impl RemindMe {
                         multi_line: true,
                     }),
                 },
+                BotCommandParam {
+                    name: "when".to_string(),
+                    description: Some("When to send the reminder, e.g. \"in 2 hours\", \"tomorrow at 9am\" or \"next monday\"".to_string()),
+                    placeholder: Some("e.g. tomorrow at 9am".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 100,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
                 BotCommandParam {
                     name: "minutes".to_string(),
-                    description: Some("How many minutes from now to send the reminder".to_string()),
+                    description: Some("How many minutes from now to send the reminder (if \"when\" isn't given)".to_string()),
                     placeholder: Some("Enter minutes".to_string()),
-                    required: true,
+                    required: false,
                     param_type: BotCommandParamType::DecimalParam(DecimalParam {
                         min_value: 1.0,
                         max_value: 10080.0, // Max 1 week (7 days * 24 hours * 60 minutes)
                         choices: Vec::new(),
                     }),
                 },
+                BotCommandParam {
+                    name: "repeat".to_string(),
+                    description: Some("Repeat the reminder after it fires".to_string()),
+                    placeholder: Some("Choose how often".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "none".to_string(),
+                                value: "none".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "daily".to_string(),
+                                value: "daily".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "weekly".to_string(),
+                                value: "weekly".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
             ],
             permissions: BotPermissions::from_message_permission(MessagePermission::Text),
             default_role: None,
             direct_messages: Some(true),
         }
     }
-} 
\ No newline at end of file
+
+    async fn add_reminder(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        reminder: &str,
+        fire_at: DateTime<Utc>,
+        repeat: Option<Repeat>,
+        timezone: Tz,
+    ) -> Result<String, String> {
+        let pending = self
+            .store
+            .count_pending_for_user(user_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        if pending >= self.max_active_per_user {
+            return Ok(format!(
+                "You already have {} reminders waiting, the most allowed. Please wait for one to fire before setting another.",
+                pending
+            ));
+        }
+        
+        let stored = self
+            .store
+            .add(chat_id, user_id, reminder, fire_at, repeat, timezone)
+            .await
+            .map_err(|e| e.to_string())?;
+        reminders::schedule(self.store.clone(), stored, self.webhook.clone());
+        
+        let at = fire_at.with_timezone(&timezone).format("%a %-d %b at %H:%M %Z");
+        Ok(match repeat {
+            Some(Repeat::Daily) => format!("I'll remind you on {} and then every day about: {}", at, reminder),
+            Some(Repeat::Weekly) => format!("I'll remind you on {} and then every week about: {}", at, reminder),
+            None => format!("I'll remind you on {} about: {}", at, reminder),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn remind_me(max_active_per_user: usize) -> RemindMe {
+        RemindMe {
+            store: Arc::new(ReminderStore::new(":memory:").unwrap()),
+            max_active_per_user,
+            timezones: None,
+            webhook: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn rejects_reminders_past_the_limit() {
+        let remind_me = remind_me(2);
+        let fire_at = Utc::now() + Duration::hours(1);
+
+        for text in ["stretch", "drink water"] {
+            let response = remind_me.add_reminder("group:1", "alice", text, fire_at, None, Tz::UTC).await.unwrap();
+            assert!(response.starts_with("I'll remind you on"), "{}", response);
+        }
+
+        let response = remind_me.add_reminder("group:1", "alice", "one too many", fire_at, None, Tz::UTC).await.unwrap();
+        assert_eq!(
+            response,
+            "You already have 2 reminders waiting, the most allowed. Please wait for one to fire before setting another."
+        );
+        assert_eq!(remind_me.store.count_pending_for_user("alice").await.unwrap(), 2);
+
+        // The limit is per user
+        let response = remind_me.add_reminder("group:1", "bob", "stretch", fire_at, None, Tz::UTC).await.unwrap();
+        assert!(response.starts_with("I'll remind you on"), "{}", response);
+    }
+}
@@ -0,0 +1,218 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use rand::Rng;
+use std::sync::LazyLock;
+use tracing::info;
+
+use super::params;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Roll::definition);
+
+// Bounds that keep a roll, and the message listing it, small
+const MAX_DICE: u32 = 100;
+const MAX_SIDES: u32 = 1000;
+const MAX_MODIFIER: i64 = 10_000;
+// Used when no notation is given
+const DEFAULT_NOTATION: &str = "1d6";
+
+pub struct Roll;
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Roll {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let notation = params::optional_string(client.context(), "dice")
+            .unwrap_or_else(|| DEFAULT_NOTATION.to_string());
+
+        info!("Processing roll command with dice: {}", notation);
+
+        let text = match parse_dice(&notation) {
+            Ok(dice) => render_roll(&dice, &dice.roll(&mut rand::thread_rng())),
+            Err(e) => format!("I couldn't roll that: {}", e),
+        };
+
+        let message = client
+            .send_text_message(text)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Roll {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "roll".to_string(),
+            description: Some("Roll dice, e.g. 2d6+3".to_string()),
+            placeholder: Some("Rolling...".to_string()),
+            params: vec![BotCommandParam {
+                name: "dice".to_string(),
+                description: Some(format!(
+                    "Dice notation: count, 'd', sides and an optional +/- modifier (default {})",
+                    DEFAULT_NOTATION
+                )),
+                placeholder: Some("e.g. 2d6+3".to_string()),
+                required: false,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 20,
+                    choices: Vec::new(),
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+}
+
+/// A roll in dice notation, e.g. `2d6+3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Dice {
+    count: u32,
+    sides: u32,
+    modifier: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RollResult {
+    rolls: Vec<u32>,
+    total: i64,
+}
+
+impl Dice {
+    fn roll<R: Rng>(&self, rng: &mut R) -> RollResult {
+        let rolls: Vec<u32> = (0..self.count).map(|_| rng.gen_range(1..=self.sides)).collect();
+        let total = rolls.iter().map(|&roll| roll as i64).sum::<i64>() + self.modifier;
+        RollResult { rolls, total }
+    }
+}
+
+// Parse `[count]d<sides>[+|-modifier]`, ignoring case and spaces; the count defaults to 1
+fn parse_dice(raw: &str) -> Result<Dice, String> {
+    let notation = raw.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_lowercase();
+    let invalid = || format!("'{}' isn't dice notation like 2d6+3", raw.trim());
+
+    let (count, rest) = notation.split_once('d').ok_or_else(invalid)?;
+    let (sides, modifier) = match rest.find(['+', '-']) {
+        Some(at) => rest.split_at(at),
+        None => (rest, ""),
+    };
+
+    let count = if count.is_empty() {
+        1
+    } else {
+        count.parse::<u32>().map_err(|_| invalid())?
+    };
+    let sides = sides.parse::<u32>().map_err(|_| invalid())?;
+    let modifier = if modifier.is_empty() {
+        0
+    } else {
+        // `parse` accepts a leading '+' as well as '-'
+        modifier.parse::<i64>().map_err(|_| invalid())?
+    };
+
+    if count == 0 || count > MAX_DICE {
+        return Err(format!("roll between 1 and {} dice", MAX_DICE));
+    }
+    if !(2..=MAX_SIDES).contains(&sides) {
+        return Err(format!("dice need between 2 and {} sides", MAX_SIDES));
+    }
+    if modifier.abs() > MAX_MODIFIER {
+        return Err(format!("the modifier must be between -{} and {}", MAX_MODIFIER, MAX_MODIFIER));
+    }
+
+    Ok(Dice { count, sides, modifier })
+}
+
+fn render_roll(dice: &Dice, result: &RollResult) -> String {
+    let notation = match dice.modifier {
+        0 => format!("{}d{}", dice.count, dice.sides),
+        modifier => format!("{}d{}{:+}", dice.count, dice.sides, modifier),
+    };
+    if dice.count == 1 && dice.modifier == 0 {
+        return format!("🎲 **{}:** {}", notation, result.total);
+    }
+
+    let rolls: Vec<String> = result.rolls.iter().map(u32::to_string).collect();
+    let modifier = match dice.modifier {
+        0 => String::new(),
+        modifier if modifier > 0 => format!(" + {}", modifier),
+        modifier => format!(" - {}", -modifier),
+    };
+    format!("🎲 **{}:** [{}]{} = **{}**", notation, rolls.join(", "), modifier, result.total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn parses_full_notation() {
+        assert_eq!(parse_dice("2d6+3"), Ok(Dice { count: 2, sides: 6, modifier: 3 }));
+        assert_eq!(parse_dice("3d8-2"), Ok(Dice { count: 3, sides: 8, modifier: -2 }));
+    }
+
+    #[test]
+    fn count_defaults_to_one_and_case_and_spaces_are_ignored() {
+        assert_eq!(parse_dice("d20"), Ok(Dice { count: 1, sides: 20, modifier: 0 }));
+        assert_eq!(parse_dice(" 4 D 10 + 1 "), Ok(Dice { count: 4, sides: 10, modifier: 1 }));
+    }
+
+    #[test]
+    fn rejects_malformed_notation() {
+        for raw in ["", "abc", "2x6", "2d", "d", "2d6+", "2d6+x", "-1d6", "2d6+3+1"] {
+            assert!(parse_dice(raw).is_err(), "{:?} should not parse", raw);
+        }
+    }
+
+    #[test]
+    fn enforces_bounds() {
+        assert!(parse_dice("0d6").is_err());
+        assert!(parse_dice(&format!("{}d6", MAX_DICE)).is_ok());
+        assert!(parse_dice(&format!("{}d6", MAX_DICE + 1)).is_err());
+        assert!(parse_dice("1d1").is_err());
+        assert!(parse_dice("1d2").is_ok());
+        assert!(parse_dice(&format!("1d{}", MAX_SIDES)).is_ok());
+        assert!(parse_dice(&format!("1d{}", MAX_SIDES + 1)).is_err());
+        assert!(parse_dice(&format!("1d6-{}", MAX_MODIFIER)).is_ok());
+        assert!(parse_dice(&format!("1d6+{}", MAX_MODIFIER + 1)).is_err());
+    }
+
+    #[test]
+    fn seeded_roll_is_repeatable_and_in_range() {
+        let dice = Dice { count: 5, sides: 6, modifier: -2 };
+        let first = dice.roll(&mut StdRng::seed_from_u64(42));
+        let second = dice.roll(&mut StdRng::seed_from_u64(42));
+
+        assert_eq!(first, second);
+        assert_eq!(first.rolls.len(), 5);
+        assert!(first.rolls.iter().all(|roll| (1..=6).contains(roll)));
+        assert_eq!(first.total, first.rolls.iter().map(|&roll| roll as i64).sum::<i64>() - 2);
+    }
+
+    #[test]
+    fn renders_single_and_multiple_rolls() {
+        let single = Dice { count: 1, sides: 20, modifier: 0 };
+        let result = RollResult { rolls: vec![17], total: 17 };
+        assert_eq!(render_roll(&single, &result), "🎲 **1d20:** 17");
+
+        let multiple = Dice { count: 2, sides: 6, modifier: -1 };
+        let result = RollResult { rolls: vec![3, 5], total: 7 };
+        assert_eq!(render_roll(&multiple, &result), "🎲 **2d6-1:** [3, 5] - 1 = **7**");
+    }
+}
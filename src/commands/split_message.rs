@@ -0,0 +1,231 @@
+// Splitting replies too long for one OpenChat message. Lengths are in characters.
+// Paragraphs are kept whole where they fit, then lines and sentences; a fenced code
+// block that has to be split is closed at the end of each part and reopened in the next.
+
+/// `text` as messages of at most `max_chars` characters, to be sent in order. There is
+/// always at least one, which is empty if `text` is only whitespace.
+pub(crate) fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    if char_len(text) <= max_chars {
+        return vec![text.to_string()];
+    }
+
+    let pieces = blocks(text)
+        .iter()
+        .flat_map(|block| fit_block(block, max_chars))
+        .map(|piece| (piece, "\n\n"))
+        .collect();
+    let parts = pack(pieces, max_chars);
+    if parts.is_empty() {
+        return vec![String::new()];
+    }
+    parts
+}
+
+// Paragraphs separated by blank lines, with each fenced code block (blank lines and all) as one
+fn blocks(text: &str) -> Vec<String> {
+    let mut blocks = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut fence: Option<String> = None;
+
+    for line in text.lines() {
+        match &fence {
+            Some(marker) => {
+                current.push(line);
+                if line.trim_start().starts_with(marker.as_str()) {
+                    blocks.push(current.join("\n"));
+                    current.clear();
+                    fence = None;
+                }
+            }
+            None if fence_marker(line).is_some() => {
+                if !current.is_empty() {
+                    blocks.push(current.join("\n"));
+                    current.clear();
+                }
+                current.push(line);
+                fence = fence_marker(line);
+            }
+            None if line.trim().is_empty() => {
+                if !current.is_empty() {
+                    blocks.push(current.join("\n"));
+                    current.clear();
+                }
+            }
+            None => current.push(line),
+        }
+    }
+    if !current.is_empty() {
+        blocks.push(current.join("\n"));
+    }
+    blocks
+}
+
+// The run of backticks or tildes opening a code fence, e.g. "```"
+fn fence_marker(line: &str) -> Option<String> {
+    let line = line.trim_start();
+    let fence_char = line.chars().next().filter(|c| *c == '`' || *c == '~')?;
+    let marker: String = line.chars().take_while(|c| *c == fence_char).collect();
+    (marker.len() >= 3).then_some(marker)
+}
+
+// Pieces of the block that each fit in a message
+fn fit_block(block: &str, max_chars: usize) -> Vec<String> {
+    if char_len(block) <= max_chars {
+        return vec![block.to_string()];
+    }
+
+    let mut lines = block.lines();
+    let first = lines.next().unwrap_or_default();
+    let Some(marker) = fence_marker(first) else {
+        return fit_prose(block, max_chars);
+    };
+
+    // Every part repeats the opening line (with its language) and gets a closing fence
+    let mut body: Vec<&str> = lines.collect();
+    if body.last().is_some_and(|line| line.trim_start().starts_with(marker.as_str())) {
+        body.pop();
+    }
+    let overhead = char_len(first) + char_len(&marker) + 2;
+    if overhead >= max_chars {
+        return hard_split(block, max_chars);
+    }
+    let budget = max_chars - overhead;
+
+    let units = body
+        .iter()
+        .flat_map(|line| hard_split(line, budget))
+        .map(|line| (line, "\n"))
+        .collect();
+    pack(units, budget)
+        .into_iter()
+        .map(|part| format!("{}\n{}\n{}", first, part, marker))
+        .collect()
+}
+
+// A long paragraph split at line breaks, then after sentences, then at spaces
+fn fit_prose(paragraph: &str, max_chars: usize) -> Vec<String> {
+    let mut units = Vec::new();
+    for line in paragraph.lines() {
+        for (i, sentence) in sentences(line).into_iter().enumerate() {
+            let separator = if i == 0 { "\n" } else { " " };
+            units.extend(hard_split(sentence, max_chars).into_iter().map(|part| (part, separator)));
+        }
+    }
+    pack(units, max_chars)
+}
+
+// The line split after each '.', '!' or '?' followed by a space
+fn sentences(line: &str) -> Vec<&str> {
+    let mut sentences = Vec::new();
+    let mut start = 0;
+    let mut chars = line.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let ends_sentence = matches!(c, '.' | '!' | '?')
+            && chars.peek().is_some_and(|(_, next)| next.is_whitespace());
+        if ends_sentence {
+            sentences.push(line[start..=i].trim());
+            start = i + 1;
+        }
+    }
+    sentences.push(line[start..].trim());
+    sentences.retain(|sentence| !sentence.is_empty());
+    sentences
+}
+
+// Cut text with no better boundary, at the last space that fits or else mid-word
+fn hard_split(text: &str, max_chars: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while char_len(rest) > max_chars {
+        let limit = rest.char_indices().nth(max_chars).map_or(rest.len(), |(i, _)| i);
+        let cut = match rest[..limit].rfind(' ') {
+            Some(space) if space > 0 => space,
+            _ => limit,
+        };
+        parts.push(rest[..cut].to_string());
+        rest = rest[cut..].strip_prefix(' ').unwrap_or(&rest[cut..]);
+    }
+    parts.push(rest.to_string());
+    parts
+}
+
+// Join units into as few parts of at most `max_chars` as possible, each unit preceded
+// by its separator unless it starts a part
+fn pack(units: Vec<(String, &str)>, max_chars: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    for (unit, separator) in units {
+        if !current.is_empty() && char_len(&current) + char_len(separator) + char_len(&unit) > max_chars {
+            parts.push(std::mem::take(&mut current));
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+        }
+        current.push_str(&unit);
+    }
+    if !current.is_empty() {
+        parts.push(current);
+    }
+    parts
+}
+
+fn char_len(text: &str) -> usize {
+    text.chars().count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_text_is_one_message() {
+        assert_eq!(split_message("Hello there", 100), vec!["Hello there"]);
+    }
+
+    #[test]
+    fn whole_paragraphs_share_a_message_where_they_fit() {
+        assert_eq!(split_message("aaaa\n\nbbbb\n\ncccc", 10), vec!["aaaa\n\nbbbb", "cccc"]);
+    }
+
+    #[test]
+    fn a_long_paragraph_splits_after_sentences() {
+        assert_eq!(
+            split_message("One two. Three four. Five six.", 12),
+            vec!["One two.", "Three four.", "Five six."]
+        );
+    }
+
+    #[test]
+    fn a_long_word_is_cut_by_characters() {
+        assert_eq!(split_message("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(split_message("ééééé", 2), vec!["éé", "éé", "é"]);
+    }
+
+    #[test]
+    fn code_fences_are_closed_and_reopened_across_parts() {
+        let lines: Vec<String> = (0..10).map(|i| format!("let x = {};", i)).collect();
+        let text = format!("Here is the code:\n\n```rust\n{}\n```", lines.join("\n"));
+
+        let parts = split_message(&text, 60);
+
+        assert_eq!(parts.len(), 4, "{:#?}", parts);
+        assert_eq!(parts[0], "Here is the code:");
+        for part in &parts {
+            assert!(char_len(part) <= 60, "{:?}", part);
+        }
+        for part in &parts[1..] {
+            assert!(part.starts_with("```rust\n") && part.ends_with("\n```"), "{:?}", part);
+        }
+        let code: Vec<&str> = parts[1..]
+            .iter()
+            .flat_map(|part| part.lines())
+            .filter(|line| !line.starts_with("```"))
+            .collect();
+        assert_eq!(code, lines);
+    }
+
+    #[test]
+    fn only_whitespace_is_one_empty_message() {
+        assert_eq!(split_message("   \n\n  ", 2), vec![""]);
+    }
+}
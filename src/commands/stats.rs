@@ -0,0 +1,240 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::collections::HashSet;
+use std::sync::atomic::Ordering;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use tracing::{error, info};
+
+use crate::chat_id::canonical_chat_id;
+use crate::commands::remindme::PENDING_REMINDERS;
+use crate::feedback::{FeedbackEntry, FeedbackStore, FeedbackSummary};
+use crate::memory::MemoryBackend;
+use crate::usage::UsageStore;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Stats::definition);
+
+// How many of the latest feedback entries to quote
+const RECENT_FEEDBACK: usize = 5;
+
+pub struct Stats {
+    pub memory_store: Option<Arc<dyn MemoryBackend>>,
+    pub usage_store: Option<Arc<UsageStore>>,
+    pub feedback_store: Option<Arc<FeedbackStore>>,
+    pub admins: Vec<String>,
+}
+
+// Activity figures for one chat; None where the backing store is disabled
+#[derive(Debug, Default)]
+struct ChatStats {
+    memories: Option<u64>,
+    users: usize,
+    pending_reminders: usize,
+    llm_calls_24h: Option<u64>,
+    feedback: Option<FeedbackSummary>,
+    recent_feedback: Vec<FeedbackEntry>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Stats {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let initiator = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing stats command for {}", chat_id);
+
+        let response = self.respond(&initiator, &chat_id).await;
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Stats {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "stats".to_string(),
+            description: Some("Show bot activity in this chat (admins only)".to_string()),
+            placeholder: Some("Collecting stats...".to_string()),
+            params: Vec::new(),
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(true),
+        }
+    }
+
+    async fn respond(&self, initiator: &str, chat_id: &str) -> String {
+        if !self.admins.iter().any(|admin| admin == initiator) {
+            return "Only admins can view bot statistics.".to_string();
+        }
+
+        match self.collect(chat_id).await {
+            Ok(stats) => render_stats(&stats),
+            Err(e) => {
+                error!("Failed to collect stats: {}", e);
+                format!("I encountered an error while collecting stats: {}", e)
+            }
+        }
+    }
+
+    async fn collect(&self, chat_id: &str) -> anyhow::Result<ChatStats> {
+        let mut stats = ChatStats {
+            pending_reminders: PENDING_REMINDERS.load(Ordering::Relaxed),
+            ..ChatStats::default()
+        };
+        let mut users: HashSet<String> = HashSet::new();
+
+        if let Some(store) = &self.memory_store {
+            stats.memories = Some(store.count_memories(chat_id).await?);
+            users.extend(store.user_ids(chat_id).await?);
+        }
+
+        if let Some(store) = &self.usage_store {
+            let since = Utc::now() - Duration::hours(24);
+            stats.llm_calls_24h = Some(store.count_calls(chat_id, since).await?);
+            users.extend(store.user_ids(chat_id).await?);
+        }
+
+        if let Some(store) = &self.feedback_store {
+            stats.feedback = Some(store.summary(chat_id).await?);
+            stats.recent_feedback = store.recent(chat_id, RECENT_FEEDBACK).await?;
+        }
+
+        stats.users = users.len();
+        Ok(stats)
+    }
+}
+
+fn render_stats(stats: &ChatStats) -> String {
+    let or_disabled = |value: Option<u64>| match value {
+        Some(value) => value.to_string(),
+        None => "n/a (disabled)".to_string(),
+    };
+
+    let feedback = match stats.feedback {
+        Some(FeedbackSummary { count, average_rating: Some(average) }) => {
+            format!("{} (average rating {:.1}/5)", count, average)
+        }
+        Some(FeedbackSummary { count, average_rating: None }) => count.to_string(),
+        None => "n/a (disabled)".to_string(),
+    };
+
+    let mut response = format!(
+        "**Bot activity in this chat**\n\n\
+        - Memories stored: {}\n\
+        - Distinct users: {}\n\
+        - LLM calls (last 24h): {}\n\
+        - Reminders pending (all chats): {}\n\
+        - Feedback received: {}",
+        or_disabled(stats.memories),
+        stats.users,
+        or_disabled(stats.llm_calls_24h),
+        stats.pending_reminders,
+        feedback
+    );
+
+    if !stats.recent_feedback.is_empty() {
+        response.push_str("\n\n**Latest feedback**\n");
+        for entry in &stats.recent_feedback {
+            response.push_str(&format!(
+                "\n- {}/5 from {} on {}: {}",
+                entry.rating,
+                entry.user_id,
+                entry.created_at.format("%Y-%m-%d"),
+                entry.text
+            ));
+        }
+    }
+
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::TokenUsage;
+    use crate::memory::MemoryStore;
+    use crate::test_support::memory;
+    use crate::usage::UsageContext;
+
+    async fn stats() -> Stats {
+        let memory_store = MemoryStore::new(":memory:").unwrap();
+        memory_store.store_memory(memory("first").user("alice").age_secs(3).build()).await.unwrap();
+        memory_store.store_memory(memory("second").user("bob").age_secs(2).build()).await.unwrap();
+        memory_store.store_memory(memory("third").user("bob").age_secs(1).build()).await.unwrap();
+
+        let usage_store = UsageStore::new(":memory:").unwrap();
+        for user_id in ["alice", "carol"] {
+            let context = UsageContext {
+                chat_id: "group:1".to_string(),
+                user_id: user_id.to_string(),
+                command: "ask".to_string(),
+            };
+            usage_store.record_usage(context, TokenUsage::default()).await.unwrap();
+        }
+
+        Stats {
+            memory_store: Some(Arc::new(memory_store)),
+            usage_store: Some(Arc::new(usage_store)),
+            feedback_store: None,
+            admins: vec!["admin".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn aggregates_across_stores() {
+        let stats = stats().await.collect("group:1").await.unwrap();
+
+        assert_eq!(stats.memories, Some(3));
+        assert_eq!(stats.llm_calls_24h, Some(2));
+        // alice, bob and carol, counted once each
+        assert_eq!(stats.users, 3);
+    }
+
+    #[tokio::test]
+    async fn other_chats_are_empty() {
+        let stats = stats().await.collect("group:2").await.unwrap();
+
+        assert_eq!(stats.memories, Some(0));
+        assert_eq!(stats.llm_calls_24h, Some(0));
+        assert_eq!(stats.users, 0);
+    }
+
+    #[tokio::test]
+    async fn disabled_stores_render_as_disabled() {
+        let stats = Stats {
+            memory_store: None,
+            usage_store: None,
+            feedback_store: None,
+            admins: vec!["admin".to_string()],
+        };
+
+        let response = stats.respond("admin", "group:1").await;
+        assert!(response.contains("- Memories stored: n/a (disabled)"), "{}", response);
+        assert!(response.contains("- LLM calls (last 24h): n/a (disabled)"), "{}", response);
+    }
+
+    #[tokio::test]
+    async fn only_admins_see_stats() {
+        let stats = stats().await;
+
+        assert_eq!(stats.respond("alice", "group:1").await, "Only admins can view bot statistics.");
+        assert!(stats.respond("admin", "group:1").await.contains("- Memories stored: 3"));
+    }
+}
@@ -1,76 +1,414 @@
 use async_trait::async_trait;
 use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
 use oc_bots_sdk::api::definition::*;
-use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk::types::{BotCommandContext, ChatPermission};
 use oc_bots_sdk_offchain::AgentRuntime;
 use oc_bots_sdk::oc_api::client::Client;
-use std::sync::LazyLock;
+use std::collections::HashSet;
 use std::sync::Arc;
 use tracing::{error, info};
 
-use crate::llm::MistralClient;
+use super::recent_messages::{fetch_messages_since, fetch_recent_messages, RecentMessage};
+use super::{params, Visibility};
+use crate::chat_id::canonical_chat_id;
+use crate::llm::{is_rate_limited, LlmProvider, SummaryContent, SummaryOptions};
+use crate::summaries::{RollingSummary, SummaryStore};
 
-static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Summarize::definition);
+const MAX_FETCHED_MESSAGES: usize = 200;
+// Messages read to start a chat's rolling summary when no count is given
+const DEFAULT_ROLLING_MESSAGES: usize = 50;
+/// Shortest pasted text worth summarizing
+pub const MIN_TEXT_LENGTH: u16 = 10;
 
 pub struct Summarize {
-    pub llm: Arc<MistralClient>,
+    llm: Arc<dyn LlmProvider>,
+    visibility: Visibility,
+    definition: BotCommandDefinition,
+    // Rolling summaries per chat, for the "update" mode
+    summaries: Option<Arc<SummaryStore>>,
 }
 
 #[async_trait]
 impl CommandHandler<AgentRuntime> for Summarize {
     fn definition(&self) -> &BotCommandDefinition {
-        &DEFINITION
+        &self.definition
     }
 
     async fn execute(
         &self,
         client: Client<AgentRuntime, BotCommandContext>,
     ) -> Result<SuccessResult, String> {
-        let text = client.context().command.arg::<String>("text").to_string();
+        let text = params::optional_string(client.context(), "text");
+        let count = params::optional_decimal(client.context(), "messages").map(|count| count as usize);
+        let length = params::optional_string(client.context(), "length");
+        let style = params::optional_string(client.context(), "style");
+        let mode = params::optional_string(client.context(), "mode");
         
-        info!("Processing summarize command with text of length: {}", text.len());
+        let mut options = match parse_options(length.as_deref(), style.as_deref()) {
+            Ok(options) => options,
+            Err(e) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+        };
+        
+        if mode.as_deref() == Some("update") {
+            let Some(store) = &self.summaries else {
+                let response = "Rolling summaries aren't available right now.".to_string();
+                return Ok(super::reply(&client, response, self.visibility.is_ephemeral(true)));
+            };
+            
+            let (response, is_error) = match self.update_rolling(&client, store, count, &options).await {
+                Ok(response) => (response, false),
+                Err(e) if is_rate_limited(&e) => {
+                    error!("Summarization rate limited: {}", e);
+                    return Err(super::rate_limited_error());
+                }
+                Err(e) => {
+                    error!("Error updating rolling summary: {}", e);
+                    (format!("I encountered an error while summarizing: {}", e), true)
+                }
+            };
+            return Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)));
+        }
+        
+        // Summarize the chat's recent messages when asked to, otherwise the pasted text
+        let text = match (text, count) {
+            (_, Some(count)) => match fetch_recent_messages(&client, count.min(MAX_FETCHED_MESSAGES)).await {
+                Ok(messages) if !messages.is_empty() => render_messages(&messages),
+                Ok(_) => {
+                    let response = "There are no recent text messages in this chat to summarize.".to_string();
+                    return Ok(super::reply(&client, response, self.visibility.is_ephemeral(true)));
+                }
+                Err(e) => {
+                    error!("Failed to fetch recent messages: {}", e);
+                    return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true)));
+                }
+            },
+            // Pasted source code gets an explanation of what it does instead
+            (Some(text), None) => {
+                options.content = SummaryContent::detect(&text);
+                text
+            }
+            (None, None) => {
+                let response = "Please paste the text to summarize, or choose how many recent messages to summarize.".to_string();
+                return Ok(super::reply(&client, response, self.visibility.is_ephemeral(true)));
+            }
+        };
+        
+        info!("Processing summarize command with {:?} of length: {}", options.content, text.len());
         
-        // Use the LLM to summarize the text
-        let summary = match self.llm.summarize(&text).await {
-            Ok(summary) => summary,
+        // Use the LLM to summarize the text, chunking it when it is too long for one request
+        let (response, is_error) = match self.llm.summarize_long(&text, &options).await {
+            Ok(summary) => {
+                info!("Summary generated of length: {}", summary.len());
+                (format!("**Summary:**\n\n{}", summary), false)
+            }
+            Err(e) if is_rate_limited(&e) => {
+                error!("Summarization rate limited: {}", e);
+                return Err(super::rate_limited_error());
+            }
             Err(e) => {
                 error!("Error summarizing text: {}", e);
-                format!("I encountered an error while summarizing: {}", e)
+                (format!("I encountered an error while summarizing: {}", e), true)
             }
         };
         
-        info!("Summary generated of length: {}", summary.len());
-        
-        let message = client
-            .send_text_message(format!("**Summary:**\n\n{}", summary))
-            .with_block_level_markdown(true)
-            .execute_then_return_message(|_, _| ());
-
-        Ok(SuccessResult { message })
+        Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)))
     }
 }
 
 impl Summarize {
-    fn definition() -> BotCommandDefinition {
+    /// `max_length` caps how much pasted text OpenChat accepts
+    pub fn new(llm: Arc<dyn LlmProvider>, visibility: Visibility, max_length: u16) -> Self {
+        Self {
+            llm,
+            visibility,
+            definition: Self::definition(max_length),
+            summaries: None,
+        }
+    }
+    
+    /// Keep a rolling summary per chat that `mode: update` extends with new messages only
+    pub fn with_summary_store(mut self, summaries: Arc<SummaryStore>) -> Self {
+        self.summaries = Some(summaries);
+        self
+    }
+    
+    // Fold the messages posted since the chat's last rolling summary into it, or start
+    // one from the last `count` messages
+    async fn update_rolling(
+        &self,
+        client: &Client<AgentRuntime, BotCommandContext>,
+        store: &SummaryStore,
+        count: Option<usize>,
+        options: &SummaryOptions,
+    ) -> anyhow::Result<String> {
+        let chat_id = canonical_chat_id(&client.context().scope);
+        let previous = store.get(&chat_id).await?;
+        
+        let messages = match &previous {
+            Some(previous) => fetch_messages_since(client, previous.watermark, MAX_FETCHED_MESSAGES).await,
+            None => {
+                let count = count.unwrap_or(DEFAULT_ROLLING_MESSAGES).min(MAX_FETCHED_MESSAGES);
+                fetch_recent_messages(client, count).await
+            }
+        }
+        .map_err(|e| anyhow::anyhow!(e))?;
+        
+        self.fold_into_rolling(store, &chat_id, previous, &messages, options).await
+    }
+    
+    // Summarize `messages` into the chat's previous rolling summary, if any, and store the result
+    async fn fold_into_rolling(
+        &self,
+        store: &SummaryStore,
+        chat_id: &str,
+        previous: Option<RollingSummary>,
+        messages: &[RecentMessage],
+        options: &SummaryOptions,
+    ) -> anyhow::Result<String> {
+        let Some(watermark) = messages.last().map(|message| message.event_index) else {
+            return Ok(match previous {
+                Some(previous) => format!("**Summary** (no new messages since the last update):\n\n{}", previous.summary),
+                None => "There are no recent text messages in this chat to summarize.".to_string(),
+            });
+        };
+        
+        info!("Updating rolling summary of {} with {} new messages", chat_id, messages.len());
+        
+        let text = render_messages(messages);
+        let summary = match &previous {
+            Some(previous) => self.llm.update_summary(&previous.summary, &text, options).await?,
+            None => self.llm.summarize_long(&text, options).await?,
+        };
+        store.set(chat_id, &summary, watermark).await?;
+        
+        Ok(format!("**Summary** (updated with {} new messages):\n\n{}", messages.len(), summary))
+    }
+    
+    fn definition(max_length: u16) -> BotCommandDefinition {
         BotCommandDefinition {
             name: "summarize".to_string(),
             description: Some("Summarize a block of text or a discussion".to_string()),
             placeholder: Some("Summarizing...".to_string()),
-            params: vec![BotCommandParam {
-                name: "text".to_string(),
-                description: Some("The text to summarize".to_string()),
-                placeholder: Some("Paste the text you want to summarize".to_string()),
-                required: true,
-                param_type: BotCommandParamType::StringParam(StringParam {
-                    min_length: 10,
-                    max_length: 50000,
-                    choices: Vec::new(),
-                    multi_line: true,
-                }),
-            }],
-            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            params: vec![
+                BotCommandParam {
+                    name: "text".to_string(),
+                    description: Some("The text to summarize".to_string()),
+                    placeholder: Some("Paste the text you want to summarize".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: MIN_TEXT_LENGTH,
+                        max_length,
+                        choices: Vec::new(),
+                        multi_line: true,
+                    }),
+                },
+                BotCommandParam {
+                    name: "messages".to_string(),
+                    description: Some("Summarize this many recent messages of the chat instead of pasted text".to_string()),
+                    placeholder: Some("Enter a number".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::DecimalParam(DecimalParam {
+                        min_value: 1.0,
+                        max_value: MAX_FETCHED_MESSAGES as f64,
+                        choices: Vec::new(),
+                    }),
+                },
+                BotCommandParam {
+                    name: "length".to_string(),
+                    description: Some("short, medium, long, or a number of bullet points".to_string()),
+                    placeholder: Some("e.g. short or 5".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "style".to_string(),
+                    description: Some("How to lay out the summary".to_string()),
+                    placeholder: Some("Choose a style".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "bullets".to_string(),
+                                value: "bullets".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "paragraph".to_string(),
+                                value: "paragraph".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "tl;dr".to_string(),
+                                value: "tldr".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "mode".to_string(),
+                    description: Some("update: add only the messages posted since the chat's last rolling summary to it".to_string()),
+                    placeholder: Some("Choose a mode".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "full".to_string(),
+                                value: "full".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "update".to_string(),
+                                value: "update".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+            ],
+            // Reading messages is needed to summarize the chat's recent history
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text)
+                .with_chat(&HashSet::from([ChatPermission::ReadMessages])),
             default_role: None,
             direct_messages: Some(true),
         }
     }
-} 
\ No newline at end of file
+}
+
+// One "sender: text" line per message
+fn render_messages(messages: &[RecentMessage]) -> String {
+    messages
+        .iter()
+        .map(|message| format!("{}: {}", message.sender, message.text))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Summary options from the optional command args; unset args keep the default behavior
+fn parse_options(length: Option<&str>, style: Option<&str>) -> Result<SummaryOptions, String> {
+    Ok(SummaryOptions {
+        length: length.map(str::parse).transpose()?,
+        style: style.map(str::parse).transpose()?,
+        content: SummaryContent::Prose,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{MockLlm, SummaryLength, SummaryStyle};
+
+    // Messages as fetch_recent_messages returns them, oldest first
+    fn history() -> Vec<RecentMessage> {
+        [("alice", "Shall we ship on Friday?"), ("bob", "Only if the tests pass."), ("alice", "They do.")]
+            .into_iter()
+            .enumerate()
+            .map(|(i, (sender, text))| RecentMessage {
+                event_index: 10 + i as u32,
+                sender: sender.to_string(),
+                text: text.to_string(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn renders_fetched_messages_one_per_line() {
+        assert_eq!(
+            render_messages(&history()),
+            "alice: Shall we ship on Friday?\nbob: Only if the tests pass.\nalice: They do."
+        );
+    }
+
+    #[tokio::test]
+    async fn summarizes_the_fetched_history() {
+        let summary = MockLlm
+            .summarize_long(&render_messages(&history()), &SummaryOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(
+            summary,
+            "[mock] alice: Shall we ship on Friday?\nbob: Only if the tests pass.\nalice: They do."
+        );
+    }
+
+    #[test]
+    fn parses_options_from_command_args() {
+        assert_eq!(parse_options(None, None), Ok(SummaryOptions::default()));
+        assert_eq!(
+            parse_options(Some("3"), Some("tldr")),
+            Ok(SummaryOptions {
+                length: Some(SummaryLength::Points(3)),
+                style: Some(SummaryStyle::TlDr),
+                content: SummaryContent::Prose,
+            })
+        );
+        assert!(parse_options(Some("huge"), None).is_err());
+    }
+
+    #[test]
+    fn the_configured_limit_flows_into_the_definition() {
+        let summarize = Summarize::new(Arc::new(MockLlm), Visibility::default(), 2000);
+
+        let text = &summarize.definition().params[0];
+        assert_eq!(text.name, "text");
+        match &text.param_type {
+            BotCommandParamType::StringParam(param) => {
+                assert_eq!(param.max_length, 2000);
+                assert_eq!(param.min_length, MIN_TEXT_LENGTH);
+            }
+            _ => panic!("expected a string param"),
+        }
+    }
+
+    #[tokio::test]
+    async fn a_rolling_update_only_reads_new_messages() {
+        let store = SummaryStore::new(":memory:").unwrap();
+        let summarize = Summarize::new(Arc::new(MockLlm), Visibility::default(), 10000);
+        let options = SummaryOptions::default();
+
+        summarize.fold_into_rolling(&store, "group:1", None, &history(), &options).await.unwrap();
+        let first = store.get("group:1").await.unwrap().unwrap();
+        assert_eq!(first.watermark, 12);
+
+        let new_messages = vec![RecentMessage {
+            event_index: 13,
+            sender: "carol".to_string(),
+            text: "Ship it.".to_string(),
+        }];
+        let response = summarize
+            .fold_into_rolling(&store, "group:1", Some(first.clone()), &new_messages, &options)
+            .await
+            .unwrap();
+
+        // MockLlm echoes what it was asked to summarize: the old summary and only the new message
+        let expected = format!("[mock] Existing summary:\n{}\n\nNew messages:\ncarol: Ship it.", first.summary);
+        assert_eq!(response, format!("**Summary** (updated with 1 new messages):\n\n{}", expected));
+        let updated = store.get("group:1").await.unwrap().unwrap();
+        assert_eq!((updated.summary, updated.watermark), (expected, 13));
+    }
+
+    #[tokio::test]
+    async fn nothing_new_keeps_the_previous_summary() {
+        let store = SummaryStore::new(":memory:").unwrap();
+        let summarize = Summarize::new(Arc::new(MockLlm), Visibility::default(), 10000);
+        let previous = RollingSummary {
+            summary: "They agreed to ship on Friday.".to_string(),
+            watermark: 12,
+        };
+
+        let response = summarize
+            .fold_into_rolling(&store, "group:1", Some(previous), &[], &SummaryOptions::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response, "**Summary** (no new messages since the last update):\n\nThey agreed to ship on Friday.");
+        assert!(store.get("group:1").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,314 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Url;
+use scraper::{Html, Selector};
+use std::sync::LazyLock;
+use std::sync::Arc;
+use std::time::Duration;
+use tracing::{error, info};
+
+use super::{params, Visibility};
+use crate::llm::{is_rate_limited, LlmProvider, SummaryOptions};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(SummarizeUrl::definition);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+// Pages bigger than this are refused rather than downloaded
+const MAX_PAGE_BYTES: usize = 2 * 1024 * 1024;
+// Pages with less readable text than this have nothing worth summarizing
+const MIN_PAGE_TEXT: usize = 50;
+// Elements whose text isn't part of the page's readable content
+const SKIPPED_ELEMENTS: &[&str] = &["script", "style", "noscript", "nav", "header", "footer", "aside", "form", "svg"];
+
+pub struct SummarizeUrl {
+    pub http: reqwest::Client,
+    pub llm: Arc<dyn LlmProvider>,
+    pub visibility: Visibility,
+}
+
+// The readable parts of a fetched page
+#[derive(Debug)]
+struct Page {
+    title: Option<String>,
+    text: String,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for SummarizeUrl {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let url = match params::required_string(client.context(), "url") {
+            Ok(url) => url,
+            Err(e) => return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true))),
+        };
+
+        info!("Processing summarizeurl command for: {}", url);
+
+        let page = match parse_url(&url) {
+            Ok(url) => self.fetch(url).await,
+            Err(e) => Err(e),
+        };
+        let page = match page {
+            Ok(page) => page,
+            Err(e) => {
+                error!("Failed to read {}: {}", url, e);
+                return Ok(super::reply(&client, e, self.visibility.is_ephemeral(true)));
+            }
+        };
+
+        let (response, is_error) = match self.llm.summarize_long(&page.text, &SummaryOptions::default()).await {
+            Ok(summary) => match page.title {
+                Some(title) => (format!("**Summary of {}:**\n\n{}", title, summary), false),
+                None => (format!("**Summary:**\n\n{}", summary), false),
+            },
+            Err(e) if is_rate_limited(&e) => {
+                error!("Summarization rate limited: {}", e);
+                return Err(super::rate_limited_error());
+            }
+            Err(e) => {
+                error!("Error summarizing page: {}", e);
+                (format!("I encountered an error while summarizing: {}", e), true)
+            }
+        };
+
+        Ok(super::reply(&client, response, self.visibility.is_ephemeral(is_error)))
+    }
+}
+
+impl SummarizeUrl {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "summarizeurl".to_string(),
+            description: Some("Fetch a web page and summarize it".to_string()),
+            placeholder: Some("Reading the page...".to_string()),
+            params: vec![BotCommandParam {
+                name: "url".to_string(),
+                description: Some("Address of the page to summarize (http or https)".to_string()),
+                placeholder: Some("https://example.com/article".to_string()),
+                required: true,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 2000,
+                    choices: Vec::new(),
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    // Download the page, refusing anything that isn't a reasonably sized HTML or text document
+    async fn fetch(&self, url: Url) -> Result<Page, String> {
+        let mut response = self
+            .http
+            .get(url)
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("I couldn't fetch that page: {}", e))?;
+
+        let status = response.status();
+        if !status.is_success() {
+            return Err(format!("The page returned {}", status));
+        }
+
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or("text/html")
+            .to_lowercase();
+        let is_html = content_type.starts_with("text/html") || content_type.starts_with("application/xhtml");
+        if !is_html && !content_type.starts_with("text/plain") {
+            return Err(format!("I can only summarize web pages, but that link is {}.", content_type));
+        }
+
+        let too_large = || format!("That page is too large to summarize (over {} MB).", MAX_PAGE_BYTES / (1024 * 1024));
+        if response.content_length().is_some_and(|length| length as usize > MAX_PAGE_BYTES) {
+            return Err(too_large());
+        }
+
+        // The length header can be missing or wrong, so keep counting while reading
+        let mut body = Vec::new();
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| format!("I couldn't read that page: {}", e))?
+        {
+            if body.len() + chunk.len() > MAX_PAGE_BYTES {
+                return Err(too_large());
+            }
+            body.extend_from_slice(&chunk);
+        }
+        let body = String::from_utf8_lossy(&body);
+
+        let page = if is_html {
+            extract_page(&body)
+        } else {
+            Page { title: None, text: collapse_whitespace(&body) }
+        };
+
+        if page.text.chars().count() < MIN_PAGE_TEXT {
+            return Err("I couldn't find any readable text on that page.".to_string());
+        }
+        Ok(page)
+    }
+}
+
+// Only http(s) links: other schemes could reach files or services the bot shouldn't read
+fn parse_url(url: &str) -> Result<Url, String> {
+    let parsed = Url::parse(url).map_err(|_| format!("\"{}\" isn't a valid URL.", url))?;
+    match parsed.scheme() {
+        "http" | "https" if parsed.host_str().is_some() => Ok(parsed),
+        "http" | "https" => Err(format!("\"{}\" has no host.", url)),
+        scheme => Err(format!("Only http and https links are supported, not {}.", scheme)),
+    }
+}
+
+// The page's title and readable text, taken from its <article> when it has one
+fn extract_page(html: &str) -> Page {
+    let document = Html::parse_document(html);
+
+    let title = Selector::parse("title")
+        .ok()
+        .and_then(|selector| document.select(&selector).next())
+        .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        .filter(|title| !title.is_empty());
+
+    let root = ["article", "main", "body"]
+        .iter()
+        .filter_map(|name| Selector::parse(name).ok())
+        .find_map(|selector| document.select(&selector).next());
+
+    let mut text = String::new();
+    if let Some(root) = root {
+        for node in root.descendants() {
+            let Some(fragment) = node.value().as_text() else {
+                continue;
+            };
+            let skipped = node.ancestors().any(|ancestor| {
+                ancestor
+                    .value()
+                    .as_element()
+                    .is_some_and(|element| SKIPPED_ELEMENTS.contains(&element.name()))
+            });
+            if !skipped {
+                text.push_str(fragment);
+                text.push(' ');
+            }
+        }
+    }
+
+    Page {
+        title,
+        text: collapse_whitespace(&text),
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlm;
+    use crate::test_support::{MockResponse, MockServer};
+    use axum::http::StatusCode;
+
+    const ARTICLE: &str = r#"<html>
+<head><title>Rust 2026 roadmap</title><style>body { color: red; }</style></head>
+<body>
+  <nav>Home | Blog | About</nav>
+  <article>
+    <h1>The roadmap</h1>
+    <p>This year the project focuses on   faster compile times and better async support.</p>
+    <script>trackVisit();</script>
+  </article>
+  <footer>Copyright</footer>
+</body>
+</html>"#;
+
+    fn summarize_url() -> SummarizeUrl {
+        SummarizeUrl {
+            http: reqwest::Client::new(),
+            llm: Arc::new(MockLlm),
+            visibility: Visibility::default(),
+        }
+    }
+
+    fn html(body: &str) -> MockResponse {
+        MockResponse::status(StatusCode::OK)
+            .with_header("content-type", "text/html; charset=utf-8")
+            .with_body(body.to_string())
+    }
+
+    async fn fetch(response: MockResponse) -> Result<Page, String> {
+        let server = MockServer::start(vec![response]).await;
+        summarize_url().fetch(Url::parse(&format!("{}/article", server.url)).unwrap()).await
+    }
+
+    #[test]
+    fn only_accepts_http_links() {
+        assert!(parse_url("https://example.com/post").is_ok());
+        assert_eq!(parse_url("not a url"), Err("\"not a url\" isn't a valid URL.".to_string()));
+        assert_eq!(
+            parse_url("file:///etc/passwd"),
+            Err("Only http and https links are supported, not file.".to_string())
+        );
+    }
+
+    #[test]
+    fn extracts_the_readable_article_text() {
+        let page = extract_page(ARTICLE);
+
+        assert_eq!(page.title.as_deref(), Some("Rust 2026 roadmap"));
+        assert_eq!(
+            page.text,
+            "The roadmap This year the project focuses on faster compile times and better async support."
+        );
+    }
+
+    #[tokio::test]
+    async fn fetches_a_page_and_summarizes_its_text() {
+        let page = fetch(html(ARTICLE)).await.unwrap();
+        assert_eq!(page.title.as_deref(), Some("Rust 2026 roadmap"));
+
+        let summary = MockLlm.summarize_long(&page.text, &SummaryOptions::default()).await.unwrap();
+        assert!(summary.contains("faster compile times"), "{}", summary);
+    }
+
+    #[tokio::test]
+    async fn refuses_pages_it_cannot_summarize() {
+        assert_eq!(fetch(MockResponse::status(StatusCode::NOT_FOUND)).await.unwrap_err(), "The page returned 404 Not Found");
+
+        let pdf = MockResponse::status(StatusCode::OK)
+            .with_header("content-type", "application/pdf")
+            .with_body("%PDF-1.7".to_string());
+        assert_eq!(
+            fetch(pdf).await.unwrap_err(),
+            "I can only summarize web pages, but that link is application/pdf."
+        );
+
+        assert_eq!(
+            fetch(html("<html><body><p>Too short.</p></body></html>")).await.unwrap_err(),
+            "I couldn't find any readable text on that page."
+        );
+
+        let huge = html(&"a".repeat(MAX_PAGE_BYTES + 1));
+        assert_eq!(fetch(huge).await.unwrap_err(), "That page is too large to summarize (over 2 MB).");
+    }
+}
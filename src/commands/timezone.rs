@@ -0,0 +1,118 @@
+use async_trait::async_trait;
+use chrono_tz::Tz;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use crate::timezones::TimezoneStore;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Timezone::definition);
+
+pub struct Timezone {
+    pub store: Arc<TimezoneStore>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Timezone {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let zone = params::optional_string(client.context(), "zone");
+        let user_id = client.context().command.initiator.to_string();
+
+        info!("Processing timezone command with zone: {:?}", zone);
+
+        // Without a zone, show the current setting
+        let result = match zone {
+            Some(zone) => self.set_timezone(&user_id, &zone).await,
+            None => self.show_timezone(&user_id).await,
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing timezone command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Timezone {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "timezone".to_string(),
+            description: Some("Set the timezone used for your reminders and timestamps".to_string()),
+            placeholder: Some("Updating timezone...".to_string()),
+            params: vec![BotCommandParam {
+                name: "zone".to_string(),
+                description: Some("An IANA timezone name such as Europe/London (leave empty to see your current one)".to_string()),
+                placeholder: Some("e.g. Africa/Nairobi".to_string()),
+                required: false,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 64,
+                    choices: Vec::new(),
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    async fn set_timezone(&self, user_id: &str, zone: &str) -> Result<String, String> {
+        let timezone = parse_timezone(zone).ok_or_else(|| {
+            format!("I don't know the timezone \"{}\". Use a name like Europe/London or America/New_York.", zone)
+        })?;
+
+        self.store
+            .set(user_id, timezone)
+            .await
+            .map_err(|e| format!("Failed to store timezone: {}", e))?;
+
+        Ok(format!("Your timezone is now {}. Reminders and timestamps will use it.", timezone.name()))
+    }
+
+    async fn show_timezone(&self, user_id: &str) -> Result<String, String> {
+        let timezone = self
+            .store
+            .get(user_id)
+            .await
+            .map_err(|e| format!("Failed to load timezone: {}", e))?;
+
+        Ok(match timezone {
+            Some(timezone) => format!("Your timezone is {}.", timezone.name()),
+            None => "You haven't set a timezone, so UTC is used. Set one with e.g. `/timezone Europe/London`.".to_string(),
+        })
+    }
+}
+
+// IANA names are case-sensitive, so fall back to a case-insensitive match for "europe/london"
+fn parse_timezone(zone: &str) -> Option<Tz> {
+    zone.parse::<Tz>().ok().or_else(|| {
+        chrono_tz::TZ_VARIANTS
+            .iter()
+            .copied()
+            .find(|timezone| timezone.name().eq_ignore_ascii_case(zone))
+    })
+}
@@ -0,0 +1,216 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::{BotCommandContext, ChatRole};
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use super::params;
+use super::registry::COMMAND_NAMES;
+use crate::chat_commands::ChatCommandStore;
+use crate::chat_id::canonical_chat_id;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Toggle::definition);
+
+pub struct Toggle {
+    pub store: Arc<ChatCommandStore>,
+    pub admins: Vec<String>,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Toggle {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let action = match params::required_string(client.context(), "action") {
+            Ok(action) => action,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+        let command = params::optional_string(client.context(), "command")
+            .map(|command| command.trim_start_matches('/').to_lowercase())
+            .filter(|command| !command.is_empty());
+        let user_id = client.context().command.initiator.to_string();
+        let chat_id = canonical_chat_id(&client.context().scope);
+
+        info!("Processing toggle command with action: {} for {}", action, chat_id);
+
+        let result = if !self.admins.contains(&user_id) {
+            Ok("Only admins can turn commands on or off.".to_string())
+        } else {
+            match (action.as_str(), command) {
+                ("list", _) => self.list(&chat_id).await,
+                ("enable" | "disable", None) => Err("Please say which command to turn on or off.".to_string()),
+                ("enable", Some(command)) => self.enable(&chat_id, &command).await,
+                ("disable", Some(command)) => self.disable(&chat_id, &command, &user_id).await,
+                _ => Err(format!("Unknown toggle action: {}", action)),
+            }
+        };
+
+        let response = match result {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Error processing toggle command: {}", e);
+                format!("I encountered an error: {}", e)
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Toggle {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "toggle".to_string(),
+            description: Some("Turn commands on or off in this chat (admins only)".to_string()),
+            placeholder: Some("Updating commands...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "action".to_string(),
+                    description: Some("Turn a command on or off, or list the ones that are off".to_string()),
+                    placeholder: Some("Choose an action".to_string()),
+                    required: true,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 10,
+                        choices: vec![
+                            BotCommandOptionChoice {
+                                name: "enable".to_string(),
+                                value: "enable".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "disable".to_string(),
+                                value: "disable".to_string()
+                            },
+                            BotCommandOptionChoice {
+                                name: "list".to_string(),
+                                value: "list".to_string()
+                            }
+                        ],
+                        multi_line: false,
+                    }),
+                },
+                BotCommandParam {
+                    name: "command".to_string(),
+                    description: Some("Name of the command, e.g. weather".to_string()),
+                    placeholder: Some("Enter a command name".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 30,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: Some(ChatRole::Admin),
+            direct_messages: Some(false),
+        }
+    }
+
+    async fn disable(&self, chat_id: &str, command: &str, user_id: &str) -> Result<String, String> {
+        check_command(command)?;
+        // Otherwise nobody could turn anything back on
+        if command == "toggle" {
+            return Ok("The /toggle command can't be turned off.".to_string());
+        }
+
+        self.store
+            .disable(chat_id, command, user_id)
+            .await
+            .map_err(|e| format!("Failed to turn off /{}: {}", command, e))?;
+
+        Ok(format!("/{} is now off in this chat.", command))
+    }
+
+    async fn enable(&self, chat_id: &str, command: &str) -> Result<String, String> {
+        check_command(command)?;
+
+        let was_disabled = self
+            .store
+            .enable(chat_id, command)
+            .await
+            .map_err(|e| format!("Failed to turn on /{}: {}", command, e))?;
+
+        Ok(if was_disabled {
+            format!("/{} is back on in this chat.", command)
+        } else {
+            format!("/{} was already on in this chat.", command)
+        })
+    }
+
+    async fn list(&self, chat_id: &str) -> Result<String, String> {
+        let disabled = self
+            .store
+            .disabled(chat_id)
+            .await
+            .map_err(|e| format!("Failed to load this chat's commands: {}", e))?;
+
+        if disabled.is_empty() {
+            return Ok("All commands are on in this chat.".to_string());
+        }
+
+        let lines: Vec<String> = disabled.iter().map(|command| format!("- /{}", command)).collect();
+        Ok(format!("**Commands turned off in this chat:**\n\n{}", lines.join("\n")))
+    }
+}
+
+fn check_command(command: &str) -> Result<(), String> {
+    if COMMAND_NAMES.contains(&command) {
+        Ok(())
+    } else {
+        Err(format!("There is no /{} command.", command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn toggle() -> Toggle {
+        Toggle {
+            store: Arc::new(ChatCommandStore::new(":memory:").unwrap()),
+            admins: vec!["admin".to_string()],
+        }
+    }
+
+    #[tokio::test]
+    async fn turns_commands_off_and_on_in_one_chat() {
+        let toggle = toggle();
+
+        assert_eq!(toggle.disable("group:1", "weather", "admin").await, Ok("/weather is now off in this chat.".to_string()));
+        assert!(toggle.store.is_disabled("group:1", "weather").await.unwrap());
+        assert!(!toggle.store.is_disabled("group:2", "weather").await.unwrap());
+        assert_eq!(
+            toggle.list("group:1").await,
+            Ok("**Commands turned off in this chat:**\n\n- /weather".to_string())
+        );
+        assert_eq!(toggle.list("group:2").await, Ok("All commands are on in this chat.".to_string()));
+
+        assert_eq!(toggle.enable("group:1", "weather").await, Ok("/weather is back on in this chat.".to_string()));
+        assert_eq!(toggle.enable("group:1", "weather").await, Ok("/weather was already on in this chat.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn refuses_unknown_commands_and_itself() {
+        let toggle = toggle();
+
+        assert_eq!(toggle.disable("group:1", "teleport", "admin").await, Err("There is no /teleport command.".to_string()));
+        assert_eq!(toggle.disable("group:1", "toggle", "admin").await, Ok("The /toggle command can't be turned off.".to_string()));
+        assert!(!toggle.store.is_disabled("group:1", "toggle").await.unwrap());
+    }
+}
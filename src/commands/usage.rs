@@ -0,0 +1,158 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use std::sync::LazyLock;
+use std::sync::Arc;
+use chrono::{Duration, Utc};
+use tracing::{error, info};
+
+use super::params;
+use crate::chat_id::canonical_chat_id;
+use crate::usage::{CommandUsage, UsageStore};
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Usage::definition);
+
+const DEFAULT_WINDOW_DAYS: f64 = 30.0;
+
+pub struct Usage {
+    pub usage_store: Arc<UsageStore>,
+    pub admins: Vec<String>,
+    pub prompt_cost_per_million: f64,
+    pub completion_cost_per_million: f64,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Usage {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let days = params::optional_decimal(client.context(), "days").unwrap_or(DEFAULT_WINDOW_DAYS);
+        let requested_user = params::optional_string(client.context(), "user");
+        let initiator = client.context().command.initiator.to_string();
+
+        info!("Processing usage command for {} days", days);
+
+        let scope = &client.context().scope;
+        let chat_id = canonical_chat_id(scope);
+
+        // Users can only see their own usage unless they are an admin
+        let target_user = match requested_user {
+            Some(user) if user != initiator => {
+                if self.admins.contains(&initiator) {
+                    Some(user)
+                } else {
+                    None
+                }
+            }
+            _ => Some(initiator.clone()),
+        };
+
+        let response = match target_user {
+            None => "Only admins can view other users' usage.".to_string(),
+            Some(user_id) => {
+                let since = Utc::now() - Duration::seconds((days * 86_400.0) as i64);
+                match self.usage_store.usage_by_command(&chat_id, &user_id, since).await {
+                    Ok(usage) => self.render(&usage, days, user_id == initiator),
+                    Err(e) => {
+                        error!("Failed to load usage: {}", e);
+                        format!("I encountered an error while loading usage: {}", e)
+                    }
+                }
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Usage {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "usage".to_string(),
+            description: Some("Show token usage and approximate cost in this chat".to_string()),
+            placeholder: Some("Tallying usage...".to_string()),
+            params: vec![
+                BotCommandParam {
+                    name: "days".to_string(),
+                    description: Some("How many days back to report (default 30)".to_string()),
+                    placeholder: Some("Enter days".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::DecimalParam(DecimalParam {
+                        min_value: 1.0,
+                        max_value: 365.0,
+                        choices: Vec::new(),
+                    }),
+                },
+                BotCommandParam {
+                    name: "user".to_string(),
+                    description: Some("User id to report on (admins only)".to_string()),
+                    placeholder: Some("Enter a user id".to_string()),
+                    required: false,
+                    param_type: BotCommandParamType::StringParam(StringParam {
+                        min_length: 1,
+                        max_length: 100,
+                        choices: Vec::new(),
+                        multi_line: false,
+                    }),
+                },
+            ],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    fn cost(&self, prompt_tokens: u64, completion_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.prompt_cost_per_million
+            + completion_tokens as f64 * self.completion_cost_per_million)
+            / 1_000_000.0
+    }
+
+    fn render(&self, usage: &[CommandUsage], days: f64, own: bool) -> String {
+        let who = if own { "You have" } else { "This user has" };
+
+        if usage.is_empty() {
+            return format!("{} not used any tokens in this chat in the last {} days.", who, days);
+        }
+
+        let prompt_total: u64 = usage.iter().map(|u| u.prompt_tokens).sum();
+        let completion_total: u64 = usage.iter().map(|u| u.completion_tokens).sum();
+
+        let lines: Vec<String> = usage
+            .iter()
+            .map(|u| {
+                format!(
+                    "- /{}: {} calls, {} tokens (~${:.4})",
+                    u.command,
+                    u.calls,
+                    u.prompt_tokens + u.completion_tokens,
+                    self.cost(u.prompt_tokens, u.completion_tokens)
+                )
+            })
+            .collect();
+
+        format!(
+            "{} used **{}** tokens ({} prompt, {} completion, ~${:.4}) in this chat in the last {} days:\n\n{}",
+            who,
+            prompt_total + completion_total,
+            prompt_total,
+            completion_total,
+            self.cost(prompt_total, completion_total),
+            days,
+            lines.join("\n")
+        )
+    }
+}
@@ -0,0 +1,271 @@
+use async_trait::async_trait;
+use oc_bots_sdk::api::command::{CommandHandler, SuccessResult};
+use oc_bots_sdk::api::definition::*;
+use oc_bots_sdk::types::BotCommandContext;
+use oc_bots_sdk_offchain::AgentRuntime;
+use oc_bots_sdk::oc_api::client::Client;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use std::sync::LazyLock;
+use std::time::Duration;
+use tracing::{error, info};
+
+use super::params;
+
+static DEFINITION: LazyLock<BotCommandDefinition> = LazyLock::new(Weather::definition);
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct Weather {
+    pub http: reqwest::Client,
+    // OpenWeatherMap-compatible current weather endpoint
+    pub api_url: String,
+    pub api_key: String,
+    // "metric" or "imperial"
+    pub units: String,
+}
+
+// The parts of the current weather response we render
+#[derive(Debug, Deserialize)]
+struct WeatherResponse {
+    name: String,
+    #[serde(default)]
+    sys: Option<WeatherSys>,
+    #[serde(default)]
+    weather: Vec<WeatherCondition>,
+    main: WeatherMain,
+    #[serde(default)]
+    wind: Option<WeatherWind>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeatherSys {
+    country: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeatherCondition {
+    description: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeatherMain {
+    temp: f64,
+    feels_like: Option<f64>,
+    humidity: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+struct WeatherWind {
+    speed: f64,
+}
+
+#[async_trait]
+impl CommandHandler<AgentRuntime> for Weather {
+    fn definition(&self) -> &BotCommandDefinition {
+        &DEFINITION
+    }
+
+    async fn execute(
+        &self,
+        client: Client<AgentRuntime, BotCommandContext>,
+    ) -> Result<SuccessResult, String> {
+        let location = match params::required_string(client.context(), "location") {
+            Ok(location) => location,
+            Err(e) => return Ok(super::reply(&client, e, false)),
+        };
+
+        info!("Processing weather command for location: {}", location);
+
+        let response = match self.fetch(&location).await {
+            Ok(Some(weather)) => self.render(&weather),
+            Ok(None) => format!("I couldn't find a place called **{}**. Try a city name, optionally with a country code (e.g. `Paris,FR`).", location),
+            Err(e) => {
+                error!("Error fetching weather: {}", e);
+                "The weather service is unavailable right now. Please try again later.".to_string()
+            }
+        };
+
+        let message = client
+            .send_text_message(response)
+            .with_block_level_markdown(super::markdown_enabled())
+            .execute_then_return_message(|_, _| ());
+
+        Ok(SuccessResult { message })
+    }
+}
+
+impl Weather {
+    fn definition() -> BotCommandDefinition {
+        BotCommandDefinition {
+            name: "weather".to_string(),
+            description: Some("Show the current weather for a location".to_string()),
+            placeholder: Some("Checking the weather...".to_string()),
+            params: vec![BotCommandParam {
+                name: "location".to_string(),
+                description: Some("City name, optionally with a country code".to_string()),
+                placeholder: Some("e.g. Nairobi,KE".to_string()),
+                required: true,
+                param_type: BotCommandParamType::StringParam(StringParam {
+                    min_length: 1,
+                    max_length: 100,
+                    choices: Vec::new(),
+                    multi_line: false,
+                }),
+            }],
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    // Current conditions for the location, or None if the API doesn't know it
+    async fn fetch(&self, location: &str) -> Result<Option<WeatherResponse>, String> {
+        let response = self
+            .http
+            .get(&self.api_url)
+            .query(&[
+                ("q", location),
+                ("appid", self.api_key.as_str()),
+                ("units", self.units.as_str()),
+            ])
+            .timeout(REQUEST_TIMEOUT)
+            .send()
+            .await
+            .map_err(|e| format!("Request to weather API failed: {}", e))?;
+
+        let status = response.status();
+        if status == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            return Err(format!("Weather API returned {}", status));
+        }
+
+        response
+            .json::<WeatherResponse>()
+            .await
+            .map(Some)
+            .map_err(|e| format!("Invalid response from weather API: {}", e))
+    }
+
+    fn render(&self, weather: &WeatherResponse) -> String {
+        let (temp_unit, speed_unit) = match self.units.as_str() {
+            "imperial" => ("°F", "mph"),
+            _ => ("°C", "m/s"),
+        };
+
+        let place = match weather.sys.as_ref().and_then(|sys| sys.country.as_deref()) {
+            Some(country) => format!("{}, {}", weather.name, country),
+            None => weather.name.clone(),
+        };
+
+        let conditions = weather
+            .weather
+            .iter()
+            .map(|condition| condition.description.as_str())
+            .collect::<Vec<_>>()
+            .join(", ");
+
+        let mut lines = vec![format!("**Weather in {}**", place)];
+        if !conditions.is_empty() {
+            lines.push(format!("Conditions: {}", conditions));
+        }
+        match weather.main.feels_like {
+            Some(feels_like) => lines.push(format!(
+                "Temperature: {:.1}{} (feels like {:.1}{})",
+                weather.main.temp, temp_unit, feels_like, temp_unit
+            )),
+            None => lines.push(format!("Temperature: {:.1}{}", weather.main.temp, temp_unit)),
+        }
+        if let Some(humidity) = weather.main.humidity {
+            lines.push(format!("Humidity: {:.0}%", humidity));
+        }
+        if let Some(wind) = &weather.wind {
+            lines.push(format!("Wind: {:.1} {}", wind.speed, speed_unit));
+        }
+
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use axum::http::StatusCode;
+
+    fn weather(server: &MockServer, units: &str) -> Weather {
+        Weather {
+            http: reqwest::Client::new(),
+            api_url: format!("{}/data/2.5/weather", server.url),
+            api_key: "test-key".to_string(),
+            units: units.to_string(),
+        }
+    }
+
+    fn nairobi() -> serde_json::Value {
+        serde_json::json!({
+            "name": "Nairobi",
+            "sys": { "country": "KE" },
+            "weather": [{ "description": "light rain" }],
+            "main": { "temp": 21.04, "feels_like": 20.5, "humidity": 73 },
+            "wind": { "speed": 3.6 }
+        })
+    }
+
+    #[tokio::test]
+    async fn renders_the_current_weather() {
+        let server = MockServer::start(vec![MockResponse::json(nairobi())]).await;
+        let weather = weather(&server, "metric");
+
+        let current = weather.fetch("Nairobi").await.unwrap().unwrap();
+
+        assert_eq!(
+            weather.render(&current),
+            "**Weather in Nairobi, KE**\nConditions: light rain\nTemperature: 21.0°C (feels like 20.5°C)\nHumidity: 73%\nWind: 3.6 m/s"
+        );
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/data/2.5/weather");
+        let query = requests[0].query.clone().unwrap();
+        for param in ["q=Nairobi", "appid=test-key", "units=metric"] {
+            assert!(query.contains(param), "{}", query);
+        }
+    }
+
+    #[tokio::test]
+    async fn imperial_units_and_missing_fields() {
+        let body = serde_json::json!({ "name": "Springfield", "main": { "temp": 70.0 } });
+        let server = MockServer::start(vec![MockResponse::json(body)]).await;
+        let weather = weather(&server, "imperial");
+
+        let current = weather.fetch("Springfield").await.unwrap().unwrap();
+
+        assert_eq!(weather.render(&current), "**Weather in Springfield**\nTemperature: 70.0°F");
+    }
+
+    #[tokio::test]
+    async fn an_unknown_place_is_not_found() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::NOT_FOUND)]).await;
+
+        assert!(weather(&server, "metric").fetch("Atlantis").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn server_errors_fail() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::INTERNAL_SERVER_ERROR)]).await;
+
+        let error = weather(&server, "metric").fetch("Nairobi").await.unwrap_err();
+
+        assert_eq!(error, "Weather API returned 500 Internal Server Error");
+    }
+
+    #[tokio::test]
+    async fn invalid_json_fails() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::OK).with_body("<html>".to_string())]).await;
+
+        let error = weather(&server, "metric").fetch("Nairobi").await.unwrap_err();
+
+        assert!(error.starts_with("Invalid response from weather API"), "{}", error);
+    }
+}
@@ -1,5 +1,11 @@
+use jsonwebtoken::DecodingKey;
+use oc_bots_sdk::types::ChatRole;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::fmt::Display;
 use std::fs;
+use std::path::Path;
+use std::str::FromStr;
 use tracing::Level;
 
 #[derive(Deserialize, Debug, Clone)]
@@ -12,7 +18,50 @@ pub struct Config {
     pub log_level: Level,
     pub mistral_api_key: Option<String>,
     pub sqlite_db_path: Option<String>,
+    // Where memories are kept, e.g. sqlite://./memories.db; defaults to sqlite_db_path.
+    // Only sqlite:// is supported so far
+    #[serde(default)]
+    pub database_url: Option<String>,
+    // OpenChat user ids allowed to run admin-only actions
+    #[serde(default)]
+    pub admins: Vec<String>,
+    // Chats and communities commands may run in, as canonical chat ids; empty allows all
+    #[serde(default)]
+    pub allowed_chats: Vec<String>,
+    // Longest a command may run before OpenChat is answered with 504 Gateway Timeout
+    #[serde(default = "default_command_timeout_secs")]
+    pub command_timeout_secs: u64,
     pub agent: AgentConfig,
+    #[serde(default = "default_rate_limits")]
+    pub rate_limits: HashMap<String, RateLimitConfig>,
+    #[serde(default)]
+    pub llm: LlmConfig,
+    // HTTP-backed tools the agent can call, declared as [[tools]] entries
+    #[serde(default)]
+    pub tools: Vec<HttpToolConfig>,
+    #[serde(default)]
+    pub embeddings: EmbeddingsConfig,
+    #[serde(default)]
+    pub weather: WeatherConfig,
+    #[serde(default)]
+    pub reminders: RemindersConfig,
+    #[serde(default)]
+    pub messages: MessagesConfig,
+    #[serde(default)]
+    pub input_limits: InputLimitsConfig,
+    #[serde(default)]
+    pub karma: KarmaConfig,
+    #[serde(default)]
+    pub webhook: WebhookConfig,
+    // Switch individual commands on or off by name, e.g. `weather = false`
+    #[serde(default)]
+    pub commands: HashMap<String, bool>,
+    // Stricter roles for individual commands, e.g. `[command_permissions.moderate] role = "admin"`
+    #[serde(default)]
+    pub command_permissions: HashMap<String, CommandPermissionConfig>,
+    // Extra names for commands, e.g. `s = "summarize"` to offer /s
+    #[serde(default)]
+    pub aliases: HashMap<String, String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -21,8 +70,376 @@ pub struct AgentConfig {
     pub enable_memory: bool,
     pub enable_summarization: bool,
     pub enable_moderation: bool,
+    // Register the /echo test command
+    #[serde(default)]
+    pub enable_echo: bool,
     pub memory_retention_days: u32,
+    // Most memories each chat keeps, evicting the oldest; 0 keeps everything. Glossary terms don't count.
     pub max_memory_items: usize,
+    // Halve a memory's search score every this many days of age; 0 ranks by similarity alone
+    #[serde(default)]
+    pub memory_recency_half_life_days: u32,
+    #[serde(default)]
+    pub agent_step_delay_ms: u64,
+    #[serde(default = "default_conversation_turns")]
+    pub conversation_turns: usize,
+    // /ask returns whatever it has found once this many seconds have passed
+    #[serde(default = "default_ask_timeout_secs")]
+    pub ask_timeout_secs: u64,
+    // Opens the agent's system prompt; chats can override it
+    #[serde(default = "default_persona")]
+    pub persona: String,
+    // Append suggested follow-up questions to /ask answers
+    #[serde(default)]
+    pub suggest_follow_ups: bool,
+    // Estimated tokens per /ask LLM call; the oldest history is dropped to stay under it
+    #[serde(default = "default_context_token_budget")]
+    pub context_token_budget: usize,
+    // Rate-limit retries one /ask may make across all its LLM calls
+    #[serde(default = "default_max_run_retries")]
+    pub max_run_retries: usize,
+    // Answer simple questions up to this many characters without planning; 0 always plans
+    #[serde(default)]
+    pub fast_path_max_chars: usize,
+    // Show each planning step of /ask in its placeholder message
+    #[serde(default)]
+    pub show_progress: bool,
+    // Let /ask take an image link, answered by `llm.vision_model`
+    #[serde(default)]
+    pub enable_vision: bool,
+}
+
+fn default_conversation_turns() -> usize {
+    5
+}
+
+fn default_ask_timeout_secs() -> u64 {
+    25
+}
+
+fn default_command_timeout_secs() -> u64 {
+    60
+}
+
+fn default_context_token_budget() -> usize {
+    24_000
+}
+
+fn default_max_run_retries() -> usize {
+    4
+}
+
+fn default_persona() -> String {
+    crate::agent::DEFAULT_PERSONA.to_string()
+}
+
+/// Which backend serves chat and embedding requests
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProviderKind {
+    Mistral,
+    // Deterministic offline responses for development and tests
+    Mock,
+}
+
+impl FromStr for LlmProviderKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "mistral" => Ok(Self::Mistral),
+            "mock" => Ok(Self::Mock),
+            other => Err(format!("unknown LLM provider '{}'", other)),
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct LlmConfig {
+    pub provider: LlmProviderKind,
+    pub cache_enabled: bool,
+    pub cache_capacity: usize,
+    pub cache_ttl_secs: u64,
+    // USD per million tokens, used for approximate cost reporting
+    pub prompt_cost_per_million: f64,
+    pub completion_cost_per_million: f64,
+    // Retries for rate-limited requests, and the base of their exponential backoff
+    pub max_retries: usize,
+    pub retry_base_delay_ms: u64,
+    // Embeddings kept in memory for reuse; 0 disables the cache
+    pub embedding_cache_capacity: usize,
+    // Requests to the LLM API allowed in flight at once; the rest wait their turn
+    pub max_concurrent_requests: usize,
+    // Failures in a row (network errors or 5xx) before LLM calls fail fast; 0 disables
+    pub circuit_failure_threshold: usize,
+    // How long calls fail fast before a probe request is let through
+    pub circuit_cooldown_secs: u64,
+    // Chat models tried in order when the main one fails with anything but a rate limit
+    pub fallback_models: Vec<String>,
+    // Vision-capable model for /ask questions about images, when agent.enable_vision is set
+    pub vision_model: String,
+    // Make a tiny chat and embedding call at startup to catch a bad key or URL early
+    pub startup_self_test: bool,
+    // Refuse to start when the startup self-test fails
+    pub strict_startup: bool,
+    // Log every chat request's full prompt at DEBUG; may contain personal data
+    pub log_prompts: bool,
+    // Mask emails and tokens in logged prompts
+    pub redact_logged_prompts: bool,
+    // Text put before every system prompt, e.g. a legal or safety disclaimer
+    pub system_prompt_prefix: String,
+}
+
+/// Where memory embeddings come from. Any OpenAI-compatible `/embeddings` API works;
+/// the key falls back to the Mistral key when the Mistral API is used.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct EmbeddingsConfig {
+    pub base_url: String,
+    pub api_key: Option<String>,
+    pub model: String,
+    // Embed with a local model when the API call fails; needs the local-embeddings feature
+    pub local_fallback: bool,
+}
+
+/// Limits for /remindme
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct RemindersConfig {
+    // Reminders a user may have waiting at once, across all chats
+    pub max_active_per_user: usize,
+}
+
+/// Longest text, in characters, OpenChat lets users enter for each command's main argument
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct InputLimitsConfig {
+    pub ask: u16,
+    pub echo: u16,
+    pub moderate: u16,
+    pub summarize: u16,
+    pub classify: u16,
+    pub import: u16,
+}
+
+/// Limits for /karma
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct KarmaConfig {
+    // How long before a user can give the same person another point
+    pub cooldown_secs: u64,
+}
+
+/// Outbound JSON notifications for external systems
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WebhookConfig {
+    // Where to POST events; unset disables the webhook
+    pub url: Option<String>,
+    // Which events to send, from `webhook::WEBHOOK_EVENTS`
+    pub events: Vec<String>,
+    pub timeout_secs: u64,
+}
+
+/// The /weather command, backed by an OpenWeatherMap-compatible API
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct WeatherConfig {
+    pub enabled: bool,
+    pub api_url: String,
+    pub api_key: Option<String>,
+    // "metric" or "imperial"
+    pub units: String,
+}
+
+/// Who sees command replies. Ephemeral replies are shown only to the user who ran the command.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(default)]
+pub struct MessagesConfig {
+    // Commands whose replies are always ephemeral
+    pub ephemeral_commands: Vec<String>,
+    // Send error replies ephemerally for every command
+    pub ephemeral_errors: bool,
+    // Format replies as markdown; off sends them as plain text
+    pub markdown: bool,
+    // Chats that get plain-text replies even while markdown is on, as canonical chat ids
+    pub plaintext_chats: Vec<String>,
+    // Longest message sent, in characters; longer replies are split across several
+    pub max_length: usize,
+}
+
+impl MessagesConfig {
+    pub fn visibility(&self, command: &str) -> crate::commands::Visibility {
+        crate::commands::Visibility {
+            ephemeral: self.ephemeral_commands.iter().any(|name| name == command),
+            ephemeral_errors: self.ephemeral_errors,
+        }
+    }
+    
+    /// Whether replies in the chat are formatted as markdown
+    pub fn markdown(&self, chat_id: &str) -> bool {
+        // An empty list would allow every chat
+        self.markdown && (self.plaintext_chats.is_empty() || !crate::chat_id::chat_allowed(chat_id, &self.plaintext_chats))
+    }
+}
+
+/// A tool the agent calls by filling `{param}` placeholders in `url` from its arguments.
+/// POST tools also send the arguments as a JSON body.
+#[derive(Deserialize, Debug, Clone)]
+pub struct HttpToolConfig {
+    pub name: String,
+    pub description: String,
+    pub url: String,
+    #[serde(default)]
+    pub method: HttpToolMethod,
+    // JSON Schema for the arguments object, given as a TOML table
+    #[serde(default = "default_tool_parameters")]
+    pub parameters: serde_json::Value,
+    #[serde(default = "default_tool_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum HttpToolMethod {
+    #[default]
+    Get,
+    Post,
+}
+
+fn default_tool_parameters() -> serde_json::Value {
+    serde_json::json!({ "type": "object", "properties": {} })
+}
+
+fn default_tool_timeout_secs() -> u64 {
+    10
+}
+
+impl HttpToolConfig {
+    // Problems with this tool's definition, prefixed with its name
+    fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let prefix = format!("tools.{}", self.name);
+        
+        // Mistral only accepts these characters in function names
+        if self.name.is_empty()
+            || self.name.len() > 64
+            || !self.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            problems.push(format!(
+                "{}: name must be 1-64 letters, digits, '_' or '-'",
+                prefix
+            ));
+        }
+        
+        if self.description.trim().is_empty() {
+            problems.push(format!("{}: description must not be empty", prefix));
+        }
+        
+        if self.timeout_secs == 0 {
+            problems.push(format!("{}: timeout_secs must be non-zero", prefix));
+        }
+        
+        // The URL must parse once its placeholders are filled in
+        let placeholders = crate::tools::url_placeholders(&self.url);
+        let sample_url = placeholders
+            .iter()
+            .fold(self.url.clone(), |url, name| url.replace(&format!("{{{}}}", name), "x"));
+        match reqwest::Url::parse(&sample_url) {
+            Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+            Ok(url) => problems.push(format!("{}: url scheme '{}' is not http(s)", prefix, url.scheme())),
+            Err(e) => problems.push(format!("{}: url '{}' is not a valid URL: {}", prefix, self.url, e)),
+        }
+        
+        if self.parameters.get("type").and_then(|t| t.as_str()) != Some("object") {
+            problems.push(format!("{}: parameters must be a schema with type = \"object\"", prefix));
+            return problems;
+        }
+        
+        let properties = match self.parameters.get("properties") {
+            None => None,
+            Some(serde_json::Value::Object(properties)) => Some(properties),
+            Some(_) => {
+                problems.push(format!("{}: parameters.properties must be a table", prefix));
+                return problems;
+            }
+        };
+        let declared = |name: &str| properties.is_some_and(|p| p.contains_key(name));
+        
+        for name in &placeholders {
+            if !declared(name) {
+                problems.push(format!("{}: url placeholder {{{}}} is not declared in parameters.properties", prefix, name));
+            }
+        }
+        
+        match self.parameters.get("required") {
+            None => {}
+            Some(serde_json::Value::Array(required)) => {
+                for name in required {
+                    match name.as_str() {
+                        Some(name) if declared(name) => {}
+                        _ => problems.push(format!("{}: required parameter {} is not declared in parameters.properties", prefix, name)),
+                    }
+                }
+            }
+            Some(_) => problems.push(format!("{}: parameters.required must be a list of names", prefix)),
+        }
+        
+        problems
+    }
+}
+
+// Allow at most `requests` invocations of a command per user every `per_seconds`
+#[derive(Deserialize, Debug, Clone)]
+pub struct RateLimitConfig {
+    pub requests: u32,
+    pub per_seconds: u64,
+}
+
+/// Who may run a command, overriding the role it declares. OpenChat only offers the command
+/// to members with at least `role`; the bot can't see chat roles, so for "admin" and
+/// "owner" it also refuses anyone not listed in `admins`.
+#[derive(Deserialize, Debug, Clone)]
+pub struct CommandPermissionConfig {
+    pub role: RequiredRole,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RequiredRole {
+    Participant,
+    Moderator,
+    Admin,
+    Owner,
+}
+
+impl RequiredRole {
+    pub fn chat_role(self) -> ChatRole {
+        match self {
+            RequiredRole::Participant => ChatRole::Participant,
+            RequiredRole::Moderator => ChatRole::Moderator,
+            RequiredRole::Admin => ChatRole::Admin,
+            RequiredRole::Owner => ChatRole::Owner,
+        }
+    }
+}
+
+impl CommandPermissionConfig {
+    /// Whether only users in `admins` may run the command
+    pub fn admins_only(&self) -> bool {
+        matches!(self.role, RequiredRole::Admin | RequiredRole::Owner)
+    }
+}
+
+fn default_rate_limits() -> HashMap<String, RateLimitConfig> {
+    HashMap::from([(
+        "ask".to_string(),
+        RateLimitConfig {
+            requests: 5,
+            per_seconds: 60,
+        },
+    )])
 }
 
 #[derive(Deserialize)]
@@ -36,12 +453,390 @@ enum LevelDef {
 }
 
 impl Config {
+    /// Load config from TOML, YAML or JSON, chosen by the file's extension
     pub fn from_file(path: &str) -> Result<Self, Box<dyn std::error::Error>> {
-        let content = fs::read_to_string(path)?;
-        let config: Config = toml::from_str(&content)?;
+        let content = fs::read_to_string(path)
+            .map_err(|e| format!("Cannot read config file {}: {}", path, e))?;
+        let mut config = Self::parse(path, &content)
+            .map_err(|e| format!("Invalid config file {}: {}", path, e))?;
+        
+        // Environment variables take precedence over the file
+        config.apply_env_overrides().map_err(|problems| {
+            format!("Invalid environment overrides:\n  - {}", problems.join("\n  - "))
+        })?;
+        
+        config.validate().map_err(|problems| {
+            format!("Invalid configuration:\n  - {}", problems.join("\n  - "))
+        })?;
+        
         Ok(config)
     }
     
+    fn parse(path: &str, content: &str) -> Result<Self, String> {
+        let extension = Path::new(path)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase);
+        
+        match extension.as_deref() {
+            Some("toml") => toml::from_str(content).map_err(|e| e.to_string()),
+            Some("yaml") | Some("yml") => serde_yaml::from_str(content).map_err(|e| e.to_string()),
+            Some("json") => serde_json::from_str(content).map_err(|e| e.to_string()),
+            _ => Err("unsupported file extension, expected .toml, .yaml, .yml or .json".to_string()),
+        }
+    }
+    
+    /// Override fields from `KARMASPARK_*` environment variables.
+    /// Precedence is env > file > default.
+    pub fn apply_env_overrides(&mut self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        
+        env_override(&mut self.pem_file, "KARMASPARK_PEM_FILE", &mut problems);
+        env_override(&mut self.ic_url, "KARMASPARK_IC_URL", &mut problems);
+        env_override(&mut self.oc_public_key, "KARMASPARK_OC_PUBLIC_KEY", &mut problems);
+        env_override(&mut self.port, "KARMASPARK_PORT", &mut problems);
+        env_override(&mut self.log_level, "KARMASPARK_LOG_LEVEL", &mut problems);
+        env_override_opt(&mut self.mistral_api_key, "KARMASPARK_MISTRAL_API_KEY", &mut problems);
+        env_override_opt(&mut self.sqlite_db_path, "KARMASPARK_SQLITE_DB_PATH", &mut problems);
+        env_override_opt(&mut self.database_url, "KARMASPARK_DATABASE_URL", &mut problems);
+        if let Ok(raw) = std::env::var("KARMASPARK_ADMINS") {
+            self.admins = raw
+                .split(',')
+                .map(|admin| admin.trim().to_string())
+                .filter(|admin| !admin.is_empty())
+                .collect();
+        }
+        if let Ok(raw) = std::env::var("KARMASPARK_ALLOWED_CHATS") {
+            self.allowed_chats = raw
+                .split(',')
+                .map(|chat| chat.trim().to_string())
+                .filter(|chat| !chat.is_empty())
+                .collect();
+        }
+        
+        env_override(&mut self.command_timeout_secs, "KARMASPARK_COMMAND_TIMEOUT_SECS", &mut problems);
+        
+        let agent = &mut self.agent;
+        env_override(&mut agent.enable_agent_planning, "KARMASPARK_AGENT_ENABLE_AGENT_PLANNING", &mut problems);
+        env_override(&mut agent.enable_memory, "KARMASPARK_AGENT_ENABLE_MEMORY", &mut problems);
+        env_override(&mut agent.enable_summarization, "KARMASPARK_AGENT_ENABLE_SUMMARIZATION", &mut problems);
+        env_override(&mut agent.enable_moderation, "KARMASPARK_AGENT_ENABLE_MODERATION", &mut problems);
+        env_override(&mut agent.enable_echo, "KARMASPARK_AGENT_ENABLE_ECHO", &mut problems);
+        env_override(&mut agent.memory_retention_days, "KARMASPARK_AGENT_MEMORY_RETENTION_DAYS", &mut problems);
+        env_override(&mut agent.max_memory_items, "KARMASPARK_AGENT_MAX_MEMORY_ITEMS", &mut problems);
+        env_override(&mut agent.memory_recency_half_life_days, "KARMASPARK_AGENT_MEMORY_RECENCY_HALF_LIFE_DAYS", &mut problems);
+        env_override(&mut agent.agent_step_delay_ms, "KARMASPARK_AGENT_AGENT_STEP_DELAY_MS", &mut problems);
+        env_override(&mut agent.conversation_turns, "KARMASPARK_AGENT_CONVERSATION_TURNS", &mut problems);
+        env_override(&mut agent.ask_timeout_secs, "KARMASPARK_AGENT_ASK_TIMEOUT_SECS", &mut problems);
+        env_override(&mut agent.persona, "KARMASPARK_AGENT_PERSONA", &mut problems);
+        env_override(&mut agent.suggest_follow_ups, "KARMASPARK_AGENT_SUGGEST_FOLLOW_UPS", &mut problems);
+        env_override(&mut agent.context_token_budget, "KARMASPARK_AGENT_CONTEXT_TOKEN_BUDGET", &mut problems);
+        env_override(&mut agent.max_run_retries, "KARMASPARK_AGENT_MAX_RUN_RETRIES", &mut problems);
+        env_override(&mut agent.fast_path_max_chars, "KARMASPARK_AGENT_FAST_PATH_MAX_CHARS", &mut problems);
+        env_override(&mut agent.show_progress, "KARMASPARK_AGENT_SHOW_PROGRESS", &mut problems);
+        env_override(&mut agent.enable_vision, "KARMASPARK_AGENT_ENABLE_VISION", &mut problems);
+        
+        let llm = &mut self.llm;
+        env_override(&mut llm.provider, "KARMASPARK_LLM_PROVIDER", &mut problems);
+        env_override(&mut llm.cache_enabled, "KARMASPARK_LLM_CACHE_ENABLED", &mut problems);
+        env_override(&mut llm.cache_capacity, "KARMASPARK_LLM_CACHE_CAPACITY", &mut problems);
+        env_override(&mut llm.cache_ttl_secs, "KARMASPARK_LLM_CACHE_TTL_SECS", &mut problems);
+        env_override(&mut llm.prompt_cost_per_million, "KARMASPARK_LLM_PROMPT_COST_PER_MILLION", &mut problems);
+        env_override(&mut llm.completion_cost_per_million, "KARMASPARK_LLM_COMPLETION_COST_PER_MILLION", &mut problems);
+        env_override(&mut llm.max_retries, "KARMASPARK_LLM_MAX_RETRIES", &mut problems);
+        env_override(&mut llm.retry_base_delay_ms, "KARMASPARK_LLM_RETRY_BASE_DELAY_MS", &mut problems);
+        env_override(&mut llm.circuit_failure_threshold, "KARMASPARK_LLM_CIRCUIT_FAILURE_THRESHOLD", &mut problems);
+        env_override(&mut llm.circuit_cooldown_secs, "KARMASPARK_LLM_CIRCUIT_COOLDOWN_SECS", &mut problems);
+        env_override(&mut llm.embedding_cache_capacity, "KARMASPARK_LLM_EMBEDDING_CACHE_CAPACITY", &mut problems);
+        env_override(&mut llm.max_concurrent_requests, "KARMASPARK_LLM_MAX_CONCURRENT_REQUESTS", &mut problems);
+        env_override(&mut llm.vision_model, "KARMASPARK_LLM_VISION_MODEL", &mut problems);
+        if let Ok(raw) = std::env::var("KARMASPARK_LLM_FALLBACK_MODELS") {
+            llm.fallback_models = raw
+                .split(',')
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty())
+                .collect();
+        }
+        env_override(&mut llm.startup_self_test, "KARMASPARK_LLM_STARTUP_SELF_TEST", &mut problems);
+        env_override(&mut llm.strict_startup, "KARMASPARK_LLM_STRICT_STARTUP", &mut problems);
+        env_override(&mut llm.log_prompts, "KARMASPARK_LLM_LOG_PROMPTS", &mut problems);
+        env_override(&mut llm.redact_logged_prompts, "KARMASPARK_LLM_REDACT_LOGGED_PROMPTS", &mut problems);
+        env_override(&mut llm.system_prompt_prefix, "KARMASPARK_LLM_SYSTEM_PROMPT_PREFIX", &mut problems);
+        
+        let embeddings = &mut self.embeddings;
+        env_override(&mut embeddings.base_url, "KARMASPARK_EMBEDDINGS_BASE_URL", &mut problems);
+        env_override_opt(&mut embeddings.api_key, "KARMASPARK_EMBEDDINGS_API_KEY", &mut problems);
+        env_override(&mut embeddings.model, "KARMASPARK_EMBEDDINGS_MODEL", &mut problems);
+        env_override(&mut embeddings.local_fallback, "KARMASPARK_EMBEDDINGS_LOCAL_FALLBACK", &mut problems);
+        
+        let weather = &mut self.weather;
+        env_override(&mut weather.enabled, "KARMASPARK_WEATHER_ENABLED", &mut problems);
+        env_override(&mut weather.api_url, "KARMASPARK_WEATHER_API_URL", &mut problems);
+        env_override_opt(&mut weather.api_key, "KARMASPARK_WEATHER_API_KEY", &mut problems);
+        env_override(&mut weather.units, "KARMASPARK_WEATHER_UNITS", &mut problems);
+        
+        env_override(&mut self.reminders.max_active_per_user, "KARMASPARK_REMINDERS_MAX_ACTIVE_PER_USER", &mut problems);
+        
+        env_override(&mut self.karma.cooldown_secs, "KARMASPARK_KARMA_COOLDOWN_SECS", &mut problems);
+        
+        env_override_opt(&mut self.webhook.url, "KARMASPARK_WEBHOOK_URL", &mut problems);
+        env_override(&mut self.webhook.timeout_secs, "KARMASPARK_WEBHOOK_TIMEOUT_SECS", &mut problems);
+        if let Ok(raw) = std::env::var("KARMASPARK_WEBHOOK_EVENTS") {
+            self.webhook.events = raw
+                .split(',')
+                .map(|event| event.trim().to_string())
+                .filter(|event| !event.is_empty())
+                .collect();
+        }
+        
+        let input_limits = &mut self.input_limits;
+        env_override(&mut input_limits.ask, "KARMASPARK_INPUT_LIMITS_ASK", &mut problems);
+        env_override(&mut input_limits.echo, "KARMASPARK_INPUT_LIMITS_ECHO", &mut problems);
+        env_override(&mut input_limits.moderate, "KARMASPARK_INPUT_LIMITS_MODERATE", &mut problems);
+        env_override(&mut input_limits.summarize, "KARMASPARK_INPUT_LIMITS_SUMMARIZE", &mut problems);
+        env_override(&mut input_limits.classify, "KARMASPARK_INPUT_LIMITS_CLASSIFY", &mut problems);
+        env_override(&mut input_limits.import, "KARMASPARK_INPUT_LIMITS_IMPORT", &mut problems);
+        
+        env_override(&mut self.messages.ephemeral_errors, "KARMASPARK_MESSAGES_EPHEMERAL_ERRORS", &mut problems);
+        env_override(&mut self.messages.markdown, "KARMASPARK_MESSAGES_MARKDOWN", &mut problems);
+        env_override(&mut self.messages.max_length, "KARMASPARK_MESSAGES_MAX_LENGTH", &mut problems);
+        if let Ok(raw) = std::env::var("KARMASPARK_MESSAGES_EPHEMERAL_COMMANDS") {
+            self.messages.ephemeral_commands = raw
+                .split(',')
+                .map(|command| command.trim().to_string())
+                .filter(|command| !command.is_empty())
+                .collect();
+        }
+        if let Ok(raw) = std::env::var("KARMASPARK_MESSAGES_PLAINTEXT_CHATS") {
+            self.messages.plaintext_chats = raw
+                .split(',')
+                .map(|chat| chat.trim().to_string())
+                .filter(|chat| !chat.is_empty())
+                .collect();
+        }
+        
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    
+    /// The SQLite file memories are kept in: database_url's path if set, else sqlite_db_path
+    pub fn memory_db_path(&self, default_path: &str) -> String {
+        self.database_url
+            .as_deref()
+            .and_then(|url| url.strip_prefix("sqlite://"))
+            .unwrap_or(default_path)
+            .to_string()
+    }
+    
+    /// Check the parsed config for problems, reporting all of them at once
+    pub fn validate(&self) -> Result<(), Vec<String>> {
+        let mut problems = Vec::new();
+        
+        if self.port == 0 {
+            problems.push("port must be non-zero".to_string());
+        }
+        
+        if let Err(e) = reqwest::Url::parse(&self.ic_url) {
+            problems.push(format!("ic_url '{}' is not a valid URL: {}", self.ic_url, e));
+        }
+        
+        if let Err(e) = fs::File::open(&self.pem_file) {
+            problems.push(format!("pem_file '{}' cannot be read: {}", self.pem_file, e));
+        }
+        
+        if let Err(e) = self.validate_oc_public_key() {
+            problems.push(e);
+        }
+        
+        if let Some(url) = &self.database_url {
+            match url.split_once("://") {
+                Some(("sqlite", path)) if !path.is_empty() => {}
+                Some(("sqlite", _)) => problems.push("database_url must name a file after sqlite://".to_string()),
+                Some((scheme, _)) => problems.push(format!(
+                    "database_url scheme '{}' is not supported yet; use sqlite://",
+                    scheme
+                )),
+                None => problems.push(format!("database_url '{}' has no scheme; use sqlite://<path>", url)),
+            }
+        }
+        
+        if self.command_timeout_secs == 0 {
+            problems.push("command_timeout_secs must be greater than 0".to_string());
+        }
+        // Split code blocks repeat their fences, so parts need some room
+        if self.messages.max_length < 100 {
+            problems.push("messages.max_length must be at least 100".to_string());
+        }
+        
+        if self.agent.memory_retention_days == 0 {
+            problems.push("agent.memory_retention_days must be greater than 0".to_string());
+        }
+        
+        if self.agent.ask_timeout_secs == 0 {
+            problems.push("agent.ask_timeout_secs must be greater than 0".to_string());
+        }
+        
+        if self.agent.context_token_budget == 0 {
+            problems.push("agent.context_token_budget must be greater than 0".to_string());
+        }
+        
+        let persona_chars = self.agent.persona.chars().count();
+        if self.agent.persona.trim().is_empty() {
+            problems.push("agent.persona must not be empty".to_string());
+        } else if persona_chars > crate::agent::MAX_PERSONA_CHARS {
+            problems.push(format!(
+                "agent.persona must be at most {} characters, got {}",
+                crate::agent::MAX_PERSONA_CHARS, persona_chars
+            ));
+        }
+        
+        for (command, limit) in &self.rate_limits {
+            if limit.requests == 0 || limit.per_seconds == 0 {
+                problems.push(format!(
+                    "rate_limits.{} must have non-zero requests and per_seconds",
+                    command
+                ));
+            }
+        }
+        
+        if self.llm.max_retries > 10 {
+            problems.push(format!("llm.max_retries must be at most 10, got {}", self.llm.max_retries));
+        }
+        
+        if self.llm.max_concurrent_requests == 0 {
+            problems.push("llm.max_concurrent_requests must be greater than 0".to_string());
+        }
+        
+        if self.llm.strict_startup && !self.llm.startup_self_test {
+            problems.push("llm.strict_startup needs llm.startup_self_test to be enabled".to_string());
+        }
+        
+        if self.agent.enable_vision && self.llm.vision_model.trim().is_empty() {
+            problems.push("llm.vision_model must not be empty when agent.enable_vision is set".to_string());
+        }
+        if self.llm.fallback_models.iter().any(|model| model.trim().is_empty()) {
+            problems.push("llm.fallback_models must not contain empty names".to_string());
+        }
+        
+        if self.llm.circuit_failure_threshold > 0 && self.llm.circuit_cooldown_secs == 0 {
+            problems.push("llm.circuit_cooldown_secs must be greater than 0 when the circuit breaker is enabled".to_string());
+        }
+        
+        for command in self.commands.keys() {
+            if !crate::commands::registry::COMMAND_NAMES.contains(&command.as_str()) {
+                problems.push(format!("commands.{}: unknown command", command));
+            }
+        }
+        for (alias, command) in &self.aliases {
+            if crate::commands::registry::COMMAND_NAMES.contains(&alias.as_str()) {
+                problems.push(format!("aliases.{}: clashes with the /{} command", alias, alias));
+            } else if alias.is_empty() || !alias.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_') {
+                problems.push(format!("aliases.{}: names may only use lowercase letters, digits and '_'", alias));
+            }
+            if !crate::commands::registry::COMMAND_NAMES.contains(&command.as_str()) {
+                problems.push(format!("aliases.{}: unknown command '{}'", alias, command));
+            }
+        }
+        for (command, permission) in &self.command_permissions {
+            if !crate::commands::registry::COMMAND_NAMES.contains(&command.as_str()) {
+                problems.push(format!("command_permissions.{}: unknown command", command));
+            } else if permission.admins_only() && self.admins.is_empty() {
+                problems.push(format!(
+                    "command_permissions.{}: admin-only commands need at least one user in admins",
+                    command
+                ));
+            }
+        }
+        
+        let mut tool_names = std::collections::HashSet::new();
+        for tool in &self.tools {
+            problems.extend(tool.problems());
+            if crate::tools::BUILTIN_TOOLS.contains(&tool.name.as_str()) {
+                problems.push(format!("tools.{}: name clashes with a built-in tool", tool.name));
+            }
+            if !tool_names.insert(tool.name.as_str()) {
+                problems.push(format!("tools.{}: defined more than once", tool.name));
+            }
+        }
+        
+        for chat in &self.allowed_chats {
+            if !crate::chat_id::is_canonical_chat_id(chat) {
+                problems.push(format!(
+                    "allowed_chats: '{}' is not a chat id like group:<id>, channel:<community id>:<channel id>, community:<id> or direct:<id>",
+                    chat
+                ));
+            }
+        }
+        
+        if self.reminders.max_active_per_user == 0 {
+            problems.push("reminders.max_active_per_user must be greater than 0".to_string());
+        }
+        
+        if let Some(url) = &self.webhook.url {
+            if let Err(e) = reqwest::Url::parse(url) {
+                problems.push(format!("webhook.url '{}' is not a valid URL: {}", url, e));
+            }
+        }
+        for event in &self.webhook.events {
+            if !crate::webhook::WEBHOOK_EVENTS.contains(&event.as_str()) {
+                problems.push(format!(
+                    "webhook.events: unknown event '{}', expected one of {}",
+                    event,
+                    crate::webhook::WEBHOOK_EVENTS.join(", ")
+                ));
+            }
+        }
+        if self.webhook.timeout_secs == 0 {
+            problems.push("webhook.timeout_secs must be greater than 0".to_string());
+        }
+        
+        // Each limit has to allow at least the command's minimum length
+        let limits = &self.input_limits;
+        for (name, limit, min) in [
+            ("ask", limits.ask, 1),
+            ("echo", limits.echo, 1),
+            ("moderate", limits.moderate, 1),
+            ("summarize", limits.summarize, crate::commands::summarize::MIN_TEXT_LENGTH),
+            ("classify", limits.classify, 1),
+            ("import", limits.import, 2),
+        ] {
+            if limit < min {
+                problems.push(format!("input_limits.{} must be at least {}", name, min));
+            }
+        }
+        
+        if let Err(e) = reqwest::Url::parse(&self.embeddings.base_url) {
+            problems.push(format!("embeddings.base_url '{}' is not a valid URL: {}", self.embeddings.base_url, e));
+        }
+        if self.embeddings.model.trim().is_empty() {
+            problems.push("embeddings.model must not be empty".to_string());
+        }
+        if self.embeddings.local_fallback && !cfg!(feature = "local-embeddings") {
+            problems.push("embeddings.local_fallback needs a build with the local-embeddings feature".to_string());
+        }
+        
+        if self.weather.enabled {
+            if let Err(e) = reqwest::Url::parse(&self.weather.api_url) {
+                problems.push(format!("weather.api_url '{}' is not a valid URL: {}", self.weather.api_url, e));
+            }
+            if self.weather.units != "metric" && self.weather.units != "imperial" {
+                problems.push(format!("weather.units must be \"metric\" or \"imperial\", got '{}'", self.weather.units));
+            }
+            if let Err(e) = self.weather_api_key() {
+                problems.push(format!("{} (set weather.api_key or WEATHER_API_KEY)", e));
+            }
+        }
+        
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(problems)
+        }
+    }
+    
     pub fn mistral_api_key(&self) -> Result<String, String> {
         if let Some(key) = &self.mistral_api_key {
             if !key.is_empty() {
@@ -58,6 +853,74 @@ impl Config {
         
         Err("Mistral API key not found in config or environment".to_string())
     }
+    
+    /// Key for the embeddings API. The Mistral API shares the chat key; other
+    /// endpoints without a key of their own are called unauthenticated.
+    pub fn embeddings_api_key(&self) -> Result<String, String> {
+        if let Some(key) = &self.embeddings.api_key {
+            if !key.is_empty() {
+                return Ok(key.clone());
+            }
+        }
+        
+        let base_url = self.embeddings.base_url.trim_end_matches('/');
+        if base_url == crate::llm::MISTRAL_API_URL {
+            self.mistral_api_key()
+        } else {
+            Ok(String::new())
+        }
+    }
+    
+    pub fn weather_api_key(&self) -> Result<String, String> {
+        if let Some(key) = &self.weather.api_key {
+            if !key.is_empty() {
+                return Ok(key.clone());
+            }
+        }
+        
+        // Try to get from environment
+        if let Ok(key) = std::env::var("WEATHER_API_KEY") {
+            if !key.is_empty() {
+                return Ok(key);
+            }
+        }
+        
+        Err("Weather API key not found in config or environment".to_string())
+    }
+    
+    /// Check that `oc_public_key` is a PEM-encoded EC public key usable for verifying OpenChat JWTs
+    pub fn validate_oc_public_key(&self) -> Result<(), String> {
+        DecodingKey::from_ec_pem(self.oc_public_key.trim().as_bytes())
+            .map(|_| ())
+            .map_err(|e| format!("oc_public_key is not a valid PEM-encoded EC public key: {}", e))
+    }
+}
+
+// Replace `field` with the parsed value of env var `name` if it is set
+fn env_override<T>(field: &mut T, name: &str, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(raw) = std::env::var(name) {
+        match raw.trim().parse() {
+            Ok(value) => *field = value,
+            Err(e) => problems.push(format!("{}='{}' is invalid: {}", name, raw, e)),
+        }
+    }
+}
+
+fn env_override_opt<T>(field: &mut Option<T>, name: &str, problems: &mut Vec<String>)
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    if let Ok(raw) = std::env::var(name) {
+        match raw.trim().parse() {
+            Ok(value) => *field = Some(value),
+            Err(e) => problems.push(format!("{}='{}' is invalid: {}", name, raw, e)),
+        }
+    }
 }
 
 impl Default for AgentConfig {
@@ -69,6 +932,605 @@ impl Default for AgentConfig {
             enable_moderation: false,
             memory_retention_days: 30,
             max_memory_items: 1000,
+            memory_recency_half_life_days: 0,
+            agent_step_delay_ms: 0,
+            enable_echo: false,
+            conversation_turns: default_conversation_turns(),
+            ask_timeout_secs: default_ask_timeout_secs(),
+            persona: default_persona(),
+            suggest_follow_ups: false,
+            context_token_budget: default_context_token_budget(),
+            max_run_retries: default_max_run_retries(),
+            fast_path_max_chars: 0,
+            show_progress: false,
+            enable_vision: false,
+        }
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: LlmProviderKind::Mistral,
+            cache_enabled: false,
+            cache_capacity: 256,
+            cache_ttl_secs: 3600,
+            prompt_cost_per_million: 2.7,
+            completion_cost_per_million: 8.1,
+            max_retries: 3,
+            retry_base_delay_ms: 1000,
+            embedding_cache_capacity: 512,
+            max_concurrent_requests: 4,
+            circuit_failure_threshold: 5,
+            circuit_cooldown_secs: 30,
+            fallback_models: Vec::new(),
+            vision_model: "pixtral-12b-2409".to_string(),
+            startup_self_test: false,
+            strict_startup: false,
+            log_prompts: false,
+            redact_logged_prompts: true,
+            system_prompt_prefix: String::new(),
+        }
+    }
+}
+
+impl Default for MessagesConfig {
+    fn default() -> Self {
+        Self {
+            ephemeral_commands: vec!["moderate".to_string()],
+            ephemeral_errors: true,
+            markdown: true,
+            plaintext_chats: Vec::new(),
+            max_length: crate::commands::DEFAULT_MAX_MESSAGE_LENGTH,
+        }
+    }
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            base_url: crate::llm::MISTRAL_API_URL.to_string(),
+            api_key: None,
+            model: crate::llm::DEFAULT_EMBEDDING_MODEL.to_string(),
+            local_fallback: false,
+        }
+    }
+}
+
+impl Default for RemindersConfig {
+    fn default() -> Self {
+        Self {
+            max_active_per_user: 20,
+        }
+    }
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            url: None,
+            events: crate::webhook::WEBHOOK_EVENTS.iter().map(|event| event.to_string()).collect(),
+            timeout_secs: 5,
+        }
+    }
+}
+
+impl Default for KarmaConfig {
+    fn default() -> Self {
+        Self {
+            cooldown_secs: 3600,
+        }
+    }
+}
+
+impl Default for InputLimitsConfig {
+    fn default() -> Self {
+        Self {
+            ask: 10000,
+            echo: 10000,
+            moderate: 10000,
+            summarize: 50000,
+            classify: 10000,
+            import: 60000,
+        }
+    }
+}
+
+impl Default for WeatherConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            api_url: "https://api.openweathermap.org/data/2.5/weather".to_string(),
+            api_key: None,
+            units: "metric".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    // Environment variables are shared by every test thread
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    const OC_PUBLIC_KEY: &str = "-----BEGIN PUBLIC KEY-----
+MFkwEwYHKoZIzj0CAQYIKoZIzj0DAQcDQgAEguYmXxujDaFxwScUXGCuIbAKCa70
+GZlCS/Uy+MZjOxbO7JGHaizIRE+DvO+ILp7ZZ/juFsAee8RlnsGxRTotTg==
+-----END PUBLIC KEY-----";
+
+    // A minimal config that passes validation
+    fn config() -> Config {
+        config_with("")
+    }
+
+    // The minimal config followed by `extra` TOML
+    fn config_with(extra: &str) -> Config {
+        Config::parse("config.toml", &toml_with(extra)).unwrap()
+    }
+
+    fn toml_with(extra: &str) -> String {
+        format!(
+            r#"
+pem_file = "{}"
+ic_url = "https://icp0.io"
+oc_public_key = """{}"""
+port = 3000
+log_level = "INFO"
+
+[agent]
+enable_agent_planning = true
+enable_memory = true
+enable_summarization = true
+enable_moderation = true
+memory_retention_days = 30
+max_memory_items = 1000
+{}
+"#,
+            concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml"),
+            OC_PUBLIC_KEY,
+            extra
+        )
+    }
+
+    #[test]
+    fn accepts_a_valid_oc_public_key() {
+        assert_eq!(config().validate_oc_public_key(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_an_invalid_oc_public_key() {
+        let mut config = config();
+        config.oc_public_key = "not a key".to_string();
+
+        let error = config.validate_oc_public_key().unwrap_err();
+        assert!(error.starts_with("oc_public_key is not a valid PEM-encoded EC public key"), "{}", error);
+    }
+
+    #[test]
+    fn a_minimal_config_is_valid() {
+        assert_eq!(config().validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_port_zero() {
+        let mut config = config();
+        config.port = 0;
+
+        assert_eq!(config.validate(), Err(vec!["port must be non-zero".to_string()]));
+    }
+
+    #[test]
+    fn rejects_an_unparseable_ic_url() {
+        let mut config = config();
+        config.ic_url = "not a url".to_string();
+
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("ic_url 'not a url' is not a valid URL"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn rejects_a_missing_pem_file() {
+        let mut config = config();
+        config.pem_file = "/nonexistent/identity.pem".to_string();
+
+        let problems = config.validate().unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("pem_file '/nonexistent/identity.pem' cannot be read"), "{}", problems[0]);
+    }
+
+    #[test]
+    fn rejects_zero_memory_retention_days() {
+        let mut config = config();
+        config.agent.memory_retention_days = 0;
+
+        assert_eq!(
+            config.validate(),
+            Err(vec!["agent.memory_retention_days must be greater than 0".to_string()])
+        );
+    }
+
+    #[test]
+    fn reports_every_problem_at_once() {
+        let mut config = config();
+        config.port = 0;
+        config.ic_url = "not a url".to_string();
+        config.agent.memory_retention_days = 0;
+
+        assert_eq!(config.validate().unwrap_err().len(), 3);
+    }
+
+    // Run `f` with the environment variables set, removing them afterwards
+    fn with_env<T>(vars: &[(&str, &str)], f: impl FnOnce() -> T) -> T {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        for (name, value) in vars {
+            std::env::set_var(name, value);
         }
+        let result = f();
+        for (name, _) in vars {
+            std::env::remove_var(name);
+        }
+        result
+    }
+
+    #[test]
+    fn env_vars_win_over_file_values() {
+        let mut config = config();
+        let result = with_env(
+            &[
+                ("KARMASPARK_PORT", "8080"),
+                ("KARMASPARK_IC_URL", "https://ic0.app"),
+                ("KARMASPARK_AGENT_ENABLE_MEMORY", "false"),
+                ("KARMASPARK_LLM_PROVIDER", "mock"),
+                ("KARMASPARK_ADMINS", "alice, bob,"),
+            ],
+            || config.apply_env_overrides(),
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(config.port, 8080);
+        assert_eq!(config.ic_url, "https://ic0.app");
+        assert!(!config.agent.enable_memory);
+        assert_eq!(config.llm.provider, LlmProviderKind::Mock);
+        assert_eq!(config.admins, vec!["alice", "bob"]);
+    }
+
+    #[test]
+    fn aliases_must_not_clash_with_commands() {
+        let config = config_with("[aliases]\ns = \"summarize\"\nq = \"ask\"\n");
+        assert_eq!(config.validate(), Ok(()));
+        assert_eq!(config.aliases["s"], "summarize");
+
+        let config = config_with("[aliases]\nask = \"summarize\"\n\"Big\" = \"ask\"\nx = \"launch\"\n");
+        let mut problems = config.validate().unwrap_err();
+        problems.sort();
+        assert_eq!(
+            problems,
+            vec![
+                "aliases.Big: names may only use lowercase letters, digits and '_'",
+                "aliases.ask: clashes with the /ask command",
+                "aliases.x: unknown command 'launch'",
+            ]
+        );
+    }
+
+    #[test]
+    fn command_permissions_need_known_commands_and_admins() {
+        let mut config = config_with(
+            r#"
+[command_permissions.moderate]
+role = "admin"
+
+[command_permissions.poll]
+role = "moderator"
+"#,
+        );
+        assert!(config.command_permissions["moderate"].admins_only());
+        assert!(!config.command_permissions["poll"].admins_only());
+        assert_eq!(config.command_permissions["moderate"].role.chat_role(), ChatRole::Admin);
+
+        let problems = config.validate().unwrap_err();
+        assert_eq!(
+            problems,
+            vec!["command_permissions.moderate: admin-only commands need at least one user in admins"]
+        );
+
+        config.admins = vec!["alice".to_string()];
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = config_with("[command_permissions.launch]\nrole = \"owner\"\n");
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains(&"command_permissions.launch: unknown command".to_string()));
+    }
+
+    #[test]
+    fn file_values_stay_without_env_vars() {
+        let mut config = config();
+        let result = with_env(&[], || config.apply_env_overrides());
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(config.port, 3000);
+        assert_eq!(config.ic_url, "https://icp0.io");
+    }
+
+    #[test]
+    fn reports_unparseable_env_vars() {
+        let mut config = config();
+        let result = with_env(
+            &[("KARMASPARK_PORT", "eighty"), ("KARMASPARK_AGENT_ENABLE_MEMORY", "yes")],
+            || config.apply_env_overrides(),
+        );
+
+        let problems = result.unwrap_err();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("KARMASPARK_PORT='eighty' is invalid"), "{}", problems[0]);
+        assert_eq!(config.port, 3000);
+    }
+
+    #[test]
+    fn system_prompt_prefix_is_empty_unless_configured() {
+        assert_eq!(config().llm.system_prompt_prefix, "");
+
+        let config = config_with(
+            r#"
+[llm]
+system_prompt_prefix = "Answers are not legal advice."
+"#,
+        );
+        assert_eq!(config.llm.system_prompt_prefix, "Answers are not legal advice.");
+    }
+
+    #[test]
+    fn parses_llm_retry_settings() {
+        let config = config_with(
+            r#"
+[llm]
+max_retries = 0
+retry_base_delay_ms = 250
+"#,
+        );
+
+        assert_eq!(config.llm.max_retries, 0);
+        assert_eq!(config.llm.retry_base_delay_ms, 250);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn llm_retry_settings_default_to_the_old_constants() {
+        let config = config();
+
+        assert_eq!(config.llm.max_retries, 3);
+        assert_eq!(config.llm.retry_base_delay_ms, 1000);
+    }
+
+    #[test]
+    fn rejects_more_than_ten_retries() {
+        let mut config = config();
+        config.llm.max_retries = 11;
+
+        assert_eq!(config.validate(), Err(vec!["llm.max_retries must be at most 10, got 11".to_string()]));
     }
-} 
\ No newline at end of file
+
+    #[test]
+    fn accepts_a_well_formed_tool() {
+        let config = config_with(
+            r#"
+[[tools]]
+name = "lookup"
+description = "Look up a word"
+url = "https://example.com/words/{word}"
+
+[tools.parameters]
+type = "object"
+required = ["word"]
+
+[tools.parameters.properties.word]
+type = "string"
+"#,
+        );
+
+        assert_eq!(config.tools[0].method, HttpToolMethod::Get);
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn reports_problems_with_tool_definitions() {
+        let config = config_with(
+            r#"
+[[tools]]
+name = "search"
+description = ""
+url = "ftp://example.com/{word}"
+"#,
+        );
+
+        assert_eq!(
+            config.validate(),
+            Err(vec![
+                "tools.search: description must not be empty".to_string(),
+                "tools.search: url scheme 'ftp' is not http(s)".to_string(),
+                "tools.search: url placeholder {word} is not declared in parameters.properties".to_string(),
+                "tools.search: name clashes with a built-in tool".to_string(),
+            ])
+        );
+    }
+
+    #[test]
+    fn persona_defaults_and_is_limited() {
+        let mut config = config();
+        assert_eq!(config.agent.persona, crate::agent::DEFAULT_PERSONA);
+
+        config.agent.persona = "x".repeat(crate::agent::MAX_PERSONA_CHARS + 1);
+        assert_eq!(
+            config.validate(),
+            Err(vec![format!(
+                "agent.persona must be at most {} characters, got {}",
+                crate::agent::MAX_PERSONA_CHARS,
+                crate::agent::MAX_PERSONA_CHARS + 1
+            )])
+        );
+
+        config.agent.persona = "  ".to_string();
+        assert_eq!(config.validate(), Err(vec!["agent.persona must not be empty".to_string()]));
+    }
+
+    #[test]
+    fn echo_is_off_unless_enabled() {
+        assert!(!config().agent.enable_echo);
+
+        let mut config = config();
+        let result = with_env(&[("KARMASPARK_AGENT_ENABLE_ECHO", "true")], || config.apply_env_overrides());
+        assert_eq!(result, Ok(()));
+        assert!(config.agent.enable_echo);
+    }
+
+    #[test]
+    fn commands_toggle_by_name() {
+        let config = config_with("[commands]\nweather = false\necho = true\n");
+        assert_eq!(config.commands.get("weather"), Some(&false));
+        assert_eq!(config.commands.get("echo"), Some(&true));
+        assert_eq!(config.validate(), Ok(()));
+    }
+
+    #[test]
+    fn rejects_unknown_command_names() {
+        let config = config_with("[commands]\nteleport = true\n");
+        assert_eq!(config.validate(), Err(vec!["commands.teleport: unknown command".to_string()]));
+    }
+
+    #[test]
+    fn embeddings_default_to_mistral() {
+        let embeddings = config().embeddings;
+        assert_eq!(embeddings.base_url, crate::llm::MISTRAL_API_URL);
+        assert_eq!(embeddings.model, crate::llm::DEFAULT_EMBEDDING_MODEL);
+        assert_eq!(embeddings.api_key, None);
+    }
+
+    #[test]
+    fn parses_a_separate_embeddings_provider() {
+        let mut config = config_with(
+            "[embeddings]\nbase_url = \"http://localhost:11434/v1\"\napi_key = \"local-key\"\nmodel = \"all-minilm\"\n",
+        );
+        assert_eq!(config.embeddings.base_url, "http://localhost:11434/v1");
+        assert_eq!(config.embeddings.model, "all-minilm");
+        assert_eq!(config.embeddings_api_key(), Ok("local-key".to_string()));
+        assert_eq!(config.validate(), Ok(()));
+
+        // Other endpoints don't borrow the Mistral key
+        config.embeddings.api_key = None;
+        assert_eq!(config.embeddings_api_key(), Ok(String::new()));
+    }
+
+    #[test]
+    fn reads_the_same_config_from_toml_yaml_and_json() {
+        let toml = toml_with("conversation_turns = 7\n\n[commands]\nweather = false\n");
+        let value: serde_json::Value = toml::from_str(&toml).unwrap();
+        let yaml = serde_yaml::to_string(&value).unwrap();
+        let json = serde_json::to_string_pretty(&value).unwrap();
+
+        for (path, content) in [("config.toml", &toml), ("config.yaml", &yaml), ("config.yml", &yaml), ("Config.JSON", &json)] {
+            let config = Config::parse(path, content).unwrap_or_else(|e| panic!("{}: {}", path, e));
+            assert_eq!(config.port, 3000, "{}", path);
+            assert_eq!(config.ic_url, "https://icp0.io", "{}", path);
+            assert_eq!(config.oc_public_key.trim(), OC_PUBLIC_KEY, "{}", path);
+            assert_eq!(config.agent.conversation_turns, 7, "{}", path);
+            assert_eq!(config.commands.get("weather"), Some(&false), "{}", path);
+            assert_eq!(config.validate(), Ok(()), "{}", path);
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_config_extensions() {
+        assert_eq!(
+            Config::parse("config.ini", "port = 3000").unwrap_err(),
+            "unsupported file extension, expected .toml, .yaml, .yml or .json"
+        );
+        assert!(Config::parse("config", "port = 3000").is_err());
+    }
+
+    #[test]
+    fn input_limits_default_and_can_be_lowered() {
+        let limits = config().input_limits;
+        assert_eq!((limits.ask, limits.echo, limits.summarize), (10000, 10000, 50000));
+
+        let config = config_with("[input_limits]\nask = 2000\nsummarize = 8000\n");
+        assert_eq!(config.input_limits.ask, 2000);
+        assert_eq!(config.input_limits.summarize, 8000);
+        assert_eq!(config.input_limits.echo, 10000);
+        assert_eq!(config.validate(), Ok(()));
+
+        let config = config_with("[input_limits]\nsummarize = 5\n");
+        assert_eq!(config.validate(), Err(vec!["input_limits.summarize must be at least 10".to_string()]));
+    }
+
+    #[test]
+    fn circuit_breaker_settings() {
+        let llm = config().llm;
+        assert_eq!((llm.circuit_failure_threshold, llm.circuit_cooldown_secs), (5, 30));
+
+        let config = config_with("[llm]\ncircuit_failure_threshold = 3\ncircuit_cooldown_secs = 0\n");
+        assert_eq!(config.llm.circuit_failure_threshold, 3);
+        assert_eq!(
+            config.validate(),
+            Err(vec!["llm.circuit_cooldown_secs must be greater than 0 when the circuit breaker is enabled".to_string()])
+        );
+    }
+
+    #[test]
+    fn allowed_chats_must_be_chat_ids() {
+        let mut config = config();
+        assert!(config.allowed_chats.is_empty());
+
+        let result = with_env(&[("KARMASPARK_ALLOWED_CHATS", "group:abc, community:def,")], || config.apply_env_overrides());
+        assert_eq!(result, Ok(()));
+        assert_eq!(config.allowed_chats, vec!["group:abc".to_string(), "community:def".to_string()]);
+        assert_eq!(config.validate(), Ok(()));
+
+        config.allowed_chats.push("abc".to_string());
+        assert_eq!(
+            config.validate(),
+            Err(vec!["allowed_chats: 'abc' is not a chat id like group:<id>, channel:<community id>:<channel id>, community:<id> or direct:<id>".to_string()])
+        );
+    }
+
+    #[test]
+    fn markdown_is_on_unless_turned_off_per_chat() {
+        let default = config();
+        assert!(default.messages.markdown("group:abc"));
+
+        let config = config_with(
+            r#"
+[messages]
+plaintext_chats = ["group:abc", "community:def"]
+"#,
+        );
+        assert!(!config.messages.markdown("group:abc"));
+        assert!(!config.messages.markdown("channel:def:1"));
+        assert!(config.messages.markdown("group:xyz"));
+
+        let plain = config_with(
+            r#"
+[messages]
+markdown = false
+"#,
+        );
+        assert!(!plain.messages.markdown("group:xyz"));
+    }
+
+    #[test]
+    fn plaintext_chats_come_from_the_environment() {
+        let mut config = config();
+        let result = with_env(
+            &[("KARMASPARK_MESSAGES_PLAINTEXT_CHATS", "group:abc, group:def,")],
+            || config.apply_env_overrides(),
+        );
+
+        assert_eq!(result, Ok(()));
+        assert_eq!(config.messages.plaintext_chats, vec!["group:abc".to_string(), "group:def".to_string()]);
+        assert!(!config.messages.markdown("group:def"));
+    }
+}
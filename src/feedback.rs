@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Lowest and highest rating a user can give
+pub const MIN_RATING: u8 = 1;
+pub const MAX_RATING: u8 = 5;
+
+/// One piece of feedback left by a user
+#[derive(Debug, Clone)]
+pub struct FeedbackEntry {
+    pub user_id: String,
+    pub rating: u8,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Feedback received in a chat: how much, and the average rating
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FeedbackSummary {
+    pub count: u64,
+    // None while there is no feedback
+    pub average_rating: Option<f64>,
+}
+
+/// Whether `rating` is a whole number between MIN_RATING and MAX_RATING
+pub fn parse_rating(rating: f64) -> Option<u8> {
+    if rating.fract() != 0.0 || rating < MIN_RATING as f64 || rating > MAX_RATING as f64 {
+        return None;
+    }
+    Some(rating as u8)
+}
+
+/// Feedback users send about the bot, so admins can see what to improve
+#[derive(Debug, Clone)]
+pub struct FeedbackStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl FeedbackStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS feedback (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                rating INTEGER NOT NULL,
+                text TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn add(&self, chat_id: &str, user_id: &str, rating: u8, text: &str) -> Result<()> {
+        if !(MIN_RATING..=MAX_RATING).contains(&rating) {
+            return Err(anyhow!("Rating must be between {} and {}", MIN_RATING, MAX_RATING));
+        }
+
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let text = text.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO feedback (chat_id, user_id, rating, text, created_at)
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chat_id, user_id, rating, text, Utc::now().to_rfc3339()],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+
+    pub async fn summary(&self, chat_id: &str) -> Result<FeedbackSummary> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<FeedbackSummary> {
+            let conn = db.lock().unwrap();
+
+            let (count, average_rating): (i64, Option<f64>) = conn.query_row(
+                "SELECT COUNT(*), AVG(rating) FROM feedback WHERE chat_id = ?1",
+                params![chat_id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )?;
+
+            Ok(FeedbackSummary {
+                count: count as u64,
+                average_rating,
+            })
+        }).await?
+    }
+
+    /// The chat's most recent feedback, newest first
+    pub async fn recent(&self, chat_id: &str, limit: usize) -> Result<Vec<FeedbackEntry>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<FeedbackEntry>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT user_id, rating, text, created_at FROM feedback
+                 WHERE chat_id = ?1
+                 ORDER BY id DESC
+                 LIMIT ?2",
+            )?;
+            let rows = stmt
+                .query_map(params![chat_id, limit as i64], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, u8>(1)?,
+                        row.get::<_, String>(2)?,
+                        row.get::<_, String>(3)?,
+                    ))
+                })?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            rows.into_iter()
+                .map(|(user_id, rating, text, created_at)| {
+                    Ok(FeedbackEntry {
+                        user_id,
+                        rating,
+                        text,
+                        created_at: DateTime::parse_from_rfc3339(&created_at)?.with_timezone(&Utc),
+                    })
+                })
+                .collect()
+        }).await?
+    }
+
+    /// Delete all feedback the user left, in any chat; returns how many entries there were
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM feedback WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted)
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratings_are_whole_numbers_from_one_to_five() {
+        assert_eq!(parse_rating(1.0), Some(1));
+        assert_eq!(parse_rating(5.0), Some(5));
+        for rating in [0.0, 6.0, 3.5, -1.0, f64::NAN] {
+            assert_eq!(parse_rating(rating), None, "{}", rating);
+        }
+    }
+
+    #[tokio::test]
+    async fn stores_feedback_per_chat() {
+        let store = FeedbackStore::new(":memory:").unwrap();
+        store.add("group:1", "alice", 4, "Handy bot").await.unwrap();
+        store.add("group:1", "bob", 2, "Too slow").await.unwrap();
+        store.add("group:2", "carol", 5, "Great").await.unwrap();
+
+        let summary = store.summary("group:1").await.unwrap();
+        assert_eq!(summary.count, 2);
+        assert_eq!(summary.average_rating, Some(3.0));
+
+        let recent = store.recent("group:1", 10).await.unwrap();
+        let entries: Vec<(&str, u8, &str)> = recent
+            .iter()
+            .map(|entry| (entry.user_id.as_str(), entry.rating, entry.text.as_str()))
+            .collect();
+        assert_eq!(entries, vec![("bob", 2, "Too slow"), ("alice", 4, "Handy bot")]);
+
+        let empty = store.summary("group:3").await.unwrap();
+        assert_eq!((empty.count, empty.average_rating), (0, None));
+    }
+
+    #[tokio::test]
+    async fn rejects_ratings_out_of_range() {
+        let store = FeedbackStore::new(":memory:").unwrap();
+
+        let error = store.add("group:1", "alice", 6, "Off the scale").await.unwrap_err();
+        assert_eq!(error.to_string(), "Rating must be between 1 and 5");
+        assert_eq!(store.summary("group:1").await.unwrap().count, 0);
+    }
+}
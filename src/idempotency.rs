@@ -0,0 +1,138 @@
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::OnceCell;
+use tracing::info;
+
+use crate::cache::TtlCache;
+
+// How long a response is kept for answering retries of the same invocation
+const RESPONSE_TTL: Duration = Duration::from_secs(5 * 60);
+const MAX_TRACKED_INVOCATIONS: usize = 10_000;
+
+type Response = (StatusCode, Bytes);
+
+/// Remembers the response to each command invocation for a short window, so an
+/// `/execute` request OpenChat retries is answered without running the command twice
+pub struct IdempotencyGuard {
+    responses: TtlCache<String, Arc<OnceCell<Response>>>,
+}
+
+impl IdempotencyGuard {
+    pub fn new() -> Self {
+        Self {
+            responses: TtlCache::new(MAX_TRACKED_INVOCATIONS, RESPONSE_TTL),
+        }
+    }
+
+    /// Run `execute` for invocation `key` unless it already ran, returning its response either way.
+    /// A duplicate that arrives while the first request is still running waits for its response.
+    /// Responses asking OpenChat to retry later (429 and 5xx) are not kept, so the retry runs again.
+    pub async fn run<F>(&self, key: String, execute: F) -> Response
+    where
+        F: Future<Output = Response>,
+    {
+        let cell = self
+            .responses
+            .get_or_insert_with(key.clone(), || Arc::new(OnceCell::new()));
+
+        let mut executed = false;
+        let ran = &mut executed;
+        let response = cell
+            .get_or_init(|| async move {
+                *ran = true;
+                execute.await
+            })
+            .await
+            .clone();
+
+        if !executed {
+            info!("Returning the earlier response for duplicate invocation {}", key);
+            metrics::counter!("karmaspark_command_duplicates_total").increment(1);
+        } else if is_retryable(response.0) {
+            self.responses.remove(&key);
+        }
+
+        response
+    }
+}
+
+// Failures a retry of the same invocation may get past, such as rate limits and timeouts
+fn is_retryable(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+impl Default for IdempotencyGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    fn respond(runs: &AtomicUsize, status: StatusCode) -> impl Future<Output = Response> + '_ {
+        async move {
+            runs.fetch_add(1, Ordering::SeqCst);
+            (status, Bytes::from("done"))
+        }
+    }
+
+    #[tokio::test]
+    async fn runs_a_repeated_invocation_once() {
+        let guard = IdempotencyGuard::new();
+        let runs = AtomicUsize::new(0);
+
+        let first = guard.run("cmd-1".to_string(), respond(&runs, StatusCode::OK)).await;
+        let second = guard.run("cmd-1".to_string(), respond(&runs, StatusCode::OK)).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn a_concurrent_duplicate_waits_for_the_first() {
+        let guard = IdempotencyGuard::new();
+        let runs = AtomicUsize::new(0);
+        let slow = async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            respond(&runs, StatusCode::OK).await
+        };
+
+        let (first, second) = tokio::join!(
+            guard.run("cmd-1".to_string(), slow),
+            guard.run("cmd-1".to_string(), respond(&runs, StatusCode::OK)),
+        );
+
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+        assert_eq!(first, second);
+    }
+
+    #[tokio::test]
+    async fn different_invocations_each_run() {
+        let guard = IdempotencyGuard::new();
+        let runs = AtomicUsize::new(0);
+
+        guard.run("cmd-1".to_string(), respond(&runs, StatusCode::OK)).await;
+        guard.run("cmd-2".to_string(), respond(&runs, StatusCode::OK)).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn retryable_failures_run_again() {
+        let guard = IdempotencyGuard::new();
+        let runs = AtomicUsize::new(0);
+
+        guard.run("cmd-1".to_string(), respond(&runs, StatusCode::TOO_MANY_REQUESTS)).await;
+        guard.run("cmd-1".to_string(), respond(&runs, StatusCode::GATEWAY_TIMEOUT)).await;
+        let last = guard.run("cmd-1".to_string(), respond(&runs, StatusCode::OK)).await;
+
+        assert_eq!(runs.load(Ordering::SeqCst), 3);
+        assert_eq!(last.0, StatusCode::OK);
+    }
+}
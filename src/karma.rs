@@ -0,0 +1,228 @@
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// What happened when one user tried to give another a point
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GiveOutcome {
+    // The receiver's new total
+    Given { points: i64 },
+    // The giver already gave this user a point too recently
+    CoolingDown { remaining: Duration },
+}
+
+/// Reputation points per user, kept separately for each chat
+#[derive(Debug, Clone)]
+pub struct KarmaStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl KarmaStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS karma (
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                points INTEGER NOT NULL,
+                PRIMARY KEY (chat_id, user_id)
+            )",
+            [],
+        )?;
+
+        // When each giver last gave each receiver a point, for the cooldown
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS karma_gives (
+                chat_id TEXT NOT NULL,
+                giver_id TEXT NOT NULL,
+                receiver_id TEXT NOT NULL,
+                given_at TEXT NOT NULL,
+                PRIMARY KEY (chat_id, giver_id, receiver_id)
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Give `receiver_id` a point from `giver_id`, unless the giver did so within `cooldown`
+    pub async fn give(&self, chat_id: &str, giver_id: &str, receiver_id: &str, cooldown: Duration) -> Result<GiveOutcome> {
+        let chat_id = chat_id.to_string();
+        let giver_id = giver_id.to_string();
+        let receiver_id = receiver_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<GiveOutcome> {
+            let mut conn = db.lock().unwrap();
+            let tx = conn.transaction()?;
+            let now = Utc::now();
+
+            let last_given = tx.query_row(
+                "SELECT given_at FROM karma_gives WHERE chat_id = ?1 AND giver_id = ?2 AND receiver_id = ?3",
+                params![chat_id, giver_id, receiver_id],
+                |row| row.get::<_, String>(0),
+            );
+            let last_given = match last_given {
+                Ok(given_at) => Some(DateTime::parse_from_rfc3339(&given_at)?.with_timezone(&Utc)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => None,
+                Err(e) => return Err(e.into()),
+            };
+            if let Some(last_given) = last_given {
+                let remaining = last_given + cooldown - now;
+                if remaining > Duration::zero() {
+                    return Ok(GiveOutcome::CoolingDown { remaining });
+                }
+            }
+
+            tx.execute(
+                "INSERT INTO karma_gives (chat_id, giver_id, receiver_id, given_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(chat_id, giver_id, receiver_id) DO UPDATE SET given_at = excluded.given_at",
+                params![chat_id, giver_id, receiver_id, now.to_rfc3339()],
+            )?;
+            tx.execute(
+                "INSERT INTO karma (chat_id, user_id, points)
+                VALUES (?1, ?2, 1)
+                ON CONFLICT(chat_id, user_id) DO UPDATE SET points = points + 1",
+                params![chat_id, receiver_id],
+            )?;
+            let points: i64 = tx.query_row(
+                "SELECT points FROM karma WHERE chat_id = ?1 AND user_id = ?2",
+                params![chat_id, receiver_id],
+                |row| row.get(0),
+            )?;
+            tx.commit()?;
+
+            Ok(GiveOutcome::Given { points })
+        }).await?
+    }
+
+    /// A user's points in the chat; 0 if they never received any
+    pub async fn points(&self, chat_id: &str, user_id: &str) -> Result<i64> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<i64> {
+            let conn = db.lock().unwrap();
+
+            let result = conn.query_row(
+                "SELECT points FROM karma WHERE chat_id = ?1 AND user_id = ?2",
+                params![chat_id, user_id],
+                |row| row.get(0),
+            );
+
+            match result {
+                Ok(points) => Ok(points),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(0),
+                Err(e) => Err(e.into()),
+            }
+        }).await?
+    }
+
+    /// The chat's top users by points, highest first; ties go to whoever sorts first by id
+    pub async fn leaderboard(&self, chat_id: &str, limit: usize) -> Result<Vec<(String, i64)>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<(String, i64)>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT user_id, points FROM karma
+                 WHERE chat_id = ?1
+                 ORDER BY points DESC, user_id ASC
+                 LIMIT ?2",
+            )?;
+            let rows = stmt
+                .query_map(params![chat_id, limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(rows)
+        }).await?
+    }
+
+    /// Delete the user's points and every give they made or received, in all chats.
+    /// Points they gave others stay with the receivers.
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let mut conn = db.lock().unwrap();
+            let tx = conn.transaction()?;
+
+            let points = tx.execute("DELETE FROM karma WHERE user_id = ?1", params![user_id])?;
+            let gives = tx.execute(
+                "DELETE FROM karma_gives WHERE giver_id = ?1 OR receiver_id = ?1",
+                params![user_id],
+            )?;
+
+            tx.commit()?;
+            Ok(points + gives)
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn each_give_adds_a_point() {
+        let store = KarmaStore::new(":memory:").unwrap();
+
+        assert_eq!(store.points("group:1", "bob").await.unwrap(), 0);
+        assert_eq!(
+            store.give("group:1", "alice", "bob", Duration::zero()).await.unwrap(),
+            GiveOutcome::Given { points: 1 }
+        );
+        assert_eq!(
+            store.give("group:1", "carol", "bob", Duration::zero()).await.unwrap(),
+            GiveOutcome::Given { points: 2 }
+        );
+        assert_eq!(store.points("group:1", "bob").await.unwrap(), 2);
+        // Points are kept per chat
+        assert_eq!(store.points("group:2", "bob").await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn the_same_giver_cools_down() {
+        let store = KarmaStore::new(":memory:").unwrap();
+        let cooldown = Duration::hours(1);
+
+        store.give("group:1", "alice", "bob", cooldown).await.unwrap();
+        match store.give("group:1", "alice", "bob", cooldown).await.unwrap() {
+            GiveOutcome::CoolingDown { remaining } => {
+                assert!(remaining > Duration::minutes(59) && remaining <= cooldown, "{}", remaining);
+            }
+            outcome => panic!("expected a cooldown, got {:?}", outcome),
+        }
+
+        // Other givers, receivers and chats aren't affected
+        assert_eq!(store.give("group:1", "carol", "bob", cooldown).await.unwrap(), GiveOutcome::Given { points: 2 });
+        assert_eq!(store.give("group:1", "alice", "carol", cooldown).await.unwrap(), GiveOutcome::Given { points: 1 });
+        assert_eq!(store.give("group:2", "alice", "bob", cooldown).await.unwrap(), GiveOutcome::Given { points: 1 });
+        assert_eq!(store.points("group:1", "bob").await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn leaderboard_orders_by_points_then_id() {
+        let store = KarmaStore::new(":memory:").unwrap();
+        for (giver, receiver) in [("a", "dave"), ("b", "dave"), ("c", "dave"), ("a", "carol"), ("a", "bob"), ("b", "bob")] {
+            store.give("group:1", giver, receiver, Duration::zero()).await.unwrap();
+        }
+        store.give("group:2", "a", "erin", Duration::zero()).await.unwrap();
+
+        assert_eq!(
+            store.leaderboard("group:1", 10).await.unwrap(),
+            vec![("dave".to_string(), 3), ("bob".to_string(), 2), ("carol".to_string(), 1)]
+        );
+        assert_eq!(store.leaderboard("group:1", 2).await.unwrap().len(), 2);
+    }
+}
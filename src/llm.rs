@@ -1,22 +1,49 @@
 use anyhow::{anyhow, Result};
-use async_openai::{
-    config::OpenAIConfig,
-    types::{
-        ChatCompletionRequestMessage, CreateChatCompletionRequest, Role,
-    },
-    Client,
-};
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use regex::Regex;
+use reqwest::header::{HeaderMap, RETRY_AFTER};
+use reqwest::StatusCode;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::time::Duration;
+use std::collections::hash_map::DefaultHasher;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use tokio::time::sleep;
-use tracing::{error, info, warn};
+use tracing::{debug, error, info, warn};
 
+use crate::cache::TtlCache;
+use crate::circuit_breaker::CircuitBreaker;
 use crate::memory::EmbeddingModel;
+use crate::usage::{UsageContext, UsageStore};
+
+pub const MISTRAL_API_URL: &str = "https://api.mistral.ai/v1";
+pub const DEFAULT_CHAT_MODEL: &str = "mistral-medium";
+pub const DEFAULT_EMBEDDING_MODEL: &str = "mistral-embed";
+const DEFAULT_MAX_RETRIES: usize = 3;
+const DEFAULT_RETRY_DELAY_MS: u64 = 1000;
+// Upper bound on how long we are willing to wait between retries
+const MAX_RETRY_DELAY_SECS: u64 = 60;
+// Rough size of the chunks long documents are split into before summarizing
+const SUMMARY_CHUNK_TOKENS: usize = 3000;
+// Crude token estimate, good enough for sizing chunks
+const CHARS_PER_TOKEN: usize = 4;
+// Map rounds before the partial summaries are combined regardless of size
+const MAX_SUMMARY_ROUNDS: usize = 3;
 
-const MISTRAL_API_URL: &str = "https://api.mistral.ai/v1";
-const MAX_RETRIES: usize = 3;
-const RETRY_DELAY_MS: u64 = 1000;
+/// Rough token count for Mistral models: about four ASCII characters per token, while
+/// other scripts (accents, CJK, emoji) tend to cost a token per character or more.
+pub fn estimate_tokens(text: &str) -> usize {
+    let ascii = text.chars().filter(char::is_ascii).count();
+    let other = text.chars().count() - ascii;
+    ascii.div_ceil(CHARS_PER_TOKEN) + other
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ChatMessage {
@@ -24,150 +51,741 @@ pub struct ChatMessage {
     pub content: String,
 }
 
+#[derive(Debug, Serialize)]
+struct ChatCompletionRequest<'a> {
+    model: &'a str,
+    messages: Vec<ChatMessage>,
+    temperature: f32,
+    top_p: f32,
+    max_tokens: u32,
+    stream: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    tools: Vec<ToolSpec<'a>>,
+}
+
+// Chat request whose messages are made of content parts, so one can carry an image
+#[derive(Debug, Serialize)]
+struct MultimodalRequest<'a> {
+    model: &'a str,
+    messages: Vec<MultimodalMessage<'a>>,
+    temperature: f32,
+    top_p: f32,
+    max_tokens: u32,
+    stream: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct MultimodalMessage<'a> {
+    role: &'a str,
+    content: Vec<ContentPart<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ContentPart<'a> {
+    Text { text: &'a str },
+    ImageUrl { image_url: &'a str },
+}
+
+// The conversation as content parts, attaching the image to the last user message
+fn multimodal_messages<'a>(messages: &'a [ChatMessage], image_url: &'a str) -> Vec<MultimodalMessage<'a>> {
+    let last_user = messages.iter().rposition(|message| message.role == "user");
+    
+    messages
+        .iter()
+        .enumerate()
+        .map(|(i, message)| {
+            let mut content = vec![ContentPart::Text { text: &message.content }];
+            if Some(i) == last_user {
+                content.push(ContentPart::ImageUrl { image_url });
+            }
+            MultimodalMessage {
+                role: &message.role,
+                content,
+            }
+        })
+        .collect()
+}
+
+// Wire format of a tool offered to the model
+#[derive(Debug, Serialize)]
+struct ToolSpec<'a> {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    function: &'a ToolDefinition,
+}
+
+/// A function the model may call, with a JSON Schema for its parameters
+#[derive(Debug, Clone, Serialize)]
+pub struct ToolDefinition {
+    pub name: String,
+    pub description: String,
+    pub parameters: serde_json::Value,
+}
+
+/// A tool invocation requested by the model
 #[derive(Debug, Clone)]
-pub struct MistralClient {
-    client: Client<OpenAIConfig>,
-    model: String,
+pub struct ToolCall {
+    pub name: String,
+    pub arguments: serde_json::Value,
 }
 
-impl MistralClient {
-    pub fn new(api_key: &str) -> Self {
-        let config = OpenAIConfig::new()
-            .with_api_key(api_key)
-            .with_api_base(MISTRAL_API_URL);
-        
-        let client = Client::with_config(config);
-        
+/// Reply to a chat request that offered tools: either plain text or tool calls
+#[derive(Debug, Clone)]
+pub enum ToolReply {
+    Text(String),
+    ToolCalls(Vec<ToolCall>),
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatCompletionResponse {
+    choices: Vec<ChatChoice>,
+    usage: Option<TokenUsage>,
+}
+
+/// Token counts reported by the API for a single completion
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TokenUsage {
+    pub prompt_tokens: u32,
+    pub completion_tokens: u32,
+    pub total_tokens: u32,
+}
+
+/// A completion together with the tokens it cost. `usage` is `None` when the
+/// API did not report it or the response was served from cache.
+#[derive(Debug, Clone)]
+pub struct ChatResult {
+    pub content: String,
+    pub usage: Option<TokenUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatChoice {
+    message: ChatResponseMessage,
+}
+
+#[derive(Debug, Deserialize)]
+struct ChatResponseMessage {
+    content: Option<String>,
+    #[serde(default)]
+    tool_calls: Option<Vec<RawToolCall>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawToolCall {
+    function: RawFunctionCall,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawFunctionCall {
+    name: String,
+    // Usually a JSON-encoded string, though some models return an object
+    arguments: serde_json::Value,
+}
+
+impl From<RawToolCall> for ToolCall {
+    fn from(call: RawToolCall) -> Self {
+        let arguments = match call.function.arguments {
+            serde_json::Value::String(raw) => {
+                serde_json::from_str(&raw).unwrap_or(serde_json::Value::String(raw))
+            }
+            other => other,
+        };
         Self {
-            client,
-            model: "mistral-medium".to_string(), // Default model
+            name: call.function.name,
+            arguments,
         }
     }
-    
-    pub fn with_model(mut self, model: &str) -> Self {
-        self.model = model.to_string();
-        self
+}
+
+#[derive(Debug, Serialize)]
+struct EmbeddingRequest<'a> {
+    model: &'a str,
+    input: Vec<&'a str>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingResponse {
+    data: Vec<EmbeddingData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct EmbeddingData {
+    embedding: Vec<f32>,
+}
+
+/// Failures talking to the LLM API. Returned inside `anyhow::Error`; use
+/// `is_rate_limited` to check for the case callers treat specially.
+#[derive(Debug, thiserror::Error)]
+pub enum LlmError {
+    #[error("Rate limit exceeded. Please try again in a few minutes.")]
+    RateLimited,
+    #[error("API error after {retries} retries: {status} {body}")]
+    Api {
+        status: StatusCode,
+        body: String,
+        retries: usize,
+    },
+    #[error("Request to Mistral API failed: {0}")]
+    Request(#[source] reqwest::Error),
+    #[error("Invalid response from Mistral API: {0}")]
+    InvalidResponse(#[source] reqwest::Error),
+    #[error("The LLM service is temporarily unavailable. Please try again in a minute.")]
+    Unavailable,
+}
+
+impl LlmError {
+    // Failures that suggest the API is down rather than that our request was bad
+    fn is_outage(&self) -> bool {
+        match self {
+            LlmError::Request(_) => true,
+            LlmError::Api { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+}
+
+/// Whether `error` is, or was caused by, the API rate-limiting us after all retries
+pub fn is_rate_limited(error: &anyhow::Error) -> bool {
+    error
+        .chain()
+        .any(|cause| matches!(cause.downcast_ref::<LlmError>(), Some(LlmError::RateLimited)))
+}
+
+// Whether another model might succeed where this error's failed. Rate limits and an open
+// circuit breaker apply to the whole API, so switching models wouldn't help.
+fn worth_another_model(error: &anyhow::Error) -> bool {
+    !error.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<LlmError>(),
+            Some(LlmError::RateLimited | LlmError::Unavailable)
+        )
+    })
+}
+
+/// How rate-limited requests are retried
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    // Retries after the first attempt; 0 disables retrying
+    pub max_retries: usize,
+    // Base of the exponential backoff window
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: DEFAULT_MAX_RETRIES,
+            base_delay: Duration::from_millis(DEFAULT_RETRY_DELAY_MS),
+        }
+    }
+}
+
+/// Rate-limit retries shared by every LLM call made within one scope, such as an agent
+/// run, so backoff can't pile up call after call. Calls outside a scope only follow
+/// their client's `RetryPolicy`.
+#[derive(Debug)]
+pub struct RetryBudget {
+    retries_left: AtomicUsize,
+    // No retry may still be backing off past this
+    deadline: Instant,
+}
+
+tokio::task_local! {
+    static CURRENT_BUDGET: Arc<RetryBudget>;
+}
+
+impl RetryBudget {
+    pub fn new(max_retries: usize, max_wait: Duration) -> Self {
+        Self {
+            retries_left: AtomicUsize::new(max_retries),
+            deadline: Instant::now() + max_wait,
+        }
+    }
+
+    /// Run `fut` with this budget attached, so LLM calls inside it draw on it
+    pub async fn scope<F: Future>(self: Arc<Self>, fut: F) -> F::Output {
+        CURRENT_BUDGET.scope(self, fut).await
+    }
+
+    // Take one retry from the current budget, if any, for a wait of `backoff`. Once the
+    // budget is used up, or the wait would overrun it, the caller should give up.
+    fn allows_retry(backoff: Duration) -> bool {
+        CURRENT_BUDGET
+            .try_with(|budget| {
+                Instant::now() + backoff <= budget.deadline
+                    && budget
+                        .retries_left
+                        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |left| left.checked_sub(1))
+                        .is_ok()
+            })
+            .unwrap_or(true)
+    }
+}
+
+// HTTP transport shared by the chat and embedding clients, with rate-limit retries
+#[derive(Debug, Clone)]
+struct ApiClient {
+    http: reqwest::Client,
+    api_key: String,
+    base_url: String,
+    retry: RetryPolicy,
+    // When the API last answered with 429
+    last_rate_limited: Arc<Mutex<Option<Instant>>>,
+    // Caps requests in flight; shared between clients so the limit is global
+    concurrency: Option<Arc<Semaphore>>,
+    // Fails requests fast while the API looks down
+    breaker: Option<Arc<CircuitBreaker>>,
+}
+
+impl ApiClient {
+    fn new(api_key: &str, base_url: &str) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            api_key: api_key.to_string(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry: RetryPolicy::default(),
+            last_rate_limited: Arc::new(Mutex::new(None)),
+            concurrency: None,
+            breaker: None,
+        }
     }
     
-    pub async fn chat(
-        &self,
-        system_prompt: &str,
-        messages: &[ChatMessage],
-    ) -> Result<String> {
-        // Convert messages to OpenAI format
-        let mut chat_messages: Vec<ChatCompletionRequestMessage> = Vec::new();
-        
-        // Add system message
-        chat_messages.push(ChatCompletionRequestMessage {
-            content: Some(system_prompt.to_string()),
-            name: None,
-            role: Role::System,
-            function_call: None,
-        });
-        
-        // Add user/assistant messages
-        for msg in messages {
-            match msg.role.as_str() {
-                "user" => {
-                    chat_messages.push(ChatCompletionRequestMessage {
-                        content: Some(msg.content.clone()),
-                        name: None,
-                        role: Role::User,
-                        function_call: None,
-                    });
-                }
-                "assistant" => {
-                    chat_messages.push(ChatCompletionRequestMessage {
-                        content: Some(msg.content.clone()),
-                        name: None,
-                        role: Role::Assistant,
-                        function_call: None,
-                    });
-                }
-                _ => {
-                    return Err(anyhow!("Unsupported message role: {}", msg.role));
-                }
+    fn rate_limited_within(&self, window: Duration) -> bool {
+        self.last_rate_limited
+            .lock()
+            .unwrap()
+            .is_some_and(|at| at.elapsed() <= window)
+    }
+
+    async fn post<T: Serialize, R: DeserializeOwned>(&self, path: &str, body: &T) -> Result<R> {
+        if let Some(breaker) = &self.breaker {
+            if !breaker.allow() {
+                metrics::counter!("karmaspark_llm_errors_total", "endpoint" => path.to_string()).increment(1);
+                return Err(LlmError::Unavailable.into());
             }
         }
         
-        // Create request
-        let request = CreateChatCompletionRequest {
-            model: self.model.clone(),
-            messages: chat_messages,
-            temperature: Some(0.7),
-            top_p: Some(0.95),
-            max_tokens: Some(1024),
-            stream: Some(false),
-            frequency_penalty: Some(0.0),
-            presence_penalty: Some(0.0),
-            ..Default::default()
-        };
+        let started = Instant::now();
+        let result = self.post_with_retry(path, body).await;
         
-        // Send request with retry logic
+        if let Some(breaker) = &self.breaker {
+            let outage = result
+                .as_ref()
+                .err()
+                .and_then(|e| e.downcast_ref::<LlmError>())
+                .is_some_and(LlmError::is_outage);
+            if outage {
+                breaker.record_failure();
+            } else {
+                breaker.record_success();
+            }
+        }
+
+        metrics::histogram!("karmaspark_llm_request_duration_seconds", "endpoint" => path.to_string())
+            .record(started.elapsed().as_secs_f64());
+        if result.is_err() {
+            metrics::counter!("karmaspark_llm_errors_total", "endpoint" => path.to_string()).increment(1);
+        }
+
+        result
+    }
+
+    async fn post_with_retry<T: Serialize, R: DeserializeOwned>(&self, path: &str, body: &T) -> Result<R> {
+        let url = format!("{}/{}", self.base_url, path);
         let mut retries = 0;
-        let mut last_error = None;
-        
-        while retries < MAX_RETRIES {
-            match self.client.chat().create(request.clone()).await {
-                Ok(response) => {
-                    // Extract response
-                    let choice = response
-                        .choices
-                        .first()
-                        .ok_or_else(|| anyhow!("No choices in response"))?;
-                    
-                    let content = choice
-                        .message
-                        .content
+
+        loop {
+            // Wait for a free slot; it is released while backing off so others can proceed
+            let permit = match &self.concurrency {
+                Some(semaphore) => Some(
+                    semaphore
                         .clone()
-                        .unwrap_or_default();
-                    
-                    return Ok(content);
-                },
-                Err(e) => {
-                    let error_string = e.to_string();
-                    
-                    // Check for rate limit errors
-                    if error_string.contains("rate limit") || error_string.contains("Requests rate limit exceeded") {
+                        .acquire_owned()
+                        .await
+                        .map_err(|e| anyhow!("Request slot unavailable: {}", e))?,
+                ),
+                None => None,
+            };
+            
+            // Self-hosted endpoints may not need a key
+            let mut request = self.http.post(&url);
+            if !self.api_key.is_empty() {
+                request = request.bearer_auth(&self.api_key);
+            }
+            let response = request
+                .json(body)
+                .send()
+                .await
+                .map_err(LlmError::Request)?;
+
+            let status = response.status();
+
+            // Check for rate limit errors
+            if status == StatusCode::TOO_MANY_REQUESTS {
+                *self.last_rate_limited.lock().unwrap() = Some(Instant::now());
+                if retries < self.retry.max_retries {
+                    // Prefer the server's Retry-After hint over our own exponential guess
+                    let backoff = retry_after_delay(response.headers(), Utc::now())
+                        .unwrap_or_else(|| jittered_backoff(self.retry.base_delay, retries + 1));
+                    if RetryBudget::allows_retry(backoff) {
+                        drop(permit);
                         retries += 1;
-                        if retries < MAX_RETRIES {
-                            let backoff = RETRY_DELAY_MS * (2_u64.pow(retries as u32));
-                            info!("Rate limit exceeded, retrying in {}ms (attempt {}/{})", 
-                                  backoff, retries, MAX_RETRIES);
-                            sleep(Duration::from_millis(backoff)).await;
-                            continue;
-                        } else {
-                            error!("Rate limit exceeded after {} retries: {}", retries, error_string);
-                            return Err(anyhow!("Rate limit exceeded. Please try again in a few minutes."));
-                        }
+                        info!("Rate limit exceeded on /{}, retrying in {}ms (attempt {}/{})",
+                              path, backoff.as_millis(), retries, self.retry.max_retries);
+                        sleep(backoff).await;
+                        continue;
                     }
-                    
-                    // For other errors
-                    error!("Error from Mistral API: {}", error_string);
-                    last_error = Some(e);
-                    break;
+                    info!("Rate limit exceeded on /{} and the retry budget is used up", path);
                 }
+                error!("Rate limit exceeded on /{} after {} retries", path, retries);
+                return Err(LlmError::RateLimited.into());
+            }
+
+            // For other errors
+            if !status.is_success() {
+                let error_body = response.text().await.unwrap_or_default();
+                error!("Error from Mistral API on /{} ({}): {}", path, status, error_body);
+                return Err(LlmError::Api {
+                    status,
+                    body: error_body,
+                    retries,
+                }.into());
+            }
+
+            return response
+                .json::<R>()
+                .await
+                .map_err(|e| LlmError::InvalidResponse(e).into());
+        }
+    }
+}
+
+// Exponential backoff with full jitter, so concurrent callers don't retry in lockstep
+fn jittered_backoff(base_delay: Duration, retries: usize) -> Duration {
+    let window_ms = (base_delay.as_millis() as u64)
+        .saturating_mul(2_u64.saturating_pow(retries as u32))
+        .min(MAX_RETRY_DELAY_SECS * 1000);
+    Duration::from_millis(rand::thread_rng().gen_range(0..=window_ms))
+}
+
+// Parse a Retry-After header given either as delay-seconds or as an HTTP-date,
+// capped at MAX_RETRY_DELAY_SECS
+fn retry_after_delay(headers: &HeaderMap, now: DateTime<Utc>) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?.trim();
+
+    let seconds = match value.parse::<u64>() {
+        Ok(seconds) => seconds,
+        Err(_) => {
+            let date = DateTime::parse_from_rfc2822(value).ok()?;
+            (date.with_timezone(&Utc) - now).num_seconds().max(0) as u64
+        }
+    };
+
+    Some(Duration::from_secs(seconds.min(MAX_RETRY_DELAY_SECS)))
+}
+
+// Upper bound on a requested number of bullet points
+pub const MAX_SUMMARY_POINTS: usize = 20;
+
+/// How long a summary should be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryLength {
+    Short,
+    Medium,
+    Long,
+    // An exact number of bullet points
+    Points(usize),
+}
+
+impl FromStr for SummaryLength {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "short" => Ok(SummaryLength::Short),
+            "medium" => Ok(SummaryLength::Medium),
+            "long" => Ok(SummaryLength::Long),
+            other => match other.parse::<usize>() {
+                Ok(points) if (1..=MAX_SUMMARY_POINTS).contains(&points) => Ok(SummaryLength::Points(points)),
+                _ => Err(format!(
+                    "Length must be short, medium, long or a number of bullet points from 1 to {}",
+                    MAX_SUMMARY_POINTS
+                )),
+            },
+        }
+    }
+}
+
+/// How a summary should be laid out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SummaryStyle {
+    Bullets,
+    Paragraph,
+    TlDr,
+}
+
+impl FromStr for SummaryStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "bullets" => Ok(SummaryStyle::Bullets),
+            "paragraph" => Ok(SummaryStyle::Paragraph),
+            "tldr" | "tl;dr" => Ok(SummaryStyle::TlDr),
+            _ => Err("Style must be bullets, paragraph or tldr".to_string()),
+        }
+    }
+}
+
+/// What is being summarized, which decides the prompt
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SummaryContent {
+    #[default]
+    Prose,
+    // Source code, described by what it does rather than condensed
+    Code,
+}
+
+// Texts shorter than this many non-blank lines are always treated as prose
+const MIN_CODE_LINES: usize = 3;
+// Share of non-whitespace characters that are these symbols above which text reads as code
+const CODE_SYMBOLS: &str = "{}()[];=<>&|*/\\";
+const CODE_SYMBOL_DENSITY: f64 = 0.1;
+
+impl SummaryContent {
+    /// Guess whether `text` is mostly source code: most of its lines are inside code
+    /// fences or look like code, or it is dense with symbols and a fair share of lines look like code
+    pub fn detect(text: &str) -> Self {
+        let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+        if lines.len() < MIN_CODE_LINES {
+            return SummaryContent::Prose;
+        }
+        
+        let mut in_fence = false;
+        let mut fenced = 0;
+        for line in &lines {
+            if line.starts_with("```") || line.starts_with("~~~") {
+                in_fence = !in_fence;
+                fenced += 1;
+            } else if in_fence {
+                fenced += 1;
             }
         }
+        if fenced * 2 > lines.len() {
+            return SummaryContent::Code;
+        }
+        
+        let code_lines = lines.iter().filter(|line| looks_like_code(line)).count();
+        let visible = text.chars().filter(|c| !c.is_whitespace()).count();
+        let symbols = text.chars().filter(|c| CODE_SYMBOLS.contains(*c)).count();
+        let dense = symbols as f64 > visible as f64 * CODE_SYMBOL_DENSITY;
+        
+        if code_lines * 2 > lines.len() || (dense && code_lines * 4 > lines.len()) {
+            SummaryContent::Code
+        } else {
+            SummaryContent::Prose
+        }
+    }
+}
+
+// Line endings and openings that prose rarely has
+fn looks_like_code(line: &str) -> bool {
+    const KEYWORDS: &[&str] = &[
+        "fn ", "pub ", "use ", "let ", "const ", "var ", "def ", "class ", "import ", "from ",
+        "function ", "return ", "package ", "public ", "private ", "#include", "if (", "for (",
+        "while (", "//", "/*", "#!",
+    ];
+    line.ends_with([';', '{', '}', ')', ']']) || KEYWORDS.iter().any(|keyword| line.starts_with(keyword))
+}
+
+/// Shape of the summary to produce. The default leaves it to the model,
+/// which gives the plain concise summary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SummaryOptions {
+    pub length: Option<SummaryLength>,
+    pub style: Option<SummaryStyle>,
+    pub content: SummaryContent,
+}
+
+impl SummaryOptions {
+    // Extra instructions appended to the summarization prompts
+    fn instructions(&self) -> String {
+        let mut instructions = Vec::new();
+        
+        match self.length {
+            Some(SummaryLength::Short) => instructions.push("Keep it very short: one or two sentences.".to_string()),
+            Some(SummaryLength::Medium) => instructions.push("Keep it to about one paragraph of three to five sentences.".to_string()),
+            Some(SummaryLength::Long) => instructions.push("Write a detailed summary of several paragraphs that covers every main point.".to_string()),
+            Some(SummaryLength::Points(points)) => instructions.push(format!("Use exactly {} bullet points.", points)),
+            None => {}
+        }
+        
+        match self.style {
+            Some(SummaryStyle::Bullets) => instructions.push("Format the summary as a bulleted list.".to_string()),
+            Some(SummaryStyle::Paragraph) => instructions.push("Write the summary as flowing prose without lists.".to_string()),
+            Some(SummaryStyle::TlDr) => instructions.push("Start with a single line beginning \"TL;DR:\" that gives the gist, then add any essential detail.".to_string()),
+            None => {}
+        }
         
-        // If we got here, all retries failed
-        Err(anyhow!("API error after {} retries: {}", 
-                    retries, 
-                    last_error.map_or("Unknown error".to_string(), |e| e.to_string())))
+        instructions.join(" ")
+    }
+    
+    /// System prompt for summarizing a single piece of text
+    pub fn summary_prompt(&self) -> String {
+        let prompt = match self.content {
+            SummaryContent::Prose => "You are a highly efficient text summarizer. Create a concise summary of the following text while retaining the key points.",
+            SummaryContent::Code => "You are an experienced software engineer. Explain concisely what the following source code does: its purpose, its main parts and how they fit together, and notable inputs, outputs and side effects. Describe its behavior rather than restating it line by line.",
+        };
+        with_instructions(prompt, &self.instructions())
+    }
+    
+    /// System prompt for merging the partial summaries of a long document
+    pub fn combine_prompt(&self) -> String {
+        let prompt = match self.content {
+            SummaryContent::Prose => "You are a highly efficient text summarizer. The following are summaries of consecutive parts of one long document. Combine them into a single concise, coherent summary that retains the key points.",
+            SummaryContent::Code => "You are an experienced software engineer. The following describe consecutive parts of one long piece of source code. Combine them into a single concise explanation of what the code does as a whole.",
+        };
+        with_instructions(prompt, &self.instructions())
+    }
+    
+    /// System prompt for folding new messages into an earlier summary of the same conversation
+    pub fn update_prompt(&self) -> String {
+        with_instructions(
+            "You are a highly efficient text summarizer. You are given an existing summary of a conversation followed by the messages posted since. Write a single updated, concise summary of the whole conversation that keeps the key points of both.",
+            &self.instructions(),
+        )
+    }
+}
+
+// User message for `LlmProvider::update_summary`: the earlier summary, then only the new messages
+fn update_summary_input(previous_summary: &str, new_text: &str) -> String {
+    format!("Existing summary:\n{}\n\nNew messages:\n{}", previous_summary, new_text)
+}
+
+fn with_instructions(prompt: &str, instructions: &str) -> String {
+    if instructions.is_empty() {
+        prompt.to_string()
+    } else {
+        format!("{} {}", prompt, instructions)
+    }
+}
+
+/// A chat-completion backend. Commands and the agent only depend on this trait,
+/// so the Mistral client can be swapped for `MockLlm` in development.
+#[async_trait]
+pub trait LlmProvider: Send + Sync {
+    /// Complete a conversation, returning the reply and the token usage it cost
+    async fn chat_with_usage(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+    ) -> Result<ChatResult>;
+    
+    /// Whether the backend rate-limited us within the given window
+    fn rate_limited_within(&self, _window: Duration) -> bool {
+        false
+    }
+    
+    /// Complete a conversation whose last user message comes with an image. Backends
+    /// without a vision model refuse rather than answer without seeing it.
+    async fn chat_with_image(
+        &self,
+        _system_prompt: &str,
+        _messages: &[ChatMessage],
+        _image_url: &str,
+    ) -> Result<String> {
+        Err(anyhow!("This model can't read images"))
+    }
+    
+    /// Complete a conversation, letting the model call one of `tools`.
+    /// Backends without tool support just answer in text.
+    async fn chat_with_tools(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+        _tools: &[ToolDefinition],
+    ) -> Result<ToolReply> {
+        Ok(ToolReply::Text(self.chat(system_prompt, messages).await?))
+    }
+    
+    async fn chat(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+    ) -> Result<String> {
+        Ok(self.chat_with_usage(system_prompt, messages).await?.content)
     }
     
-    pub async fn summarize(&self, text: &str) -> Result<String> {
-        let system_prompt = "You are a highly efficient text summarizer. Create a concise summary of the following text while retaining the key points.";
+    async fn summarize(&self, text: &str, options: &SummaryOptions) -> Result<String> {
+        let system_prompt = options.summary_prompt();
         
         let messages = vec![ChatMessage {
             role: "user".to_string(),
             content: text.to_string(),
         }];
         
-        self.chat(system_prompt, &messages).await
+        self.chat(&system_prompt, &messages).await
+    }
+    
+    /// Bring `previous_summary` up to date with `new_text`, without re-reading what it already covers
+    async fn update_summary(&self, previous_summary: &str, new_text: &str, options: &SummaryOptions) -> Result<String> {
+        let system_prompt = options.update_prompt();
+        
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: update_summary_input(previous_summary, new_text),
+        }];
+        
+        self.chat(&system_prompt, &messages).await
+    }
+    
+    /// Summarize text too long for a single request: summarize token-bounded chunks,
+    /// then combine the partial summaries in a final call
+    async fn summarize_long(&self, text: &str, options: &SummaryOptions) -> Result<String> {
+        let max_chunk_chars = SUMMARY_CHUNK_TOKENS * CHARS_PER_TOKEN;
+        
+        let mut chunks = split_into_chunks(text, max_chunk_chars);
+        if chunks.len() <= 1 {
+            return self.summarize(text, options).await;
+        }
+        
+        // Map: summarize each chunk, repeating while the summaries still don't fit in one request
+        let mut round = 0;
+        let combined = loop {
+            round += 1;
+            info!("Summarizing {} chunks (round {})", chunks.len(), round);
+            
+            let mut partials = Vec::with_capacity(chunks.len());
+            for (i, chunk) in chunks.iter().enumerate() {
+                // Partial summaries keep their default shape; options apply to the combined one
+                let partial_options = SummaryOptions {
+                    content: options.content,
+                    ..SummaryOptions::default()
+                };
+                partials.push(self.summarize(chunk, &partial_options).await?);
+                info!("Summarized chunk {}/{}", i + 1, chunks.len());
+            }
+            
+            let combined = partials.join("\n\n");
+            if combined.len() <= max_chunk_chars || round >= MAX_SUMMARY_ROUNDS {
+                break combined;
+            }
+            chunks = split_into_chunks(&combined, max_chunk_chars);
+        };
+        
+        // Reduce: merge the partial summaries into one
+        let system_prompt = options.combine_prompt();
+        
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: combined,
+        }];
+        
+        self.chat(&system_prompt, &messages).await
     }
     
-    pub async fn moderate(&self, text: &str) -> Result<(bool, String)> {
+    async fn moderate(&self, text: &str) -> Result<(bool, String)> {
         let system_prompt = "You are a content moderation system. Analyze the following text for any harmful, offensive, or inappropriate content. If you find such content, respond with 'FLAGGED: <reason>'. If the content is safe, respond with 'SAFE'.";
         
         let messages = vec![ChatMessage {
@@ -182,98 +800,1525 @@ impl MistralClient {
     }
 }
 
-// Implementation of embedding model using Mistral API
-pub struct MistralEmbedding {
-    client: Client<OpenAIConfig>,
-    model: String,
+// Split text into chunks of at most `max_chars` bytes, preferring paragraph
+// and then word boundaries, and only cutting inside a word when it alone is too long
+pub(crate) fn split_into_chunks(text: &str, max_chars: usize) -> Vec<String> {
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+    
+    let mut push_piece = |piece: &str, separator: &str, current: &mut String| {
+        if !current.is_empty() && current.len() + separator.len() + piece.len() > max_chars {
+            chunks.push(std::mem::take(current));
+        }
+        if !current.is_empty() {
+            current.push_str(separator);
+        }
+        current.push_str(piece);
+    };
+    
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        if paragraph.len() <= max_chars {
+            push_piece(paragraph, "\n\n", &mut current);
+            continue;
+        }
+        
+        for word in paragraph.split_whitespace() {
+            if word.len() <= max_chars {
+                push_piece(word, " ", &mut current);
+                continue;
+            }
+            
+            // Cut an oversized word on char boundaries
+            let mut piece = String::new();
+            for c in word.chars() {
+                if piece.len() + c.len_utf8() > max_chars {
+                    push_piece(&piece, " ", &mut current);
+                    piece.clear();
+                }
+                piece.push(c);
+            }
+            push_piece(&piece, " ", &mut current);
+        }
+    }
+    
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
 }
 
-impl MistralEmbedding {
-    pub fn new(api_key: &str) -> Self {
-        let config = OpenAIConfig::new()
-            .with_api_key(api_key)
-            .with_api_base(MISTRAL_API_URL);
-        
-        let client = Client::with_config(config);
-        
-        Self {
-            client,
-            model: "mistral-embed".to_string(),
+impl ChatCompletionRequest<'_> {
+    // Cache key covering everything that affects the completion
+    fn cache_key(&self) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        self.model.hash(&mut hasher);
+        self.temperature.to_bits().hash(&mut hasher);
+        self.top_p.to_bits().hash(&mut hasher);
+        self.max_tokens.hash(&mut hasher);
+        for message in &self.messages {
+            message.role.hash(&mut hasher);
+            message.content.hash(&mut hasher);
         }
+        hasher.finish()
     }
 }
 
-#[async_trait]
-impl EmbeddingModel for MistralEmbedding {
-    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
-        let request = async_openai::types::CreateEmbeddingRequest {
-            model: self.model.clone(),
-            input: async_openai::types::EmbeddingInput::String(text.to_string()),
-            user: None,
-        };
+// Patterns masked in logged prompts: email addresses, bearer tokens, well-known
+// API key prefixes and long opaque strings that are likely secrets
+static SECRET_PATTERNS: LazyLock<Vec<(Regex, &'static str)>> = LazyLock::new(|| {
+    vec![
+        (Regex::new(r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}").unwrap(), "[email]"),
+        (Regex::new(r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]+").unwrap(), "Bearer [token]"),
+        (Regex::new(r"\b(?:sk|pk|rk|ghp|gho|ghs|xox[abpr])[-_][A-Za-z0-9_-]{10,}").unwrap(), "[token]"),
+        (Regex::new(r"\b[A-Za-z0-9_-]{32,}\b").unwrap(), "[token]"),
+    ]
+});
+
+// Mask email addresses and things that look like API keys or tokens
+fn redact_secrets(text: &str) -> String {
+    SECRET_PATTERNS
+        .iter()
+        .fold(text.to_string(), |text, (pattern, replacement)| {
+            pattern.replace_all(&text, *replacement).into_owned()
+        })
+}
+
+// How much of each prompt to log at DEBUG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PromptLogging {
+    Off,
+    Redacted,
+    Full,
+}
+
+#[derive(Clone)]
+pub struct MistralClient {
+    api: ApiClient,
+    model: String,
+    // Tried in order when `model` fails, for chat and tool-calling requests
+    fallback_models: Vec<String>,
+    cache: Option<Arc<TtlCache<u64, String>>>,
+    usage_store: Option<Arc<UsageStore>>,
+    // Model used for requests with an image; None when image input is disabled
+    vision_model: Option<String>,
+    prompt_logging: PromptLogging,
+    // Put before every system prompt, e.g. a compliance disclaimer; empty adds nothing
+    system_prompt_prefix: String,
+}
+
+impl MistralClient {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api: ApiClient::new(api_key, MISTRAL_API_URL),
+            model: DEFAULT_CHAT_MODEL.to_string(),
+            fallback_models: Vec::new(),
+            cache: None,
+            usage_store: None,
+            vision_model: None,
+            prompt_logging: PromptLogging::Off,
+            system_prompt_prefix: String::new(),
+        }
+    }
+    
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self
+    }
+    
+    /// Models to try, in order, when the main one fails with anything but a rate limit
+    pub fn with_fallback_models(mut self, models: &[String]) -> Self {
+        self.fallback_models = models.to_vec();
+        self
+    }
+    
+    /// Start every system prompt sent, from commands and the agent alike, with `prefix`
+    pub fn with_system_prompt_prefix(mut self, prefix: &str) -> Self {
+        self.system_prompt_prefix = prefix.trim().to_string();
+        self
+    }
+    
+    /// Accept images in `chat_with_image`, sending those requests to a vision-capable model
+    pub fn with_vision_model(mut self, model: &str) -> Self {
+        self.vision_model = Some(model.to_string());
+        self
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.api.retry = retry;
+        self
+    }
+    
+    /// Queue requests beyond the semaphore's permits instead of sending them all at once
+    pub fn with_concurrency_limit(mut self, limit: Arc<Semaphore>) -> Self {
+        self.api.concurrency = Some(limit);
+        self
+    }
+    
+    /// Fail fast with `LlmError::Unavailable` while the breaker is open
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.api.breaker = Some(breaker);
+        self
+    }
+    
+    /// Record token usage of calls made while a command's `UsageContext` is active
+    pub fn with_usage_store(mut self, usage_store: Arc<UsageStore>) -> Self {
+        self.usage_store = Some(usage_store);
+        self
+    }
+    
+    /// Serve identical requests from an in-memory cache instead of calling the API again
+    pub fn with_cache(mut self, capacity: usize, ttl: Duration) -> Self {
+        self.cache = Some(Arc::new(TtlCache::new(capacity, ttl)));
+        self
+    }
+    
+    /// Log each request's full system prompt and messages at DEBUG, first masking
+    /// emails and tokens in them when `redact` is set
+    pub fn with_prompt_logging(mut self, redact: bool) -> Self {
+        self.prompt_logging = if redact { PromptLogging::Redacted } else { PromptLogging::Full };
+        self
+    }
+    
+    fn log_prompt(&self, model: &str, messages: &[ChatMessage]) {
+        if self.prompt_logging == PromptLogging::Off {
+            return;
+        }
         
-        // Send request with retry logic
-        let mut retries = 0;
-        let mut last_error = None;
-        
-        while retries < MAX_RETRIES {
-            match self.client.embeddings().create(request.clone()).await {
-                Ok(response) => {
-                    let embedding = response
-                        .data
-                        .first()
-                        .ok_or_else(|| anyhow!("No embedding returned"))?
-                        .embedding
-                        .clone();
-                    
-                    return Ok(embedding);
-                },
-                Err(e) => {
-                    let error_string = e.to_string();
-                    
-                    // Check for rate limit errors
-                    if error_string.contains("rate limit") || error_string.contains("Requests rate limit exceeded") {
-                        retries += 1;
-                        if retries < MAX_RETRIES {
-                            let backoff = RETRY_DELAY_MS * (2_u64.pow(retries as u32));
-                            info!("Rate limit exceeded for embeddings, retrying in {}ms (attempt {}/{})", 
-                                  backoff, retries, MAX_RETRIES);
-                            sleep(Duration::from_millis(backoff)).await;
-                            continue;
-                        } else {
-                            error!("Rate limit exceeded for embeddings after {} retries: {}", retries, error_string);
-                            return Err(anyhow!("Rate limit exceeded. Please try again in a few minutes."));
-                        }
-                    }
-                    
-                    // For other errors
-                    error!("Error from Mistral API when creating embeddings: {}", error_string);
-                    last_error = Some(e);
-                    break;
+        let prompt = messages
+            .iter()
+            .map(|message| format!("[{}] {}", message.role, message.content))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let prompt = match self.prompt_logging {
+            PromptLogging::Redacted => redact_secrets(&prompt),
+            _ => prompt,
+        };
+        debug!("Sending {} messages to {}:\n{}", messages.len(), model, prompt);
+    }
+    
+    // System prompt followed by the conversation, rejecting roles the API doesn't take from us
+    fn build_messages(&self, system_prompt: &str, messages: &[ChatMessage]) -> Result<Vec<ChatMessage>> {
+        let mut chat_messages: Vec<ChatMessage> = Vec::with_capacity(messages.len() + 1);
+        
+        // Add system message
+        let content = if self.system_prompt_prefix.is_empty() {
+            system_prompt.to_string()
+        } else {
+            format!("{}\n\n{}", self.system_prompt_prefix, system_prompt)
+        };
+        chat_messages.push(ChatMessage {
+            role: "system".to_string(),
+            content,
+        });
+        
+        // Add user/assistant messages
+        for msg in messages {
+            match msg.role.as_str() {
+                "user" | "assistant" => chat_messages.push(msg.clone()),
+                _ => {
+                    return Err(anyhow!("Unsupported message role: {}", msg.role));
+                }
+            }
+        }
+        
+        Ok(chat_messages)
+    }
+    
+    fn request<'a>(&'a self, model: &'a str, messages: Vec<ChatMessage>, tools: &'a [ToolDefinition]) -> ChatCompletionRequest<'a> {
+        ChatCompletionRequest {
+            model,
+            messages,
+            temperature: 0.7,
+            top_p: 0.95,
+            max_tokens: 1024,
+            stream: false,
+            tools: tools
+                .iter()
+                .map(|function| ToolSpec { kind: "function", function })
+                .collect(),
+        }
+    }
+    
+    fn record_usage(&self, usage: &TokenUsage) {
+        info!("Chat completion used {} prompt + {} completion tokens",
+              usage.prompt_tokens, usage.completion_tokens);
+        metrics::counter!("karmaspark_llm_tokens_total", "kind" => "prompt")
+            .increment(usage.prompt_tokens as u64);
+        metrics::counter!("karmaspark_llm_tokens_total", "kind" => "completion")
+            .increment(usage.completion_tokens as u64);
+        
+        // Persist the spend in the background so it never slows down the reply
+        if let (Some(store), Some(context)) = (self.usage_store.clone(), UsageContext::current()) {
+            let usage = *usage;
+            tokio::spawn(async move {
+                if let Err(e) = store.record_usage(context, usage).await {
+                    error!("Failed to record token usage: {}", e);
                 }
+            });
+        }
+    }
+    
+    // Call `call` with the main model, then with each fallback model in turn while the
+    // failures are ones another model might not have
+    async fn with_fallbacks<'a, T, F, Fut>(&'a self, mut call: F) -> Result<T>
+    where
+        F: FnMut(&'a str) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let mut model = self.model.as_str();
+        let mut result = call(model).await;
+        
+        for fallback in &self.fallback_models {
+            match &result {
+                Err(e) if worth_another_model(e) => {
+                    warn!("Chat completion with {} failed, trying {}: {}", model, fallback, e);
+                    model = fallback.as_str();
+                    result = call(model).await;
+                }
+                _ => break,
             }
         }
         
-        // If we got here, all retries failed
-        Err(anyhow!("API error after {} retries: {}", 
-                    retries, 
-                    last_error.map_or("Unknown error".to_string(), |e| e.to_string())))
+        if result.is_ok() && model != self.model {
+            info!("Chat completion served by fallback model {}", model);
+        }
+        result
+    }
+    
+    async fn complete(&self, model: &str, messages: &[ChatMessage]) -> Result<ChatResult> {
+        let request = self.request(model, messages.to_vec(), &[]);
+        self.log_prompt(model, &request.messages);
+        
+        let cache_key = request.cache_key();
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+            info!("Serving chat completion from cache");
+            return Ok(ChatResult {
+                content: cached,
+                usage: None,
+            });
+        }
+        
+        let response: ChatCompletionResponse = self.api.post("chat/completions", &request).await?;
+        
+        // Extract response
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No choices in response"))?;
+        
+        let content = choice.message.content.unwrap_or_default();
+        if let Some(cache) = &self.cache {
+            cache.insert(cache_key, content.clone());
+        }
+        
+        if let Some(usage) = &response.usage {
+            self.record_usage(usage);
+        }
+        
+        Ok(ChatResult {
+            content,
+            usage: response.usage,
+        })
+    }
+    
+    async fn complete_with_tools(
+        &self,
+        model: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> Result<ToolReply> {
+        let request = self.request(model, messages.to_vec(), tools);
+        self.log_prompt(model, &request.messages);
+        let response: ChatCompletionResponse = self.api.post("chat/completions", &request).await?;
+        
+        if let Some(usage) = &response.usage {
+            self.record_usage(usage);
+        }
+        
+        let message = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No choices in response"))?
+            .message;
+        
+        match message.tool_calls {
+            Some(calls) if !calls.is_empty() => {
+                Ok(ToolReply::ToolCalls(calls.into_iter().map(ToolCall::from).collect()))
+            }
+            _ => Ok(ToolReply::Text(message.content.unwrap_or_default())),
+        }
+    }
+}
+
+#[async_trait]
+impl LlmProvider for MistralClient {
+    async fn chat_with_usage(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+    ) -> Result<ChatResult> {
+        let messages = self.build_messages(system_prompt, messages)?;
+        self.with_fallbacks(|model| self.complete(model, &messages)).await
+    }
+    
+    async fn chat_with_tools(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+        tools: &[ToolDefinition],
+    ) -> Result<ToolReply> {
+        // Tool-calling replies are not cached, as the next step depends on fresh tool output
+        let messages = self.build_messages(system_prompt, messages)?;
+        self.with_fallbacks(|model| self.complete_with_tools(model, &messages, tools)).await
+    }
+    
+    async fn chat_with_image(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+        image_url: &str,
+    ) -> Result<String> {
+        let Some(vision_model) = &self.vision_model else {
+            return Err(anyhow!("Image input is not enabled"));
+        };
+        
+        let messages = self.build_messages(system_prompt, messages)?;
+        self.log_prompt(vision_model, &messages);
+        let request = MultimodalRequest {
+            model: vision_model,
+            messages: multimodal_messages(&messages, image_url),
+            temperature: 0.7,
+            top_p: 0.95,
+            max_tokens: 1024,
+            stream: false,
+        };
+        let response: ChatCompletionResponse = self.api.post("chat/completions", &request).await?;
+        
+        if let Some(usage) = &response.usage {
+            self.record_usage(usage);
+        }
+        
+        let choice = response
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No choices in response"))?;
+        
+        Ok(choice.message.content.unwrap_or_default())
+    }
+    
+    fn rate_limited_within(&self, window: Duration) -> bool {
+        self.api.rate_limited_within(window)
+    }
+}
+
+// Implementation of embedding model using the Mistral API, or any endpoint
+// speaking the same OpenAI-style /embeddings protocol
+pub struct MistralEmbedding {
+    api: ApiClient,
+    model: String,
+    // Embeddings by normalized input text
+    cache: Option<Arc<TtlCache<String, Vec<f32>>>>,
+    // Known up front for mistral-embed, otherwise taken from the first embedding
+    dimension: Arc<AtomicUsize>,
+}
+
+impl MistralEmbedding {
+    pub fn new(api_key: &str) -> Self {
+        Self {
+            api: ApiClient::new(api_key, MISTRAL_API_URL),
+            dimension: Arc::new(AtomicUsize::new(known_embedding_dimension(DEFAULT_EMBEDDING_MODEL))),
+            model: DEFAULT_EMBEDDING_MODEL.to_string(),
+            cache: None,
+        }
+    }
+    
+    /// Send requests to another OpenAI-compatible API, e.g. a self-hosted model
+    pub fn with_base_url(mut self, base_url: &str) -> Self {
+        self.api.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+    
+    pub fn with_model(mut self, model: &str) -> Self {
+        self.model = model.to_string();
+        self.dimension = Arc::new(AtomicUsize::new(known_embedding_dimension(model)));
+        self
+    }
+    
+    pub fn with_retry_policy(mut self, retry: RetryPolicy) -> Self {
+        self.api.retry = retry;
+        self
+    }
+    
+    pub fn with_concurrency_limit(mut self, limit: Arc<Semaphore>) -> Self {
+        self.api.concurrency = Some(limit);
+        self
+    }
+    
+    /// Fail fast with `LlmError::Unavailable` while the breaker is open
+    pub fn with_circuit_breaker(mut self, breaker: Arc<CircuitBreaker>) -> Self {
+        self.api.breaker = Some(breaker);
+        self
+    }
+    
+    /// Reuse embeddings of recently seen texts, so repeated recall queries skip the API
+    pub fn with_cache(mut self, capacity: usize) -> Self {
+        self.cache = Some(Arc::new(TtlCache::new(capacity, EMBEDDING_CACHE_TTL)));
+        self
+    }
+}
+
+fn known_embedding_dimension(model: &str) -> usize {
+    match model {
+        "mistral-embed" => 1024,
+        _ => 0,
+    }
+}
+
+// Embeddings only change with the model, so entries can live long
+const EMBEDDING_CACHE_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+// Cache key ignoring case and whitespace differences
+fn normalize_embedding_input(text: &str) -> String {
+    text.split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[async_trait]
+impl EmbeddingModel for MistralEmbedding {
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let cache_key = normalize_embedding_input(text);
+        if let Some(cached) = self.cache.as_ref().and_then(|cache| cache.get(&cache_key)) {
+            return Ok(cached);
+        }
+        
+        let request = EmbeddingRequest {
+            model: &self.model,
+            input: vec![text],
+        };
+        
+        let response: EmbeddingResponse = self.api.post("embeddings", &request).await?;
+        
+        let embedding = response
+            .data
+            .into_iter()
+            .next()
+            .ok_or_else(|| anyhow!("No embedding returned"))?
+            .embedding;
+        
+        // The first embedding fixes the dimension; later ones must match it
+        let expected = match self.dimension.compare_exchange(0, embedding.len(), Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => embedding.len(),
+            Err(known) => known,
+        };
+        if embedding.len() != expected {
+            return Err(anyhow!(
+                "Embedding has dimension {}, expected {}",
+                embedding.len(),
+                expected
+            ));
+        }
+        
+        if let Some(cache) = &self.cache {
+            cache.insert(cache_key, embedding.clone());
+        }
+        
+        Ok(embedding)
     }
     
-    async fn similarity(&self, embedding1: &[f32], embedding2: &[f32]) -> f32 {
-        // Cosine similarity calculation
-        if embedding1.len() != embedding2.len() || embedding1.is_empty() {
-            return 0.0;
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
         }
         
-        let dot_product: f32 = embedding1.iter().zip(embedding2.iter()).map(|(x, y)| x * y).sum();
-        let magnitude1: f32 = embedding1.iter().map(|x| x * x).sum::<f32>().sqrt();
-        let magnitude2: f32 = embedding2.iter().map(|x| x * x).sum::<f32>().sqrt();
+        let request = EmbeddingRequest {
+            model: &self.model,
+            input: texts.iter().map(String::as_str).collect(),
+        };
         
-        if magnitude1 == 0.0 || magnitude2 == 0.0 {
-            return 0.0;
+        let response: EmbeddingResponse = self.api.post("embeddings", &request).await?;
+        if response.data.len() != texts.len() {
+            return Err(anyhow!(
+                "Asked for {} embeddings, got {}",
+                texts.len(),
+                response.data.len()
+            ));
         }
         
-        dot_product / (magnitude1 * magnitude2)
+        let embeddings: Vec<Vec<f32>> = response.data.into_iter().map(|data| data.embedding).collect();
+        let expected = match self.dimension.compare_exchange(0, embeddings[0].len(), Ordering::Relaxed, Ordering::Relaxed) {
+            Ok(_) => embeddings[0].len(),
+            Err(known) => known,
+        };
+        if let Some(embedding) = embeddings.iter().find(|embedding| embedding.len() != expected) {
+            return Err(anyhow!(
+                "Embedding has dimension {}, expected {}",
+                embedding.len(),
+                expected
+            ));
+        }
+        
+        Ok(embeddings)
+    }
+    
+    fn name(&self) -> &str {
+        &self.model
+    }
+    
+    fn dimension(&self) -> usize {
+        self.dimension.load(Ordering::Relaxed)
+    }
+}
+
+/// Offline stand-in for the Mistral API that echoes the last user message.
+/// Selected with `[llm] provider = "mock"`.
+#[derive(Debug, Clone, Default)]
+pub struct MockLlm;
+
+#[async_trait]
+impl LlmProvider for MockLlm {
+    async fn chat_with_usage(
+        &self,
+        _system_prompt: &str,
+        messages: &[ChatMessage],
+    ) -> Result<ChatResult> {
+        let last_user_message = messages
+            .iter()
+            .rev()
+            .find(|msg| msg.role == "user")
+            .map(|msg| msg.content.as_str())
+            .unwrap_or("");
+        
+        Ok(ChatResult {
+            content: format!("[mock] {}", last_user_message),
+            usage: None,
+        })
     }
-} 
\ No newline at end of file
+    
+    async fn summarize(&self, text: &str, _options: &SummaryOptions) -> Result<String> {
+        let summary: String = text.chars().take(200).collect();
+        if summary.len() < text.len() {
+            Ok(format!("[mock] {}...", summary))
+        } else {
+            Ok(format!("[mock] {}", summary))
+        }
+    }
+    
+    async fn moderate(&self, _text: &str) -> Result<(bool, String)> {
+        Ok((false, "SAFE".to_string()))
+    }
+    
+    async fn chat_with_image(
+        &self,
+        system_prompt: &str,
+        messages: &[ChatMessage],
+        image_url: &str,
+    ) -> Result<String> {
+        let reply = self.chat(system_prompt, messages).await?;
+        Ok(format!("{} (image: {})", reply, image_url))
+    }
+}
+
+/// Offline embedding model hashing words into a fixed-size bag-of-words vector,
+/// so texts sharing words still score as similar
+#[derive(Debug, Clone, Default)]
+pub struct MockEmbedding;
+
+const MOCK_EMBEDDING_DIMENSION: usize = 64;
+
+#[async_trait]
+impl EmbeddingModel for MockEmbedding {
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let mut embedding = vec![0.0f32; MOCK_EMBEDDING_DIMENSION];
+        
+        for word in text.split_whitespace() {
+            let mut hasher = DefaultHasher::new();
+            word.to_lowercase().hash(&mut hasher);
+            embedding[(hasher.finish() % MOCK_EMBEDDING_DIMENSION as u64) as usize] += 1.0;
+        }
+        
+        Ok(embedding)
+    }
+    
+    fn name(&self) -> &str {
+        "mock"
+    }
+    
+    fn dimension(&self) -> usize {
+        MOCK_EMBEDDING_DIMENSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use chrono::TimeZone;
+    use reqwest::header::HeaderValue;
+
+    fn user_message(content: &str) -> ChatMessage {
+        ChatMessage {
+            role: "user".to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    fn chat_response(content: &str) -> serde_json::Value {
+        serde_json::json!({
+            "choices": [{ "message": { "content": content } }],
+            "usage": { "prompt_tokens": 12, "completion_tokens": 3, "total_tokens": 15 }
+        })
+    }
+
+    fn mock_client(server: &MockServer) -> MistralClient {
+        let mut client = MistralClient::new("test-key");
+        client.api.base_url = server.url.clone();
+        client
+    }
+
+    fn embedding_response(embeddings: &[Vec<f32>]) -> serde_json::Value {
+        serde_json::json!({
+            "data": embeddings.iter().map(|embedding| serde_json::json!({ "embedding": embedding })).collect::<Vec<_>>()
+        })
+    }
+
+    fn mock_embedding(server: &MockServer) -> MistralEmbedding {
+        MistralEmbedding::new("test-key").with_base_url(&server.url)
+    }
+
+    // Answers "summary <n>" and records each (system prompt, last message) it was sent
+    #[derive(Default)]
+    struct RecordingLlm {
+        calls: Mutex<Vec<(String, String)>>,
+    }
+
+    impl RecordingLlm {
+        fn calls(&self) -> Vec<(String, String)> {
+            self.calls.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl LlmProvider for RecordingLlm {
+        async fn chat_with_usage(&self, system_prompt: &str, messages: &[ChatMessage]) -> Result<ChatResult> {
+            let mut calls = self.calls.lock().unwrap();
+            let last = messages.last().map(|m| m.content.clone()).unwrap_or_default();
+            calls.push((system_prompt.to_string(), last));
+            Ok(ChatResult {
+                content: format!("summary {}", calls.len()),
+                usage: None,
+            })
+        }
+    }
+
+    fn retry_after(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RETRY_AFTER, HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    #[test]
+    fn retry_after_in_seconds() {
+        assert_eq!(retry_after_delay(&retry_after("5"), Utc::now()), Some(Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn retry_after_as_http_date() {
+        let now = Utc.with_ymd_and_hms(2015, 10, 21, 7, 27, 50).unwrap();
+        let headers = retry_after("Wed, 21 Oct 2015 07:28:00 GMT");
+        assert_eq!(retry_after_delay(&headers, now), Some(Duration::from_secs(10)));
+
+        // A date already past means no wait
+        let later = Utc.with_ymd_and_hms(2015, 10, 21, 8, 0, 0).unwrap();
+        assert_eq!(retry_after_delay(&headers, later), Some(Duration::ZERO));
+    }
+
+    #[test]
+    fn retry_after_is_capped() {
+        assert_eq!(
+            retry_after_delay(&retry_after("3600"), Utc::now()),
+            Some(Duration::from_secs(MAX_RETRY_DELAY_SECS))
+        );
+    }
+
+    #[test]
+    fn retry_after_missing_or_malformed() {
+        assert_eq!(retry_after_delay(&HeaderMap::new(), Utc::now()), None);
+        assert_eq!(retry_after_delay(&retry_after("soon"), Utc::now()), None);
+    }
+
+    #[tokio::test]
+    async fn waits_as_long_as_retry_after_says() {
+        let server = MockServer::start(vec![
+            MockResponse::status(StatusCode::TOO_MANY_REQUESTS).with_header("retry-after", "1"),
+            MockResponse::json(chat_response("hello")),
+        ])
+        .await;
+        // A backoff window far longer than the header asks for
+        let client = mock_client(&server).with_retry_policy(RetryPolicy {
+            max_retries: 1,
+            base_delay: Duration::from_secs(30),
+        });
+
+        let started = Instant::now();
+        let reply = client.chat("system", &[user_message("hi")]).await.unwrap();
+        let waited = started.elapsed();
+
+        assert_eq!(reply, "hello");
+        assert_eq!(server.requests().len(), 2);
+        assert!(waited >= Duration::from_secs(1) && waited < Duration::from_secs(5), "waited {:?}", waited);
+    }
+
+    #[tokio::test]
+    async fn identical_requests_are_served_from_cache() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("cached"))]).await;
+        let client = mock_client(&server).with_cache(16, Duration::from_secs(60));
+
+        let first = client.chat_with_usage("system", &[user_message("hi")]).await.unwrap();
+        let second = client.chat_with_usage("system", &[user_message("hi")]).await.unwrap();
+
+        assert_eq!(first.content, "cached");
+        assert_eq!(second.content, "cached");
+        assert_eq!(second.usage, None);
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_different_prompt_misses_the_cache() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("fresh"))]).await;
+        let client = mock_client(&server).with_cache(16, Duration::from_secs(60));
+
+        client.chat("system", &[user_message("hi")]).await.unwrap();
+        client.chat("another system prompt", &[user_message("hi")]).await.unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[test]
+    fn cache_key_covers_model_and_params() {
+        let client = MistralClient::new("test-key");
+        let messages = vec![user_message("hi")];
+        let request = client.request("model-a", messages.clone(), &[]);
+
+        assert_eq!(request.cache_key(), client.request("model-a", messages.clone(), &[]).cache_key());
+        assert_ne!(request.cache_key(), client.request("model-b", messages.clone(), &[]).cache_key());
+
+        let mut warmer = client.request("model-a", messages, &[]);
+        warmer.temperature = 1.0;
+        assert_ne!(request.cache_key(), warmer.cache_key());
+    }
+
+    #[tokio::test]
+    async fn parses_token_usage() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("hello"))]).await;
+
+        let result = mock_client(&server).chat_with_usage("system", &[user_message("hi")]).await.unwrap();
+
+        assert_eq!(result.content, "hello");
+        assert_eq!(
+            result.usage,
+            Some(TokenUsage {
+                prompt_tokens: 12,
+                completion_tokens: 3,
+                total_tokens: 15,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn usage_is_optional() {
+        let server = MockServer::start(vec![MockResponse::json(serde_json::json!({
+            "choices": [{ "message": { "content": "hello" } }]
+        }))])
+        .await;
+
+        let result = mock_client(&server).chat_with_usage("system", &[user_message("hi")]).await.unwrap();
+
+        assert_eq!(result.usage, None);
+    }
+
+    #[test]
+    fn jittered_backoff_stays_within_its_window() {
+        let base = Duration::from_millis(100);
+        for retries in 0..5 {
+            let window = base * 2_u32.pow(retries as u32);
+            for _ in 0..200 {
+                assert!(jittered_backoff(base, retries) <= window);
+            }
+        }
+    }
+
+    #[test]
+    fn jittered_backoff_is_capped() {
+        let cap = Duration::from_secs(MAX_RETRY_DELAY_SECS);
+        for _ in 0..200 {
+            assert!(jittered_backoff(Duration::from_secs(10), 20) <= cap);
+        }
+    }
+
+    #[test]
+    fn jittered_backoff_varies() {
+        let delays: std::collections::HashSet<_> = (0..50)
+            .map(|_| jittered_backoff(Duration::from_secs(1), 3))
+            .collect();
+        assert!(delays.len() > 1);
+    }
+
+    #[tokio::test]
+    async fn no_retries_when_max_retries_is_zero() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::TOO_MANY_REQUESTS)]).await;
+        let client = mock_client(&server).with_retry_policy(RetryPolicy {
+            max_retries: 0,
+            base_delay: Duration::from_millis(10),
+        });
+
+        let error = client.chat("system", &[user_message("hi")]).await.unwrap_err();
+
+        assert!(is_rate_limited(&error));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn retries_up_to_max_retries() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::TOO_MANY_REQUESTS)]).await;
+        let client = mock_client(&server).with_retry_policy(RetryPolicy {
+            max_retries: 2,
+            base_delay: Duration::from_millis(10),
+        });
+
+        let error = client.chat("system", &[user_message("hi")]).await.unwrap_err();
+
+        assert!(is_rate_limited(&error));
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn calls_in_a_run_share_its_retry_budget() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::TOO_MANY_REQUESTS)]).await;
+        let client = mock_client(&server).with_retry_policy(RetryPolicy {
+            max_retries: 5,
+            base_delay: Duration::from_millis(10),
+        });
+
+        let budget = Arc::new(RetryBudget::new(3, Duration::from_secs(60)));
+        budget
+            .scope(async {
+                // The first call spends the whole budget; the rest give up after one try
+                for _ in 0..3 {
+                    let error = client.chat("system", &[user_message("hi")]).await.unwrap_err();
+                    assert!(is_rate_limited(&error));
+                }
+            })
+            .await;
+
+        assert_eq!(server.requests().len(), 4 + 1 + 1);
+
+        // Outside the run, the client's own policy applies again
+        client.chat("system", &[user_message("hi")]).await.unwrap_err();
+        assert_eq!(server.requests().len(), 6 + 6);
+    }
+
+    #[tokio::test]
+    async fn no_retry_may_wait_past_the_run_deadline() {
+        let server = MockServer::start(vec![
+            MockResponse::status(StatusCode::TOO_MANY_REQUESTS).with_header("retry-after", "30"),
+        ])
+        .await;
+        let client = mock_client(&server).with_retry_policy(RetryPolicy {
+            max_retries: 3,
+            base_delay: Duration::from_millis(10),
+        });
+
+        let started = Instant::now();
+        let budget = Arc::new(RetryBudget::new(10, Duration::from_secs(5)));
+        let error = budget.scope(client.chat("system", &[user_message("hi")])).await.unwrap_err();
+
+        assert!(is_rate_limited(&error));
+        assert_eq!(server.requests().len(), 1);
+        assert!(started.elapsed() < Duration::from_secs(5));
+    }
+
+    #[test]
+    fn redacts_emails_and_tokens() {
+        assert_eq!(
+            redact_secrets("Mail jane.doe+bot@example.co.uk about it"),
+            "Mail [email] about it"
+        );
+        assert_eq!(redact_secrets("Authorization: Bearer abc.def-123"), "Authorization: Bearer [token]");
+        assert_eq!(redact_secrets("key sk-live_0123456789abcdef here"), "key [token] here");
+        assert_eq!(redact_secrets(&format!("id {}", "a1B2".repeat(10))), "id [token]");
+        assert_eq!(redact_secrets("Nothing secret in 2026, see issue #42"), "Nothing secret in 2026, see issue #42");
+    }
+
+    // What `log_prompt` writes at DEBUG with the given logging
+    fn logged_prompt(client: &MistralClient, messages: &[ChatMessage]) -> String {
+        #[derive(Clone, Default)]
+        struct Capture(Arc<Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_max_level(tracing::Level::DEBUG)
+            .with_writer(move || writer.clone())
+            .finish();
+        tracing::subscriber::with_default(subscriber, || client.log_prompt("mistral-small", messages));
+
+        let logs = capture.0.lock().unwrap().clone();
+        String::from_utf8(logs).unwrap()
+    }
+
+    #[test]
+    fn prompt_logging_masks_emails_when_redacting() {
+        let messages = [user_message("Email me at jane@example.com")];
+
+        let redacted = logged_prompt(&MistralClient::new("test-key").with_prompt_logging(true), &messages);
+        assert!(redacted.contains("Sending 1 messages to mistral-small:"), "{}", redacted);
+        assert!(redacted.contains("[user] Email me at [email]"), "{}", redacted);
+        assert!(!redacted.contains("jane@example.com"));
+
+        let full = logged_prompt(&MistralClient::new("test-key").with_prompt_logging(false), &messages);
+        assert!(full.contains("[user] Email me at jane@example.com"), "{}", full);
+
+        // Off unless asked for
+        assert_eq!(logged_prompt(&MistralClient::new("test-key"), &messages), "");
+    }
+
+    #[tokio::test]
+    async fn a_failing_model_falls_back_to_the_next() {
+        let server = MockServer::start(vec![
+            MockResponse::status(StatusCode::SERVICE_UNAVAILABLE).with_body("model overloaded".to_string()),
+            MockResponse::status(StatusCode::INTERNAL_SERVER_ERROR),
+            MockResponse::json(chat_response("from the fallback")),
+        ])
+        .await;
+        let client = mock_client(&server)
+            .with_fallback_models(&["mistral-small".to_string(), "open-mistral-nemo".to_string()]);
+
+        let reply = client.chat("system", &[user_message("hi")]).await.unwrap();
+
+        assert_eq!(reply, "from the fallback");
+        let models: Vec<serde_json::Value> = server.requests().iter().map(|r| r.json()["model"].clone()).collect();
+        assert_eq!(models, vec![DEFAULT_CHAT_MODEL, "mistral-small", "open-mistral-nemo"]);
+    }
+
+    #[tokio::test]
+    async fn rate_limits_are_not_passed_to_fallbacks() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::TOO_MANY_REQUESTS)]).await;
+        let client = mock_client(&server)
+            .with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                base_delay: Duration::from_millis(10),
+            })
+            .with_fallback_models(&["mistral-small".to_string()]);
+
+        let error = client.chat("system", &[user_message("hi")]).await.unwrap_err();
+
+        assert!(is_rate_limited(&error));
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn gives_up_once_every_model_has_failed() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::INTERNAL_SERVER_ERROR)]).await;
+        let client = mock_client(&server).with_fallback_models(&["mistral-small".to_string()]);
+
+        assert!(client.chat("system", &[user_message("hi")]).await.is_err());
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn offers_tools_and_parses_tool_calls() {
+        let server = MockServer::start(vec![MockResponse::json(serde_json::json!({
+            "choices": [{ "message": {
+                "content": "",
+                "tool_calls": [{ "function": { "name": "search", "arguments": "{\"query\":\"rust\"}" } }]
+            } }]
+        }))])
+        .await;
+        let tools = [ToolDefinition {
+            name: "search".to_string(),
+            description: "Look things up".to_string(),
+            parameters: serde_json::json!({ "type": "object", "properties": {} }),
+        }];
+
+        let reply = mock_client(&server)
+            .chat_with_tools("system", &[user_message("hi")], &tools)
+            .await
+            .unwrap();
+
+        let ToolReply::ToolCalls(calls) = reply else {
+            panic!("expected tool calls, got {:?}", reply);
+        };
+        assert_eq!(calls.len(), 1);
+        assert_eq!(calls[0].name, "search");
+        assert_eq!(calls[0].arguments, serde_json::json!({ "query": "rust" }));
+
+        let sent = server.requests()[0].json();
+        assert_eq!(sent["tools"][0]["type"], "function");
+        assert_eq!(sent["tools"][0]["function"]["name"], "search");
+    }
+
+    #[test]
+    fn chunks_break_at_paragraphs_then_words() {
+        assert_eq!(split_into_chunks("one\n\ntwo\n\nthree", 8), vec!["one\n\ntwo", "three"]);
+        assert_eq!(split_into_chunks("alpha beta gamma", 11), vec!["alpha beta", "gamma"]);
+        assert!(split_into_chunks(" \n\n ", 10).is_empty());
+    }
+
+    #[test]
+    fn chunks_cut_oversized_words_on_char_boundaries() {
+        assert_eq!(split_into_chunks("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        let chunks = split_into_chunks(&"é".repeat(5), 4);
+        assert_eq!(chunks, vec!["éé", "éé", "é"]);
+    }
+
+    #[test]
+    fn chunks_stay_within_the_limit_and_keep_every_word() {
+        let text: String = (0..2000).map(|i| format!("word{} ", i)).collect();
+        let chunks = split_into_chunks(&text, 500);
+
+        assert!(chunks.iter().all(|chunk| chunk.len() <= 500));
+        assert_eq!(chunks.join(" ").split_whitespace().count(), 2000);
+    }
+
+    #[tokio::test]
+    async fn summarizes_long_text_chunk_by_chunk_then_combines() {
+        let llm = RecordingLlm::default();
+        let chunk_chars = SUMMARY_CHUNK_TOKENS * CHARS_PER_TOKEN;
+        // Three paragraphs that each fill most of a chunk
+        let paragraph = "word ".repeat(chunk_chars / 5 - 100);
+        let text = [paragraph.as_str(); 3].join("\n\n");
+        let options = SummaryOptions {
+            style: Some(SummaryStyle::Bullets),
+            ..SummaryOptions::default()
+        };
+
+        let summary = llm.summarize_long(&text, &options).await.unwrap();
+
+        let calls = llm.calls();
+        assert_eq!(calls.len(), 4);
+        for (system_prompt, input) in &calls[..3] {
+            assert_eq!(*system_prompt, SummaryOptions::default().summary_prompt());
+            assert_eq!(*input, paragraph.trim());
+        }
+        assert_eq!(calls[3].0, options.combine_prompt());
+        assert_eq!(calls[3].1, "summary 1\n\nsummary 2\n\nsummary 3");
+        assert_eq!(summary, "summary 4");
+    }
+
+    #[tokio::test]
+    async fn short_text_is_summarized_in_one_call() {
+        let llm = RecordingLlm::default();
+
+        llm.summarize_long("A short note.", &SummaryOptions::default()).await.unwrap();
+
+        assert_eq!(llm.calls(), vec![(SummaryOptions::default().summary_prompt(), "A short note.".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn repeated_queries_reuse_the_cached_embedding() {
+        let server = MockServer::start(vec![MockResponse::json(embedding_response(&[vec![0.5; 1024]]))]).await;
+        let model = mock_embedding(&server).with_cache(8);
+
+        let first = model.embed_text("Where is  the deploy doc?").await.unwrap();
+        let second = model.embed_text("where is the DEPLOY doc?").await.unwrap();
+
+        assert_eq!(first, second);
+        assert_eq!(server.requests().len(), 1);
+
+        model.embed_text("something else").await.unwrap();
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn embeddings_are_not_cached_by_default() {
+        let server = MockServer::start(vec![MockResponse::json(embedding_response(&[vec![0.5; 1024]]))]).await;
+        let model = mock_embedding(&server);
+
+        model.embed_text("hello").await.unwrap();
+        model.embed_text("hello").await.unwrap();
+
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn requests_wait_for_a_free_slot() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("hello"))]).await;
+        let limit = Arc::new(Semaphore::new(2));
+        let client = mock_client(&server).with_concurrency_limit(limit.clone());
+
+        // Two slots are busy elsewhere, e.g. with embedding requests sharing the limit
+        let busy = limit.clone().acquire_many_owned(2).await.unwrap();
+        let pending = tokio::spawn({
+            let client = client.clone();
+            async move { client.chat("system", &[user_message("hi")]).await }
+        });
+        sleep(Duration::from_millis(100)).await;
+        assert!(server.requests().is_empty());
+
+        drop(busy);
+        assert_eq!(pending.await.unwrap().unwrap(), "hello");
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(limit.available_permits(), 2);
+    }
+
+    #[tokio::test]
+    async fn no_more_requests_in_flight_than_the_limit() {
+        let server = MockServer::start(vec![
+            MockResponse::json(chat_response("hello")).with_delay(Duration::from_millis(50)),
+        ])
+        .await;
+        let client = mock_client(&server).with_concurrency_limit(Arc::new(Semaphore::new(2)));
+
+        let calls: Vec<_> = (0..8)
+            .map(|i| {
+                let client = client.clone();
+                tokio::spawn(async move { client.chat("system", &[user_message(&i.to_string())]).await })
+            })
+            .collect();
+        for call in calls {
+            call.await.unwrap().unwrap();
+        }
+
+        assert_eq!(server.requests().len(), 8);
+        assert_eq!(server.max_in_flight(), 2);
+    }
+
+    #[tokio::test]
+    async fn mistral_embed_reports_its_known_dimension() {
+        let server = MockServer::start(vec![MockResponse::json(embedding_response(&[vec![0.1; 1024]]))]).await;
+        let model = mock_embedding(&server);
+
+        assert_eq!(model.dimension(), 1024);
+        assert_eq!(model.embed_text("hello").await.unwrap().len(), model.dimension());
+    }
+
+    #[tokio::test]
+    async fn other_models_take_the_dimension_of_the_first_embedding() {
+        let server = MockServer::start(vec![
+            MockResponse::json(embedding_response(&[vec![0.1; 384]])),
+            MockResponse::json(embedding_response(&[vec![0.1; 512]])),
+        ])
+        .await;
+        let model = mock_embedding(&server).with_model("all-minilm");
+
+        assert_eq!(model.dimension(), 0);
+        let embedding = model.embed_text("hello").await.unwrap();
+        assert_eq!(model.dimension(), embedding.len());
+
+        let error = model.embed_text("again").await.unwrap_err();
+        assert_eq!(error.to_string(), "Embedding has dimension 512, expected 384");
+    }
+
+    #[tokio::test]
+    async fn mock_llm_echoes_the_last_user_message() {
+        let messages = [
+            user_message("Hello"),
+            ChatMessage { role: "assistant".to_string(), content: "Hi!".to_string() },
+            user_message("What is the capital of France?"),
+        ];
+
+        assert_eq!(MockLlm.chat("Be brief.", &messages).await.unwrap(), "[mock] What is the capital of France?");
+        assert_eq!(MockLlm.chat("Be brief.", &[]).await.unwrap(), "[mock] ");
+    }
+
+    #[tokio::test]
+    async fn mock_llm_summarizes_to_the_first_200_characters() {
+        let options = SummaryOptions::default();
+
+        assert_eq!(MockLlm.summarize("A short note.", &options).await.unwrap(), "[mock] A short note.");
+        let long = "a".repeat(250);
+        assert_eq!(
+            MockLlm.summarize_long(&long, &options).await.unwrap(),
+            format!("[mock] {}...", "a".repeat(200))
+        );
+    }
+
+    #[tokio::test]
+    async fn mock_llm_finds_everything_safe() {
+        let result = crate::moderation::moderate_content(&MockLlm, "You are an idiot").await.unwrap();
+
+        assert_eq!(result, crate::moderation::ModerationResult::Safe);
+    }
+
+    #[tokio::test]
+    async fn mock_embeddings_find_memories_sharing_words() {
+        use crate::memory::{MemoryBackend, MemoryStore};
+        use crate::test_support::memory;
+
+        let store = MemoryStore::new(":memory:").unwrap();
+        for (content, age) in [("Deploys happen on Fridays", 20), ("Alice likes green tea", 10)] {
+            let embedding = MockEmbedding.embed_text(content).await.unwrap();
+            store
+                .store_memory(memory(content).embedding(embedding).age_secs(age).build())
+                .await
+                .unwrap();
+        }
+
+        let query = MockEmbedding.embed_text("when do deploys happen").await.unwrap();
+        let results = store
+            .search_similar_memories("group:1", None, &query, MockEmbedding.name(), 1)
+            .await
+            .unwrap();
+
+        assert_eq!(results[0].0.content, "Deploys happen on Fridays");
+    }
+
+    #[tokio::test]
+    async fn mock_embeddings_match_their_dimension() {
+        let embedding = MockEmbedding.embed_text("the quick brown fox").await.unwrap();
+
+        assert_eq!(embedding.len(), MockEmbedding.dimension());
+    }
+
+    #[test]
+    fn parses_summary_lengths_and_styles() {
+        assert_eq!("Short".parse(), Ok(SummaryLength::Short));
+        assert_eq!(" long ".parse(), Ok(SummaryLength::Long));
+        assert_eq!("5".parse(), Ok(SummaryLength::Points(5)));
+        assert!("0".parse::<SummaryLength>().is_err());
+        assert!((MAX_SUMMARY_POINTS + 1).to_string().parse::<SummaryLength>().is_err());
+        assert!("huge".parse::<SummaryLength>().is_err());
+
+        assert_eq!("bullets".parse(), Ok(SummaryStyle::Bullets));
+        assert_eq!("TL;DR".parse(), Ok(SummaryStyle::TlDr));
+        assert!("haiku".parse::<SummaryStyle>().is_err());
+    }
+
+    #[test]
+    fn default_summary_prompt_is_unchanged() {
+        assert_eq!(
+            SummaryOptions::default().summary_prompt(),
+            "You are a highly efficient text summarizer. Create a concise summary of the following text while retaining the key points."
+        );
+    }
+
+    #[test]
+    fn summary_options_add_their_instructions() {
+        let prompt = |length, style| SummaryOptions { length, style, ..SummaryOptions::default() }.summary_prompt();
+
+        assert!(prompt(Some(SummaryLength::Short), None).ends_with("Keep it very short: one or two sentences."));
+        assert!(prompt(Some(SummaryLength::Medium), None).contains("three to five sentences"));
+        assert!(prompt(Some(SummaryLength::Long), None).contains("several paragraphs"));
+        assert!(prompt(Some(SummaryLength::Points(4)), None).ends_with("Use exactly 4 bullet points."));
+        assert!(prompt(None, Some(SummaryStyle::Bullets)).ends_with("Format the summary as a bulleted list."));
+        assert!(prompt(None, Some(SummaryStyle::Paragraph)).ends_with("Write the summary as flowing prose without lists."));
+        assert!(prompt(None, Some(SummaryStyle::TlDr)).contains("\"TL;DR:\""));
+
+        let both = prompt(Some(SummaryLength::Short), Some(SummaryStyle::TlDr));
+        assert!(both.contains("one or two sentences. Start with a single line"), "{}", both);
+    }
+
+    #[test]
+    fn detects_source_code() {
+        let fenced = "Can you explain this?\n```python\ndef add(a, b):\n    return a + b\n```";
+        let rust = "use std::fs;\n\nfn main() {\n    let text = fs::read_to_string(\"notes.txt\").unwrap();\n    println!(\"{}\", text.len());\n}";
+        let python = "import os\n\ndef list_files(path):\n    for name in os.listdir(path):\n        print(name)";
+
+        for code in [fenced, rust, python] {
+            assert_eq!(SummaryContent::detect(code), SummaryContent::Code, "{}", code);
+        }
+    }
+
+    #[test]
+    fn prose_and_short_snippets_stay_prose() {
+        let notes = "Meeting notes from Tuesday.\nWe agreed to ship the release on Friday (pending QA).\nAlice will update the docs; Bob handles the blog post.\nNext sync is Monday.";
+
+        assert_eq!(SummaryContent::detect(notes), SummaryContent::Prose);
+        assert_eq!(SummaryContent::detect("let x = 1;\nfoo();"), SummaryContent::Prose);
+        assert_eq!(SummaryContent::detect(""), SummaryContent::Prose);
+    }
+
+    #[test]
+    fn code_gets_the_code_prompts() {
+        let code = SummaryOptions {
+            content: SummaryContent::Code,
+            ..SummaryOptions::default()
+        };
+
+        assert!(code.summary_prompt().starts_with("You are an experienced software engineer. Explain concisely what the following source code does"));
+        assert!(code.combine_prompt().contains("consecutive parts of one long piece of source code"));
+        assert_ne!(code.summary_prompt(), SummaryOptions::default().summary_prompt());
+        assert_ne!(code.combine_prompt(), SummaryOptions::default().combine_prompt());
+    }
+
+    #[tokio::test]
+    async fn long_code_is_described_chunk_by_chunk() {
+        let llm = RecordingLlm::default();
+        let chunk_chars = SUMMARY_CHUNK_TOKENS * CHARS_PER_TOKEN;
+        let paragraph = "let x = 1; ".repeat(chunk_chars / 11 - 100);
+        let text = [paragraph.as_str(); 3].join("\n\n");
+        let options = SummaryOptions {
+            content: SummaryContent::Code,
+            ..SummaryOptions::default()
+        };
+
+        llm.summarize_long(&text, &options).await.unwrap();
+
+        let calls = llm.calls();
+        assert_eq!(calls.len(), 4);
+        for (system_prompt, _) in &calls[..3] {
+            assert_eq!(*system_prompt, options.summary_prompt());
+        }
+        assert_eq!(calls[3].0, options.combine_prompt());
+    }
+
+    #[tokio::test]
+    async fn the_prefix_starts_every_system_prompt() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("SAFE"))]).await;
+        let client = mock_client(&server).with_system_prompt_prefix("  Answers are not legal advice.\n");
+
+        client.chat("You are helpful.", &[user_message("Hi")]).await.unwrap();
+        client.summarize("Some text.", &SummaryOptions::default()).await.unwrap();
+        client.moderate("Hello there").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 3);
+        for request in &requests {
+            let system = request.json()["messages"][0].clone();
+            assert_eq!(system["role"], "system");
+            assert!(
+                system["content"].as_str().unwrap().starts_with("Answers are not legal advice.\n\nYou are"),
+                "{}",
+                system["content"]
+            );
+        }
+        assert_eq!(
+            requests[0].json()["messages"][0]["content"],
+            "Answers are not legal advice.\n\nYou are helpful."
+        );
+    }
+
+    #[tokio::test]
+    async fn without_a_prefix_the_system_prompt_is_unchanged() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("Hello"))]).await;
+
+        mock_client(&server).chat("You are helpful.", &[user_message("Hi")]).await.unwrap();
+
+        assert_eq!(server.requests()[0].json()["messages"][0]["content"], "You are helpful.");
+    }
+
+    #[tokio::test]
+    async fn summarize_sends_the_options_prompt() {
+        let llm = RecordingLlm::default();
+        let options = SummaryOptions {
+            length: Some(SummaryLength::Points(3)),
+            ..SummaryOptions::default()
+        };
+
+        llm.summarize("Some text.", &options).await.unwrap();
+
+        assert_eq!(llm.calls(), vec![(options.summary_prompt(), "Some text.".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn embeddings_go_to_the_configured_endpoint() {
+        let server = MockServer::start(vec![MockResponse::json(embedding_response(&[vec![0.5; 384]]))]).await;
+        let model = MistralEmbedding::new("local-key")
+            .with_base_url(&format!("{}/v1/", server.url))
+            .with_model("all-minilm");
+
+        model.embed_text("hello").await.unwrap();
+
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].path, "/v1/embeddings");
+        assert_eq!(requests[0].json()["model"], "all-minilm");
+        assert_eq!(requests[0].json()["input"], serde_json::json!(["hello"]));
+    }
+
+    #[test]
+    fn estimates_tokens_per_script() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcdefgh"), 2);
+        assert_eq!(estimate_tokens("abcdefghi"), 3);
+        // Non-ASCII characters cost a token each
+        assert_eq!(estimate_tokens("héllo"), 2);
+        assert_eq!(estimate_tokens("你好"), 2);
+    }
+
+    #[tokio::test]
+    async fn an_open_circuit_fails_fast() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::SERVICE_UNAVAILABLE)]).await;
+        let client = mock_client(&server)
+            .with_retry_policy(RetryPolicy {
+                max_retries: 0,
+                base_delay: Duration::from_millis(10),
+            })
+            .with_circuit_breaker(Arc::new(CircuitBreaker::new(2, Duration::from_secs(60))));
+
+        for _ in 0..2 {
+            client.chat("system", &[user_message("hi")]).await.unwrap_err();
+        }
+        let error = client.chat("system", &[user_message("hi")]).await.unwrap_err();
+
+        assert!(matches!(error.downcast_ref::<LlmError>(), Some(LlmError::Unavailable)), "{}", error);
+        assert_eq!(server.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn sends_images_as_content_parts_to_the_vision_model() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("A cat on a sofa"))]).await;
+        let client = mock_client(&server).with_vision_model("pixtral-12b-2409");
+
+        let reply = client
+            .chat_with_image("system", &[user_message("What is in this picture?")], "https://example.com/cat.png")
+            .await
+            .unwrap();
+
+        assert_eq!(reply, "A cat on a sofa");
+        let body = server.requests()[0].json();
+        assert_eq!(body["model"], "pixtral-12b-2409");
+        assert_eq!(
+            body["messages"],
+            serde_json::json!([
+                { "role": "system", "content": [{ "type": "text", "text": "system" }] },
+                { "role": "user", "content": [
+                    { "type": "text", "text": "What is in this picture?" },
+                    { "type": "image_url", "image_url": "https://example.com/cat.png" },
+                ] },
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn text_only_requests_stay_plain() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("hello"))]).await;
+        let client = mock_client(&server).with_vision_model("pixtral-12b-2409");
+
+        client.chat("system", &[user_message("hi")]).await.unwrap();
+
+        let body = server.requests()[0].json();
+        assert_ne!(body["model"], "pixtral-12b-2409");
+        assert_eq!(body["messages"][1], serde_json::json!({ "role": "user", "content": "hi" }));
+    }
+
+    #[tokio::test]
+    async fn images_need_a_vision_model() {
+        let server = MockServer::start(vec![MockResponse::json(chat_response("unused"))]).await;
+
+        let error = mock_client(&server)
+            .chat_with_image("system", &[user_message("What is this?")], "https://example.com/cat.png")
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "Image input is not enabled");
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+// Without the feature only the fallback wrapper remains, and nothing constructs it
+#![cfg_attr(not(feature = "local-embeddings"), allow(dead_code))]
+
+use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
+use tracing::warn;
+
+use crate::memory::EmbeddingModel;
+
+/// Name recorded with memories embedded by the local model
+pub const LOCAL_EMBEDDING_MODEL: &str = "local:bge-small-en-v1.5";
+
+/// Embeds with the remote model, and with a local one when that fails, so memories are
+/// still stored searchable during an outage. Local embeddings live in a different vector
+/// space; they are tagged with `LOCAL_EMBEDDING_MODEL` so they're only compared with each other.
+pub struct FallbackEmbedding {
+    primary: Arc<dyn EmbeddingModel + Send + Sync>,
+    fallback: Arc<dyn EmbeddingModel + Send + Sync>,
+}
+
+impl FallbackEmbedding {
+    pub fn new(
+        primary: Arc<dyn EmbeddingModel + Send + Sync>,
+        fallback: Arc<dyn EmbeddingModel + Send + Sync>,
+    ) -> Self {
+        Self { primary, fallback }
+    }
+}
+
+#[async_trait]
+impl EmbeddingModel for FallbackEmbedding {
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        Ok(self.embed_with_model(text).await?.0)
+    }
+
+    async fn embed_with_model(&self, text: &str) -> Result<(Vec<f32>, String)> {
+        match self.primary.embed_text(text).await {
+            Ok(embedding) => Ok((embedding, self.primary.name().to_string())),
+            Err(e) => {
+                warn!("Embedding with {} failed, using {}: {}", self.primary.name(), self.fallback.name(), e);
+                let embedding = self.fallback.embed_text(text).await?;
+                Ok((embedding, self.fallback.name().to_string()))
+            }
+        }
+    }
+
+    // Batches re-embed stored memories into the remote model's space, so they never fall back
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        self.primary.embed_batch(texts).await
+    }
+
+    fn name(&self) -> &str {
+        self.primary.name()
+    }
+
+    // Callers checking stored vectors expect the remote model's; local ones carry their own tag
+    fn dimension(&self) -> usize {
+        self.primary.dimension()
+    }
+}
+
+/// Wrap `primary` in a `FallbackEmbedding` backed by the local model. Loading the model can
+/// download it on first use; if that fails, `primary` is returned on its own.
+pub async fn with_local_fallback(
+    primary: Arc<dyn EmbeddingModel + Send + Sync>,
+) -> Arc<dyn EmbeddingModel + Send + Sync> {
+    #[cfg(feature = "local-embeddings")]
+    {
+        match LocalEmbedding::load().await {
+            Ok(local) => {
+                tracing::info!("Falling back to {} when {} fails", LOCAL_EMBEDDING_MODEL, primary.name());
+                Arc::new(FallbackEmbedding::new(primary, Arc::new(local)))
+            }
+            Err(e) => {
+                warn!("Failed to load the local embedding model; running without a fallback: {}", e);
+                primary
+            }
+        }
+    }
+    #[cfg(not(feature = "local-embeddings"))]
+    {
+        warn!("Built without the local-embeddings feature; running without an embedding fallback");
+        primary
+    }
+}
+
+/// BGE small, run on the CPU with fastembed
+#[cfg(feature = "local-embeddings")]
+pub struct LocalEmbedding {
+    model: Arc<fastembed::TextEmbedding>,
+}
+
+#[cfg(feature = "local-embeddings")]
+const LOCAL_EMBEDDING_DIMENSION: usize = 384;
+
+#[cfg(feature = "local-embeddings")]
+impl LocalEmbedding {
+    pub async fn load() -> Result<Self> {
+        let model = tokio::task::spawn_blocking(|| {
+            fastembed::TextEmbedding::try_new(fastembed::InitOptions::new(fastembed::EmbeddingModel::BGESmallENV15))
+        })
+        .await??;
+        Ok(Self { model: Arc::new(model) })
+    }
+}
+
+#[cfg(feature = "local-embeddings")]
+#[async_trait]
+impl EmbeddingModel for LocalEmbedding {
+    async fn embed_text(&self, text: &str) -> Result<Vec<f32>> {
+        let model = self.model.clone();
+        let text = text.to_string();
+
+        // Inference is CPU-bound, so keep it off the async workers
+        let mut embeddings = tokio::task::spawn_blocking(move || model.embed(vec![text], None)).await??;
+        embeddings.pop().ok_or_else(|| anyhow::anyhow!("Local embedding model returned no embedding"))
+    }
+
+    fn name(&self) -> &str {
+        LOCAL_EMBEDDING_MODEL
+    }
+
+    fn dimension(&self) -> usize {
+        LOCAL_EMBEDDING_DIMENSION
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockEmbedding;
+    use crate::memory::{MemoryBackend, MemoryStore};
+    use crate::test_support::memory;
+
+    // A remote model that is either up, answering with a fixed vector, or down
+    struct Remote {
+        up: bool,
+    }
+
+    #[async_trait]
+    impl EmbeddingModel for Remote {
+        async fn embed_text(&self, _text: &str) -> Result<Vec<f32>> {
+            if self.up {
+                Ok(vec![1.0, 0.0, 0.0])
+            } else {
+                Err(anyhow::anyhow!("503 Service Unavailable"))
+            }
+        }
+
+        fn name(&self) -> &str {
+            "mistral-embed"
+        }
+
+        fn dimension(&self) -> usize {
+            3
+        }
+    }
+
+    fn fallback(up: bool) -> FallbackEmbedding {
+        FallbackEmbedding::new(Arc::new(Remote { up }), Arc::new(MockEmbedding))
+    }
+
+    #[tokio::test]
+    async fn uses_the_remote_model_while_it_works() {
+        let (embedding, model) = fallback(true).embed_with_model("hello").await.unwrap();
+        assert_eq!(embedding, vec![1.0, 0.0, 0.0]);
+        assert_eq!(model, "mistral-embed");
+    }
+
+    #[tokio::test]
+    async fn falls_back_to_a_usable_local_embedding() {
+        let model = fallback(false);
+        let (embedding, name) = model.embed_with_model("deploys happen on fridays").await.unwrap();
+        assert_eq!(name, "mock");
+        assert_eq!(embedding, MockEmbedding.embed_text("deploys happen on fridays").await.unwrap());
+        // The wrapper still presents itself as the remote model
+        assert_eq!(model.name(), "mistral-embed");
+        assert_eq!(model.dimension(), 3);
+
+        // Stored with its own tag, the memory is found by local queries but never compared
+        // with the remote model's vectors
+        let store = MemoryStore::new(":memory:").unwrap();
+        let memory = memory("deploys happen on fridays")
+            .embedding(embedding.clone())
+            .embedding_model(&name)
+            .build();
+        store.store_memory(memory).await.unwrap();
+
+        let local = store.search_similar_memories("group:1", None, &embedding, "mock", 5).await.unwrap();
+        assert_eq!(local.len(), 1);
+        let remote = store
+            .search_similar_memories("group:1", None, &[1.0, 0.0, 0.0], "mistral-embed", 5)
+            .await
+            .unwrap();
+        assert!(remote.is_empty());
+    }
+
+    #[tokio::test]
+    async fn batches_never_fall_back() {
+        assert!(fallback(false).embed_batch(&["hello".to_string()]).await.is_err());
+    }
+}
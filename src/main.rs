@@ -6,31 +6,99 @@ use axum::{
     Router,
 };
 use dotenv::dotenv;
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use oc_bots_sdk::api::command::{CommandHandlerRegistry, CommandResponse};
-use oc_bots_sdk::api::definition::BotDefinition;
+use oc_bots_sdk::api::definition::{BotCommandDefinition, BotDefinition};
 use oc_bots_sdk::oc_api::client::ClientFactory;
+use oc_bots_sdk::types::{BotCommandContext, BotCommandScope};
 use oc_bots_sdk_offchain::{env, AgentRuntime};
+use std::collections::HashMap;
+use std::future::Future;
 use std::net::{Ipv4Addr, SocketAddr};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
-use tracing::{info, error};
+use tracing::{field, info, info_span, error, warn, Instrument, Span};
 use tracing_subscriber::fmt::format::FmtSpan;
 
+mod cache;
+mod chat_commands;
+mod chat_id;
+mod circuit_breaker;
+mod command_log;
 mod config;
+mod idempotency;
+mod karma;
+mod feedback;
+mod quotes;
 mod commands;
 mod memory;
+mod memory_export;
+mod moderation;
 mod llm;
+mod local_embeddings;
 mod agent;
+mod rate_limit;
+mod reminders;
+mod self_test;
+mod summaries;
+mod time_parse;
+mod timezones;
+mod usage;
+mod webhook;
+mod tools;
+#[cfg(test)]
+mod test_support;
 
-use crate::agent::Agent;
-use crate::llm::{MistralClient, MistralEmbedding};
-use crate::memory::MemoryStore;
+use crate::agent::{Agent, AgentConfig};
+use crate::chat_commands::ChatCommandStore;
+use crate::chat_id::{canonical_chat_id, chat_allowed};
+use crate::circuit_breaker::CircuitBreaker;
+use crate::command_log::{CommandLogEntry, CommandLogStore};
+use crate::commands::registry::CommandRegistrations;
+use crate::tools::{HttpTool, ToolRegistry};
+use crate::config::{CommandPermissionConfig, LlmProviderKind, MessagesConfig};
+use crate::idempotency::IdempotencyGuard;
+use crate::karma::KarmaStore;
+use crate::feedback::FeedbackStore;
+use crate::quotes::QuoteStore;
+use crate::llm::{LlmProvider, DEFAULT_CHAT_MODEL, MistralClient, MistralEmbedding, MockEmbedding, MockLlm, RetryPolicy};
+use crate::memory::{EmbeddingModel, MemoryBackend, MemoryStore};
+use crate::rate_limit::RateLimiter;
+use crate::reminders::ReminderStore;
+use crate::summaries::SummaryStore;
+use crate::timezones::TimezoneStore;
+use crate::usage::{UsageContext, UsageStore};
+use crate::webhook::WebhookNotifier;
 
 // Structure to hold application state
 struct AppState {
     oc_public_key: String,
+    // Chats commands may run in; empty allows all
+    allowed_chats: Vec<String>,
+    // Commands admins turned off in particular chats
+    chat_commands: Option<Arc<ChatCommandStore>>,
+    // Served in the bot definition, with the build version and enabled features
+    description: String,
     commands: CommandHandlerRegistry<AgentRuntime>,
+    memory_store: Option<Arc<dyn MemoryBackend>>,
+    mistral_key_configured: bool,
+    started_at: Instant,
+    metrics: PrometheusHandle,
+    rate_limiter: RateLimiter,
+    idempotency: IdempotencyGuard,
+    command_log: Option<Arc<CommandLogStore>>,
+    // How long a command may run before the request is answered with 504
+    command_timeout: Duration,
+    // Reply formatting, which may differ per chat
+    messages: MessagesConfig,
+    // Roles configured for individual commands, applied to the definition and checked on execution
+    command_permissions: HashMap<String, CommandPermissionConfig>,
+    admins: Vec<String>,
+    // Extra command names and the command each one runs
+    aliases: HashMap<String, String>,
 }
 
 #[tokio::main]
@@ -42,14 +110,13 @@ async fn main() -> std::io::Result<()> {
     let config_file_path = std::env::var("CONFIG_FILE").unwrap_or("./config.toml".to_string());
     println!("Config file path: {:?}", config_file_path);
 
-    // Load & parse config
+    // Load, parse & validate config
     let config = config::Config::from_file(&config_file_path).map_err(|e| {
         std::io::Error::new(
             std::io::ErrorKind::Other,
             format!("Failed to load config file: {}", e),
         )
     })?;
-    println!("Config: {:?}", config);
 
     // Setup logging
     tracing_subscriber::fmt()
@@ -58,32 +125,239 @@ async fn main() -> std::io::Result<()> {
         .init();
 
     info!("Starting KarmaSpark bot for OpenChat");
+    let started_at = Instant::now();
 
-    // Get Mistral API key
-    let mistral_api_key = match config.mistral_api_key() {
-        Ok(key) => key,
+    // Install the Prometheus recorder; metrics are only rendered when /metrics is scraped
+    let metrics_handle = PrometheusBuilder::new().install_recorder().map_err(|e| {
+        std::io::Error::new(
+            std::io::ErrorKind::Other,
+            format!("Failed to install metrics recorder: {}", e),
+        )
+    })?;
+
+    let db_path = config.sqlite_db_path.clone().unwrap_or("./karmaspark.db".to_string());
+    
+    // Initialize token usage store
+    let usage_store = match UsageStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize usage store: {}", e);
+            None
+        }
+    };
+    
+    // Outbound notifications for external systems, if configured
+    let webhook = config.webhook.url.as_ref().map(|url| {
+        info!("Sending {} events to webhook", config.webhook.events.join(", "));
+        Arc::new(WebhookNotifier::new(url, &config.webhook.events, Duration::from_secs(config.webhook.timeout_secs)))
+    });
+    
+    // Initialize reminder store, picking up reminders set before a restart
+    let reminder_store = match ReminderStore::new(&db_path) {
+        Ok(store) => {
+            let store = Arc::new(store);
+            match reminders::resume_pending(store.clone(), webhook.clone()).await {
+                Ok(count) if count > 0 => info!("Resumed {} pending reminders", count),
+                Ok(_) => {}
+                Err(e) => error!("Failed to resume pending reminders: {}", e),
+            }
+            Some(store)
+        }
+        Err(e) => {
+            error!("Failed to initialize reminder store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize per-user timezones
+    let timezone_store = match TimezoneStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize timezone store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize karma points
+    let karma_store = match KarmaStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize karma store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize rolling chat summaries
+    let summary_store = match SummaryStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize summary store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize per-chat command toggles
+    let chat_commands = match ChatCommandStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize chat command store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize user feedback
+    let feedback_store = match FeedbackStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize feedback store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize saved chat quotes
+    let quote_store = match QuoteStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
+        Err(e) => {
+            error!("Failed to initialize quote store: {}", e);
+            None
+        }
+    };
+    
+    // Initialize command audit log
+    let command_log = match CommandLogStore::new(&db_path) {
+        Ok(store) => Some(Arc::new(store)),
         Err(e) => {
-            error!("Failed to get Mistral API key: {}", e);
+            error!("Failed to initialize command log: {}", e);
+            None
+        }
+    };
+    
+    let retry_policy = RetryPolicy {
+        max_retries: config.llm.max_retries,
+        base_delay: Duration::from_millis(config.llm.retry_base_delay_ms),
+    };
+    
+    // One limit shared by chat and embedding requests
+    let concurrency_limit = Arc::new(Semaphore::new(config.llm.max_concurrent_requests));
+    
+    // Chat and embeddings may be served by different APIs, so each gets its own breaker
+    let circuit_breaker = || {
+        (config.llm.circuit_failure_threshold > 0).then(|| {
+            Arc::new(CircuitBreaker::new(
+                config.llm.circuit_failure_threshold,
+                Duration::from_secs(config.llm.circuit_cooldown_secs),
+            ))
+        })
+    };
+    
+    // Initialize LLM client. Without a Mistral key the bot runs in degraded mode,
+    // with only the commands that don't need an LLM.
+    let llm_client: Option<Arc<dyn LlmProvider>> = match config.llm.provider {
+        LlmProviderKind::Mock => {
+            info!("Using mock LLM provider; responses are canned");
+            Some(Arc::new(MockLlm))
+        }
+        LlmProviderKind::Mistral => match config.mistral_api_key() {
+            Ok(mistral_api_key) => {
+                let mut llm_client = MistralClient::new(&mistral_api_key)
+                    .with_retry_policy(retry_policy)
+                    .with_concurrency_limit(concurrency_limit.clone());
+                if let Some(store) = &usage_store {
+                    llm_client = llm_client.with_usage_store(store.clone());
+                }
+                if let Some(breaker) = circuit_breaker() {
+                    llm_client = llm_client.with_circuit_breaker(breaker);
+                }
+                if !config.llm.fallback_models.is_empty() {
+                    info!("Falling back to {} when {} fails", config.llm.fallback_models.join(", "), DEFAULT_CHAT_MODEL);
+                    llm_client = llm_client.with_fallback_models(&config.llm.fallback_models);
+                }
+                if !config.llm.system_prompt_prefix.trim().is_empty() {
+                    info!("Prefixing every system prompt ({} characters)", config.llm.system_prompt_prefix.trim().chars().count());
+                    llm_client = llm_client.with_system_prompt_prefix(&config.llm.system_prompt_prefix);
+                }
+                if config.agent.enable_vision {
+                    llm_client = llm_client.with_vision_model(&config.llm.vision_model);
+                }
+                if config.llm.log_prompts {
+                    info!("Logging LLM prompts at DEBUG (redacted: {})", config.llm.redact_logged_prompts);
+                    llm_client = llm_client.with_prompt_logging(config.llm.redact_logged_prompts);
+                }
+                if config.llm.cache_enabled {
+                    info!("LLM response cache enabled (capacity {}, ttl {}s)", config.llm.cache_capacity, config.llm.cache_ttl_secs);
+                    llm_client = llm_client.with_cache(
+                        config.llm.cache_capacity,
+                        Duration::from_secs(config.llm.cache_ttl_secs),
+                    );
+                }
+                Some(Arc::new(llm_client))
+            }
+            Err(e) => {
+                warn!(
+                    "{} (set mistral_api_key or MISTRAL_API_KEY); starting in degraded mode without LLM-backed commands",
+                    e
+                );
+                None
+            }
+        },
+    };
+    
+    // Initialize embedding model, configured separately from the chat provider
+    let embedding_model: Option<Arc<dyn EmbeddingModel + Send + Sync>> = match config.llm.provider {
+        LlmProviderKind::Mock => Some(Arc::new(MockEmbedding)),
+        LlmProviderKind::Mistral => match config.embeddings_api_key() {
+            Ok(api_key) => {
+                info!("Using embedding model {} at {}", config.embeddings.model, config.embeddings.base_url);
+                let mut embedding_model = MistralEmbedding::new(&api_key)
+                    .with_base_url(&config.embeddings.base_url)
+                    .with_model(&config.embeddings.model)
+                    .with_retry_policy(retry_policy)
+                    .with_concurrency_limit(concurrency_limit);
+                if config.llm.embedding_cache_capacity > 0 {
+                    embedding_model = embedding_model.with_cache(config.llm.embedding_cache_capacity);
+                }
+                if let Some(breaker) = circuit_breaker() {
+                    embedding_model = embedding_model.with_circuit_breaker(breaker);
+                }
+                if config.embeddings.local_fallback {
+                    Some(local_embeddings::with_local_fallback(Arc::new(embedding_model)).await)
+                } else {
+                    Some(Arc::new(embedding_model))
+                }
+            }
+            Err(e) => {
+                warn!("{}; memory commands are disabled", e);
+                None
+            }
+        },
+    };
+    
+    // Catch a bad key or base URL now rather than on the first command
+    if config.llm.startup_self_test {
+        let problems = self_test::run(llm_client.as_deref(), embedding_model.as_deref()).await;
+        if !problems.is_empty() && config.llm.strict_startup {
             return Err(std::io::Error::new(
                 std::io::ErrorKind::Other,
-                "Mistral API key not found. Please set it in config.toml or MISTRAL_API_KEY environment variable.",
+                format!("Startup self-test failed: {}", problems.join("; ")),
             ));
         }
-    };
-
-    // Initialize LLM client
-    let llm_client = Arc::new(MistralClient::new(&mistral_api_key));
-    
-    // Initialize embedding model
-    let embedding_model = Arc::new(MistralEmbedding::new(&mistral_api_key));
+    }
     
     // Initialize memory store if enabled
-    let memory_store = if config.agent.enable_memory {
-        let db_path = config.sqlite_db_path.clone().unwrap_or("./karmaspark.db".to_string());
-        match MemoryStore::new(&db_path) {
-            Ok(store) => {
-                info!("Memory store initialized with database at {}", db_path);
-                Some(Arc::new(store))
+    let memory_store: Option<Arc<dyn MemoryBackend>> = if config.agent.enable_memory {
+        let memory_db_path = config.memory_db_path(&db_path);
+        match MemoryStore::new(&memory_db_path) {
+            Ok(mut store) => {
+                info!("Memory store initialized with database at {}", memory_db_path);
+                if config.agent.memory_recency_half_life_days > 0 {
+                    store = store.with_recency_half_life(chrono::Duration::days(config.agent.memory_recency_half_life_days as i64));
+                }
+                if config.agent.max_memory_items > 0 {
+                    store = store.with_max_items(config.agent.max_memory_items);
+                }
+                let store: Arc<dyn MemoryBackend> = Arc::new(store);
+                memory::spawn_retention_cleanup(store.clone(), config.agent.memory_retention_days);
+                Some(store)
             }
             Err(e) => {
                 error!("Failed to initialize memory store: {}", e);
@@ -95,10 +369,57 @@ async fn main() -> std::io::Result<()> {
         None
     };
     
+    // Models in use, as reported by /ping
+    let mut models = Vec::new();
+    if llm_client.is_some() {
+        match config.llm.provider {
+            LlmProviderKind::Mock => models.push(("chat", "mock".to_string())),
+            LlmProviderKind::Mistral => {
+                models.push(("chat", DEFAULT_CHAT_MODEL.to_string()));
+                for model in &config.llm.fallback_models {
+                    models.push(("fallback", model.clone()));
+                }
+                if config.agent.enable_vision {
+                    models.push(("vision", config.llm.vision_model.clone()));
+                }
+            }
+        }
+    }
+    if embedding_model.is_some() {
+        let model = match config.llm.provider {
+            LlmProviderKind::Mock => "mock".to_string(),
+            LlmProviderKind::Mistral => config.embeddings.model.clone(),
+        };
+        models.push(("embeddings", model));
+    }
+    
     // Initialize agent
-    let agent = Arc::new(Agent::new(
-        llm_client.as_ref().clone(),
-    ));
+    let agent = llm_client.as_ref().map(|llm_client| {
+        let mut agent = Agent::new(llm_client.clone()).with_config(AgentConfig {
+            step_delay: Duration::from_millis(config.agent.agent_step_delay_ms),
+            history_turns: config.agent.conversation_turns,
+            timeout: Duration::from_secs(config.agent.ask_timeout_secs),
+            persona: config.agent.persona.clone(),
+            suggest_follow_ups: config.agent.suggest_follow_ups,
+            context_token_budget: config.agent.context_token_budget,
+            max_run_retries: config.agent.max_run_retries,
+            fast_path_max_chars: config.agent.fast_path_max_chars,
+            show_progress: config.agent.show_progress,
+            ..AgentConfig::default()
+        });
+        if let Some(store) = &memory_store {
+            agent = agent.with_memory_store(store.clone());
+        }
+        if !config.tools.is_empty() {
+            let mut tools = ToolRegistry::with_builtin_tools(llm_client.clone());
+            for tool in &config.tools {
+                info!("Registering HTTP tool {}", tool.name);
+                tools.register(Arc::new(HttpTool::new(tool.clone())));
+            }
+            agent = agent.with_tools(tools);
+        }
+        Arc::new(agent)
+    });
 
     // Build agent for OpenChat communication
     let oc_agent = oc_bots_sdk_offchain::build_agent(config.ic_url.clone(), &config.pem_file).await;
@@ -112,45 +433,168 @@ async fn main() -> std::io::Result<()> {
     })?);
     let client_factory = Arc::new(ClientFactory::new(runtime));
 
-    // Create command registry and register commands
-    let mut command_registry = CommandHandlerRegistry::new(client_factory);
-    
-    // Register the original echo command
-    command_registry = command_registry.register(commands::echo::Echo);
-    
-    // Register new commands
-    
-    // Ask command
-    command_registry = command_registry.register(commands::ask::Ask {
-        agent: agent.clone(),
-    });
-    
-    // Summarize command
-    command_registry = command_registry.register(commands::summarize::Summarize {
-        llm: llm_client.clone(),
-    });
-    
-    // RemindMe command
-    command_registry = command_registry.register(commands::remindme::RemindMe);
-    
-    // Moderate command
-    if config.agent.enable_moderation {
-        command_registry = command_registry.register(commands::moderate::Moderate {
-            llm: llm_client.clone(),
-        });
-    }
+    // Create command registry and register the enabled commands
+    let weather_api_key = if config.weather.enabled {
+        match config.weather_api_key() {
+            Ok(api_key) => Some(api_key),
+            Err(e) => {
+                error!("Weather command disabled: {}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
     
-    // Memory command
-    if config.agent.enable_memory && memory_store.is_some() {
-        command_registry = command_registry.register(commands::memory::MemoryCmd {
-            memory_store: memory_store.clone().unwrap(),
-            embedding_model: embedding_model,
-        });
-    }
+    let command_registry = CommandRegistrations::new()
+        .add("echo", config.agent.enable_echo, Some(commands::echo::Echo::new(config.input_limits.echo)))
+        .add("ask", true, agent.map(|agent| {
+            let ask = commands::ask::Ask::new(agent, config.messages.visibility("ask"), config.input_limits.ask);
+            if config.agent.enable_vision {
+                ask.with_image_input()
+            } else {
+                ask
+            }
+        }))
+        .add("summarize", true, llm_client.clone().map(|llm| {
+            let summarize = commands::summarize::Summarize::new(llm, config.messages.visibility("summarize"), config.input_limits.summarize);
+            match &summary_store {
+                Some(store) => summarize.with_summary_store(store.clone()),
+                None => summarize,
+            }
+        }))
+        .add("summarizeurl", true, llm_client.clone().map(|llm| commands::summarizeurl::SummarizeUrl {
+            http: reqwest::Client::new(),
+            llm,
+            visibility: config.messages.visibility("summarizeurl"),
+        }))
+        .add("remindme", true, reminder_store.clone().map(|store| commands::remindme::RemindMe {
+            store,
+            max_active_per_user: config.reminders.max_active_per_user,
+            timezones: timezone_store.clone(),
+            webhook: webhook.clone(),
+        }))
+        .add("poll", true, Some(commands::poll::Poll))
+        .add("roll", true, Some(commands::roll::Roll))
+        .add("moderate", config.agent.enable_moderation, llm_client.clone().map(|llm| {
+            let moderate = commands::moderate::Moderate::new(llm, config.messages.visibility("moderate"), config.input_limits.moderate);
+            match &webhook {
+                Some(webhook) => moderate.with_webhook(webhook.clone()),
+                None => moderate,
+            }
+        }))
+        .add("classify", true, llm_client.clone().map(|llm| {
+            commands::classify::Classify::new(llm, config.messages.visibility("classify"), config.input_limits.classify)
+        }))
+        .add("memory", config.agent.enable_memory, memory_store.clone().zip(embedding_model.clone()).map(|(store, embedding_model)| {
+            commands::memory::MemoryCmd {
+                memory_store: store,
+                embedding_model,
+                timezones: timezone_store.clone(),
+            }
+        }))
+        .add("usage", true, usage_store.clone().map(|store| commands::usage::Usage {
+            usage_store: store,
+            admins: config.admins.clone(),
+            prompt_cost_per_million: config.llm.prompt_cost_per_million,
+            completion_cost_per_million: config.llm.completion_cost_per_million,
+        }))
+        .add("define", true, memory_store.clone().map(|store| commands::define::Define {
+            memory_store: store,
+        }))
+        .add("history", true, memory_store.clone().map(|store| commands::history::History {
+            memory_store: store,
+            timezones: timezone_store.clone(),
+        }))
+        .add("persona", true, memory_store.clone().map(|store| commands::persona::Persona {
+            memory_store: store,
+            admins: config.admins.clone(),
+        }))
+        .add("export", true, memory_store.clone().map(|store| commands::export::Export {
+            memory_store: store,
+            admins: config.admins.clone(),
+        }))
+        .add("import", true, memory_store.clone().map(|store| {
+            commands::import::Import::new(store, embedding_model.clone(), config.admins.clone(), config.input_limits.import)
+        }))
+        .add("reembed", true, memory_store.clone().zip(embedding_model.clone()).map(|(store, embedding_model)| {
+            commands::reembed::Reembed {
+                memory_store: store,
+                embedding_model,
+                admins: config.admins.clone(),
+            }
+        }))
+        .add("stats", true, Some(commands::stats::Stats {
+            memory_store: memory_store.clone(),
+            usage_store: usage_store.clone(),
+            feedback_store: feedback_store.clone(),
+            admins: config.admins.clone(),
+        }))
+        .add("weather", config.weather.enabled, weather_api_key.map(|api_key| commands::weather::Weather {
+            http: reqwest::Client::new(),
+            api_url: config.weather.api_url.clone(),
+            api_key,
+            units: config.weather.units.clone(),
+        }))
+        .add("timezone", true, timezone_store.clone().map(|store| commands::timezone::Timezone { store }))
+        .add("karma", true, karma_store.clone().map(|store| commands::karma::Karma {
+            store,
+            cooldown: chrono::Duration::seconds(config.karma.cooldown_secs as i64),
+        }))
+        .add("feedback", true, feedback_store.clone().map(|store| commands::feedback::Feedback { store }))
+        .add("quote", true, quote_store.clone().map(|store| commands::quote::QuoteCmd { store }))
+        .add("ping", true, Some(commands::ping::Ping { started_at, models }))
+        .add("forgetme", true, Some(commands::forgetme::ForgetMe {
+            memory_store: memory_store.clone(),
+            usage_store: usage_store.clone(),
+            karma_store,
+            feedback_store,
+            reminder_store,
+            timezone_store,
+            quote_store,
+            command_log: command_log.clone(),
+        }))
+        .add("toggle", true, chat_commands.clone().map(|store| commands::toggle::Toggle {
+            store,
+            admins: config.admins.clone(),
+        }))
+        .register_all(CommandHandlerRegistry::new(client_factory), &config.commands, &config.aliases);
+
+    // Features worth knowing about when checking which build is deployed
+    let features: Vec<&str> = [
+        ("llm", llm_client.is_some()),
+        ("planning", config.agent.enable_agent_planning),
+        ("memory", memory_store.is_some()),
+        ("summarization", config.agent.enable_summarization),
+        ("moderation", config.agent.enable_moderation),
+        ("follow-ups", config.agent.suggest_follow_ups),
+        ("vision", config.agent.enable_vision),
+        ("weather", config.weather.enabled),
+    ]
+    .into_iter()
+    .filter_map(|(feature, enabled)| enabled.then_some(feature))
+    .collect();
+    let description = bot_description(&features);
+    info!("{}", description);
 
     let app_state = AppState {
-        oc_public_key: config.oc_public_key,
+        oc_public_key: config.oc_public_key.clone(),
+        allowed_chats: config.allowed_chats.clone(),
+        chat_commands,
+        description,
         commands: command_registry,
+        memory_store,
+        mistral_key_configured: config.mistral_api_key().is_ok(),
+        started_at,
+        metrics: metrics_handle,
+        rate_limiter: RateLimiter::new(config.rate_limits.clone()),
+        idempotency: IdempotencyGuard::new(),
+        command_log,
+        command_timeout: Duration::from_secs(config.command_timeout_secs),
+        messages: config.messages.clone(),
+        command_permissions: config.command_permissions.clone(),
+        admins: config.admins.clone(),
+        aliases: config.aliases.clone(),
     };
 
     // Create router with endpoints
@@ -159,6 +603,8 @@ async fn main() -> std::io::Result<()> {
         .route("/bot_definition", get(bot_definition))
         .route("/execute", post(execute_command))
         .route("/execute_command", post(execute_command))
+        .route("/health", get(health))
+        .route("/metrics", get(render_metrics))
         .layer(CorsLayer::permissive())
         .layer(TraceLayer::new_for_http())
         .with_state(Arc::new(app_state));
@@ -175,12 +621,54 @@ async fn main() -> std::io::Result<()> {
     Ok(())
 }
 
+// The SDK's definition has no version field, so it goes in the description
+fn bot_description(features: &[&str]) -> String {
+    let features = if features.is_empty() {
+        "none".to_string()
+    } else {
+        features.join(", ")
+    };
+    format!(
+        "KarmaSpark - An agentic AI bot with memory, planning, and intelligent assistance (v{}; features: {})",
+        env!("CARGO_PKG_VERSION"),
+        features
+    )
+}
+
+// Give commands, and their aliases, the stricter roles configured for them
+fn apply_command_permissions(
+    commands: &mut [BotCommandDefinition],
+    permissions: &HashMap<String, CommandPermissionConfig>,
+    aliases: &HashMap<String, String>,
+) {
+    for command in commands {
+        let name = aliases.get(&command.name).unwrap_or(&command.name);
+        if let Some(permission) = permissions.get(name) {
+            command.default_role = Some(permission.role.chat_role());
+        }
+    }
+}
+
+// Whether the command is configured admin-only and the user isn't one of the bot's admins
+fn refused_as_admin_only(
+    permissions: &HashMap<String, CommandPermissionConfig>,
+    admins: &[String],
+    command: &str,
+    user_id: &str,
+) -> bool {
+    let admins_only = permissions
+        .get(command)
+        .is_some_and(CommandPermissionConfig::admins_only);
+    admins_only && !admins.iter().any(|admin| admin == user_id)
+}
+
 // Bot definition endpoint
 async fn bot_definition(State(state): State<Arc<AppState>>) -> (StatusCode, Bytes) {
-    let commands = state.commands.definitions();
+    let mut commands = state.commands.definitions();
+    apply_command_permissions(&mut commands, &state.command_permissions, &state.aliases);
     
     let definition = BotDefinition {
-        description: "KarmaSpark - An agentic AI bot with memory, planning, and intelligent assistance".to_string(),
+        description: state.description.clone(),
         commands,
         autonomous_config: None,
     };
@@ -191,11 +679,109 @@ async fn bot_definition(State(state): State<Arc<AppState>>) -> (StatusCode, Byte
     )
 }
 
+// Health check endpoint reporting the state of downstream dependencies
+async fn health(State(state): State<Arc<AppState>>) -> (StatusCode, Bytes) {
+    let database = match &state.memory_store {
+        Some(store) => Some(store.ping().await),
+        None => None,
+    };
+    let (status, body) = health_report(database, state.mistral_key_configured, state.started_at.elapsed());
+    (status, Bytes::from(serde_json::to_vec(&body).unwrap()))
+}
+
+// Status and body of the health check, given the database ping's result (None when memory is disabled)
+fn health_report(
+    database: Option<anyhow::Result<()>>,
+    mistral_key_configured: bool,
+    uptime: Duration,
+) -> (StatusCode, serde_json::Value) {
+    let (database, healthy) = match database {
+        Some(Ok(())) => ("ok".to_string(), true),
+        Some(Err(e)) => {
+            error!("Health check database ping failed: {}", e);
+            (format!("error: {}", e), false)
+        }
+        None => ("disabled".to_string(), true),
+    };
+
+    let body = serde_json::json!({
+        "status": if healthy { "ok" } else { "unavailable" },
+        "database": database,
+        "mistral_key_configured": mistral_key_configured,
+        "uptime_seconds": uptime.as_secs(),
+    });
+
+    let status = if healthy {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (status, body)
+}
+
+// Duration and outcome of a command that ran to completion
+fn record_command_metrics(command: &str, elapsed: Duration, succeeded: bool) {
+    metrics::histogram!("karmaspark_command_duration_seconds", "command" => command.to_string())
+        .record(elapsed.as_secs_f64());
+    if !succeeded {
+        metrics::counter!("karmaspark_command_errors_total", "command" => command.to_string()).increment(1);
+    }
+}
+
+// Prometheus metrics endpoint
+async fn render_metrics(State(state): State<Arc<AppState>>) -> (StatusCode, String) {
+    (StatusCode::OK, state.metrics.render())
+}
+
+// Who is running which command, extracted from the JWT
+struct CommandIdentity {
+    command: String,
+    user_id: String,
+    chat_id: String,
+    // Identifies this invocation, so retries of it can be recognised
+    invocation_id: String,
+}
+
+// Command name, initiator and chat from the JWT, used for instrumentation, rate limiting
+// and usage attribution. The registry parses and validates the token again itself, so
+// failures here are not fatal.
+fn command_identity(jwt: &str, public_key: &str) -> Option<CommandIdentity> {
+    let context = BotCommandContext::parse(jwt.to_string(), public_key, env::now()).ok()?;
+    let chat_id = canonical_chat_id(&context.scope);
+    
+    // Chat commands carry the id of the message the bot will reply with. Community
+    // commands have none, but a retried request resends the same token.
+    let invocation_id = match &context.scope {
+        BotCommandScope::Chat(chat_details) => format!("{}:{:?}", chat_id, chat_details.message_id),
+        BotCommandScope::Community(_) => jwt.to_string(),
+    };
+    
+    Some(CommandIdentity {
+        chat_id,
+        invocation_id,
+        command: context.command.name,
+        user_id: context.command.initiator.to_string(),
+    })
+}
+
 // Command execution endpoint
 async fn execute_command(
     State(state): State<Arc<AppState>>, 
     headers: HeaderMap,
 ) -> (StatusCode, Bytes) {
+    // Every log line of this execution, including LLM and memory calls, carries the request id
+    let request_id = uuid::Uuid::new_v4().to_string();
+    let span = execute_span(&request_id);
+    handle_execute(&state, &headers, request_id).instrument(span).await
+}
+
+// The span around one execution; command and user are recorded once the JWT is read
+fn execute_span(request_id: &str) -> Span {
+    info_span!("execute", request_id = %request_id, command = field::Empty, user_id = field::Empty)
+}
+
+async fn handle_execute(state: &AppState, headers: &HeaderMap, request_id: String) -> (StatusCode, Bytes) {
     info!("=== Command Execution Start ===");
     info!("Headers: {:?}", headers);
     
@@ -227,12 +813,171 @@ async fn execute_command(
 
     info!("JWT length: {}", jwt.len());
     
+    // Aliases run the command they stand for, and share its rate limits, toggles and permissions
+    let identity = command_identity(&jwt, &state.oc_public_key).map(|mut identity| {
+        if let Some(target) = state.aliases.get(&identity.command) {
+            identity.command = target.clone();
+        }
+        identity
+    });
+    let command = identity
+        .as_ref()
+        .map_or_else(|| "unknown".to_string(), |identity| identity.command.clone());
+    Span::current().record("command", command.as_str());
+    if let Some(identity) = &identity {
+        Span::current().record("user_id", identity.user_id.as_str());
+    }
+    
+    // Private deployments only answer in the chats they were set up for
+    if !state.allowed_chats.is_empty() {
+        let allowed = identity
+            .as_ref()
+            .is_some_and(|identity| chat_allowed(&identity.chat_id, &state.allowed_chats));
+        if !allowed {
+            info!("Refusing command {} outside the allowed chats", command);
+            metrics::counter!("karmaspark_command_refused_total", "command" => command).increment(1);
+            return json_error(StatusCode::FORBIDDEN, "Sorry, KarmaSpark isn't available in this chat.", &request_id);
+        }
+    }
+    
+    // Admins can turn commands off per chat; the bot definition is shared by every chat,
+    // so turned-off commands are still listed there and refused here
+    if let (Some(store), Some(identity)) = (&state.chat_commands, &identity) {
+        match store.is_disabled(&identity.chat_id, &identity.command).await {
+            Ok(true) => {
+                info!("Refusing command {} turned off in {}", command, identity.chat_id);
+                metrics::counter!("karmaspark_command_refused_total", "command" => command.clone()).increment(1);
+                return json_error(StatusCode::FORBIDDEN, &format!("The /{} command is turned off in this chat.", command), &request_id);
+            }
+            Ok(false) => {}
+            Err(e) => warn!("Failed to check whether {} is turned off: {}", command, e),
+        }
+    }
+    
+    // OpenChat hides commands from members below their role, but the request could still
+    // come from anyone, so admin-only commands are checked against the bot's own admins
+    if let Some(identity) = &identity {
+        if refused_as_admin_only(&state.command_permissions, &state.admins, &identity.command, &identity.user_id) {
+            info!("Refusing admin-only command {} from {}", command, identity.user_id);
+            metrics::counter!("karmaspark_command_refused_total", "command" => command.clone()).increment(1);
+            return json_error(StatusCode::FORBIDDEN, &format!("Only admins can use /{}.", command), &request_id);
+        }
+    }
+    metrics::counter!("karmaspark_command_invocations_total", "command" => command.clone()).increment(1);
+    
+    // Answer retries of an invocation with its earlier response instead of running it again
+    match identity.as_ref().map(|identity| identity.invocation_id.clone()) {
+        Some(invocation_id) => {
+            state
+                .idempotency
+                .run(invocation_id, run_command(state, &jwt, identity, command, &request_id))
+                .await
+        }
+        None => run_command(state, &jwt, identity, command, &request_id).await,
+    }
+}
+
+// Run the command, or abandon it once `limit` has passed and answer with a 504
+async fn within_command_timeout<F: Future>(
+    limit: Duration,
+    command: &str,
+    request_id: &str,
+    execution: F,
+) -> Result<F::Output, (StatusCode, Bytes)> {
+    let started = Instant::now();
+    tokio::time::timeout(limit, execution).await.map_err(|_| {
+        error!("Command {} stalled and was abandoned after {:?} (request {})", command, started.elapsed(), request_id);
+        json_error(StatusCode::GATEWAY_TIMEOUT, "The command took too long to complete. Please try again later.", request_id)
+    })
+}
+
+async fn run_command(
+    state: &AppState,
+    jwt: &str,
+    identity: Option<CommandIdentity>,
+    command: String,
+    request_id: &str,
+) -> (StatusCode, Bytes) {
+    // Enforce per-user rate limits before dispatching
+    if let Some(identity) = &identity {
+        if !state.rate_limiter.check(&identity.user_id, &identity.command) {
+            info!("Rate limit exceeded for user {} on command {}", identity.user_id, identity.command);
+            metrics::counter!("karmaspark_command_rate_limited_total", "command" => command.clone()).increment(1);
+            return (StatusCode::TOO_MANY_REQUESTS, Bytes::new());
+        }
+    }
+    
+    let started = Instant::now();
+    
     // Parse command data from the JWT payload
-    let result = state
+    let execution = state
         .commands
-        .execute(&jwt, &state.oc_public_key, env::now())
-        .await;
+        .execute(jwt, &state.oc_public_key, env::now());
+    
+    // Attribute any LLM token usage to this command's chat and user
+    let usage_context = identity.as_ref().map(|identity| UsageContext {
+        chat_id: identity.chat_id.clone(),
+        user_id: identity.user_id.clone(),
+        command: identity.command.clone(),
+    });
+    let reply_format = commands::ReplyFormat {
+        markdown: identity
+            .as_ref()
+            .map_or(state.messages.markdown, |identity| state.messages.markdown(&identity.chat_id)),
+        max_length: state.messages.max_length,
+    };
+    let execution = commands::track_rate_limit(commands::with_reply_format(reply_format, async move {
+        match usage_context {
+            Some(context) => context.scope(execution).await,
+            None => execution.await,
+        }
+    }));
+    let (mut result, llm_rate_limited) = match within_command_timeout(state.command_timeout, &command, request_id, execution).await {
+        Ok(outcome) => outcome,
+        Err(response) => {
+            let elapsed = started.elapsed();
+            metrics::counter!("karmaspark_command_timeouts_total", "command" => command.clone()).increment(1);
+            metrics::counter!("karmaspark_command_errors_total", "command" => command).increment(1);
+            if let (Some(log), Some(identity)) = (&state.command_log, identity) {
+                log.record_in_background(CommandLogEntry {
+                    user_id: identity.user_id,
+                    chat_id: identity.chat_id,
+                    command: identity.command,
+                    success: false,
+                    latency_ms: elapsed.as_millis() as u64,
+                    error: Some(format!("Timed out after {}s", state.command_timeout.as_secs())),
+                });
+            }
+            return response;
+        }
+    };
+
+    // The command gave up because the LLM API is throttling us; let OpenChat back off
+    if llm_rate_limited {
+        info!("Command {} was rate limited by the LLM API", command);
+        metrics::counter!("karmaspark_llm_rate_limited_total", "command" => command.clone()).increment(1);
+        result = CommandResponse::TooManyRequests;
+    }
         
+    let elapsed = started.elapsed();
+    record_command_metrics(&command, elapsed, matches!(result, CommandResponse::Success(_)));
+    
+    if let (Some(log), Some(identity)) = (&state.command_log, identity) {
+        let error = match &result {
+            CommandResponse::Success(_) => None,
+            CommandResponse::BadRequest(r) => Some(format!("Bad request: {:?}", r)),
+            CommandResponse::InternalError(err) => Some(format!("Internal error: {:?}", err)),
+            CommandResponse::TooManyRequests => Some("Too many requests".to_string()),
+        };
+        log.record_in_background(CommandLogEntry {
+            user_id: identity.user_id,
+            chat_id: identity.chat_id,
+            command: identity.command,
+            success: error.is_none(),
+            latency_ms: elapsed.as_millis() as u64,
+            error,
+        });
+    }
     info!("Command execution result: {:?}", result);
     info!("=== Command Execution End ===");
     
@@ -249,15 +994,206 @@ async fn execute_command(
             )
         }
         CommandResponse::InternalError(err) => {
-            error!("Internal error: {:?}", err);
-            (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Bytes::from(format!("{err:?}")),
-            )
+            // Details stay in our logs; the client gets the request id to quote when reporting it
+            error!("Internal error (request {}): {:?}", request_id, err);
+            json_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal error while executing the command", request_id)
         }
         CommandResponse::TooManyRequests => {
             error!("Too many requests");
             (StatusCode::TOO_MANY_REQUESTS, Bytes::new())
         }
     }
-}
\ No newline at end of file
+}
+
+// A JSON error body carrying the request id, so users can quote it when reporting problems
+fn json_error(status: StatusCode, message: &str, request_id: &str) -> (StatusCode, Bytes) {
+    let body = serde_json::json!({
+        "error": message,
+        "request_id": request_id,
+    });
+    (status, Bytes::from(serde_json::to_vec(&body).unwrap()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RequiredRole;
+    use oc_bots_sdk::types::ChatRole;
+
+    #[tokio::test]
+    async fn healthy_with_a_reachable_database() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        let (status, body) = health_report(Some(store.ping().await), true, Duration::from_secs(42));
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "ok");
+        assert_eq!(body["database"], "ok");
+        assert_eq!(body["mistral_key_configured"], true);
+        assert_eq!(body["uptime_seconds"], 42);
+    }
+
+    #[test]
+    fn unavailable_when_the_database_fails() {
+        let (status, body) = health_report(Some(Err(anyhow::anyhow!("disk I/O error"))), true, Duration::ZERO);
+
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "unavailable");
+        assert_eq!(body["database"], "error: disk I/O error");
+    }
+
+    #[test]
+    fn healthy_without_memory() {
+        let (status, body) = health_report(None, false, Duration::ZERO);
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["database"], "disabled");
+        assert_eq!(body["mistral_key_configured"], false);
+    }
+
+    #[test]
+    fn metrics_include_command_series() {
+        let recorder = PrometheusBuilder::new().build_recorder();
+        let handle = recorder.handle();
+
+        metrics::with_local_recorder(&recorder, || {
+            record_command_metrics("echo", Duration::from_millis(120), true);
+            record_command_metrics("ask", Duration::from_millis(900), false);
+        });
+        let rendered = handle.render();
+
+        assert!(rendered.contains("karmaspark_command_duration_seconds"), "{}", rendered);
+        assert!(rendered.contains(r#"command="echo""#), "{}", rendered);
+        assert!(rendered.contains(r#"karmaspark_command_errors_total{command="ask"} 1"#), "{}", rendered);
+        assert!(!rendered.contains(r#"karmaspark_command_errors_total{command="echo"}"#), "{}", rendered);
+    }
+
+    #[tokio::test]
+    async fn stalled_commands_are_abandoned_with_a_504() {
+        let started = Instant::now();
+        let slow = async {
+            tokio::time::sleep(Duration::from_secs(30)).await;
+            "done"
+        };
+
+        let (status, body) = within_command_timeout(Duration::from_millis(50), "ask", "req-1", slow)
+            .await
+            .unwrap_err();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "The command took too long to complete. Please try again later.");
+        assert_eq!(body["request_id"], "req-1");
+
+        let quick = within_command_timeout(Duration::from_secs(5), "echo", "req-2", async { "done" }).await;
+        assert_eq!(quick.unwrap(), "done");
+    }
+
+    fn command_definition(name: &str) -> BotCommandDefinition {
+        use oc_bots_sdk::api::definition::{BotPermissions, MessagePermission};
+
+        BotCommandDefinition {
+            name: name.to_string(),
+            description: None,
+            placeholder: None,
+            params: Vec::new(),
+            permissions: BotPermissions::from_message_permission(MessagePermission::Text),
+            default_role: None,
+            direct_messages: Some(true),
+        }
+    }
+
+    fn permissions(entries: &[(&str, RequiredRole)]) -> HashMap<String, CommandPermissionConfig> {
+        entries
+            .iter()
+            .map(|(command, role)| (command.to_string(), CommandPermissionConfig { role: *role }))
+            .collect()
+    }
+
+    #[test]
+    fn configured_roles_appear_in_the_definition() {
+        let mut commands = vec![command_definition("moderate"), command_definition("mod"), command_definition("echo")];
+        let aliases = HashMap::from([("mod".to_string(), "moderate".to_string())]);
+
+        apply_command_permissions(&mut commands, &permissions(&[("moderate", RequiredRole::Admin)]), &aliases);
+
+        assert_eq!(commands[0].default_role, Some(ChatRole::Admin));
+        assert_eq!(commands[1].default_role, Some(ChatRole::Admin));
+        assert_eq!(commands[2].default_role, None);
+    }
+
+    #[test]
+    fn admin_only_commands_refuse_non_admins() {
+        let permissions = permissions(&[("moderate", RequiredRole::Admin), ("poll", RequiredRole::Moderator)]);
+        let admins = vec!["alice".to_string()];
+
+        assert!(refused_as_admin_only(&permissions, &admins, "moderate", "mallory"));
+        assert!(!refused_as_admin_only(&permissions, &admins, "moderate", "alice"));
+        // Chat roles are left to OpenChat, and unconfigured commands are open to everyone
+        assert!(!refused_as_admin_only(&permissions, &admins, "poll", "mallory"));
+        assert!(!refused_as_admin_only(&permissions, &admins, "echo", "mallory"));
+    }
+
+    #[test]
+    fn description_names_the_version_and_features() {
+        let description = bot_description(&["llm", "memory"]);
+
+        assert!(description.contains(&format!("v{}", env!("CARGO_PKG_VERSION"))), "{}", description);
+        assert!(description.contains("features: llm, memory"), "{}", description);
+        assert!(bot_description(&[]).contains("features: none"));
+    }
+
+    #[test]
+    fn internal_errors_are_json_with_a_request_id() {
+        let request_id = uuid::Uuid::new_v4().to_string();
+
+        let (status, body) = json_error(StatusCode::INTERNAL_SERVER_ERROR, "Internal error while executing the command", &request_id);
+
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"], "Internal error while executing the command");
+        assert_eq!(body["request_id"], request_id.as_str());
+    }
+
+    #[test]
+    fn logs_inside_an_execution_carry_its_request_id() {
+        // Collects everything the subscriber writes
+        #[derive(Clone, Default)]
+        struct Capture(Arc<std::sync::Mutex<Vec<u8>>>);
+
+        impl std::io::Write for Capture {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.lock().unwrap().extend_from_slice(buf);
+                Ok(buf.len())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let capture = Capture::default();
+        let writer = capture.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(move || writer.clone())
+            .finish();
+        let request_id = uuid::Uuid::new_v4().to_string();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let span = execute_span(&request_id);
+            let _entered = span.enter();
+            Span::current().record("command", "ask");
+            info!("dispatching");
+            info_span!("llm_call").in_scope(|| info!("calling the LLM"));
+        });
+
+        let logs = String::from_utf8(capture.0.lock().unwrap().clone()).unwrap();
+        let lines: Vec<&str> = logs.lines().collect();
+        assert_eq!(lines.len(), 2, "{}", logs);
+        for line in lines {
+            assert!(line.contains(&format!("request_id={}", request_id)), "{}", line);
+            assert!(line.contains("command=\"ask\""), "{}", line);
+        }
+    }
+}
@@ -3,29 +3,163 @@ use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::path::Path;
 use std::sync::{Arc, Mutex};
+use tracing::{error, info, warn};
+
+use crate::chat_id::migrate_legacy_chat_ids;
+
+// How often memories past the retention period are deleted
+const RETENTION_CLEANUP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(24 * 60 * 60);
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Memory {
     pub id: Option<i64>,
     pub chat_id: String,
+    // Thread the memory belongs to; None for chat-level memories
+    #[serde(default)]
+    pub thread_id: Option<String>,
     pub user_id: String,
     pub timestamp: DateTime<Utc>,
     pub content: String,
     pub embedding: Option<Vec<f32>>,
+    // Model that produced the embedding; None for memories stored before it was recorded
+    #[serde(default)]
+    pub embedding_model: Option<String>,
     pub metadata: Option<String>,
 }
 
+/// A previous `/ask` question and the answer given
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AskTurn {
+    pub question: String,
+    pub answer: String,
+    pub timestamp: DateTime<Utc>,
+}
+
 #[derive(Debug, Clone)]
 pub struct MemoryStore {
     db: Arc<Mutex<Connection>>,
+    // Age at which a memory's search score is halved; None ranks by similarity alone
+    recency_half_life: Option<chrono::Duration>,
+    // Most memories a chat keeps; the oldest go first. None keeps everything.
+    max_items: Option<usize>,
+}
+
+/// Where memories, `/ask` history and chat personas are kept. `MemoryStore` keeps them
+/// in SQLite; another database can take its place by implementing this.
+#[async_trait]
+pub trait MemoryBackend: Send + Sync {
+    /// Store a memory, or refresh the existing one if the chat already has the same content
+    /// (in the same thread, with the same metadata). Returns the memory's id either way.
+    async fn store_memory(&self, memory: Memory) -> Result<i64>;
+    
+    /// Most recent memories visible from `thread_id`: the thread's own plus chat-level ones
+    async fn get_recent_memories(&self, chat_id: &str, thread_id: Option<&str>, limit: usize) -> Result<Vec<Memory>>;
+    
+    /// Most recent memories stored by one user, visible from `thread_id`
+    async fn get_recent_memories_by_user(
+        &self,
+        chat_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        limit: usize,
+    ) -> Result<Vec<Memory>>;
+    
+    /// Memories visible from `thread_id` most similar to the query, best first, with their scores.
+    /// Only memories embedded by `embedding_model` (or by an unrecorded model) are compared.
+    async fn search_similar_memories(
+        &self,
+        chat_id: &str,
+        thread_id: Option<&str>,
+        query_embedding: &[f32],
+        embedding_model: &str,
+        limit: usize,
+    ) -> Result<Vec<(Memory, f32)>>;
+    
+    /// Delete the chat's memories older than `days_to_keep` days. Entries with metadata, such
+    /// as glossary terms, are reference data rather than conversation and are kept.
+    async fn cleanup_old_memories(&self, chat_id: &str, days_to_keep: u32) -> Result<usize>;
+    
+    /// Every chat that has memories stored
+    async fn chat_ids(&self) -> Result<Vec<String>>;
+    
+    /// Get the most recent memory in a chat with exactly this metadata
+    async fn get_memory_by_metadata(&self, chat_id: &str, metadata: &str) -> Result<Option<Memory>>;
+    
+    /// Delete all memories in a chat with exactly this metadata
+    async fn delete_memories_by_metadata(&self, chat_id: &str, metadata: &str) -> Result<usize>;
+    
+    /// Override the agent persona for a chat, replacing any earlier override
+    async fn set_chat_persona(&self, chat_id: &str, user_id: &str, persona: &str) -> Result<()>;
+    
+    /// The chat's persona override, if one is set
+    async fn get_chat_persona(&self, chat_id: &str) -> Result<Option<String>>;
+    
+    /// Remove the chat's persona override, returning whether there was one
+    async fn reset_chat_persona(&self, chat_id: &str) -> Result<bool>;
+    
+    /// Record a `/ask` exchange so follow-up questions can refer back to it
+    async fn store_ask_turn(&self, chat_id: &str, user_id: &str, question: &str, answer: &str) -> Result<()>;
+    
+    /// Number of memories stored in the chat, across all threads
+    async fn count_memories(&self, chat_id: &str) -> Result<u64>;
+    
+    /// Users who stored memories or asked questions in the chat
+    async fn user_ids(&self, chat_id: &str) -> Result<HashSet<String>>;
+    
+    /// Get the last `limit` `/ask` exchanges in a chat, oldest first
+    async fn get_recent_ask_turns(&self, chat_id: &str, limit: usize) -> Result<Vec<AskTurn>>;
+    
+    /// Run a trivial query to confirm the database is reachable
+    async fn ping(&self) -> Result<()>;
+    
+    /// Get memory by ID
+    async fn get_memory(&self, id: i64) -> Result<Option<Memory>>;
+    
+    /// Every memory stored in the chat, across all its threads, oldest first
+    async fn export_chat(&self, chat_id: &str) -> Result<Vec<Memory>>;
+    
+    /// Add exported memories to the chat in one transaction, skipping any the chat already
+    /// has (or that repeat earlier ones in `memories`). Returns how many were added and are
+    /// still there once the chat's memory limit has evicted the oldest.
+    async fn import_chat(&self, chat_id: &str, memories: Vec<Memory>) -> Result<usize>;
+    
+    /// Replace the embeddings of memories by id in one transaction, tagging them with
+    /// `embedding_model`. Returns how many memories were updated.
+    async fn update_embeddings(&self, embeddings: Vec<(i64, Vec<f32>)>, embedding_model: &str) -> Result<usize>;
+    
+    /// Delete the user's memories and question history in every chat; returns the rows removed
+    async fn delete_user(&self, user_id: &str) -> Result<usize>;
 }
 
 #[async_trait]
 pub trait EmbeddingModel {
     async fn embed_text(&self, text: &str) -> Result<Vec<f32>>;
-    async fn similarity(&self, embedding1: &[f32], embedding2: &[f32]) -> f32;
+    /// Embed the text and name the model that did it, for `Memory::embedding_model`.
+    /// Differs from `name` only for models that can fall back to another.
+    async fn embed_with_model(&self, text: &str) -> Result<(Vec<f32>, String)> {
+        Ok((self.embed_text(text).await?, self.name().to_string()))
+    }
+    /// Embed several texts at once, in order, all with this model. The default embeds them
+    /// one at a time; models whose API takes a list override it with a single call.
+    async fn embed_batch(&self, texts: &[String]) -> Result<Vec<Vec<f32>>> {
+        let mut embeddings = Vec::with_capacity(texts.len());
+        for text in texts {
+            embeddings.push(self.embed_text(text).await?);
+        }
+        Ok(embeddings)
+    }
+    /// Name recorded with the embeddings this model produces
+    fn name(&self) -> &str;
+    /// Cosine similarity of two of this model's embeddings. Pure arithmetic, so not async.
+    fn similarity(&self, embedding1: &[f32], embedding2: &[f32]) -> f32 {
+        cosine_similarity(embedding1, embedding2)
+    }
+    /// Length of the vectors `embed_text` returns, or 0 while it is not yet known
+    fn dimension(&self) -> usize;
 }
 
 impl MemoryStore {
@@ -42,23 +176,131 @@ impl MemoryStore {
                 content TEXT NOT NULL,
                 embedding BLOB,
                 metadata TEXT,
-                UNIQUE(chat_id, user_id, timestamp)
+                thread_id TEXT,
+                embedding_model TEXT,
+                content_hash TEXT
+            )",
+            [],
+        )?;
+        
+        // Databases created before thread scoping lack the column; their memories stay chat-level
+        let has_thread_id: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('memories') WHERE name = 'thread_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_thread_id {
+            conn.execute("ALTER TABLE memories ADD COLUMN thread_id TEXT", [])?;
+        }
+        
+        // Nor did they record which model made each embedding
+        let has_embedding_model: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('memories') WHERE name = 'embedding_model'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_embedding_model {
+            conn.execute("ALTER TABLE memories ADD COLUMN embedding_model TEXT", [])?;
+        }
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS ask_turns (
+                id INTEGER PRIMARY KEY,
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                question TEXT NOT NULL,
+                answer TEXT NOT NULL
             )",
             [],
         )?;
         
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS ask_turns_chat_id_idx ON ask_turns (chat_id, timestamp)",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS chat_personas (
+                chat_id TEXT PRIMARY KEY,
+                persona TEXT NOT NULL,
+                set_by TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+        
+        // Older versions keyed rows by the SDK's Debug output
+        for table in ["memories", "ask_turns"] {
+            let migrated = migrate_legacy_chat_ids(&conn, table)?;
+            if migrated > 0 {
+                info!("Migrated {} {} rows to canonical chat ids", migrated, table);
+            }
+        }
+        
+        // Databases from before deduplication lack the hash; fill it in and drop the copies
+        // that piled up, keeping the newest, so the unique index below can be built. This
+        // comes after the chat id migration, since merging chat ids can bring copies together.
+        let has_content_hash: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('memories') WHERE name = 'content_hash'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_content_hash {
+            add_content_hashes(&conn)?;
+        }
+        
+        // They also allowed one memory per user and timestamp, which now only gets in the
+        // way of the content hash: imports and updates can repeat a timestamp
+        let has_timestamp_constraint: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_index_list('memories') WHERE origin = 'u'",
+            [],
+            |row| row.get(0),
+        )?;
+        if has_timestamp_constraint {
+            drop_timestamp_constraint(&conn)?;
+        }
+        
         conn.execute(
             "CREATE INDEX IF NOT EXISTS memories_chat_id_idx ON memories (chat_id)",
             [],
         )?;
         
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS memories_chat_thread_idx ON memories (chat_id, thread_id)",
+            [],
+        )?;
+        
+        conn.execute(
+            "CREATE UNIQUE INDEX IF NOT EXISTS memories_chat_content_idx ON memories (chat_id, content_hash)",
+            [],
+        )?;
+        
         Ok(Self {
             db: Arc::new(Mutex::new(conn)),
+            recency_half_life: None,
+            max_items: None,
         })
     }
     
-    pub async fn store_memory(&self, memory: Memory) -> Result<i64> {
+    /// Favour fresh memories in similarity search: each result's score halves every `half_life` of age
+    pub fn with_recency_half_life(mut self, half_life: chrono::Duration) -> Self {
+        self.recency_half_life = Some(half_life);
+        self
+    }
+    
+    /// Keep at most `max_items` memories per chat, evicting the oldest as new ones arrive
+    pub fn with_max_items(mut self, max_items: usize) -> Self {
+        self.max_items = Some(max_items);
+        self
+    }
+}
+
+#[async_trait]
+impl MemoryBackend for MemoryStore {
+    async fn store_memory(&self, memory: Memory) -> Result<i64> {
         let db = self.db.clone();
+        let max_items = self.max_items;
         
         let result = tokio::task::spawn_blocking(move || -> Result<i64> {
             let conn = db.lock().unwrap();
@@ -69,11 +311,21 @@ impl MemoryStore {
                     .collect();
                 bytes
             });
+            let hash = content_hash(&memory);
             
-            conn.execute(
-                "INSERT OR REPLACE INTO memories 
-                (chat_id, user_id, timestamp, content, embedding, metadata) 
-                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            // Keyed on the content rather than the timestamp, so storing the same thing twice
+            // (even concurrently) leaves one row
+            let id = conn.query_row(
+                "INSERT INTO memories 
+                (chat_id, user_id, timestamp, content, embedding, metadata, thread_id, content_hash, embedding_model) 
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                ON CONFLICT(chat_id, content_hash) DO UPDATE SET
+                    user_id = excluded.user_id,
+                    timestamp = excluded.timestamp,
+                    embedding_model = CASE WHEN excluded.embedding IS NULL
+                        THEN memories.embedding_model ELSE excluded.embedding_model END,
+                    embedding = COALESCE(excluded.embedding, memories.embedding)
+                RETURNING id",
                 params![
                     memory.chat_id,
                     memory.user_id,
@@ -81,63 +333,76 @@ impl MemoryStore {
                     memory.content,
                     embedding_blob,
                     memory.metadata,
+                    memory.thread_id,
+                    hash,
+                    memory.embedding_model,
                 ],
+                |row| row.get(0),
             )?;
             
-            Ok(conn.last_insert_rowid())
+            if let Some(max_items) = max_items {
+                evict_oldest(&conn, &memory.chat_id, max_items)?;
+            }
+            
+            Ok(id)
         }).await??;
         
         Ok(result)
     }
     
-    pub async fn get_recent_memories(&self, chat_id: &str, limit: usize) -> Result<Vec<Memory>> {
+    async fn get_recent_memories(&self, chat_id: &str, thread_id: Option<&str>, limit: usize) -> Result<Vec<Memory>> {
         let chat_id = chat_id.to_string();
+        let thread_id = thread_id.map(str::to_string);
         let db = self.db.clone();
         
         let memories = tokio::task::spawn_blocking(move || -> Result<Vec<Memory>> {
             let conn = db.lock().unwrap();
             
             let mut stmt = conn.prepare(
-                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata 
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
                  FROM memories 
-                 WHERE chat_id = ?1 
+                 WHERE chat_id = ?1 AND (thread_id IS NULL OR thread_id = ?2) 
                  ORDER BY timestamp DESC 
-                 LIMIT ?2"
+                 LIMIT ?3"
             )?;
             
-            let rows = stmt.query_map(params![chat_id, limit as i64], |row| {
-                let id = row.get(0)?;
-                let chat_id = row.get(1)?;
-                let user_id = row.get(2)?;
-                let timestamp_str: String = row.get(3)?;
-                let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now());
-                let content = row.get(4)?;
-                let embedding_blob: Option<Vec<u8>> = row.get(5)?;
-                let embedding = embedding_blob.map(|blob| {
-                    let mut embedding = Vec::new();
-                    for chunk in blob.chunks(4) {
-                        if chunk.len() == 4 {
-                            let mut bytes = [0u8; 4];
-                            bytes.copy_from_slice(chunk);
-                            embedding.push(f32::from_le_bytes(bytes));
-                        }
-                    }
-                    embedding
-                });
-                let metadata = row.get(6)?;
-                
-                Ok(Memory {
-                    id: Some(id),
-                    chat_id,
-                    user_id,
-                    timestamp,
-                    content,
-                    embedding,
-                    metadata,
-                })
-            })?;
+            let rows = stmt.query_map(params![chat_id, thread_id, limit as i64], row_to_memory)?;
+            
+            let mut memories = Vec::new();
+            for row in rows {
+                memories.push(row?);
+            }
+            
+            Ok(memories)
+        }).await??;
+        
+        Ok(memories)
+    }
+    
+    async fn get_recent_memories_by_user(
+        &self,
+        chat_id: &str,
+        thread_id: Option<&str>,
+        user_id: &str,
+        limit: usize,
+    ) -> Result<Vec<Memory>> {
+        let chat_id = chat_id.to_string();
+        let thread_id = thread_id.map(str::to_string);
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+        
+        let memories = tokio::task::spawn_blocking(move || -> Result<Vec<Memory>> {
+            let conn = db.lock().unwrap();
+            
+            let mut stmt = conn.prepare(
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
+                 FROM memories 
+                 WHERE chat_id = ?1 AND (thread_id IS NULL OR thread_id = ?2) AND user_id = ?3 
+                 ORDER BY timestamp DESC 
+                 LIMIT ?4"
+            )?;
+            
+            let rows = stmt.query_map(params![chat_id, thread_id, user_id, limit as i64], row_to_memory)?;
             
             let mut memories = Vec::new();
             for row in rows {
@@ -150,14 +415,19 @@ impl MemoryStore {
         Ok(memories)
     }
     
-    pub async fn search_similar_memories(
+    async fn search_similar_memories(
         &self, 
         chat_id: &str, 
+        thread_id: Option<&str>,
         query_embedding: &[f32], 
+        embedding_model: &str,
         limit: usize
     ) -> Result<Vec<(Memory, f32)>> {
         let chat_id = chat_id.to_string();
+        let thread_id = thread_id.map(str::to_string);
         let query_embedding = query_embedding.to_vec();
+        let embedding_model = embedding_model.to_string();
+        let recency_half_life = self.recency_half_life;
         let db = self.db.clone();
         
         let memories = tokio::task::spawn_blocking(move || -> Result<Vec<(Memory, f32)>> {
@@ -165,56 +435,43 @@ impl MemoryStore {
             
             let mut memories_with_score = Vec::new();
             let mut stmt = conn.prepare(
-                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata 
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
                  FROM memories 
-                 WHERE chat_id = ?1 AND embedding IS NOT NULL"
+                 WHERE chat_id = ?1 AND (thread_id IS NULL OR thread_id = ?2) AND embedding IS NOT NULL
+                 ORDER BY timestamp DESC, id DESC"
             )?;
             
-            let rows = stmt.query_map(params![chat_id], |row| {
-                let id = row.get(0)?;
-                let chat_id = row.get(1)?;
-                let user_id = row.get(2)?;
-                let timestamp_str: String = row.get(3)?;
-                let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                    .map(|dt| dt.with_timezone(&Utc))
-                    .unwrap_or_else(|_| Utc::now());
-                let content = row.get(4)?;
-                let embedding_blob: Option<Vec<u8>> = row.get(5)?;
-                let embedding = embedding_blob.map(|blob| {
-                    let mut embedding = Vec::new();
-                    for chunk in blob.chunks(4) {
-                        if chunk.len() == 4 {
-                            let mut bytes = [0u8; 4];
-                            bytes.copy_from_slice(chunk);
-                            embedding.push(f32::from_le_bytes(bytes));
-                        }
-                    }
-                    embedding
-                });
-                let metadata = row.get(6)?;
-                
-                Ok(Memory {
-                    id: Some(id),
-                    chat_id,
-                    user_id,
-                    timestamp,
-                    content,
-                    embedding,
-                    metadata,
-                })
-            })?;
+            let rows = stmt.query_map(params![chat_id, thread_id], row_to_memory)?;
+            let now = Utc::now();
             
             for row in rows {
                 let memory = row?;
                 if let Some(ref embedding) = memory.embedding {
-                    // Calculate cosine similarity
+                    // A local fallback model can share the remote model's dimension, so its
+                    // vectors are told apart by the recorded model
+                    if memory.embedding_model.as_ref().is_some_and(|model| *model != embedding_model) {
+                        continue;
+                    }
+                    // Embeddings from another model (or a damaged blob) can't be compared
+                    if embedding.len() != query_embedding.len() {
+                        warn!("Skipping memory {:?}: embedding has {} dimensions, expected {}",
+                              memory.id, embedding.len(), query_embedding.len());
+                        continue;
+                    }
+                    
+                    // Calculate cosine similarity, discounted for age when configured
                     let similarity = cosine_similarity(&query_embedding, embedding);
-                    memories_with_score.push((memory, similarity));
+                    let score = match recency_half_life {
+                        Some(half_life) => recency_weighted(similarity, memory.timestamp, now, half_life),
+                        None => similarity,
+                    };
+                    memories_with_score.push((memory, score));
                 }
             }
             
-            // Sort by similarity score
-            memories_with_score.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+            // Sort by score
+            // Scores are always finite; ties keep the newest-first order of the scan
+            memories_with_score.sort_by(|a, b| b.1.total_cmp(&a.1));
             
             // Return top N results
             Ok(memories_with_score.into_iter().take(limit).collect())
@@ -223,7 +480,7 @@ impl MemoryStore {
         Ok(memories)
     }
     
-    pub async fn cleanup_old_memories(&self, chat_id: &str, days_to_keep: u32) -> Result<usize> {
+    async fn cleanup_old_memories(&self, chat_id: &str, days_to_keep: u32) -> Result<usize> {
         let chat_id = chat_id.to_string();
         let db = self.db.clone();
         
@@ -233,7 +490,7 @@ impl MemoryStore {
             let cutoff_date = (Utc::now() - chrono::Duration::days(days_to_keep as i64)).to_rfc3339();
             
             let deleted = conn.execute(
-                "DELETE FROM memories WHERE chat_id = ?1 AND timestamp < ?2",
+                "DELETE FROM memories WHERE chat_id = ?1 AND timestamp < ?2 AND metadata IS NULL",
                 params![chat_id, cutoff_date],
             )?;
             
@@ -243,50 +500,246 @@ impl MemoryStore {
         Ok(deleted)
     }
 
-    /// Get memory by ID
-    pub async fn get_memory(&self, id: i64) -> Result<Option<Memory>> {
+    async fn chat_ids(&self) -> Result<Vec<String>> {
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<Vec<String>> {
+            let conn = db.lock().unwrap();
+            
+            let mut stmt = conn.prepare("SELECT DISTINCT chat_id FROM memories")?;
+            let chat_ids = stmt
+                .query_map([], |row| row.get(0))?
+                .collect::<rusqlite::Result<Vec<String>>>()?;
+            
+            Ok(chat_ids)
+        }).await?
+    }
+    
+    async fn get_memory_by_metadata(&self, chat_id: &str, metadata: &str) -> Result<Option<Memory>> {
+        let chat_id = chat_id.to_string();
+        let metadata = metadata.to_string();
         let db = self.db.clone();
         
         tokio::task::spawn_blocking(move || {
             let conn = db.lock().unwrap();
             
             let result = conn.query_row(
-                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata 
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
+                 FROM memories 
+                 WHERE chat_id = ?1 AND metadata = ?2 
+                 ORDER BY timestamp DESC 
+                 LIMIT 1",
+                params![chat_id, metadata],
+                row_to_memory,
+            );
+            
+            match result {
+                Ok(memory) => Ok(Some(memory)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(anyhow!("Error retrieving memory: {}", e)),
+            }
+        }).await?
+    }
+    
+    async fn delete_memories_by_metadata(&self, chat_id: &str, metadata: &str) -> Result<usize> {
+        let chat_id = chat_id.to_string();
+        let metadata = metadata.to_string();
+        let db = self.db.clone();
+        
+        let deleted = tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            
+            let deleted = conn.execute(
+                "DELETE FROM memories WHERE chat_id = ?1 AND metadata = ?2",
+                params![chat_id, metadata],
+            )?;
+            
+            Ok(deleted)
+        }).await??;
+        
+        Ok(deleted)
+    }
+    
+    async fn set_chat_persona(&self, chat_id: &str, user_id: &str, persona: &str) -> Result<()> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let persona = persona.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+            
+            conn.execute(
+                "INSERT INTO chat_personas (chat_id, persona, set_by, updated_at) 
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(chat_id) DO UPDATE SET 
+                    persona = excluded.persona, 
+                    set_by = excluded.set_by, 
+                    updated_at = excluded.updated_at",
+                params![chat_id, persona, user_id, Utc::now().to_rfc3339()],
+            )?;
+            
+            Ok(())
+        }).await?
+    }
+    
+    async fn get_chat_persona(&self, chat_id: &str) -> Result<Option<String>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<Option<String>> {
+            let conn = db.lock().unwrap();
+            
+            let result = conn.query_row(
+                "SELECT persona FROM chat_personas WHERE chat_id = ?1",
+                params![chat_id],
+                |row| row.get(0),
+            );
+            
+            match result {
+                Ok(persona) => Ok(Some(persona)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(anyhow!("Error retrieving chat persona: {}", e)),
+            }
+        }).await?
+    }
+    
+    async fn reset_chat_persona(&self, chat_id: &str) -> Result<bool> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+            
+            let deleted = conn.execute(
+                "DELETE FROM chat_personas WHERE chat_id = ?1",
+                params![chat_id],
+            )?;
+            
+            Ok(deleted > 0)
+        }).await?
+    }
+    
+    async fn store_ask_turn(&self, chat_id: &str, user_id: &str, question: &str, answer: &str) -> Result<()> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let question = question.to_string();
+        let answer = answer.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+            
+            conn.execute(
+                "INSERT INTO ask_turns (chat_id, user_id, timestamp, question, answer) 
+                VALUES (?1, ?2, ?3, ?4, ?5)",
+                params![chat_id, user_id, Utc::now().to_rfc3339(), question, answer],
+            )?;
+            
+            Ok(())
+        }).await?
+    }
+    
+    async fn count_memories(&self, chat_id: &str) -> Result<u64> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<u64> {
+            let conn = db.lock().unwrap();
+            
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM memories WHERE chat_id = ?1",
+                params![chat_id],
+                |row| row.get(0),
+            )?;
+            
+            Ok(count as u64)
+        }).await?
+    }
+    
+    async fn user_ids(&self, chat_id: &str) -> Result<HashSet<String>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<HashSet<String>> {
+            let conn = db.lock().unwrap();
+            
+            let mut stmt = conn.prepare(
+                "SELECT user_id FROM memories WHERE chat_id = ?1
+                 UNION
+                 SELECT user_id FROM ask_turns WHERE chat_id = ?1"
+            )?;
+            
+            let rows = stmt.query_map(params![chat_id], |row| row.get(0))?;
+            
+            let mut user_ids = HashSet::new();
+            for row in rows {
+                user_ids.insert(row?);
+            }
+            
+            Ok(user_ids)
+        }).await?
+    }
+    
+    async fn get_recent_ask_turns(&self, chat_id: &str, limit: usize) -> Result<Vec<AskTurn>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        let mut turns = tokio::task::spawn_blocking(move || -> Result<Vec<AskTurn>> {
+            let conn = db.lock().unwrap();
+            
+            let mut stmt = conn.prepare(
+                "SELECT question, answer, timestamp 
+                 FROM ask_turns 
+                 WHERE chat_id = ?1 
+                 ORDER BY timestamp DESC, id DESC 
+                 LIMIT ?2"
+            )?;
+            
+            let rows = stmt.query_map(params![chat_id, limit as i64], |row| {
+                let timestamp_str: String = row.get(2)?;
+                Ok(AskTurn {
+                    question: row.get(0)?,
+                    answer: row.get(1)?,
+                    timestamp: DateTime::parse_from_rfc3339(&timestamp_str)
+                        .map(|dt| dt.with_timezone(&Utc))
+                        .unwrap_or_else(|_| Utc::now()),
+                })
+            })?;
+            
+            let mut turns = Vec::new();
+            for row in rows {
+                turns.push(row?);
+            }
+            
+            Ok(turns)
+        }).await??;
+        
+        turns.reverse();
+        Ok(turns)
+    }
+    
+    async fn ping(&self) -> Result<()> {
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().map_err(|_| anyhow!("Database lock poisoned"))?;
+            conn.query_row("SELECT 1", [], |_| Ok(()))?;
+            Ok(())
+        }).await?
+    }
+
+    async fn get_memory(&self, id: i64) -> Result<Option<Memory>> {
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || {
+            let conn = db.lock().unwrap();
+            
+            let result = conn.query_row(
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
                  FROM memories WHERE id = ?1",
                 params![id],
-                |row| {
-                    let id = row.get(0)?;
-                    let chat_id = row.get(1)?;
-                    let user_id = row.get(2)?;
-                    let timestamp_str: String = row.get(3)?;
-                    let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
-                        .map(|dt| dt.with_timezone(&Utc))
-                        .unwrap_or_else(|_| Utc::now());
-                    let content = row.get(4)?;
-                    let embedding_blob: Option<Vec<u8>> = row.get(5)?;
-                    let embedding = embedding_blob.map(|blob| {
-                        let mut embedding = Vec::new();
-                        for chunk in blob.chunks(4) {
-                            if chunk.len() == 4 {
-                                let mut bytes = [0u8; 4];
-                                bytes.copy_from_slice(chunk);
-                                embedding.push(f32::from_le_bytes(bytes));
-                            }
-                        }
-                        embedding
-                    });
-                    let metadata = row.get(6)?;
-                    
-                    Ok(Memory {
-                        id: Some(id),
-                        chat_id,
-                        user_id,
-                        timestamp,
-                        content,
-                        embedding,
-                        metadata,
-                    })
-                },
+                row_to_memory,
             );
             
             match result {
@@ -296,21 +749,810 @@ impl MemoryStore {
             }
         }).await?
     }
+
+    async fn export_chat(&self, chat_id: &str) -> Result<Vec<Memory>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<Vec<Memory>> {
+            let conn = db.lock().unwrap();
+            
+            let mut stmt = conn.prepare(
+                "SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model 
+                 FROM memories 
+                 WHERE chat_id = ?1 
+                 ORDER BY timestamp ASC, id ASC"
+            )?;
+            
+            let memories = stmt
+                .query_map(params![chat_id], row_to_memory)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            
+            Ok(memories)
+        }).await?
+    }
+    
+    async fn import_chat(&self, chat_id: &str, memories: Vec<Memory>) -> Result<usize> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+        let max_items = self.max_items;
+        
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let mut conn = db.lock().unwrap();
+            let tx = conn.transaction()?;
+            
+            let mut imported_ids = Vec::new();
+            for memory in memories {
+                let embedding_blob = memory.embedding.as_ref().map(|e| {
+                    e.iter().flat_map(|&f| f.to_le_bytes()).collect::<Vec<u8>>()
+                });
+                let hash = content_hash(&memory);
+                
+                // Ids are left to the database, as the exported ones belonged to another store;
+                // duplicates hit the content index and are ignored
+                let inserted = tx.execute(
+                    "INSERT OR IGNORE INTO memories 
+                    (chat_id, user_id, timestamp, content, embedding, metadata, thread_id, content_hash, embedding_model) 
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                    params![
+                        chat_id,
+                        memory.user_id,
+                        memory.timestamp.to_rfc3339(),
+                        memory.content,
+                        embedding_blob,
+                        memory.metadata,
+                        memory.thread_id,
+                        hash,
+                        memory.embedding_model,
+                    ],
+                )?;
+                if inserted > 0 {
+                    imported_ids.push(tx.last_insert_rowid());
+                }
+            }
+            
+            // Imported memories keep their timestamps, so older ones can be evicted straight away
+            let mut imported = imported_ids.len();
+            if let Some(max_items) = max_items {
+                if evict_oldest(&tx, &chat_id, max_items)? > 0 {
+                    let mut exists = tx.prepare("SELECT EXISTS(SELECT 1 FROM memories WHERE id = ?1)")?;
+                    imported = 0;
+                    for id in &imported_ids {
+                        if exists.query_row(params![id], |row| row.get::<_, bool>(0))? {
+                            imported += 1;
+                        }
+                    }
+                }
+            }
+            
+            tx.commit()?;
+            Ok(imported)
+        }).await?
+    }
+    
+    async fn update_embeddings(&self, embeddings: Vec<(i64, Vec<f32>)>, embedding_model: &str) -> Result<usize> {
+        let embedding_model = embedding_model.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let mut conn = db.lock().unwrap();
+            let tx = conn.transaction()?;
+            
+            let mut updated = 0;
+            for (id, embedding) in embeddings {
+                let embedding_blob: Vec<u8> = embedding.iter().flat_map(|&f| f.to_le_bytes()).collect();
+                updated += tx.execute(
+                    "UPDATE memories SET embedding = ?1, embedding_model = ?2 WHERE id = ?3",
+                    params![embedding_blob, embedding_model, id],
+                )?;
+            }
+            
+            tx.commit()?;
+            Ok(updated)
+        }).await?
+    }
+    
+    async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+        
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let mut conn = db.lock().unwrap();
+            let tx = conn.transaction()?;
+            
+            let memories = tx.execute("DELETE FROM memories WHERE user_id = ?1", params![user_id])?;
+            let turns = tx.execute("DELETE FROM ask_turns WHERE user_id = ?1", params![user_id])?;
+            
+            tx.commit()?;
+            Ok(memories + turns)
+        }).await?
+    }
+}
+
+// Decode a `SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model` row
+fn row_to_memory(row: &rusqlite::Row) -> rusqlite::Result<Memory> {
+    let id: i64 = row.get(0)?;
+    let chat_id = row.get(1)?;
+    let user_id = row.get(2)?;
+    let timestamp_str: String = row.get(3)?;
+    let timestamp = DateTime::parse_from_rfc3339(&timestamp_str)
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|_| Utc::now());
+    let content = row.get(4)?;
+    let embedding_blob: Option<Vec<u8>> = row.get(5)?;
+    // A corrupt embedding only costs the memory its place in similarity search
+    let embedding = embedding_blob.and_then(|blob| match decode_embedding(&blob) {
+        Ok(embedding) => Some(embedding),
+        Err(e) => {
+            warn!("Ignoring embedding of memory {}: {}", id, e);
+            None
+        }
+    });
+    let metadata = row.get(6)?;
+    let thread_id = row.get(7)?;
+    let embedding_model = row.get(8)?;
+    
+    Ok(Memory {
+        id: Some(id),
+        chat_id,
+        thread_id,
+        user_id,
+        timestamp,
+        content,
+        embedding,
+        embedding_model,
+        metadata,
+    })
 }
 
-// Utility function to calculate cosine similarity between two vectors
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+/// Delete memories past the retention period in every chat, now and then daily for as
+/// long as the bot runs
+pub fn spawn_retention_cleanup(store: Arc<dyn MemoryBackend>, days_to_keep: u32) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(RETENTION_CLEANUP_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        
+        loop {
+            interval.tick().await;
+            match cleanup_all_chats(store.as_ref(), days_to_keep).await {
+                Ok(0) => {}
+                Ok(deleted) => info!("Retention cleanup deleted {} memories older than {} days", deleted, days_to_keep),
+                Err(e) => error!("Retention cleanup failed: {}", e),
+            }
+        }
+    });
+}
+
+// One cleanup pass; a chat that fails is logged and skipped so the others still get cleaned
+async fn cleanup_all_chats(store: &dyn MemoryBackend, days_to_keep: u32) -> Result<usize> {
+    let mut deleted = 0;
+    for chat_id in store.chat_ids().await? {
+        match store.cleanup_old_memories(&chat_id, days_to_keep).await {
+            Ok(count) => deleted += count,
+            Err(e) => warn!("Failed to clean up memories in {}: {}", chat_id, e),
+        }
+    }
+    Ok(deleted)
+}
+
+// Delete the chat's oldest memories beyond the newest `max_items`; returns how many went.
+// Memories with metadata, such as glossary terms, are kept and don't count towards the limit.
+fn evict_oldest(conn: &Connection, chat_id: &str, max_items: usize) -> Result<usize> {
+    let evicted = conn.execute(
+        "DELETE FROM memories WHERE chat_id = ?1 AND metadata IS NULL AND id NOT IN (
+            SELECT id FROM memories WHERE chat_id = ?1 AND metadata IS NULL
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ?2
+        )",
+        params![chat_id, max_items as i64],
+    )?;
+    if evicted > 0 {
+        info!("Evicted {} old memories from {} to stay within {}", evicted, chat_id, max_items);
+    }
+    Ok(evicted)
+}
+
+// What makes two memories in a chat the same: their text, thread and metadata. Metadata
+// counts so glossary terms with the same definition stay separate.
+fn content_hash(memory: &Memory) -> String {
+    hash_content(memory.thread_id.as_deref(), memory.metadata.as_deref(), &memory.content)
+}
+
+fn hash_content(thread_id: Option<&str>, metadata: Option<&str>, content: &str) -> String {
+    let mut hasher = Sha256::new();
+    for part in [thread_id.unwrap_or(""), metadata.unwrap_or(""), content.trim()] {
+        hasher.update(part.as_bytes());
+        // Separator, so ("ab", "c") and ("a", "bc") hash differently
+        hasher.update([0]);
+    }
+    format!("{:x}", hasher.finalize())
+}
+
+// Add the content_hash column to an older database, hash its memories and delete duplicates
+fn add_content_hashes(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("ALTER TABLE memories ADD COLUMN content_hash TEXT", [])?;
+    
+    let rows = {
+        let mut stmt = tx.prepare("SELECT id, thread_id, metadata, content FROM memories")?;
+        let rows = stmt
+            .query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, Option<String>>(1)?,
+                    row.get::<_, Option<String>>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+    for (id, thread_id, metadata, content) in &rows {
+        let hash = hash_content(thread_id.as_deref(), metadata.as_deref(), content);
+        tx.execute("UPDATE memories SET content_hash = ?1 WHERE id = ?2", params![hash, id])?;
+    }
+    
+    let removed = tx.execute(
+        "DELETE FROM memories WHERE id NOT IN (
+            SELECT MAX(id) FROM memories GROUP BY chat_id, content_hash
+        )",
+        [],
+    )?;
+    tx.commit()?;
+    
+    info!("Added content hashes to {} memories, removing {} duplicates", rows.len(), removed);
+    Ok(())
+}
+
+// SQLite can't drop a table constraint, so copy the memories into a table without the
+// UNIQUE(chat_id, user_id, timestamp) of older versions. Its indexes go with the old table
+// and are created again afterwards.
+fn drop_timestamp_constraint(conn: &Connection) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute_batch(
+        "CREATE TABLE memories_rebuilt (
+            id INTEGER PRIMARY KEY,
+            chat_id TEXT NOT NULL,
+            user_id TEXT NOT NULL,
+            timestamp TEXT NOT NULL,
+            content TEXT NOT NULL,
+            embedding BLOB,
+            metadata TEXT,
+            thread_id TEXT,
+            embedding_model TEXT,
+            content_hash TEXT
+        );
+        INSERT INTO memories_rebuilt
+            (id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model, content_hash)
+            SELECT id, chat_id, user_id, timestamp, content, embedding, metadata, thread_id, embedding_model, content_hash
+            FROM memories;
+        DROP TABLE memories;
+        ALTER TABLE memories_rebuilt RENAME TO memories;",
+    )?;
+    tx.commit()?;
+    
+    info!("Dropped the per-timestamp uniqueness of memories");
+    Ok(())
+}
+
+// Decode an embedding stored as little-endian f32s, rejecting blobs that were truncated
+// rather than quietly dropping the partial component
+fn decode_embedding(blob: &[u8]) -> Result<Vec<f32>> {
+    if blob.len() % 4 != 0 {
+        return Err(anyhow!("Embedding blob of {} bytes is not a whole number of f32s", blob.len()));
+    }
+    
+    Ok(blob
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+// Similarity scaled by an exponential decay on the memory's age: a memory `half_life` old
+// keeps half its score. Memories dated in the future count as brand new.
+fn recency_weighted(similarity: f32, timestamp: DateTime<Utc>, now: DateTime<Utc>, half_life: chrono::Duration) -> f32 {
+    let age = (now - timestamp).num_seconds().max(0) as f64;
+    let half_life = half_life.num_seconds().max(1) as f64;
+    (similarity as f64 * 0.5f64.powf(age / half_life)) as f32
+}
+
+// Non-finite components (from a bad embedding) contribute nothing rather than poisoning the score
+fn finite(x: f32) -> f32 {
+    if x.is_finite() { x } else { 0.0 }
+}
+
+// Utility function to calculate cosine similarity between two vectors.
+// Always returns a finite value in [-1, 1].
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     if a.len() != b.len() || a.is_empty() {
         return 0.0;
     }
     
-    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
-    let magnitude_a: f32 = a.iter().map(|x| x * x).sum::<f32>().sqrt();
-    let magnitude_b: f32 = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| finite(*x) * finite(*y)).sum();
+    let magnitude_a: f32 = a.iter().map(|x| finite(*x) * finite(*x)).sum::<f32>().sqrt();
+    let magnitude_b: f32 = b.iter().map(|x| finite(*x) * finite(*x)).sum::<f32>().sqrt();
     
     if magnitude_a == 0.0 || magnitude_b == 0.0 {
         return 0.0;
     }
     
-    dot_product / (magnitude_a * magnitude_b)
-} 
\ No newline at end of file
+    clamp_similarity(dot_product / (magnitude_a * magnitude_b))
+}
+
+// Overflow can still produce inf/NaN from finite inputs
+fn clamp_similarity(similarity: f32) -> f32 {
+    if similarity.is_finite() {
+        similarity.clamp(-1.0, 1.0)
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory;
+
+    #[test]
+    fn cosine_similarity_of_simple_vectors() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-6);
+        assert!(cosine_similarity(&[1.0, 0.0], &[0.0, 1.0]).abs() < 1e-6);
+        assert!((cosine_similarity(&[1.0, 0.0], &[-1.0, 0.0]) + 1.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn cosine_similarity_of_degenerate_vectors_is_zero() {
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 1.0]), 0.0);
+        assert_eq!(cosine_similarity(&[], &[]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[test]
+    fn cosine_similarity_ignores_non_finite_components() {
+        let similarity = cosine_similarity(&[1.0, f32::NAN, 0.0], &[1.0, 5.0, f32::INFINITY]);
+        assert!((similarity - 1.0).abs() < 1e-6);
+
+        assert_eq!(cosine_similarity(&[f32::NAN, f32::NAN], &[1.0, 1.0]), 0.0);
+        assert!(cosine_similarity(&[f32::MAX, f32::MAX], &[f32::MAX, f32::MAX]).is_finite());
+    }
+
+    #[test]
+    fn model_similarity_is_the_cosine_similarity() {
+        use crate::llm::{MistralEmbedding, MockEmbedding};
+
+        let pairs: [(&[f32], &[f32]); 4] = [
+            (&[0.6, 0.8], &[1.0, 0.0]),
+            (&[3.0, 4.0], &[-1.0, 2.0]),
+            (&[0.0, 0.0], &[1.0, 1.0]),
+            (&[1.0, f32::NAN], &[1.0, 5.0]),
+        ];
+        let models: [&dyn EmbeddingModel; 2] = [&MockEmbedding, &MistralEmbedding::new("test-key")];
+
+        for model in models {
+            for (a, b) in pairs {
+                assert_eq!(model.similarity(a, b), cosine_similarity(a, b), "{} {:?} {:?}", model.name(), a, b);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn search_scores_are_the_cosine_similarity() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        // The same direction at unit length, as mistral-embed returns, and scaled up
+        let unit = vec![0.6, 0.8];
+        store.store_memory(memory("unit").embedding(unit.clone()).age_secs(20).build()).await.unwrap();
+        store.store_memory(memory("scaled").embedding(vec![3.0, 4.0]).age_secs(10).build()).await.unwrap();
+
+        let query = [1.0, 0.0];
+        let results = store
+            .search_similar_memories("group:1", None, &query, "mock", 10)
+            .await
+            .unwrap();
+        let expected = cosine_similarity(&query, &unit);
+        assert!((expected - 0.6).abs() < 1e-6);
+        assert_eq!(results.len(), 2);
+        for (_, score) in results {
+            assert!((score - expected).abs() < 1e-6);
+        }
+    }
+
+    #[tokio::test]
+    async fn nan_embeddings_do_not_disturb_ranking() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        store.store_memory(memory("close").embedding(vec![1.0, 0.1]).age_secs(30).build()).await.unwrap();
+        store.store_memory(memory("broken").embedding(vec![f32::NAN, f32::NAN]).age_secs(20).build()).await.unwrap();
+        store.store_memory(memory("far").embedding(vec![0.0, 1.0]).age_secs(10).build()).await.unwrap();
+
+        for _ in 0..3 {
+            let results = store
+                .search_similar_memories("group:1", None, &[1.0, 0.0], "mock", 10)
+                .await
+                .unwrap();
+            let contents: Vec<&str> = results.iter().map(|(m, _)| m.content.as_str()).collect();
+            // The broken and far memories tie at 0; the newer comes first
+            assert_eq!(contents, vec!["close", "far", "broken"]);
+            assert!(results.iter().all(|(_, score)| score.is_finite()));
+        }
+    }
+
+    #[tokio::test]
+    async fn thread_memories_stay_in_their_thread() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        let in_a = memory("thread A plans").age_secs(30).thread("A").build();
+        let in_b = memory("thread B plans").age_secs(20).thread("B").build();
+        store.store_memory(in_a).await.unwrap();
+        store.store_memory(in_b).await.unwrap();
+        store.store_memory(memory("chat-wide note").age_secs(10).build()).await.unwrap();
+
+        let contents = |memories: Vec<Memory>| -> Vec<String> { memories.into_iter().map(|m| m.content).collect() };
+        assert_eq!(
+            contents(store.get_recent_memories("group:1", Some("B"), 10).await.unwrap()),
+            vec!["chat-wide note", "thread B plans"]
+        );
+        assert_eq!(
+            contents(store.get_recent_memories("group:1", None, 10).await.unwrap()),
+            vec!["chat-wide note"]
+        );
+    }
+
+    #[tokio::test]
+    async fn adds_the_thread_column_to_old_databases() {
+        let path = std::env::temp_dir().join(format!("karmaspark-{}.db", uuid::Uuid::new_v4()));
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(
+                "CREATE TABLE memories (
+                    id INTEGER PRIMARY KEY,
+                    chat_id TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    timestamp TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    embedding BLOB,
+                    metadata TEXT,
+                    UNIQUE(chat_id, user_id, timestamp)
+                )",
+                [],
+            )
+            .unwrap();
+            conn.execute(
+                "INSERT INTO memories (chat_id, user_id, timestamp, content) VALUES ('group:1', 'alice', ?1, 'old note')",
+                params![Utc::now().to_rfc3339()],
+            )
+            .unwrap();
+        }
+
+        let store = MemoryStore::new(&path).unwrap();
+        let memories = store.get_recent_memories("group:1", Some("A"), 10).await.unwrap();
+        std::fs::remove_file(&path).ok();
+
+        assert_eq!(memories.len(), 1);
+        assert_eq!(memories[0].content, "old note");
+        assert_eq!(memories[0].thread_id, None);
+    }
+
+    #[test]
+    fn decodes_whole_f32s_only() {
+        let blob: Vec<u8> = [1.5f32, -2.0].iter().flat_map(|f| f.to_le_bytes()).collect();
+        assert_eq!(decode_embedding(&blob).unwrap(), vec![1.5, -2.0]);
+        assert_eq!(decode_embedding(&[]).unwrap(), Vec::<f32>::new());
+
+        let error = decode_embedding(&blob[..7]).unwrap_err();
+        assert_eq!(error.to_string(), "Embedding blob of 7 bytes is not a whole number of f32s");
+    }
+
+    #[tokio::test]
+    async fn search_skips_truncated_and_mismatched_embeddings() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        store.store_memory(memory("intact").embedding(vec![1.0, 0.0]).age_secs(30).build()).await.unwrap();
+        store.store_memory(memory("truncated").embedding(vec![1.0, 0.0]).age_secs(20).build()).await.unwrap();
+        store.store_memory(memory("other model").embedding(vec![1.0, 0.0, 0.0]).age_secs(10).build()).await.unwrap();
+
+        // Cut the last byte off one stored embedding
+        store
+            .db
+            .lock()
+            .unwrap()
+            .execute("UPDATE memories SET embedding = substr(embedding, 1, 7) WHERE content = 'truncated'", [])
+            .unwrap();
+
+        let results = store
+            .search_similar_memories("group:1", None, &[1.0, 0.0], "mock", 10)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = results.iter().map(|(m, _)| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["intact"]);
+
+        let recent = store.get_recent_memories("group:1", None, 10).await.unwrap();
+        let truncated = recent.iter().find(|m| m.content == "truncated").unwrap();
+        assert_eq!(truncated.embedding, None);
+    }
+
+    #[test]
+    fn recency_weighting_halves_per_half_life() {
+        let now = Utc::now();
+        let week = chrono::Duration::days(7);
+        assert!((recency_weighted(0.8, now, now, week) - 0.8).abs() < 1e-6);
+        assert!((recency_weighted(0.8, now - week, now, week) - 0.4).abs() < 1e-6);
+        assert!((recency_weighted(0.8, now - week * 2, now, week) - 0.2).abs() < 1e-6);
+        // Future timestamps are treated as brand new
+        assert!((recency_weighted(0.8, now + week, now, week) - 0.8).abs() < 1e-6);
+    }
+
+    #[tokio::test]
+    async fn recency_weighting_lets_fresh_memories_outrank_stale_ones() {
+        let month = 30 * 24 * 60 * 60;
+        let seed = |store: MemoryStore| async move {
+            store.store_memory(memory("stale").embedding(vec![1.0, 0.0]).age_secs(month).build()).await.unwrap();
+            store.store_memory(memory("fresh").embedding(vec![1.0, 0.1]).age_secs(10).build()).await.unwrap();
+            let results = store
+                .search_similar_memories("group:1", None, &[1.0, 0.0], "mock", 10)
+                .await
+                .unwrap();
+            results.into_iter().map(|(m, _)| m.content).collect::<Vec<_>>()
+        };
+
+        // Pure similarity keeps the slightly closer stale memory on top
+        assert_eq!(seed(MemoryStore::new(":memory:").unwrap()).await, vec!["stale", "fresh"]);
+
+        let weighted = MemoryStore::new(":memory:").unwrap().with_recency_half_life(chrono::Duration::days(7));
+        assert_eq!(seed(weighted).await, vec!["fresh", "stale"]);
+    }
+
+    #[tokio::test]
+    async fn equally_similar_memories_rank_newest_first_when_weighted() {
+        let store = MemoryStore::new(":memory:").unwrap().with_recency_half_life(chrono::Duration::days(7));
+        store.store_memory(memory("newer").embedding(vec![1.0, 0.0]).age_secs(60).build()).await.unwrap();
+        store.store_memory(memory("older").embedding(vec![1.0, 0.0]).age_secs(3 * 24 * 60 * 60).build()).await.unwrap();
+
+        let results = store
+            .search_similar_memories("group:1", None, &[1.0, 0.0], "mock", 10)
+            .await
+            .unwrap();
+        let contents: Vec<&str> = results.iter().map(|(m, _)| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["newer", "older"]);
+        assert!(results[0].1 > results[1].1);
+    }
+
+    #[tokio::test]
+    async fn storing_the_same_content_twice_keeps_one_row() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        let first = store.store_memory(memory("Deploys happen on Fridays").age_secs(30).build()).await.unwrap();
+        let second = store
+            .store_memory(memory("  Deploys happen on Fridays ").embedding(vec![1.0, 0.0]).age_secs(10).build())
+            .await
+            .unwrap();
+        assert_eq!(first, second);
+
+        // Concurrent stores of the same content can't race each other into two rows
+        let (a, b) = tokio::join!(
+            store.store_memory(memory("Deploys happen on Fridays").age_secs(5).build()),
+            store.store_memory(memory("Deploys happen on Fridays").age_secs(4).build()),
+        );
+        assert_eq!(a.unwrap(), first);
+        assert_eq!(b.unwrap(), first);
+
+        let memories = store.get_recent_memories("group:1", None, 10).await.unwrap();
+        assert_eq!(memories.len(), 1);
+        // The newer store refreshed the row, without losing its embedding
+        assert_eq!(memories[0].embedding, Some(vec![1.0, 0.0]));
+        assert!(Utc::now() - memories[0].timestamp < chrono::Duration::seconds(10));
+    }
+
+    #[tokio::test]
+    async fn same_text_in_another_thread_or_with_metadata_is_separate() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        store.store_memory(memory("SLO").age_secs(30).build()).await.unwrap();
+        let in_thread = memory("SLO").age_secs(20).thread("A").build();
+        store.store_memory(in_thread).await.unwrap();
+        let term = memory("SLO").age_secs(10).metadata(r#"{"glossary_term":"SLO"}"#).build();
+        store.store_memory(term).await.unwrap();
+
+        let count: i64 = store
+            .db
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM memories", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 3);
+    }
+
+    #[tokio::test]
+    async fn hashes_old_databases_and_drops_their_duplicates() {
+        let path = std::env::temp_dir().join(format!("karmaspark-{}.db", uuid::Uuid::new_v4()));
+        {
+            let conn = Connection::open(&path).unwrap();
+            conn.execute(
+                "CREATE TABLE memories (
+                    id INTEGER PRIMARY KEY,
+                    chat_id TEXT NOT NULL,
+                    user_id TEXT NOT NULL,
+                    timestamp TEXT NOT NULL,
+                    content TEXT NOT NULL,
+                    embedding BLOB,
+                    metadata TEXT,
+                    thread_id TEXT,
+                    UNIQUE(chat_id, user_id, timestamp)
+                )",
+                [],
+            )
+            .unwrap();
+            for (user_id, age, content) in [("alice", 30, "old note"), ("bob", 20, "old note"), ("alice", 10, "other note")] {
+                conn.execute(
+                    "INSERT INTO memories (chat_id, user_id, timestamp, content) VALUES ('group:1', ?1, ?2, ?3)",
+                    params![user_id, (Utc::now() - chrono::Duration::seconds(age)).to_rfc3339(), content],
+                )
+                .unwrap();
+            }
+        }
+
+        let store = MemoryStore::new(&path).unwrap();
+        let mut memories = store.get_recent_memories("group:1", None, 10).await.unwrap();
+        store.store_memory(memory("other note").age_secs(5).build()).await.unwrap();
+        let after = store.get_recent_memories("group:1", None, 10).await.unwrap().len();
+        // Nor is the old one-memory-per-timestamp rule kept
+        let now = Utc::now();
+        store.store_memory(memory("first").at(now).build()).await.unwrap();
+        store.store_memory(memory("second").at(now).build()).await.unwrap();
+        let with_same_timestamp = store.get_recent_memories("group:1", None, 10).await.unwrap().len();
+        drop(store);
+        let reopened = MemoryStore::new(&path).unwrap().get_recent_memories("group:1", None, 10).await.unwrap().len();
+        std::fs::remove_file(&path).ok();
+
+        memories.sort_by_key(|m| m.id);
+        let kept: Vec<(&str, &str)> = memories.iter().map(|m| (m.user_id.as_str(), m.content.as_str())).collect();
+        // The newest copy survives
+        assert_eq!(kept, vec![("bob", "old note"), ("alice", "other note")]);
+        assert_eq!(after, 2);
+        assert_eq!(with_same_timestamp, 4);
+        assert_eq!(reopened, 4);
+    }
+
+    #[tokio::test]
+    async fn different_memories_can_share_a_timestamp() {
+        let store = MemoryStore::new(":memory:").unwrap();
+        let earlier = Utc::now() - chrono::Duration::seconds(30);
+        let now = Utc::now();
+
+        store.store_memory(memory("Deploys happen on Fridays").at(now).build()).await.unwrap();
+        store.store_memory(memory("The wiki moved to Notion").at(now).build()).await.unwrap();
+        store.store_memory(memory("Standup is at 9").at(earlier).build()).await.unwrap();
+        // Storing it again moves it to a timestamp another memory already has
+        store.store_memory(memory("Standup is at 9").at(now).build()).await.unwrap();
+
+        let memories = store.get_recent_memories("group:1", None, 10).await.unwrap();
+        assert_eq!(memories.len(), 3);
+        assert!(memories.iter().all(|m| m.timestamp == now));
+    }
+
+    #[tokio::test]
+    async fn evicts_the_oldest_memories_beyond_the_cap() {
+        let store = MemoryStore::new(":memory:").unwrap().with_max_items(3);
+        for (i, content) in ["first", "second", "third", "fourth", "fifth"].into_iter().enumerate() {
+            store.store_memory(memory(content).age_secs(50 - i as i64).build()).await.unwrap();
+        }
+        let other_chat = memory("elsewhere").age_secs(100).chat("group:2").build();
+        store.store_memory(other_chat).await.unwrap();
+
+        let contents: Vec<String> = store
+            .get_recent_memories("group:1", None, 10)
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, vec!["fifth", "fourth", "third"]);
+        // The cap is per chat
+        assert_eq!(store.get_recent_memories("group:2", None, 10).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn glossary_terms_are_never_evicted() {
+        let store = MemoryStore::new(":memory:").unwrap().with_max_items(1);
+        let term = memory("Service level objective").age_secs(60).metadata(r#"{"glossary_term":"SLO"}"#).build();
+        store.store_memory(term).await.unwrap();
+        store.store_memory(memory("older note").age_secs(30).build()).await.unwrap();
+        store.store_memory(memory("newer note").age_secs(10).build()).await.unwrap();
+
+        let count = |sql: &str| -> i64 { store.db.lock().unwrap().query_row(sql, [], |row| row.get(0)).unwrap() };
+        assert_eq!(count("SELECT COUNT(*) FROM memories WHERE metadata IS NOT NULL"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM memories WHERE content = 'newer note'"), 1);
+        assert_eq!(count("SELECT COUNT(*) FROM memories"), 2);
+    }
+
+    #[tokio::test]
+    async fn retention_cleanup_covers_every_chat() {
+        let day = 24 * 60 * 60;
+        let store = MemoryStore::new(":memory:").unwrap();
+        for chat_id in ["group:1", "group:2"] {
+            for (content, age) in [("old", 40 * day), ("recent", day)] {
+                let memory = memory(&format!("{} in {}", content, chat_id)).age_secs(age).chat(chat_id).build();
+                store.store_memory(memory).await.unwrap();
+            }
+        }
+        let term = memory("Service level objective").age_secs(400 * day).metadata(r#"{"glossary_term":"SLO"}"#).build();
+        store.store_memory(term).await.unwrap();
+
+        assert_eq!(cleanup_all_chats(&store, 30).await.unwrap(), 2);
+
+        for chat_id in ["group:1", "group:2"] {
+            let contents: Vec<String> = store
+                .get_recent_memories(chat_id, None, 10)
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|m| m.metadata.is_none())
+                .map(|m| m.content)
+                .collect();
+            assert_eq!(contents, vec![format!("recent in {}", chat_id)]);
+        }
+        let terms: i64 = store
+            .db
+            .lock()
+            .unwrap()
+            .query_row("SELECT COUNT(*) FROM memories WHERE metadata IS NOT NULL", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(terms, 1);
+
+        // A second pass finds nothing more to delete
+        assert_eq!(cleanup_all_chats(&store, 30).await.unwrap(), 0);
+    }
+
+    // Behaviour every MemoryBackend should share, whatever database is behind it
+    async fn backend_suite(backend: &dyn MemoryBackend) {
+        backend.ping().await.unwrap();
+        assert!(backend.chat_ids().await.unwrap().is_empty());
+
+        let first = backend.store_memory(memory("alice's note").age_secs(30).build()).await.unwrap();
+        let bobs = memory("bob's note").age_secs(20).user("bob").build();
+        backend.store_memory(bobs).await.unwrap();
+        let term = memory("Service level objective").age_secs(10).metadata(r#"{"glossary_term":"SLO"}"#).build();
+        backend.store_memory(term).await.unwrap();
+
+        assert_eq!(backend.get_memory(first).await.unwrap().unwrap().content, "alice's note");
+        assert!(backend.get_memory(first + 1000).await.unwrap().is_none());
+        assert_eq!(backend.count_memories("group:1").await.unwrap(), 3);
+        assert_eq!(backend.chat_ids().await.unwrap(), vec!["group:1".to_string()]);
+        let by_bob = backend.get_recent_memories_by_user("group:1", None, "bob", 10).await.unwrap();
+        assert_eq!(by_bob.iter().map(|m| m.content.as_str()).collect::<Vec<_>>(), vec!["bob's note"]);
+
+        let metadata = r#"{"glossary_term":"SLO"}"#;
+        assert_eq!(
+            backend.get_memory_by_metadata("group:1", metadata).await.unwrap().unwrap().content,
+            "Service level objective"
+        );
+        assert_eq!(backend.delete_memories_by_metadata("group:1", metadata).await.unwrap(), 1);
+        assert!(backend.get_memory_by_metadata("group:1", metadata).await.unwrap().is_none());
+
+        assert_eq!(backend.update_embeddings(vec![(first, vec![1.0, 0.0])], "mock").await.unwrap(), 1);
+        let found = backend.search_similar_memories("group:1", None, &[1.0, 0.0], "mock", 5).await.unwrap();
+        assert_eq!(found[0].0.id, Some(first));
+
+        assert!(backend.get_chat_persona("group:1").await.unwrap().is_none());
+        backend.set_chat_persona("group:1", "alice", "You are a pirate.").await.unwrap();
+        backend.set_chat_persona("group:1", "alice", "You are a poet.").await.unwrap();
+        assert_eq!(backend.get_chat_persona("group:1").await.unwrap().as_deref(), Some("You are a poet."));
+        assert!(backend.reset_chat_persona("group:1").await.unwrap());
+        assert!(!backend.reset_chat_persona("group:1").await.unwrap());
+
+        backend.store_ask_turn("group:1", "carol", "First?", "One").await.unwrap();
+        backend.store_ask_turn("group:1", "carol", "Second?", "Two").await.unwrap();
+        let turns = backend.get_recent_ask_turns("group:1", 10).await.unwrap();
+        assert_eq!(turns.iter().map(|t| t.question.as_str()).collect::<Vec<_>>(), vec!["First?", "Second?"]);
+        assert_eq!(
+            backend.user_ids("group:1").await.unwrap(),
+            HashSet::from(["alice".to_string(), "bob".to_string(), "carol".to_string()])
+        );
+
+        let exported = backend.export_chat("group:1").await.unwrap();
+        assert_eq!(backend.import_chat("group:2", exported.clone()).await.unwrap(), exported.len());
+        assert_eq!(backend.import_chat("group:2", exported).await.unwrap(), 0);
+
+        assert_eq!(backend.delete_user("carol").await.unwrap(), 2);
+        assert!(backend.get_recent_ask_turns("group:1", 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn sqlite_backend_passes_the_shared_suite() {
+        backend_suite(&MemoryStore::new(":memory:").unwrap()).await;
+    }
+}
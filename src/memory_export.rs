@@ -0,0 +1,217 @@
+use anyhow::{anyhow, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::memory::Memory;
+
+/// Bumped whenever the dump format changes in a way older readers can't handle
+pub const EXPORT_FORMAT_VERSION: u32 = 1;
+
+/// A portable dump of one chat's memories, as produced by `/export`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryExport {
+    pub version: u32,
+    pub chat_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub memories: Vec<ExportedMemory>,
+}
+
+/// One memory in an export. Ids aren't kept, as they mean nothing to another store.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExportedMemory {
+    #[serde(default)]
+    pub thread_id: Option<String>,
+    pub user_id: String,
+    pub timestamp: DateTime<Utc>,
+    pub content: String,
+    // Base64 of the vector's little-endian f32s, as stored in the database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding: Option<String>,
+    // Model that made the embedding, so an import can tell whether it is still comparable
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub embedding_model: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<String>,
+}
+
+impl MemoryExport {
+    /// Embeddings make up most of an export's size, so they are only included on request
+    pub fn new(chat_id: &str, memories: &[Memory], include_embeddings: bool) -> Self {
+        let memories = memories
+            .iter()
+            .map(|memory| ExportedMemory {
+                thread_id: memory.thread_id.clone(),
+                user_id: memory.user_id.clone(),
+                timestamp: memory.timestamp,
+                content: memory.content.clone(),
+                embedding: memory
+                    .embedding
+                    .as_deref()
+                    .filter(|_| include_embeddings)
+                    .map(encode_embedding),
+                embedding_model: memory.embedding_model.clone().filter(|_| include_embeddings),
+                metadata: memory.metadata.clone(),
+            })
+            .collect();
+
+        Self {
+            version: EXPORT_FORMAT_VERSION,
+            chat_id: chat_id.to_string(),
+            exported_at: Utc::now(),
+            memories,
+        }
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+
+    /// Read an export back, as pasted from `/export`'s messages: the code fences and
+    /// "Part i/n" headers around each part are dropped and the parts joined up again
+    pub fn from_pasted(text: &str) -> Result<Self> {
+        // The JSON itself is on a single line, so none of its lines look like these. Kept
+        // lines aren't trimmed, as a part can start or end inside a string.
+        let json: String = text
+            .lines()
+            .filter(|line| !is_fence(line.trim()) && !is_part_header(line.trim()))
+            .collect();
+
+        let export: MemoryExport = serde_json::from_str(&json).map_err(|e| anyhow!("Not a valid memory export: {}", e))?;
+        if export.version > EXPORT_FORMAT_VERSION {
+            return Err(anyhow!(
+                "This export uses format version {}, but only versions up to {} are supported",
+                export.version,
+                EXPORT_FORMAT_VERSION
+            ));
+        }
+        Ok(export)
+    }
+}
+
+impl ExportedMemory {
+    /// The memory to store in `chat_id`, which needn't be the chat it was exported from
+    pub fn into_memory(self, chat_id: &str) -> Result<Memory> {
+        let embedding = self.embedding.as_deref().map(decode_embedding).transpose()?;
+
+        Ok(Memory {
+            id: None,
+            chat_id: chat_id.to_string(),
+            thread_id: self.thread_id,
+            user_id: self.user_id,
+            timestamp: self.timestamp,
+            content: self.content,
+            embedding,
+            embedding_model: self.embedding_model,
+            metadata: self.metadata,
+        })
+    }
+}
+
+fn encode_embedding(embedding: &[f32]) -> String {
+    let bytes: Vec<u8> = embedding.iter().flat_map(|f| f.to_le_bytes()).collect();
+    BASE64.encode(bytes)
+}
+
+fn decode_embedding(encoded: &str) -> Result<Vec<f32>> {
+    let bytes = BASE64.decode(encoded).map_err(|e| anyhow!("Invalid embedding: {}", e))?;
+    if bytes.len() % 4 != 0 {
+        return Err(anyhow!("Embedding of {} bytes is not a whole number of f32s", bytes.len()));
+    }
+    Ok(bytes
+        .chunks_exact(4)
+        .map(|chunk| f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]))
+        .collect())
+}
+
+fn is_fence(line: &str) -> bool {
+    let body = line.trim_start_matches('`');
+    line.len() - body.len() >= 3 && (body.is_empty() || body == "json")
+}
+
+fn is_part_header(line: &str) -> bool {
+    line.strip_prefix("Part ")
+        .and_then(|rest| rest.split_once('/'))
+        .is_some_and(|(part, count)| {
+            !part.is_empty() && !count.is_empty()
+                && part.chars().all(|c| c.is_ascii_digit())
+                && count.chars().all(|c| c.is_ascii_digit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory;
+
+    #[test]
+    fn round_trips_through_json_with_embeddings() {
+        let original = memory("Deploys happen on Fridays")
+            .id(7)
+            .thread("A")
+            .embedding(vec![0.25, -1.5, 3.0])
+            .embedding_model("mistral-embed")
+            .metadata(r#"{"source":"remember"}"#)
+            .build();
+        let json = MemoryExport::new("group:1", &[original.clone()], true).to_json().unwrap();
+
+        let export = MemoryExport::from_pasted(&json).unwrap();
+        assert_eq!(export.version, EXPORT_FORMAT_VERSION);
+        assert_eq!(export.chat_id, "group:1");
+
+        let restored = export.memories.into_iter().next().unwrap().into_memory("group:2").unwrap();
+        assert_eq!(restored.id, None);
+        assert_eq!(restored.chat_id, "group:2");
+        assert_eq!(restored.thread_id, original.thread_id);
+        assert_eq!(restored.user_id, original.user_id);
+        assert_eq!(restored.timestamp, original.timestamp);
+        assert_eq!(restored.content, original.content);
+        assert_eq!(restored.embedding, original.embedding);
+        assert_eq!(restored.embedding_model, original.embedding_model);
+        assert_eq!(restored.metadata, original.metadata);
+    }
+
+    #[test]
+    fn leaves_embeddings_out_unless_asked() {
+        let export = MemoryExport::new("group:1", &[memory("note").embedding(vec![1.0]).build()], false);
+        let json = export.to_json().unwrap();
+        assert!(!json.contains("embedding"));
+
+        let restored = export.memories.into_iter().next().unwrap().into_memory("group:1").unwrap();
+        assert_eq!(restored.embedding, None);
+        assert_eq!(restored.embedding_model, None);
+    }
+
+    #[test]
+    fn reads_pasted_parts_back() {
+        let json = MemoryExport::new("group:1", &[memory("split me").build()], false).to_json().unwrap();
+        let (first, second) = json.split_at(json.find("split").unwrap() + 3);
+        let pasted = format!("Part 1/2\n```json\n{}\n```\nPart 2/2\n````json\n{}\n````\n", first, second);
+
+        let export = MemoryExport::from_pasted(&pasted).unwrap();
+        assert_eq!(export.memories[0].content, "split me");
+    }
+
+    #[test]
+    fn rejects_newer_formats_and_bad_embeddings() {
+        let mut export = MemoryExport::new("group:1", &[], false);
+        export.version = EXPORT_FORMAT_VERSION + 1;
+        let error = MemoryExport::from_pasted(&export.to_json().unwrap()).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "This export uses format version {}, but only versions up to {} are supported",
+                EXPORT_FORMAT_VERSION + 1,
+                EXPORT_FORMAT_VERSION
+            )
+        );
+
+        assert!(MemoryExport::from_pasted("not json").unwrap_err().to_string().starts_with("Not a valid memory export"));
+
+        let mut exported = MemoryExport::new("group:1", &[memory("note").build()], false).memories.remove(0);
+        exported.embedding = Some(BASE64.encode([0u8; 6]));
+        let error = exported.into_memory("group:1").unwrap_err();
+        assert_eq!(error.to_string(), "Embedding of 6 bytes is not a whole number of f32s");
+    }
+}
@@ -0,0 +1,84 @@
+use anyhow::Result;
+
+use crate::llm::LlmProvider;
+
+/// The verdict on one piece of text, for `/moderate` and anything else that checks content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationResult {
+    Safe,
+    Flagged { reason: String },
+}
+
+impl ModerationResult {
+    // The model answers "FLAGGED: <reason>" or "SAFE"; only the reason is kept
+    fn from_response(is_flagged: bool, response: &str) -> Self {
+        if !is_flagged {
+            return ModerationResult::Safe;
+        }
+
+        let reason = response.trim().strip_prefix("FLAGGED:").unwrap_or(response).trim();
+        ModerationResult::Flagged {
+            reason: if reason.is_empty() {
+                "No reason given".to_string()
+            } else {
+                reason.to_string()
+            },
+        }
+    }
+}
+
+/// Ask the LLM whether `text` is harmful or inappropriate
+pub async fn moderate_content(llm: &dyn LlmProvider, text: &str) -> Result<ModerationResult> {
+    let (is_flagged, response) = llm.moderate(text).await?;
+    Ok(ModerationResult::from_response(is_flagged, &response))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatMessage, ChatResult};
+    use async_trait::async_trait;
+
+    // Answers every moderation request with `reply`, or fails when there is none
+    struct Verdict(Option<&'static str>);
+
+    #[async_trait]
+    impl LlmProvider for Verdict {
+        async fn chat_with_usage(&self, _system_prompt: &str, _messages: &[ChatMessage]) -> Result<ChatResult> {
+            match self.0 {
+                Some(reply) => Ok(ChatResult { content: reply.to_string(), usage: None }),
+                None => Err(anyhow::anyhow!("model unavailable")),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn safe_content_is_safe() {
+        let result = moderate_content(&Verdict(Some("SAFE")), "Lunch at noon?").await.unwrap();
+
+        assert_eq!(result, ModerationResult::Safe);
+    }
+
+    #[tokio::test]
+    async fn flagged_content_keeps_only_the_reason() {
+        let result = moderate_content(&Verdict(Some("FLAGGED:  personal insult \n")), "You are an idiot")
+            .await
+            .unwrap();
+
+        assert_eq!(result, ModerationResult::Flagged { reason: "personal insult".to_string() });
+    }
+
+    #[tokio::test]
+    async fn a_flag_without_a_reason_says_so() {
+        let result = moderate_content(&Verdict(Some("FLAGGED:")), "You are an idiot").await.unwrap();
+
+        assert_eq!(result, ModerationResult::Flagged { reason: "No reason given".to_string() });
+    }
+
+    #[tokio::test]
+    async fn model_errors_are_returned() {
+        let error = moderate_content(&Verdict(None), "anything").await.unwrap_err();
+
+        assert_eq!(error.to_string(), "model unavailable");
+    }
+}
@@ -0,0 +1,231 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// A memorable line someone saved in a chat
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub id: i64,
+    // Who added it
+    pub user_id: String,
+    pub text: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Quotes saved with /quote, kept per chat
+#[derive(Debug, Clone)]
+pub struct QuoteStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl QuoteStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS quotes (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS quotes_chat_idx ON quotes (chat_id)",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    /// Save a quote; returns its id
+    pub async fn add(&self, chat_id: &str, user_id: &str, text: &str) -> Result<i64> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let text = text.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<i64> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO quotes (chat_id, user_id, text, created_at) VALUES (?1, ?2, ?3, ?4)",
+                params![chat_id, user_id, text, Utc::now().to_rfc3339()],
+            )?;
+
+            Ok(conn.last_insert_rowid())
+        }).await?
+    }
+
+    /// One of the chat's quotes, each equally likely; None if it has none
+    pub async fn random(&self, chat_id: &str) -> Result<Option<Quote>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<Quote>> {
+            let conn = db.lock().unwrap();
+
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM quotes WHERE chat_id = ?1",
+                params![chat_id],
+                |row| row.get(0),
+            )?;
+            if count == 0 {
+                return Ok(None);
+            }
+
+            // Picking a position rather than ORDER BY RANDOM() avoids sorting the whole chat,
+            // and unlike picking an id it isn't skewed by gaps from other chats' quotes
+            let offset = rand::thread_rng().gen_range(0..count);
+            let result = conn.query_row(
+                "SELECT id, user_id, text, created_at FROM quotes
+                 WHERE chat_id = ?1
+                 ORDER BY id
+                 LIMIT 1 OFFSET ?2",
+                params![chat_id, offset],
+                row_to_quote,
+            );
+
+            match result {
+                Ok(quote) => Ok(Some(quote)),
+                // Deleted between the count and the pick
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        }).await?
+    }
+
+    /// The chat's quotes containing `keyword`, ignoring ASCII case, newest first
+    pub async fn search(&self, chat_id: &str, keyword: &str, limit: usize) -> Result<Vec<Quote>> {
+        let chat_id = chat_id.to_string();
+        // Match the keyword literally, not as a LIKE pattern
+        let pattern = format!(
+            "%{}%",
+            keyword.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<Quote>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT id, user_id, text, created_at FROM quotes
+                 WHERE chat_id = ?1 AND text LIKE ?2 ESCAPE '\\'
+                 ORDER BY id DESC
+                 LIMIT ?3",
+            )?;
+            let quotes = stmt
+                .query_map(params![chat_id, pattern, limit as i64], row_to_quote)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(quotes)
+        }).await?
+    }
+
+    /// Delete every quote the user added, in any chat; returns how many there were
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM quotes WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted)
+        }).await?
+    }
+}
+
+fn row_to_quote(row: &Row) -> rusqlite::Result<Quote> {
+    let created_at: String = row.get(3)?;
+    let created_at = DateTime::parse_from_rfc3339(&created_at)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))?
+        .with_timezone(&Utc);
+
+    Ok(Quote {
+        id: row.get(0)?,
+        user_id: row.get(1)?,
+        text: row.get(2)?,
+        created_at,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[tokio::test]
+    async fn adds_quotes_to_one_chat() {
+        let store = QuoteStore::new(":memory:").unwrap();
+        let first = store.add("group:1", "alice", "Ship it on Friday").await.unwrap();
+        let second = store.add("group:1", "bob", "It works on my machine").await.unwrap();
+        assert!(second > first);
+
+        let quote = store.random("group:1").await.unwrap().unwrap();
+        assert!([first, second].contains(&quote.id));
+        assert!(store.random("group:2").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn random_picks_are_uniform_across_the_chat() {
+        let store = QuoteStore::new(":memory:").unwrap();
+        // Other chats' quotes interleave with this one's, leaving gaps in its ids
+        for i in 0..4 {
+            store.add("group:1", "alice", &format!("quote {}", i)).await.unwrap();
+            store.add("group:2", "bob", "elsewhere").await.unwrap();
+            store.add("group:2", "bob", "elsewhere too").await.unwrap();
+        }
+
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        for _ in 0..4000 {
+            let quote = store.random("group:1").await.unwrap().unwrap();
+            *counts.entry(quote.text).or_default() += 1;
+        }
+
+        assert_eq!(counts.len(), 4);
+        for (text, count) in counts {
+            // 1000 expected each; this range is over 6 standard deviations wide
+            assert!((820..=1180).contains(&count), "{} picked {} times", text, count);
+        }
+    }
+
+    #[tokio::test]
+    async fn searches_literally_and_ignoring_case() {
+        let store = QuoteStore::new(":memory:").unwrap();
+        store.add("group:1", "alice", "Coffee first, then code").await.unwrap();
+        store.add("group:1", "bob", "100% done, 90% of the time").await.unwrap();
+        store.add("group:1", "carol", "No coffee_machine, no deploy").await.unwrap();
+        store.add("group:2", "dave", "Coffee is elsewhere").await.unwrap();
+
+        let texts = |quotes: Vec<Quote>| -> Vec<String> { quotes.into_iter().map(|q| q.text).collect() };
+        assert_eq!(
+            texts(store.search("group:1", "COFFEE", 5).await.unwrap()),
+            vec!["No coffee_machine, no deploy", "Coffee first, then code"]
+        );
+        assert_eq!(texts(store.search("group:1", "coffee", 1).await.unwrap()), vec!["No coffee_machine, no deploy"]);
+        assert_eq!(texts(store.search("group:1", "100%", 5).await.unwrap()), vec!["100% done, 90% of the time"]);
+        assert_eq!(texts(store.search("group:1", "e_m", 5).await.unwrap()), vec!["No coffee_machine, no deploy"]);
+        assert!(store.search("group:1", "%", 5).await.unwrap().len() == 1);
+        assert!(store.search("group:1", "tea", 5).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn deletes_only_the_users_quotes() {
+        let store = QuoteStore::new(":memory:").unwrap();
+        store.add("group:1", "alice", "one").await.unwrap();
+        store.add("group:2", "alice", "two").await.unwrap();
+        store.add("group:1", "bob", "three").await.unwrap();
+
+        assert_eq!(store.delete_user("alice").await.unwrap(), 2);
+        assert_eq!(store.random("group:1").await.unwrap().unwrap().text, "three");
+        assert!(store.random("group:2").await.unwrap().is_none());
+    }
+}
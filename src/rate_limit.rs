@@ -0,0 +1,151 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::RateLimitConfig;
+
+// How often buckets that have refilled completely are dropped
+const PRUNE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Token bucket state for a single (user, command) pair
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+#[derive(Debug)]
+struct Buckets {
+    buckets: HashMap<(String, String), Bucket>,
+    last_pruned: Instant,
+}
+
+/// Per-user, per-command token bucket rate limiter.
+/// Commands without a configured limit are never throttled.
+#[derive(Debug)]
+pub struct RateLimiter {
+    limits: HashMap<String, RateLimitConfig>,
+    buckets: Mutex<Buckets>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: HashMap<String, RateLimitConfig>) -> Self {
+        Self {
+            limits,
+            buckets: Mutex::new(Buckets {
+                buckets: HashMap::new(),
+                last_pruned: Instant::now(),
+            }),
+        }
+    }
+
+    /// Take a token for `user_id` running `command`, returning false if the user is over the limit
+    pub fn check(&self, user_id: &str, command: &str) -> bool {
+        self.check_at(user_id, command, Instant::now())
+    }
+
+    fn check_at(&self, user_id: &str, command: &str, now: Instant) -> bool {
+        let limit = match self.limits.get(command) {
+            Some(limit) => limit,
+            None => return true,
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+        if now.saturating_duration_since(buckets.last_pruned) >= PRUNE_INTERVAL {
+            self.prune(&mut buckets, now);
+        }
+
+        let capacity = limit.requests as f64;
+        let bucket = buckets
+            .buckets
+            .entry((user_id.to_string(), command.to_string()))
+            .or_insert(Bucket {
+                tokens: capacity,
+                last_refill: now,
+            });
+
+        // Refill according to the time elapsed since the last request
+        bucket.tokens = refilled(bucket, limit, now);
+        bucket.last_refill = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            true
+        } else {
+            false
+        }
+    }
+
+    // A full bucket is the same as a missing one, so only users still being limited are kept
+    fn prune(&self, buckets: &mut Buckets, now: Instant) {
+        let limits = &self.limits;
+        buckets.buckets.retain(|(_, command), bucket| match limits.get(command) {
+            Some(limit) => refilled(bucket, limit, now) < limit.requests as f64,
+            None => false,
+        });
+        buckets.last_pruned = now;
+    }
+}
+
+// The bucket's tokens after refilling up to `now`, never above the limit's capacity
+fn refilled(bucket: &Bucket, limit: &RateLimitConfig, now: Instant) -> f64 {
+    let capacity = limit.requests as f64;
+    let refill_per_sec = capacity / limit.per_seconds as f64;
+    let elapsed = now.saturating_duration_since(bucket.last_refill).as_secs_f64();
+    (bucket.tokens + elapsed * refill_per_sec).min(capacity)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limiter(requests: u32, per_seconds: u64) -> RateLimiter {
+        RateLimiter::new(HashMap::from([(
+            "ask".to_string(),
+            RateLimitConfig { requests, per_seconds },
+        )]))
+    }
+
+    #[test]
+    fn refuses_a_user_over_the_limit() {
+        let limiter = limiter(3, 60);
+        let now = Instant::now();
+
+        assert!((0..3).all(|_| limiter.check_at("alice", "ask", now)));
+        assert!(!limiter.check_at("alice", "ask", now));
+        // Other users and commands have their own buckets
+        assert!(limiter.check_at("bob", "ask", now));
+        assert!(limiter.check_at("alice", "echo", now));
+    }
+
+    #[test]
+    fn refills_over_time() {
+        let limiter = limiter(2, 10);
+        let now = Instant::now();
+
+        assert!(limiter.check_at("alice", "ask", now));
+        assert!(limiter.check_at("alice", "ask", now));
+        assert!(!limiter.check_at("alice", "ask", now));
+        // One token comes back every 5 seconds
+        assert!(!limiter.check_at("alice", "ask", now + Duration::from_secs(4)));
+        assert!(limiter.check_at("alice", "ask", now + Duration::from_secs(10)));
+    }
+
+    #[test]
+    fn drops_buckets_that_have_refilled() {
+        let limiter = limiter(2, 10);
+        let now = Instant::now();
+
+        limiter.check_at("alice", "ask", now);
+        limiter.check_at("bob", "ask", now + PRUNE_INTERVAL - Duration::from_secs(1));
+        limiter.check_at("bob", "ask", now + PRUNE_INTERVAL - Duration::from_secs(1));
+        assert_eq!(limiter.buckets.lock().unwrap().buckets.len(), 2);
+
+        // Alice's bucket is full again by now, Bob's isn't
+        limiter.check_at("carol", "ask", now + PRUNE_INTERVAL);
+        let buckets = limiter.buckets.lock().unwrap();
+        let users: Vec<&str> = buckets.buckets.keys().map(|(user, _)| user.as_str()).collect();
+        assert_eq!(users.len(), 2);
+        assert!(users.contains(&"bob") && users.contains(&"carol"));
+    }
+}
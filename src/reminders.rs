@@ -0,0 +1,406 @@
+use anyhow::Result;
+use chrono::{DateTime, Days, TimeZone, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection, Row};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use tracing::{error, info};
+
+use crate::commands::remindme::PENDING_REMINDERS;
+use crate::webhook::{WebhookNotifier, WebhookPayload};
+
+/// How often a reminder comes back after firing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    Daily,
+    Weekly,
+}
+
+impl Repeat {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Repeat::Daily => "daily",
+            Repeat::Weekly => "weekly",
+        }
+    }
+    
+    fn days(&self) -> u64 {
+        match self {
+            Repeat::Daily => 1,
+            Repeat::Weekly => 7,
+        }
+    }
+}
+
+impl FromStr for Repeat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "daily" => Ok(Repeat::Daily),
+            "weekly" => Ok(Repeat::Weekly),
+            other => Err(format!("unknown repeat '{}'", other)),
+        }
+    }
+}
+
+/// A reminder that has not fired yet
+#[derive(Debug, Clone)]
+pub struct Reminder {
+    pub id: i64,
+    pub chat_id: String,
+    pub user_id: String,
+    pub text: String,
+    pub fire_at: DateTime<Utc>,
+    // None for one-shot reminders
+    pub repeat: Option<Repeat>,
+    // The user's timezone when it was set, which recurring reminders keep to
+    pub timezone: Tz,
+}
+
+/// When a recurring reminder that was due at `fire_at` should next fire: the first
+/// occurrence after `now`, so occurrences missed while the bot was down are skipped.
+/// Days are counted in `tz`, so the wall-clock time survives DST changes.
+pub fn next_fire_at<Tz: TimeZone>(fire_at: DateTime<Utc>, repeat: Repeat, now: DateTime<Utc>, tz: &Tz) -> Option<DateTime<Utc>> {
+    let local = fire_at.with_timezone(tz).naive_local();
+    let mut occurrence = 1;
+    loop {
+        let naive = local.checked_add_days(Days::new(repeat.days() * occurrence))?;
+        // A time skipped by a DST jump falls back to an hour later
+        let next = tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .or_else(|| tz.from_local_datetime(&(naive + chrono::Duration::hours(1))).earliest())?
+            .with_timezone(&Utc);
+        if next > now {
+            return Some(next);
+        }
+        occurrence += 1;
+    }
+}
+
+/// Pending reminders, kept in SQLite so they survive restarts
+#[derive(Debug, Clone)]
+pub struct ReminderStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl ReminderStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS reminders (
+                id INTEGER PRIMARY KEY,
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                text TEXT NOT NULL,
+                fire_at TEXT NOT NULL,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS reminders_user_idx ON reminders (user_id)",
+            [],
+        )?;
+        
+        // Recurring reminders were added later
+        let has_repeat: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('reminders') WHERE name = 'repeat'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_repeat {
+            conn.execute("ALTER TABLE reminders ADD COLUMN repeat TEXT", [])?;
+        }
+        
+        let has_timezone: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('reminders') WHERE name = 'timezone'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !has_timezone {
+            conn.execute("ALTER TABLE reminders ADD COLUMN timezone TEXT", [])?;
+        }
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn add(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        text: &str,
+        fire_at: DateTime<Utc>,
+        repeat: Option<Repeat>,
+        timezone: Tz,
+    ) -> Result<Reminder> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let text = text.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Reminder> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO reminders (chat_id, user_id, text, fire_at, created_at, repeat, timezone)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    chat_id,
+                    user_id,
+                    text,
+                    fire_at.to_rfc3339(),
+                    Utc::now().to_rfc3339(),
+                    repeat.map(|repeat| repeat.as_str()),
+                    timezone.name(),
+                ],
+            )?;
+
+            Ok(Reminder {
+                id: conn.last_insert_rowid(),
+                chat_id,
+                user_id,
+                text,
+                fire_at,
+                repeat,
+                timezone,
+            })
+        }).await?
+    }
+
+    /// Number of reminders the user has waiting to fire, across all chats
+    pub async fn count_pending_for_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM reminders WHERE user_id = ?1",
+                params![user_id],
+                |row| row.get(0),
+            )?;
+
+            Ok(count as usize)
+        }).await?
+    }
+
+    /// Every reminder still waiting to fire, soonest first
+    pub async fn pending(&self) -> Result<Vec<Reminder>> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<Reminder>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT id, chat_id, user_id, text, fire_at, repeat, timezone FROM reminders ORDER BY fire_at ASC",
+            )?;
+            let reminders = stmt
+                .query_map([], row_to_reminder)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok(reminders)
+        }).await?
+    }
+
+    /// Move a recurring reminder to its next occurrence; false if it no longer exists
+    pub async fn reschedule(&self, id: i64, fire_at: DateTime<Utc>) -> Result<bool> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+            let updated = conn.execute(
+                "UPDATE reminders SET fire_at = ?1 WHERE id = ?2",
+                params![fire_at.to_rfc3339(), id],
+            )?;
+            Ok(updated > 0)
+        }).await?
+    }
+
+    /// Forget a reminder once it has fired
+    pub async fn remove(&self, id: i64) -> Result<()> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+            conn.execute("DELETE FROM reminders WHERE id = ?1", params![id])?;
+            Ok(())
+        }).await?
+    }
+
+    /// Whether the reminder is still stored, i.e. hasn't been deleted since it was scheduled
+    pub async fn exists(&self, id: i64) -> Result<bool> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM reminders WHERE id = ?1",
+                params![id],
+                |row| row.get(0),
+            )?;
+            Ok(count > 0)
+        }).await?
+    }
+
+    /// Drop every reminder the user has set, in any chat; returns how many there were
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM reminders WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted)
+        }).await?
+    }
+}
+
+fn row_to_reminder(row: &Row) -> rusqlite::Result<Reminder> {
+    let fire_at: String = row.get(4)?;
+    let fire_at = DateTime::parse_from_rfc3339(&fire_at)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(4, rusqlite::types::Type::Text, Box::new(e)))?
+        .with_timezone(&Utc);
+    // Unknown values from a newer version are treated as one-shot
+    let repeat: Option<String> = row.get(5)?;
+    // Reminders from before timezones were tracked count as UTC
+    let timezone: Option<String> = row.get(6)?;
+
+    Ok(Reminder {
+        id: row.get(0)?,
+        chat_id: row.get(1)?,
+        user_id: row.get(2)?,
+        text: row.get(3)?,
+        fire_at,
+        repeat: repeat.and_then(|repeat| repeat.parse().ok()),
+        timezone: timezone.and_then(|timezone| timezone.parse().ok()).unwrap_or(Tz::UTC),
+    })
+}
+
+/// Fire `reminder` at its time (immediately if that has passed). One-shot reminders are
+/// then removed from the store; recurring ones are moved to their next occurrence.
+pub fn schedule(store: Arc<ReminderStore>, mut reminder: Reminder, webhook: Option<Arc<WebhookNotifier>>) {
+    PENDING_REMINDERS.fetch_add(1, Ordering::Relaxed);
+
+    tokio::spawn(async move {
+        let delay = (reminder.fire_at - Utc::now()).to_std().unwrap_or_default();
+        info!("Reminder #{} scheduled to trigger in {} seconds", reminder.id, delay.as_secs());
+        tokio::time::sleep(delay).await;
+        PENDING_REMINDERS.fetch_sub(1, Ordering::Relaxed);
+
+        // Deleted while we were waiting, e.g. by /forgetme
+        match store.exists(reminder.id).await {
+            Ok(false) => return,
+            Ok(true) => {}
+            Err(e) => error!("Failed to check reminder #{}: {}", reminder.id, e),
+        }
+
+        info!("REMINDER #{} TRIGGERED for user {} in {}: {}",
+              reminder.id, reminder.user_id, reminder.chat_id, reminder.text);
+        if let Some(webhook) = &webhook {
+            webhook.notify(WebhookPayload::reminder_fired(&reminder.chat_id, &reminder.user_id, &reminder.text));
+        }
+
+        let next = reminder
+            .repeat
+            .and_then(|repeat| next_fire_at(reminder.fire_at, repeat, Utc::now(), &reminder.timezone));
+        match next {
+            Some(next) => match store.reschedule(reminder.id, next).await {
+                Ok(true) => {
+                    reminder.fire_at = next;
+                    schedule(store, reminder, webhook);
+                }
+                // Removed while we were waiting
+                Ok(false) => {}
+                Err(e) => error!("Failed to reschedule reminder #{}: {}", reminder.id, e),
+            },
+            None => {
+                if let Err(e) = store.remove(reminder.id).await {
+                    error!("Failed to remove fired reminder #{}: {}", reminder.id, e);
+                }
+            }
+        }
+    });
+}
+
+/// Reschedule reminders stored before a restart; overdue ones fire right away
+pub async fn resume_pending(store: Arc<ReminderStore>, webhook: Option<Arc<WebhookNotifier>>) -> Result<usize> {
+    let reminders = store.pending().await?;
+    let count = reminders.len();
+    for reminder in reminders {
+        schedule(store.clone(), reminder, webhook.clone());
+    }
+    Ok(count)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn daily_reminders_keep_their_local_time_across_dst() {
+        let berlin = chrono_tz::Europe::Berlin;
+
+        // 09:00 CET the day before clocks go forward, then 09:00 CEST
+        let fire_at = utc("2026-03-28T08:00:00Z");
+        assert_eq!(
+            next_fire_at(fire_at, Repeat::Daily, fire_at, &berlin),
+            Some(utc("2026-03-29T07:00:00Z"))
+        );
+
+        // 09:00 CEST the week before clocks go back, then 09:00 CET
+        let fire_at = utc("2026-10-20T07:00:00Z");
+        assert_eq!(
+            next_fire_at(fire_at, Repeat::Weekly, fire_at, &berlin),
+            Some(utc("2026-10-27T08:00:00Z"))
+        );
+    }
+
+    #[test]
+    fn a_time_skipped_by_dst_moves_an_hour_later() {
+        // 02:30 doesn't exist in Berlin on 2026-03-29
+        let fire_at = utc("2026-03-28T01:30:00Z");
+        assert_eq!(
+            next_fire_at(fire_at, Repeat::Daily, fire_at, &chrono_tz::Europe::Berlin),
+            Some(utc("2026-03-29T01:30:00Z"))
+        );
+    }
+
+    #[test]
+    fn missed_occurrences_are_skipped() {
+        let fire_at = utc("2026-03-01T09:00:00Z");
+        let now = utc("2026-03-10T12:00:00Z");
+
+        assert_eq!(next_fire_at(fire_at, Repeat::Daily, now, &Utc), Some(utc("2026-03-11T09:00:00Z")));
+        assert_eq!(next_fire_at(fire_at, Repeat::Weekly, now, &Utc), Some(utc("2026-03-15T09:00:00Z")));
+    }
+
+    #[tokio::test]
+    async fn stores_the_repeat_and_next_fire_time() {
+        let store = ReminderStore::new(":memory:").unwrap();
+        let reminder = store
+            .add("group:1", "alice", "stand-up", utc("2026-03-01T09:00:00Z"), Some(Repeat::Weekly), Tz::Europe__Berlin)
+            .await
+            .unwrap();
+
+        assert!(store.reschedule(reminder.id, utc("2026-03-08T09:00:00Z")).await.unwrap());
+
+        let pending = store.pending().await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].fire_at, utc("2026-03-08T09:00:00Z"));
+        assert_eq!(pending[0].repeat, Some(Repeat::Weekly));
+        assert_eq!(pending[0].timezone, Tz::Europe__Berlin);
+    }
+}
@@ -0,0 +1,142 @@
+use anyhow::Result;
+use reqwest::StatusCode;
+use std::future::Future;
+use std::time::Duration;
+use tracing::{error, info};
+
+use crate::llm::{ChatMessage, LlmError, LlmProvider};
+use crate::memory::EmbeddingModel;
+
+// How long each test call may take, retries included
+const SELF_TEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Make a tiny chat and embedding call at startup, so a bad key or base URL shows up in
+/// the logs straight away instead of on the first command. Returns what failed.
+pub async fn run(
+    llm: Option<&dyn LlmProvider>,
+    embeddings: Option<&(dyn EmbeddingModel + Send + Sync)>,
+) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    if let Some(llm) = llm {
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "ping".to_string(),
+        }];
+        let call = llm.chat("Reply with the single word: pong", &messages);
+        if let Err(problem) = check("Chat", "mistral_api_key", "the chat model", call).await {
+            problems.push(problem);
+        }
+    }
+
+    if let Some(embeddings) = embeddings {
+        let call = embeddings.embed_text("ping");
+        if let Err(problem) = check("Embeddings", "embeddings.api_key", "embeddings.base_url and embeddings.model", call).await {
+            problems.push(problem);
+        }
+    }
+
+    problems
+}
+
+// Run one test call, describing a failure together with what to check
+async fn check<T>(
+    endpoint: &str,
+    key_setting: &str,
+    endpoint_settings: &str,
+    call: impl Future<Output = Result<T>>,
+) -> Result<(), String> {
+    let problem = match tokio::time::timeout(SELF_TEST_TIMEOUT, call).await {
+        Ok(Ok(_)) => {
+            info!("Self-test: {} endpoint is working", endpoint);
+            return Ok(());
+        }
+        Ok(Err(e)) => format!("{} endpoint failed: {}. {}", endpoint, e, advice(&e, key_setting, endpoint_settings)),
+        Err(_) => format!(
+            "{} endpoint didn't answer within {}s. Check {} and network access.",
+            endpoint,
+            SELF_TEST_TIMEOUT.as_secs(),
+            endpoint_settings
+        ),
+    };
+
+    error!("Self-test: {}", problem);
+    Err(problem)
+}
+
+// What the operator should look at, judging by how the call failed
+fn advice(error: &anyhow::Error, key_setting: &str, endpoint_settings: &str) -> String {
+    let cause = error.chain().find_map(|cause| cause.downcast_ref::<LlmError>());
+    match cause {
+        Some(LlmError::Api { status, .. }) if *status == StatusCode::UNAUTHORIZED || *status == StatusCode::FORBIDDEN => {
+            format!("Check that {} is set to a valid API key.", key_setting)
+        }
+        Some(LlmError::Api { status, .. }) if *status == StatusCode::NOT_FOUND => {
+            format!("Check {}.", endpoint_settings)
+        }
+        Some(LlmError::Request(_)) => format!("Check {} and network access.", endpoint_settings),
+        Some(LlmError::RateLimited) => "The API is rate limiting us; it should work once that passes.".to_string(),
+        _ => "Check the LLM settings in the config.".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatResult, MistralEmbedding, MockLlm};
+    use crate::test_support::{MockResponse, MockServer};
+    use async_trait::async_trait;
+
+    // Refuses every request the way the API does with a bad key
+    struct UnauthorizedLlm;
+
+    #[async_trait]
+    impl LlmProvider for UnauthorizedLlm {
+        async fn chat_with_usage(&self, _system_prompt: &str, _messages: &[ChatMessage]) -> Result<ChatResult> {
+            Err(LlmError::Api {
+                status: StatusCode::UNAUTHORIZED,
+                body: "Unauthorized".to_string(),
+                retries: 0,
+            }
+            .into())
+        }
+    }
+
+    fn embeddings(server: &MockServer) -> MistralEmbedding {
+        MistralEmbedding::new("test-key")
+            .with_base_url(&server.url)
+            .with_model("all-minilm")
+    }
+
+    #[tokio::test]
+    async fn working_endpoints_report_no_problems() {
+        let server = MockServer::start(vec![MockResponse::json(serde_json::json!({
+            "data": [{ "embedding": [0.1, 0.2, 0.3] }]
+        }))])
+        .await;
+
+        let problems = run(Some(&MockLlm), Some(&embeddings(&server))).await;
+
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(server.requests().len(), 1);
+        assert_eq!(server.requests()[0].path, "/embeddings");
+    }
+
+    #[tokio::test]
+    async fn failing_endpoints_say_what_to_check() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::NOT_FOUND)]).await;
+
+        let problems = run(Some(&UnauthorizedLlm), Some(&embeddings(&server))).await;
+
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        assert!(problems[0].starts_with("Chat endpoint failed: "), "{}", problems[0]);
+        assert!(problems[0].ends_with("Check that mistral_api_key is set to a valid API key."), "{}", problems[0]);
+        assert!(problems[1].starts_with("Embeddings endpoint failed: "), "{}", problems[1]);
+        assert!(problems[1].ends_with("Check embeddings.base_url and embeddings.model."), "{}", problems[1]);
+    }
+
+    #[tokio::test]
+    async fn skips_what_is_not_configured() {
+        assert!(run(None, None).await.is_empty());
+    }
+}
@@ -0,0 +1,86 @@
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// The latest summary of a chat and how far into the chat it reaches
+#[derive(Debug, Clone)]
+pub struct RollingSummary {
+    pub summary: String,
+    // Event index of the last message the summary covers
+    pub watermark: u32,
+}
+
+/// One rolling summary per chat, so re-summarizing only has to read the messages posted since
+#[derive(Debug, Clone)]
+pub struct SummaryStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl SummaryStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS summaries (
+                chat_id TEXT PRIMARY KEY,
+                summary TEXT NOT NULL,
+                watermark INTEGER NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn get(&self, chat_id: &str) -> Result<Option<RollingSummary>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<RollingSummary>> {
+            let conn = db.lock().unwrap();
+
+            let result = conn.query_row(
+                "SELECT summary, watermark FROM summaries WHERE chat_id = ?1",
+                params![chat_id],
+                |row| Ok(RollingSummary {
+                    summary: row.get(0)?,
+                    watermark: row.get(1)?,
+                }),
+            );
+
+            match result {
+                Ok(summary) => Ok(Some(summary)),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(anyhow!("Error retrieving summary: {}", e)),
+            }
+        }).await?
+    }
+
+    /// Replace the chat's summary with one covering messages up to `watermark`
+    pub async fn set(&self, chat_id: &str, summary: &str, watermark: u32) -> Result<()> {
+        let chat_id = chat_id.to_string();
+        let summary = summary.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO summaries (chat_id, summary, watermark, updated_at)
+                VALUES (?1, ?2, ?3, ?4)
+                ON CONFLICT(chat_id) DO UPDATE SET
+                    summary = excluded.summary,
+                    watermark = excluded.watermark,
+                    updated_at = excluded.updated_at",
+                params![chat_id, summary, watermark, Utc::now().to_rfc3339()],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+}
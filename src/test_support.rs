@@ -0,0 +1,218 @@
+// Helpers for unit tests: a local server standing in for the Mistral, weather and other
+// APIs the bot calls, and a builder for stored memories. Not every test uses every helper.
+#![allow(dead_code)]
+
+use axum::body::{Body, Bytes};
+use axum::http::{Response, StatusCode, Uri};
+use axum::Router;
+use chrono::{DateTime, Utc};
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::memory::Memory;
+
+/// One canned reply of a `MockServer`
+#[derive(Debug, Clone)]
+pub struct MockResponse {
+    pub status: StatusCode,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: String,
+    // How long the server takes to answer
+    pub delay: Duration,
+}
+
+impl MockResponse {
+    pub fn json(body: serde_json::Value) -> Self {
+        Self::status(StatusCode::OK)
+            .with_header("content-type", "application/json")
+            .with_body(body.to_string())
+    }
+
+    pub fn status(status: StatusCode) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: String::new(),
+            delay: Duration::ZERO,
+        }
+    }
+
+    pub fn with_header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    pub fn with_body(mut self, body: String) -> Self {
+        self.body = body;
+        self
+    }
+
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+}
+
+/// A request the server received
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub path: String,
+    pub query: Option<String>,
+    pub body: String,
+}
+
+impl RecordedRequest {
+    pub fn json(&self) -> serde_json::Value {
+        serde_json::from_str(&self.body).expect("request body is not JSON")
+    }
+}
+
+/// A local HTTP server answering each request with the next canned response, repeating
+/// the last one once the others are used up, and recording what it was sent
+pub struct MockServer {
+    pub url: String,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+    // Most requests it was answering at once
+    max_in_flight: Arc<AtomicUsize>,
+}
+
+impl MockServer {
+    pub async fn start(responses: Vec<MockResponse>) -> Self {
+        let responses = Arc::new(Mutex::new(VecDeque::from(responses)));
+        let requests = Arc::new(Mutex::new(Vec::new()));
+
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let in_flight = Arc::new(AtomicUsize::new(0));
+
+        let recorded = requests.clone();
+        let max_seen = max_in_flight.clone();
+        let app = Router::new().fallback(move |uri: Uri, body: Bytes| {
+            let responses = responses.clone();
+            let recorded = recorded.clone();
+            let in_flight = in_flight.clone();
+            let max_seen = max_seen.clone();
+            async move {
+                let now_in_flight = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_seen.fetch_max(now_in_flight, Ordering::SeqCst);
+
+                recorded.lock().unwrap().push(RecordedRequest {
+                    path: uri.path().to_string(),
+                    query: uri.query().map(str::to_string),
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                });
+
+                let response = {
+                    let mut responses = responses.lock().unwrap();
+                    if responses.len() > 1 {
+                        responses.pop_front()
+                    } else {
+                        responses.front().cloned()
+                    }
+                };
+                let response = response.unwrap_or_else(|| MockResponse::status(StatusCode::NOT_FOUND));
+                tokio::time::sleep(response.delay).await;
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+
+                let mut builder = Response::builder().status(response.status);
+                for (name, value) in response.headers {
+                    builder = builder.header(name, value);
+                }
+                builder.body(Body::from(response.body)).unwrap()
+            }
+        });
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+
+        Self {
+            url,
+            requests,
+            max_in_flight,
+        }
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.requests.lock().unwrap().clone()
+    }
+
+    pub fn max_in_flight(&self) -> usize {
+        self.max_in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// A memory of alice's in group:1 from just now, with no id, thread, embedding or
+/// metadata; the setters change what a test needs
+pub fn memory(content: &str) -> MemoryBuilder {
+    MemoryBuilder(Memory {
+        id: None,
+        chat_id: "group:1".to_string(),
+        thread_id: None,
+        user_id: "alice".to_string(),
+        timestamp: Utc::now(),
+        content: content.to_string(),
+        embedding: None,
+        embedding_model: None,
+        metadata: None,
+    })
+}
+
+pub struct MemoryBuilder(Memory);
+
+impl MemoryBuilder {
+    pub fn id(mut self, id: i64) -> Self {
+        self.0.id = Some(id);
+        self
+    }
+
+    pub fn chat(mut self, chat_id: &str) -> Self {
+        self.0.chat_id = chat_id.to_string();
+        self
+    }
+
+    pub fn thread(mut self, thread_id: &str) -> Self {
+        self.0.thread_id = Some(thread_id.to_string());
+        self
+    }
+
+    pub fn user(mut self, user_id: &str) -> Self {
+        self.0.user_id = user_id.to_string();
+        self
+    }
+
+    /// Stored this many seconds ago
+    pub fn age_secs(mut self, age_secs: i64) -> Self {
+        self.0.timestamp = Utc::now() - chrono::Duration::seconds(age_secs);
+        self
+    }
+
+    pub fn at(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.0.timestamp = timestamp;
+        self
+    }
+
+    /// An embedding from the "mock" model, unless `embedding_model` names another
+    pub fn embedding(mut self, embedding: Vec<f32>) -> Self {
+        self.0.embedding = Some(embedding);
+        self.0.embedding_model.get_or_insert_with(|| "mock".to_string());
+        self
+    }
+
+    pub fn embedding_model(mut self, model: &str) -> Self {
+        self.0.embedding_model = Some(model.to_string());
+        self
+    }
+
+    pub fn metadata(mut self, metadata: &str) -> Self {
+        self.0.metadata = Some(metadata.to_string());
+        self
+    }
+
+    pub fn build(self) -> Memory {
+        self.0
+    }
+}
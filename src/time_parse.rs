@@ -0,0 +1,251 @@
+use chrono::{DateTime, Datelike, Days, Duration, NaiveDate, NaiveTime, TimeZone, Utc, Weekday};
+
+// Time of day used when a phrase names a day but no time
+const DEFAULT_HOUR: u32 = 9;
+// Furthest ahead a reminder can be set
+const MAX_DAYS_AHEAD: i64 = 365;
+
+/// Turn a phrase like "in 2 hours", "tomorrow at 9am", "next monday" or "at 17:30"
+/// into an absolute time. Days and times of day are read in `tz`.
+pub fn parse_natural_time<Tz: TimeZone>(phrase: &str, now: DateTime<Utc>, tz: &Tz) -> Result<DateTime<Utc>, String> {
+    let phrase = phrase.trim().to_lowercase();
+    let words: Vec<&str> = phrase.split_whitespace().collect();
+
+    let when = match words.as_slice() {
+        [] => return Err("Please say when, e.g. \"in 2 hours\" or \"tomorrow at 9am\".".to_string()),
+        ["in", rest @ ..] => now + parse_duration(rest)?,
+        _ => parse_day_and_time(&words, now, tz)?,
+    };
+
+    if when <= now {
+        return Err(format!("\"{}\" is in the past. Please pick a later time.", phrase));
+    }
+    if when - now > Duration::days(MAX_DAYS_AHEAD) {
+        return Err(format!("Reminders can be set at most {} days ahead.", MAX_DAYS_AHEAD));
+    }
+    Ok(when)
+}
+
+// "2 hours", "an hour", "90 min", "1 day and 3 hours"
+fn parse_duration(words: &[&str]) -> Result<Duration, String> {
+    let mut total = Duration::zero();
+    let mut rest = words;
+
+    while !rest.is_empty() {
+        let (amount, unit, remaining) = match rest {
+            [amount, unit, remaining @ ..] if parse_amount(amount).is_some() => {
+                (parse_amount(amount).unwrap(), *unit, remaining)
+            }
+            // "2h", "30m"
+            [compact, remaining @ ..] => {
+                let split = compact.find(|c: char| !c.is_ascii_digit()).unwrap_or(compact.len());
+                let amount = compact[..split].parse::<i64>().map_err(|_| unknown_time(words))?;
+                (amount, &compact[split..], remaining)
+            }
+            [] => unreachable!(),
+        };
+
+        let unit = match unit.trim_end_matches(',') {
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::minutes(1),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::hours(1),
+            "d" | "day" | "days" => Duration::days(1),
+            "w" | "week" | "weeks" => Duration::weeks(1),
+            _ => return Err(unknown_time(words)),
+        };
+        if amount > MAX_DAYS_AHEAD * 24 * 60 {
+            return Err(unknown_time(words));
+        }
+        total = total + unit * amount as i32;
+
+        rest = match remaining {
+            ["and", remaining @ ..] => remaining,
+            remaining => remaining,
+        };
+    }
+
+    if total.is_zero() {
+        return Err(unknown_time(words));
+    }
+    Ok(total)
+}
+
+fn parse_amount(word: &str) -> Option<i64> {
+    match word {
+        "a" | "an" | "one" => Some(1),
+        "two" => Some(2),
+        "three" => Some(3),
+        number => number.parse().ok(),
+    }
+}
+
+// "tomorrow", "today at 5pm", "next monday at 10:30", "friday", "at noon"
+fn parse_day_and_time<Tz: TimeZone>(words: &[&str], now: DateTime<Utc>, tz: &Tz) -> Result<DateTime<Utc>, String> {
+    let today = now.with_timezone(tz).date_naive();
+
+    // Split into the day part and the time part at "at", or where a time starts
+    let time_start = words
+        .iter()
+        .position(|word| *word == "at" || parse_time(&[*word]).is_some())
+        .unwrap_or(words.len());
+    let (day_words, time_words) = words.split_at(time_start);
+    let time_words = match time_words {
+        ["at", rest @ ..] => rest,
+        rest => rest,
+    };
+
+    let time = if time_words.is_empty() {
+        None
+    } else {
+        Some(parse_time(time_words).ok_or_else(|| unknown_time(words))?)
+    };
+
+    let date = match day_words {
+        [] => {
+            // A bare time means today, or tomorrow once it has passed
+            let time = time.ok_or_else(|| unknown_time(words))?;
+            let at_today = local_to_utc(today, time, tz)?;
+            if at_today > now {
+                return Ok(at_today);
+            }
+            today.checked_add_days(Days::new(1)).ok_or_else(|| unknown_time(words))?
+        }
+        ["today"] => today,
+        ["tomorrow"] => today.checked_add_days(Days::new(1)).ok_or_else(|| unknown_time(words))?,
+        ["next", day] => next_weekday(today, parse_weekday(day).ok_or_else(|| unknown_time(words))?),
+        ["on", day] | [day] => match parse_weekday(day) {
+            Some(weekday) => next_weekday(today, weekday),
+            None => return Err(unknown_time(words)),
+        },
+        _ => return Err(unknown_time(words)),
+    };
+
+    let time = time.unwrap_or_else(|| NaiveTime::from_hms_opt(DEFAULT_HOUR, 0, 0).unwrap());
+    local_to_utc(date, time, tz)
+}
+
+// "9am", "9 am", "9:30pm", "17:30", "noon", "midnight"
+fn parse_time(words: &[&str]) -> Option<NaiveTime> {
+    let joined = words.concat();
+    match joined.as_str() {
+        "noon" | "midday" => return NaiveTime::from_hms_opt(12, 0, 0),
+        "midnight" => return NaiveTime::from_hms_opt(0, 0, 0),
+        _ => {}
+    }
+
+    let (clock, meridiem) = if let Some(clock) = joined.strip_suffix("am") {
+        (clock, Some(false))
+    } else if let Some(clock) = joined.strip_suffix("pm") {
+        (clock, Some(true))
+    } else {
+        (joined.as_str(), None)
+    };
+
+    let (hour, minute) = match clock.split_once(':') {
+        Some((hour, minute)) => (hour.parse::<u32>().ok()?, minute.parse::<u32>().ok()?),
+        // A bare number is only a time with am/pm, otherwise it's ambiguous
+        None if meridiem.is_some() => (clock.parse::<u32>().ok()?, 0),
+        None => return None,
+    };
+
+    let hour = match meridiem {
+        Some(_) if !(1..=12).contains(&hour) => return None,
+        Some(false) => hour % 12,
+        Some(true) => hour % 12 + 12,
+        None => hour,
+    };
+    NaiveTime::from_hms_opt(hour, minute, 0)
+}
+
+fn parse_weekday(word: &str) -> Option<Weekday> {
+    match word {
+        "mon" | "monday" => Some(Weekday::Mon),
+        "tue" | "tues" | "tuesday" => Some(Weekday::Tue),
+        "wed" | "wednesday" => Some(Weekday::Wed),
+        "thu" | "thurs" | "thursday" => Some(Weekday::Thu),
+        "fri" | "friday" => Some(Weekday::Fri),
+        "sat" | "saturday" => Some(Weekday::Sat),
+        "sun" | "sunday" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+// The next given weekday strictly after today
+fn next_weekday(today: NaiveDate, weekday: Weekday) -> NaiveDate {
+    let ahead = (7 + weekday.num_days_from_monday() - today.weekday().num_days_from_monday()) % 7;
+    let ahead = if ahead == 0 { 7 } else { ahead };
+    today + Days::new(ahead as u64)
+}
+
+fn local_to_utc<Tz: TimeZone>(date: NaiveDate, time: NaiveTime, tz: &Tz) -> Result<DateTime<Utc>, String> {
+    tz.from_local_datetime(&date.and_time(time))
+        .earliest()
+        .map(|when| when.with_timezone(&Utc))
+        .ok_or_else(|| format!("{} {} doesn't exist in your timezone (a clock change skips it).", date, time.format("%H:%M")))
+}
+
+fn unknown_time(words: &[&str]) -> String {
+    format!(
+        "I couldn't understand \"{}\". Try \"in 2 hours\", \"tomorrow at 9am\" or \"next monday\".",
+        words.join(" ")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A Wednesday morning
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 14, 10, 0, 0).unwrap()
+    }
+
+    fn parse(phrase: &str) -> Result<DateTime<Utc>, String> {
+        parse_natural_time(phrase, now(), &Utc)
+    }
+
+    fn utc(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn parses_relative_times() {
+        assert_eq!(parse("in 2 hours"), Ok(utc(14, 12, 0)));
+        assert_eq!(parse("in 90 min"), Ok(utc(14, 11, 30)));
+        assert_eq!(parse("in an hour"), Ok(utc(14, 11, 0)));
+        assert_eq!(parse("In 1 day and 3 hours"), Ok(utc(15, 13, 0)));
+        assert_eq!(parse("in 2h"), Ok(utc(14, 12, 0)));
+    }
+
+    #[test]
+    fn parses_days_and_times_of_day() {
+        assert_eq!(parse("tomorrow at 9am"), Ok(utc(15, 9, 0)));
+        assert_eq!(parse("tomorrow"), Ok(utc(15, 9, 0)));
+        assert_eq!(parse("next monday"), Ok(utc(19, 9, 0)));
+        assert_eq!(parse("friday at 5pm"), Ok(utc(16, 17, 0)));
+        assert_eq!(parse("today at noon"), Ok(utc(14, 12, 0)));
+        assert_eq!(parse("at 17:30"), Ok(utc(14, 17, 30)));
+        // Already passed today, so tomorrow
+        assert_eq!(parse("at 9am"), Ok(utc(15, 9, 0)));
+    }
+
+    #[test]
+    fn reads_times_in_the_users_timezone() {
+        // 09:00 EDT and 09:00 JST
+        assert_eq!(parse_natural_time("tomorrow at 9am", now(), &chrono_tz::America::New_York), Ok(utc(15, 13, 0)));
+        assert_eq!(parse_natural_time("tomorrow at 9am", now(), &chrono_tz::Asia::Tokyo), Ok(utc(15, 0, 0)));
+    }
+
+    #[test]
+    fn rejects_past_ambiguous_and_far_off_times() {
+        assert_eq!(
+            parse("today at 8am"),
+            Err("\"today at 8am\" is in the past. Please pick a later time.".to_string())
+        );
+        assert_eq!(
+            parse("at 9"),
+            Err("I couldn't understand \"at 9\". Try \"in 2 hours\", \"tomorrow at 9am\" or \"next monday\".".to_string())
+        );
+        assert_eq!(parse("in 400 days"), Err("Reminders can be set at most 365 days ahead.".to_string()));
+        assert!(parse("whenever").is_err());
+    }
+}
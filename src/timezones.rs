@@ -0,0 +1,142 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::error;
+
+/// Each user's chosen timezone, used for reminders and when showing timestamps
+#[derive(Debug, Clone)]
+pub struct TimezoneStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl TimezoneStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS user_timezones (
+                user_id TEXT PRIMARY KEY,
+                timezone TEXT NOT NULL,
+                updated_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn set(&self, user_id: &str, timezone: Tz) -> Result<()> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO user_timezones (user_id, timezone, updated_at)
+                VALUES (?1, ?2, ?3)
+                ON CONFLICT(user_id) DO UPDATE SET
+                    timezone = excluded.timezone,
+                    updated_at = excluded.updated_at",
+                params![user_id, timezone.name(), Utc::now().to_rfc3339()],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+
+    /// The user's timezone, or None if they never set one
+    pub async fn get(&self, user_id: &str) -> Result<Option<Tz>> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Option<Tz>> {
+            let conn = db.lock().unwrap();
+
+            let result = conn.query_row(
+                "SELECT timezone FROM user_timezones WHERE user_id = ?1",
+                params![user_id],
+                |row| row.get::<_, String>(0),
+            );
+
+            match result {
+                // A name this build of the tz database doesn't know counts as unset
+                Ok(timezone) => Ok(timezone.parse().ok()),
+                Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+                Err(e) => Err(e.into()),
+            }
+        }).await?
+    }
+
+    /// Forget the user's timezone; returns whether they had one
+    pub async fn delete_user(&self, user_id: &str) -> Result<bool> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<bool> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM user_timezones WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted > 0)
+        }).await?
+    }
+}
+
+/// The user's timezone, falling back to UTC when unset or when it can't be read
+pub async fn user_timezone(store: Option<&TimezoneStore>, user_id: &str) -> Tz {
+    let Some(store) = store else {
+        return Tz::UTC;
+    };
+    match store.get(user_id).await {
+        Ok(timezone) => timezone.unwrap_or(Tz::UTC),
+        Err(e) => {
+            error!("Failed to load timezone for {}: {}", user_id, e);
+            Tz::UTC
+        }
+    }
+}
+
+/// `timestamp` as shown to a user in `timezone`, e.g. "2025-03-14 09:30 CET"
+pub fn format_timestamp(timestamp: DateTime<Utc>, timezone: &Tz) -> String {
+    timestamp.with_timezone(timezone).format("%Y-%m-%d %H:%M %Z").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[tokio::test]
+    async fn falls_back_to_utc() {
+        let store = TimezoneStore::new(":memory:").unwrap();
+
+        assert_eq!(user_timezone(None, "alice").await, Tz::UTC);
+        assert_eq!(user_timezone(Some(&store), "alice").await, Tz::UTC);
+    }
+
+    #[tokio::test]
+    async fn local_reminder_times_become_utc() {
+        let store = TimezoneStore::new(":memory:").unwrap();
+        store.set("alice", Tz::America__New_York).await.unwrap();
+        store.set("alice", Tz::Europe__Berlin).await.unwrap();
+        let timezone = user_timezone(Some(&store), "alice").await;
+        assert_eq!(timezone, Tz::Europe__Berlin);
+
+        // 09:00 CEST is 07:00 UTC
+        let now = Utc.with_ymd_and_hms(2026, 6, 10, 12, 0, 0).unwrap();
+        let fire_at = crate::time_parse::parse_natural_time("tomorrow at 9am", now, &timezone).unwrap();
+        assert_eq!(fire_at, Utc.with_ymd_and_hms(2026, 6, 11, 7, 0, 0).unwrap());
+    }
+
+    #[test]
+    fn formats_timestamps_in_the_users_timezone() {
+        let timestamp = Utc.with_ymd_and_hms(2025, 3, 14, 8, 30, 0).unwrap();
+
+        assert_eq!(format_timestamp(timestamp, &Tz::Europe__Berlin), "2025-03-14 09:30 CET");
+        assert_eq!(format_timestamp(timestamp, &Tz::UTC), "2025-03-14 08:30 UTC");
+    }
+}
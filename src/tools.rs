@@ -0,0 +1,399 @@
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use crate::config::{HttpToolConfig, HttpToolMethod};
+use crate::llm::{ChatMessage, LlmProvider, ToolDefinition};
+
+/// Names of the tools every agent gets
+pub const BUILTIN_TOOLS: &[&str] = &["search", "calculate"];
+
+// Longest response body handed back to the model as an observation
+const MAX_HTTP_TOOL_RESPONSE_CHARS: usize = 4000;
+
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"\{([A-Za-z0-9_]+)\}").unwrap());
+
+/// Something the agent can call while planning. The model picks tools through
+/// the API's native tool-calling, passing arguments matching `schema`.
+#[async_trait]
+pub trait Tool: Send + Sync {
+    fn name(&self) -> &str;
+
+    fn description(&self) -> &str;
+
+    /// JSON Schema describing the parameters object the tool accepts
+    fn schema(&self) -> Value;
+
+    async fn call(&self, params: Value) -> Result<String>;
+}
+
+/// The tools offered to the model, in registration order
+#[derive(Clone, Default)]
+pub struct ToolRegistry {
+    tools: Vec<Arc<dyn Tool>>,
+}
+
+impl ToolRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The built-in tools backed by the LLM itself
+    pub fn with_builtin_tools(llm: Arc<dyn LlmProvider>) -> Self {
+        let mut registry = Self::new();
+        registry.register(Arc::new(SearchTool { llm: llm.clone() }));
+        registry.register(Arc::new(CalculateTool { llm }));
+        registry
+    }
+
+    /// Add a tool, replacing any existing tool with the same name
+    pub fn register(&mut self, tool: Arc<dyn Tool>) {
+        self.tools.retain(|existing| existing.name() != tool.name());
+        self.tools.push(tool);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Tool>> {
+        self.tools.iter().find(|tool| tool.name() == name).cloned()
+    }
+
+    /// Definitions to send along with a chat request
+    pub fn definitions(&self) -> Vec<ToolDefinition> {
+        self.tools
+            .iter()
+            .map(|tool| ToolDefinition {
+                name: tool.name().to_string(),
+                description: tool.description().to_string(),
+                parameters: tool.schema(),
+            })
+            .collect()
+    }
+
+    pub async fn call(&self, name: &str, params: Value) -> Result<String> {
+        let tool = self.get(name).ok_or_else(|| anyhow!("Unknown tool: {}", name))?;
+        tool.call(params).await
+    }
+}
+
+// Read a required, non-empty string argument
+fn string_param<'a>(params: &'a Value, name: &str) -> Result<&'a str> {
+    params
+        .get(name)
+        .and_then(|v| v.as_str())
+        .filter(|v| !v.trim().is_empty())
+        .ok_or_else(|| anyhow!("Missing required parameter: {}", name))
+}
+
+// Simulated search; in a real system this would call a search API
+struct SearchTool {
+    llm: Arc<dyn LlmProvider>,
+}
+
+#[async_trait]
+impl Tool for SearchTool {
+    fn name(&self) -> &str {
+        "search"
+    }
+
+    fn description(&self) -> &str {
+        "Look up factual information about a topic"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "query": { "type": "string", "description": "Search terms" }
+            },
+            "required": ["query"]
+        })
+    }
+
+    async fn call(&self, params: Value) -> Result<String> {
+        let query = string_param(&params, "query")?;
+
+        let search_prompt = format!(
+            "You are a search engine. Provide a brief, factual answer to this query: \"{}\"",
+            query
+        );
+
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: query.to_string(),
+        }];
+
+        self.llm
+            .chat(&search_prompt, &messages)
+            .await
+            .map_err(|e| anyhow!("Search error: {}", e))
+    }
+}
+
+// Uses the LLM to evaluate expressions; in production you'd want a proper math engine
+struct CalculateTool {
+    llm: Arc<dyn LlmProvider>,
+}
+
+#[async_trait]
+impl Tool for CalculateTool {
+    fn name(&self) -> &str {
+        "calculate"
+    }
+
+    fn description(&self) -> &str {
+        "Compute the numeric result of a math expression"
+    }
+
+    fn schema(&self) -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "expression": { "type": "string", "description": "The math expression to evaluate" }
+            },
+            "required": ["expression"]
+        })
+    }
+
+    async fn call(&self, params: Value) -> Result<String> {
+        let expression = string_param(&params, "expression")?;
+
+        let calc_prompt = format!(
+            "You are a calculator. Compute the result of this expression: \"{}\". \
+            Return only the numeric result without explanation.",
+            expression
+        );
+
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: expression.to_string(),
+        }];
+
+        self.llm
+            .chat(&calc_prompt, &messages)
+            .await
+            .map_err(|e| anyhow!("Calculation error: {}", e))
+    }
+}
+
+/// Names of the `{param}` placeholders in a URL template, in order of appearance
+pub fn url_placeholders(template: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for capture in PLACEHOLDER.captures_iter(template) {
+        let name = capture[1].to_string();
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+// Percent-encode everything outside the URL unreserved set
+fn encode_component(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// A tool declared in config that calls an external HTTP API and returns the body
+pub struct HttpTool {
+    config: HttpToolConfig,
+    http: reqwest::Client,
+}
+
+impl HttpTool {
+    pub fn new(config: HttpToolConfig) -> Self {
+        Self {
+            config,
+            http: reqwest::Client::new(),
+        }
+    }
+
+    // Fill the URL template from the call arguments
+    fn render_url(&self, params: &Value) -> Result<String> {
+        let mut url = self.config.url.clone();
+        for name in url_placeholders(&self.config.url) {
+            let value = match params.get(&name) {
+                Some(Value::String(value)) => value.clone(),
+                Some(Value::Null) | None => return Err(anyhow!("Missing required parameter: {}", name)),
+                Some(other) => other.to_string(),
+            };
+            url = url.replace(&format!("{{{}}}", name), &encode_component(&value));
+        }
+        Ok(url)
+    }
+}
+
+#[async_trait]
+impl Tool for HttpTool {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn description(&self) -> &str {
+        &self.config.description
+    }
+
+    fn schema(&self) -> Value {
+        self.config.parameters.clone()
+    }
+
+    async fn call(&self, params: Value) -> Result<String> {
+        let url = self.render_url(&params)?;
+
+        let request = match self.config.method {
+            HttpToolMethod::Get => self.http.get(&url),
+            HttpToolMethod::Post => self.http.post(&url).json(&params),
+        };
+
+        let response = request
+            .timeout(Duration::from_secs(self.config.timeout_secs))
+            .send()
+            .await
+            .map_err(|e| anyhow!("{} request failed: {}", self.config.name, e))?;
+
+        let status = response.status();
+        let body = response
+            .text()
+            .await
+            .map_err(|e| anyhow!("{} returned an unreadable body: {}", self.config.name, e))?;
+
+        if !status.is_success() {
+            return Err(anyhow!("{} returned {}", self.config.name, status));
+        }
+
+        if body.chars().count() > MAX_HTTP_TOOL_RESPONSE_CHARS {
+            let truncated: String = body.chars().take(MAX_HTTP_TOOL_RESPONSE_CHARS).collect();
+            return Ok(format!("{}... (truncated)", truncated));
+        }
+
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::MockLlm;
+    use crate::test_support::{MockResponse, MockServer};
+    use axum::http::StatusCode;
+
+    fn registry() -> ToolRegistry {
+        ToolRegistry::with_builtin_tools(Arc::new(MockLlm))
+    }
+
+    #[test]
+    fn definitions_list_the_builtin_tools_with_their_schemas() {
+        let definitions = registry().definitions();
+
+        let names: Vec<&str> = definitions.iter().map(|d| d.name.as_str()).collect();
+        assert_eq!(names, BUILTIN_TOOLS);
+        assert_eq!(definitions[0].parameters["required"], json!(["query"]));
+        assert_eq!(definitions[1].parameters["properties"]["expression"]["type"], "string");
+    }
+
+    #[tokio::test]
+    async fn dispatches_calls_by_name() {
+        let registry = registry();
+
+        let result = registry.call("search", json!({ "query": "capital of France" })).await.unwrap();
+        assert_eq!(result, "[mock] capital of France");
+
+        let result = registry.call("calculate", json!({ "expression": "2 + 2" })).await.unwrap();
+        assert_eq!(result, "[mock] 2 + 2");
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_tools_and_missing_parameters() {
+        let registry = registry();
+
+        let error = registry.call("teleport", json!({})).await.unwrap_err();
+        assert_eq!(error.to_string(), "Unknown tool: teleport");
+
+        let error = registry.call("search", json!({ "query": " " })).await.unwrap_err();
+        assert_eq!(error.to_string(), "Missing required parameter: query");
+    }
+
+    #[test]
+    fn registering_a_tool_again_replaces_it() {
+        let mut registry = registry();
+        registry.register(Arc::new(SearchTool { llm: Arc::new(MockLlm) }));
+
+        assert_eq!(registry.definitions().len(), BUILTIN_TOOLS.len());
+        assert_eq!(registry.definitions().last().unwrap().name, "search");
+    }
+
+    // A tool as it would be declared under [[tools]]
+    fn http_tool(server: &MockServer, method: &str) -> HttpTool {
+        let config: HttpToolConfig = toml::from_str(&format!(
+            r#"
+name = "lookup"
+description = "Look up a word"
+url = "{}/words/{{word}}?lang={{lang}}"
+method = "{}"
+
+[parameters]
+type = "object"
+required = ["word", "lang"]
+
+[parameters.properties.word]
+type = "string"
+
+[parameters.properties.lang]
+type = "string"
+"#,
+            server.url, method
+        ))
+        .unwrap();
+        HttpTool::new(config)
+    }
+
+    #[tokio::test]
+    async fn http_tool_fills_the_url_and_returns_the_body() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::OK).with_body("a greeting".to_string())]).await;
+        let tool = http_tool(&server, "get");
+
+        let result = tool.call(json!({ "word": "hello world", "lang": "en" })).await.unwrap();
+
+        assert_eq!(result, "a greeting");
+        assert_eq!(tool.schema()["required"], json!(["word", "lang"]));
+        let requests = server.requests();
+        assert_eq!(requests[0].path, "/words/hello%20world");
+        assert_eq!(requests[0].query.as_deref(), Some("lang=en"));
+    }
+
+    #[tokio::test]
+    async fn http_tool_posts_its_arguments() {
+        let server = MockServer::start(vec![MockResponse::json(json!({ "ok": true }))]).await;
+        let tool = http_tool(&server, "post");
+
+        tool.call(json!({ "word": "hi", "lang": "en" })).await.unwrap();
+
+        assert_eq!(server.requests()[0].json(), json!({ "word": "hi", "lang": "en" }));
+    }
+
+    #[tokio::test]
+    async fn http_tool_reports_failures() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::INTERNAL_SERVER_ERROR)]).await;
+        let tool = http_tool(&server, "get");
+
+        let error = tool.call(json!({ "word": "hi", "lang": "en" })).await.unwrap_err();
+        assert_eq!(error.to_string(), "lookup returned 500 Internal Server Error");
+
+        let error = tool.call(json!({ "word": "hi" })).await.unwrap_err();
+        assert_eq!(error.to_string(), "Missing required parameter: lang");
+        assert_eq!(server.requests().len(), 1);
+    }
+
+    #[test]
+    fn finds_url_placeholders_once_each() {
+        assert_eq!(url_placeholders("https://x/{a}/{b}?again={a}"), vec!["a", "b"]);
+        assert!(url_placeholders("https://x/plain").is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection};
+use std::collections::HashSet;
+use std::future::Future;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use tracing::info;
+
+use crate::chat_id::migrate_legacy_chat_ids;
+use crate::llm::TokenUsage;
+
+/// Who an LLM call is being made on behalf of
+#[derive(Debug, Clone)]
+pub struct UsageContext {
+    pub chat_id: String,
+    pub user_id: String,
+    pub command: String,
+}
+
+tokio::task_local! {
+    static CURRENT_CONTEXT: UsageContext;
+}
+
+impl UsageContext {
+    /// Run `fut` with this context attached, so LLM calls inside it are attributed to it
+    pub async fn scope<F: Future>(self, fut: F) -> F::Output {
+        CURRENT_CONTEXT.scope(self, fut).await
+    }
+
+    /// The context of the command currently executing on this task, if any
+    pub fn current() -> Option<Self> {
+        CURRENT_CONTEXT.try_with(|context| context.clone()).ok()
+    }
+}
+
+/// Aggregated usage for one command
+#[derive(Debug, Clone)]
+pub struct CommandUsage {
+    pub command: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct UsageStore {
+    db: Arc<Mutex<Connection>>,
+}
+
+impl UsageStore {
+    pub fn new<P: AsRef<Path>>(db_path: P) -> Result<Self> {
+        let conn = Connection::open(db_path)?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS llm_usage (
+                id INTEGER PRIMARY KEY,
+                chat_id TEXT NOT NULL,
+                user_id TEXT NOT NULL,
+                command TEXT NOT NULL,
+                timestamp TEXT NOT NULL,
+                prompt_tokens INTEGER NOT NULL,
+                completion_tokens INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE INDEX IF NOT EXISTS llm_usage_chat_user_idx ON llm_usage (chat_id, user_id, timestamp)",
+            [],
+        )?;
+        
+        // Older versions keyed rows by the SDK's Debug output
+        let migrated = migrate_legacy_chat_ids(&conn, "llm_usage")?;
+        if migrated > 0 {
+            info!("Migrated {} llm_usage rows to canonical chat ids", migrated);
+        }
+
+        Ok(Self {
+            db: Arc::new(Mutex::new(conn)),
+        })
+    }
+
+    pub async fn record_usage(&self, context: UsageContext, usage: TokenUsage) -> Result<()> {
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<()> {
+            let conn = db.lock().unwrap();
+
+            conn.execute(
+                "INSERT INTO llm_usage
+                (chat_id, user_id, command, timestamp, prompt_tokens, completion_tokens)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    context.chat_id,
+                    context.user_id,
+                    context.command,
+                    Utc::now().to_rfc3339(),
+                    usage.prompt_tokens,
+                    usage.completion_tokens,
+                ],
+            )?;
+
+            Ok(())
+        }).await?
+    }
+
+    /// Number of LLM calls made from the chat since the given time
+    pub async fn count_calls(&self, chat_id: &str, since: DateTime<Utc>) -> Result<u64> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<u64> {
+            let conn = db.lock().unwrap();
+
+            let count: i64 = conn.query_row(
+                "SELECT COUNT(*) FROM llm_usage WHERE chat_id = ?1 AND timestamp >= ?2",
+                params![chat_id, since.to_rfc3339()],
+                |row| row.get(0),
+            )?;
+
+            Ok(count as u64)
+        }).await?
+    }
+
+    /// Users whose commands made LLM calls in the chat
+    pub async fn user_ids(&self, chat_id: &str) -> Result<HashSet<String>> {
+        let chat_id = chat_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<HashSet<String>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare("SELECT DISTINCT user_id FROM llm_usage WHERE chat_id = ?1")?;
+            let rows = stmt.query_map(params![chat_id], |row| row.get(0))?;
+
+            let mut user_ids = HashSet::new();
+            for row in rows {
+                user_ids.insert(row?);
+            }
+
+            Ok(user_ids)
+        }).await?
+    }
+
+    /// Per-command usage totals for a user in a chat since the given time
+    pub async fn usage_by_command(
+        &self,
+        chat_id: &str,
+        user_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<CommandUsage>> {
+        let chat_id = chat_id.to_string();
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<Vec<CommandUsage>> {
+            let conn = db.lock().unwrap();
+
+            let mut stmt = conn.prepare(
+                "SELECT command, COUNT(*), SUM(prompt_tokens), SUM(completion_tokens)
+                 FROM llm_usage
+                 WHERE chat_id = ?1 AND user_id = ?2 AND timestamp >= ?3
+                 GROUP BY command
+                 ORDER BY SUM(prompt_tokens + completion_tokens) DESC"
+            )?;
+
+            let rows = stmt.query_map(params![chat_id, user_id, since.to_rfc3339()], |row| {
+                Ok(CommandUsage {
+                    command: row.get(0)?,
+                    calls: row.get::<_, i64>(1)? as u64,
+                    prompt_tokens: row.get::<_, i64>(2)? as u64,
+                    completion_tokens: row.get::<_, i64>(3)? as u64,
+                })
+            })?;
+
+            let mut usage = Vec::new();
+            for row in rows {
+                usage.push(row?);
+            }
+
+            Ok(usage)
+        }).await?
+    }
+
+    /// Delete the user's usage records in every chat; returns how many there were
+    pub async fn delete_user(&self, user_id: &str) -> Result<usize> {
+        let user_id = user_id.to_string();
+        let db = self.db.clone();
+
+        tokio::task::spawn_blocking(move || -> Result<usize> {
+            let conn = db.lock().unwrap();
+            let deleted = conn.execute("DELETE FROM llm_usage WHERE user_id = ?1", params![user_id])?;
+            Ok(deleted)
+        }).await?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn context(user_id: &str, command: &str) -> UsageContext {
+        UsageContext {
+            chat_id: "group:1".to_string(),
+            user_id: user_id.to_string(),
+            command: command.to_string(),
+        }
+    }
+
+    fn usage(prompt_tokens: u32, completion_tokens: u32) -> TokenUsage {
+        TokenUsage {
+            prompt_tokens,
+            completion_tokens,
+            total_tokens: prompt_tokens + completion_tokens,
+        }
+    }
+
+    #[tokio::test]
+    async fn aggregates_usage_per_command() {
+        let store = UsageStore::new(":memory:").unwrap();
+        let since = Utc::now() - Duration::minutes(1);
+        store.record_usage(context("alice", "ask"), usage(100, 20)).await.unwrap();
+        store.record_usage(context("alice", "ask"), usage(50, 10)).await.unwrap();
+        store.record_usage(context("alice", "summarize"), usage(30, 5)).await.unwrap();
+        store.record_usage(context("bob", "ask"), usage(1000, 100)).await.unwrap();
+
+        let totals = store.usage_by_command("group:1", "alice", since).await.unwrap();
+
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].command, "ask");
+        assert_eq!(totals[0].calls, 2);
+        assert_eq!(totals[0].prompt_tokens, 150);
+        assert_eq!(totals[0].completion_tokens, 30);
+        assert_eq!(totals[1].command, "summarize");
+        assert_eq!(store.count_calls("group:1", since).await.unwrap(), 4);
+        assert_eq!(store.count_calls("group:2", since).await.unwrap(), 0);
+    }
+}
@@ -0,0 +1,118 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashSet;
+use std::time::Duration;
+use tracing::{error, info};
+
+/// Names of the events a webhook can subscribe to
+pub const WEBHOOK_EVENTS: &[&str] = &["reminder_fired", "moderation_flagged"];
+
+/// Body POSTed to the webhook URL
+#[derive(Debug, Clone, Serialize)]
+pub struct WebhookPayload {
+    pub event: &'static str,
+    pub chat_id: String,
+    pub user_id: String,
+    pub content: String,
+    // Why moderation flagged the content
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl WebhookPayload {
+    pub fn reminder_fired(chat_id: &str, user_id: &str, text: &str) -> Self {
+        Self {
+            event: "reminder_fired",
+            chat_id: chat_id.to_string(),
+            user_id: user_id.to_string(),
+            content: text.to_string(),
+            reason: None,
+            timestamp: Utc::now(),
+        }
+    }
+
+    pub fn moderation_flagged(chat_id: &str, user_id: &str, content: &str, reason: &str) -> Self {
+        Self {
+            event: "moderation_flagged",
+            chat_id: chat_id.to_string(),
+            user_id: user_id.to_string(),
+            content: content.to_string(),
+            reason: Some(reason.to_string()),
+            timestamp: Utc::now(),
+        }
+    }
+}
+
+/// Tells an external system about bot events by POSTing JSON to a URL. Delivery is
+/// best-effort: it happens in the background and failures are only logged.
+#[derive(Debug, Clone)]
+pub struct WebhookNotifier {
+    http: reqwest::Client,
+    url: String,
+    events: HashSet<String>,
+}
+
+impl WebhookNotifier {
+    pub fn new(url: &str, events: &[String], timeout: Duration) -> Self {
+        Self {
+            http: reqwest::Client::builder()
+                .timeout(timeout)
+                .build()
+                .unwrap_or_default(),
+            url: url.to_string(),
+            events: events.iter().cloned().collect(),
+        }
+    }
+
+    /// Send `payload` in the background if its event is one we were asked to report
+    pub fn notify(&self, payload: WebhookPayload) {
+        if !self.events.contains(payload.event) {
+            return;
+        }
+
+        let http = self.http.clone();
+        let url = self.url.clone();
+        tokio::spawn(async move {
+            let result = http
+                .post(&url)
+                .json(&payload)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => info!("Sent {} webhook", payload.event),
+                Err(e) => error!("Failed to send {} webhook: {}", payload.event, e),
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{MockResponse, MockServer};
+    use axum::http::StatusCode;
+
+    #[tokio::test]
+    async fn only_sends_subscribed_events() {
+        let server = MockServer::start(vec![MockResponse::status(StatusCode::OK)]).await;
+        let webhook = WebhookNotifier::new(&server.url, &["reminder_fired".to_string()], Duration::from_secs(5));
+
+        webhook.notify(WebhookPayload::moderation_flagged("group:1", "bob", "rude", "insult"));
+        webhook.notify(WebhookPayload::reminder_fired("group:1", "alice", "stretch"));
+
+        for _ in 0..100 {
+            if !server.requests().is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        let requests = server.requests();
+        assert_eq!(requests.len(), 1);
+        let payload = requests[0].json();
+        assert_eq!(payload["event"], "reminder_fired");
+        assert_eq!(payload["content"], "stretch");
+        assert!(payload.get("reason").is_none());
+    }
+}